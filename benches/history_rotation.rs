@@ -0,0 +1,79 @@
+// Compares the old `Vec::remove(0)` history rotation with a `VecDeque` window, the slice ring
+// behind `RateHistory` (src/history.rs) and a `RingBuffer` sized at compile time.
+use std::collections::VecDeque;
+use criterion::{black_box, criterion_group, criterion_main, Criterion};
+
+#[path = "../src/ring_buffer.rs"]
+// Their unit tests are compiled but never run here
+#[allow(dead_code, unused_imports)]
+mod ring_buffer;
+#[path = "../src/history.rs"]
+#[allow(dead_code, unused_imports)]
+mod history;
+use history::RateHistory;
+use ring_buffer::RingBuffer;
+
+// 60 s window at the default 500 ms tick
+const MAX_SAMPLES: usize = 120;
+
+fn rotate_vec(c: &mut Criterion) {
+    let mut history = vec![0.0f64; MAX_SAMPLES];
+    let mut sample = 0.0;
+    c.bench_function("vec_remove_0_push", |b| {
+        b.iter(|| {
+            sample += 1.0;
+            history.remove(0);
+            history.push(black_box(sample));
+        })
+    });
+}
+
+// Shaped like `RateHistory::push`: the window fills up first, then each push returns the sample it evicts
+struct VecDequeWindow {
+    data: VecDeque<f64>,
+    max_samples: usize,
+}
+
+impl VecDequeWindow {
+    fn push(&mut self, value: f64) -> Option<f64> {
+        self.data.push_back(value);
+        if self.data.len() > self.max_samples { self.data.pop_front() } else { None }
+    }
+}
+
+fn rotate_vecdeque(c: &mut Criterion) {
+    let mut history = VecDequeWindow { data: VecDeque::with_capacity(MAX_SAMPLES + 1), max_samples: MAX_SAMPLES };
+    let mut sample = 0.0;
+    c.bench_function("vecdeque_pop_front_push_back", |b| {
+        b.iter(|| {
+            sample += 1.0;
+            black_box(history.push(black_box(sample)));
+        })
+    });
+}
+
+fn rotate_rate_history(c: &mut Criterion) {
+    let mut history = RateHistory::new(MAX_SAMPLES);
+    let mut sample = 0.0;
+    c.bench_function("slice_ring_push", |b| {
+        b.iter(|| {
+            sample += 1.0;
+            black_box(history.push(black_box(sample)));
+        })
+    });
+}
+
+// What the window would cost if its length were known at compile time
+fn rotate_ring_buffer(c: &mut Criterion) {
+    let mut history = RingBuffer::<f64, MAX_SAMPLES>::new();
+    let mut sample = 0.0;
+    c.bench_function("const_ring_buffer_push", |b| {
+        b.iter(|| {
+            sample += 1.0;
+            black_box(history.push(black_box(sample)));
+        })
+    });
+}
+
+criterion_group!(benches, rotate_vec, rotate_vecdeque, rotate_rate_history, rotate_ring_buffer);
+criterion_main!(benches);
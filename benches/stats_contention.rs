@@ -0,0 +1,209 @@
+// Per-packet accounting cost while a second thread drains the counters, as the
+// UI tick does. Compares the old single `Mutex<HashMap>` with `DoubleBuffer`.
+//
+// With a drain every 50 us (far more aggressive than a real tick), on one core:
+//   single_mutex_add   ~37-46 ns
+//   double_buffer_add  ~30 ns
+// The write side of `DoubleBuffer` is one compare-and-swap and never parks the
+// capture thread; a drain swaps a pointer and waits out at most one write.
+//
+// `replay_10gbps` replays 100k full size frames (120 ms of a 10 Gbps link, which
+// needs 833k frames/s) through the counters `account` keeps, with a drain every ms:
+//   single_mutex/1   ~4.8 M frames/s    double_buffer/1  ~6.0 M frames/s
+//   single_mutex/4   ~5.0 M frames/s    double_buffer/4  ~4.1 M frames/s
+// One writer is how the app runs. Four writers on one set of counters only happen
+// here; there the mutex parks losers while the double buffer spins, then yields.
+use std::{
+    collections::HashMap,
+    net::Ipv4Addr,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc, Mutex,
+    },
+    thread,
+    time::{Duration, Instant},
+};
+use criterion::{black_box, criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+
+#[path = "../src/double_buffer.rs"]
+#[allow(unused_imports)]
+mod double_buffer;
+use double_buffer::DoubleBuffer;
+
+// Synthetic LAN of 254 hosts, one 1500 byte frame per iteration
+fn next_ip(i: &mut u32) -> Ipv4Addr {
+    *i = i.wrapping_add(1);
+    Ipv4Addr::new(192, 168, 1, (*i % 254 + 1) as u8)
+}
+
+fn spawn_drainer<F: Fn() + Send + 'static>(every: Duration, drain: F) -> Arc<AtomicBool> {
+    let stop = Arc::new(AtomicBool::new(false));
+    let flag = Arc::clone(&stop);
+    thread::spawn(move || {
+        while !flag.load(Ordering::Relaxed) {
+            drain();
+            thread::sleep(every);
+        }
+    });
+    stop
+}
+
+fn single_mutex(c: &mut Criterion) {
+    let map: Arc<Mutex<HashMap<Ipv4Addr, u64>>> = Arc::new(Mutex::new(HashMap::new()));
+    let drained = Arc::clone(&map);
+    let stop = spawn_drainer(Duration::from_micros(50), move || {
+        black_box(std::mem::take(&mut *drained.lock().unwrap()));
+    });
+    let mut i = 0;
+    c.bench_function("single_mutex_add", |b| {
+        b.iter(|| {
+            *map.lock().unwrap().entry(next_ip(&mut i)).or_insert(0) += black_box(1500);
+        })
+    });
+    stop.store(true, Ordering::Relaxed);
+}
+
+fn double_buffer(c: &mut Criterion) {
+    let buffer: Arc<DoubleBuffer<HashMap<Ipv4Addr, u64>>> = Arc::new(DoubleBuffer::new());
+    let drained = Arc::clone(&buffer);
+    let stop = spawn_drainer(Duration::from_micros(50), move || {
+        black_box(drained.swap(std::mem::take));
+    });
+    let mut i = 0;
+    c.bench_function("double_buffer_add", |b| {
+        b.iter(|| {
+            *buffer.write().entry(next_ip(&mut i)).or_insert(0) += black_box(1500);
+        })
+    });
+    stop.store(true, Ordering::Relaxed);
+}
+
+// One frame of the replayed trace
+#[derive(Clone, Copy)]
+struct Frame {
+    src: Ipv4Addr,
+    dst: Ipv4Addr,
+    src_port: u16,
+    dst_port: u16,
+    dscp: u8,
+    len: u64,
+}
+
+// 10 Gbps of full size frames: one every 1.2 us
+const REPLAY_FRAME_LEN: u64 = 1500;
+// 120 ms of that link, replayed as fast as the counters allow
+const REPLAY_FRAMES: usize = 100_000;
+
+// A LAN of 254 hosts talking to 1024 remote peers over a few thousand sessions,
+// with the same frames in the same order on every run
+fn replay_trace() -> Vec<Frame> {
+    let mut state = 0x2545_f491_4f6c_dd1du64;
+    let mut next = move || {
+        state ^= state << 13;
+        state ^= state >> 7;
+        state ^= state << 17;
+        state
+    };
+    (0..REPLAY_FRAMES)
+        .map(|_| {
+            let roll = next();
+            let host = Ipv4Addr::new(192, 168, 1, (roll % 254 + 1) as u8);
+            let peer = Ipv4Addr::new(203, 0, (roll >> 8) as u8 % 4, (roll >> 16) as u8);
+            let port = 32768 + (roll >> 24) as u16 % 16;
+            let (src, dst, src_port, dst_port) = if roll >> 40 & 1 == 0 { (host, peer, port, 443) } else { (peer, host, 443, port) };
+            Frame { src, dst, src_port, dst_port, dscp: if roll >> 41 & 7 == 0 { 46 } else { 0 }, len: REPLAY_FRAME_LEN }
+        })
+        .collect()
+}
+
+// What `PacketAccounting::account` updates for a unicast TCP frame between a LAN host and a peer
+#[derive(Default)]
+struct Counters {
+    rx: u64,
+    tx: u64,
+    tx_per_ip: HashMap<Ipv4Addr, u64>,
+    rx_per_ip: HashMap<Ipv4Addr, u64>,
+    dscp_per_ip: HashMap<(Ipv4Addr, u8), u64>,
+    sessions: HashMap<(Ipv4Addr, u16, Ipv4Addr, u16), u64>,
+}
+
+impl Counters {
+    fn account(&mut self, frame: &Frame) {
+        let lan = |ip: Ipv4Addr| ip.octets()[..3] == [192, 168, 1];
+        if lan(frame.src) {
+            self.tx += frame.len;
+            *self.tx_per_ip.entry(frame.src).or_default() += frame.len;
+            *self.dscp_per_ip.entry((frame.src, frame.dscp)).or_default() += frame.len;
+        } else {
+            self.rx += frame.len;
+            *self.rx_per_ip.entry(frame.dst).or_default() += frame.len;
+            *self.dscp_per_ip.entry((frame.dst, frame.dscp)).or_default() += frame.len;
+        }
+        *self.sessions.entry((frame.src, frame.src_port, frame.dst, frame.dst_port)).or_default() += frame.len;
+    }
+}
+
+trait ReplayStats: Send + Sync + 'static {
+    fn account(&self, frame: &Frame);
+    fn drain(&self) -> Counters;
+}
+
+impl ReplayStats for Mutex<Counters> {
+    fn account(&self, frame: &Frame) {
+        self.lock().unwrap().account(frame);
+    }
+
+    fn drain(&self) -> Counters {
+        std::mem::take(&mut *self.lock().unwrap())
+    }
+}
+
+impl ReplayStats for DoubleBuffer<Counters> {
+    fn account(&self, frame: &Frame) {
+        self.write().account(frame);
+    }
+
+    fn drain(&self) -> Counters {
+        self.swap(std::mem::take)
+    }
+}
+
+// Replay the trace split across `writers` capture threads while a tick thread drains every
+// millisecond, 100 times as often as the fastest UI tick. The app runs one capture thread per
+// set of counters, so writers past the first are a stress case.
+fn replay_into<S: ReplayStats>(c: &mut Criterion, name: &str, stats: S) {
+    let stats = Arc::new(stats);
+    let trace = replay_trace();
+    let drained = Arc::clone(&stats);
+    let stop = spawn_drainer(Duration::from_millis(1), move || {
+        black_box(drained.drain());
+    });
+    let mut group = c.benchmark_group("replay_10gbps");
+    group.sample_size(20).throughput(Throughput::Elements(REPLAY_FRAMES as u64));
+    for writers in [1, 4] {
+        group.bench_with_input(BenchmarkId::new(name, writers), &writers, |b, &writers| {
+            b.iter_custom(|iters| {
+                let start = Instant::now();
+                for _ in 0..iters {
+                    thread::scope(|scope| {
+                        for part in trace.chunks(REPLAY_FRAMES / writers) {
+                            let stats = &stats;
+                            scope.spawn(move || part.iter().for_each(|frame| stats.account(frame)));
+                        }
+                    });
+                }
+                start.elapsed()
+            })
+        });
+    }
+    group.finish();
+    stop.store(true, Ordering::Relaxed);
+}
+
+fn replay(c: &mut Criterion) {
+    replay_into(c, "single_mutex", Mutex::new(Counters::default()));
+    replay_into(c, "double_buffer", DoubleBuffer::<Counters>::new());
+}
+
+criterion_group!(benches, single_mutex, double_buffer, replay);
+criterion_main!(benches);
@@ -0,0 +1,200 @@
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    env,
+    fs::{self, File},
+    io::{BufWriter, Write},
+    path::Path,
+    process::Command,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+const SERVICES_FILE: &str = "/etc/services";
+// nmap-style `XXXXXX Vendor` prefix list shipped with the repository
+const OUI_FILE: &str = "oui_database.txt";
+// Only the vendors with the most assigned prefixes are embedded
+const OUI_TOP_VENDORS: usize = 200;
+
+// Used when the build host has no /etc/services (minimal containers, Windows)
+const FALLBACK_SERVICES: &[(u16, &str, &str)] = &[
+    (7, "tcp", "echo"), (7, "udp", "echo"),
+    (20, "tcp", "ftp-data"), (21, "tcp", "ftp"),
+    (22, "tcp", "ssh"), (23, "tcp", "telnet"),
+    (25, "tcp", "smtp"), (37, "tcp", "time"),
+    (43, "tcp", "whois"), (49, "tcp", "tacacs"),
+    (53, "tcp", "domain"), (53, "udp", "domain"),
+    (67, "udp", "bootps"), (68, "udp", "bootpc"),
+    (69, "udp", "tftp"), (70, "tcp", "gopher"),
+    (79, "tcp", "finger"), (80, "tcp", "http"),
+    (80, "udp", "http"), (88, "tcp", "kerberos"),
+    (88, "udp", "kerberos"), (110, "tcp", "pop3"),
+    (111, "tcp", "sunrpc"), (111, "udp", "sunrpc"),
+    (113, "tcp", "auth"), (119, "tcp", "nntp"),
+    (123, "udp", "ntp"), (135, "tcp", "epmap"),
+    (137, "udp", "netbios-ns"), (138, "udp", "netbios-dgm"),
+    (139, "tcp", "netbios-ssn"), (143, "tcp", "imap2"),
+    (161, "udp", "snmp"), (162, "udp", "snmp-trap"),
+    (179, "tcp", "bgp"), (389, "tcp", "ldap"),
+    (443, "tcp", "https"), (443, "udp", "https"),
+    (445, "tcp", "microsoft-ds"), (465, "tcp", "submissions"),
+    (500, "udp", "isakmp"), (514, "udp", "syslog"),
+    (515, "tcp", "printer"), (520, "udp", "router"),
+    (546, "udp", "dhcpv6-client"), (547, "udp", "dhcpv6-server"),
+    (554, "tcp", "rtsp"), (587, "tcp", "submission"),
+    (631, "tcp", "ipp"), (636, "tcp", "ldaps"),
+    (853, "tcp", "domain-s"), (873, "tcp", "rsync"),
+    (993, "tcp", "imaps"), (995, "tcp", "pop3s"),
+    (1194, "udp", "openvpn"), (1433, "tcp", "ms-sql-s"),
+    (1701, "udp", "l2tp"), (1723, "tcp", "pptp"),
+    (1812, "udp", "radius"), (1883, "tcp", "mqtt"),
+    (1900, "udp", "ssdp"), (2049, "tcp", "nfs"),
+    (3306, "tcp", "mysql"), (3389, "tcp", "ms-wbt-server"),
+    (3478, "udp", "stun"), (4500, "udp", "ipsec-nat-t"),
+    (5060, "udp", "sip"), (5353, "udp", "mdns"),
+    (5432, "tcp", "postgresql"), (5900, "tcp", "vnc"),
+    (6379, "tcp", "redis"), (6881, "tcp", "bittorrent"),
+    (8080, "tcp", "http-alt"), (8443, "tcp", "https-alt"),
+    (9100, "tcp", "jetdirect"), (11211, "tcp", "memcache"),
+    (27017, "tcp", "mongodb"), (51820, "udp", "wireguard"),
+];
+
+// Parse `name port/proto [aliases...] [# comment]` lines; the first name seen for a port wins
+fn parse_services(contents: &str) -> BTreeMap<String, String> {
+    let mut services = BTreeMap::new();
+    for line in contents.lines() {
+        let line = line.split('#').next().unwrap_or("");
+        let mut fields = line.split_whitespace();
+        let (Some(name), Some(port_proto)) = (fields.next(), fields.next()) else {
+            continue;
+        };
+        let Some((port, proto)) = port_proto.split_once('/') else {
+            continue;
+        };
+        if port.parse::<u16>().is_err() || !matches!(proto, "tcp" | "udp") {
+            continue;
+        }
+        services.entry(format!("{}/{}", port, proto)).or_insert_with(|| name.to_string());
+    }
+    services
+}
+
+// Every 24-bit prefix belonging to the `OUI_TOP_VENDORS` vendors with the most prefixes
+fn parse_ouis(contents: &str) -> BTreeMap<u32, String> {
+    let mut prefixes = Vec::new();
+    let mut counts: HashMap<&str, usize> = HashMap::new();
+    for line in contents.lines().filter(|l| !l.starts_with('#')) {
+        let Some((prefix, vendor)) = line.split_once(' ') else {
+            continue;
+        };
+        // Longer MA-M/MA-S prefixes and the registry's own placeholder entries are skipped
+        if prefix.len() != 6 || vendor == "Ieee Registration Authority" {
+            continue;
+        }
+        let Ok(prefix) = u32::from_str_radix(prefix, 16) else {
+            continue;
+        };
+        *counts.entry(vendor).or_insert(0) += 1;
+        prefixes.push((prefix, vendor));
+    }
+
+    let mut ranked: Vec<(&str, usize)> = counts.into_iter().collect();
+    ranked.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(b.0)));
+    let top: HashSet<&str> = ranked.into_iter().take(OUI_TOP_VENDORS).map(|(vendor, _)| vendor).collect();
+
+    prefixes
+        .into_iter()
+        .filter(|(_, vendor)| top.contains(vendor))
+        .map(|(prefix, vendor)| (prefix, vendor.to_string()))
+        .collect()
+}
+
+fn write_services(out_dir: &Path) {
+    println!("cargo:rerun-if-changed={}", SERVICES_FILE);
+
+    let mut services = fs::read_to_string(SERVICES_FILE)
+        .map(|contents| parse_services(&contents))
+        .unwrap_or_default();
+    if services.is_empty() {
+        services = FALLBACK_SERVICES
+            .iter()
+            .map(|(port, proto, name)| (format!("{}/{}", port, proto), name.to_string()))
+            .collect();
+    }
+
+    let mut map = phf_codegen::Map::new();
+    for (key, name) in &services {
+        map.entry(key.as_str(), &format!("{:?}", name));
+    }
+
+    let mut file = BufWriter::new(File::create(out_dir.join("services.rs")).unwrap());
+    writeln!(
+        file,
+        "static SERVICES: phf::Map<&'static str, &'static str> = {};",
+        map.build()
+    )
+    .unwrap();
+}
+
+fn write_ouis(out_dir: &Path) {
+    println!("cargo:rerun-if-changed={}", OUI_FILE);
+
+    let ouis = parse_ouis(&fs::read_to_string(OUI_FILE).unwrap());
+    let mut map = phf_codegen::Map::new();
+    for (prefix, vendor) in &ouis {
+        map.entry(*prefix, &format!("{:?}", vendor));
+    }
+
+    let mut file = BufWriter::new(File::create(out_dir.join("ouis.rs")).unwrap());
+    writeln!(
+        file,
+        "static OUI_VENDORS: phf::Map<u32, &'static str> = {};",
+        map.build()
+    )
+    .unwrap();
+}
+
+// `YYYY-MM-DD` of a Unix timestamp in UTC, without pulling chrono into the build
+fn utc_date(secs: u64) -> String {
+    // Howard Hinnant's days-to-civil, with eras of 400 years starting on 0000-03-01
+    let z = (secs / 86400) as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z - era * 146097;
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!("{:04}-{:02}-{:02}", year, month, day)
+}
+
+// Build date and compiler for `--version`; SOURCE_DATE_EPOCH pins the date for reproducible builds
+fn write_build_info() {
+    println!("cargo:rerun-if-env-changed=SOURCE_DATE_EPOCH");
+
+    let secs = env::var("SOURCE_DATE_EPOCH")
+        .ok()
+        .and_then(|epoch| epoch.parse().ok())
+        .unwrap_or_else(|| SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0));
+    println!("cargo:rustc-env=BUILD_DATE={}", utc_date(secs));
+
+    let rustc = env::var("RUSTC").unwrap_or_else(|_| "rustc".to_string());
+    let version = Command::new(rustc)
+        .arg("--version")
+        .output()
+        .ok()
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .map(|version| version.trim().to_string())
+        .filter(|version| !version.is_empty())
+        .unwrap_or_else(|| "rustc (unknown version)".to_string());
+    println!("cargo:rustc-env=RUSTC_VERSION={}", version);
+}
+
+fn main() {
+    println!("cargo:rerun-if-changed=build.rs");
+
+    write_build_info();
+
+    let out_dir = env::var("OUT_DIR").unwrap();
+    write_services(Path::new(&out_dir));
+    write_ouis(Path::new(&out_dir));
+}
@@ -0,0 +1,263 @@
+use std::{
+    collections::{HashMap, HashSet, VecDeque},
+    fs::{File, OpenOptions},
+    io::{self, LineWriter, Write},
+    net::Ipv4Addr,
+    path::Path,
+};
+use chrono::{DateTime, Local};
+use crate::anomaly::AnomalyEvent;
+use crate::arp::ArpAlert;
+use crate::app::TopTalker;
+use crate::config::Config;
+use crate::icmp::{ECHO_REPLY, HIGH_ECHO_REPLY_RATE, REDIRECT};
+use crate::oui::format_mac;
+use crate::proc_stats::InterfaceCounters;
+use crate::syslog::{Severity, Syslog};
+use crate::util::{format_rate, BitrateBase, DisplayUnit};
+use crate::watchlist::Watchlist;
+
+// App keeps at most this many alerts, dropping the oldest
+pub const MAX_ALERTS: usize = 100;
+// Open connections an IP may have before `--max-connections-per-ip` alerts
+pub const DEFAULT_MAX_CONNECTIONS_PER_IP: u16 = 500;
+
+pub enum AlertKind {
+    // Average rate in bytes/s that crossed the bandwidth threshold, itself in bits/s
+    Bandwidth { rate: f64, threshold: f64 },
+    // Echo replies per second, often the reflected half of a ping flood
+    IcmpEchoReplies(f64),
+    // A host telling others to change their route, expected only from routers
+    IcmpRedirect,
+    // The IP was seen behind a second Ethernet address
+    DuplicateIp { mac1: [u8; 6], mac2: [u8; 6] },
+    // The IP's packets arrived with a TTL far from the previous one
+    TtlVariation { old_ttl: u8, new_ttl: u8 },
+    // Errors the kernel counted on a capture interface since the previous tick; `ip` is its address
+    InterfaceError { interface: String, errors: u64 },
+    // More live sessions than the limit: a port scan, or a server with many idle clients
+    Connections { count: u16, limit: u16 },
+}
+
+pub struct Alert {
+    pub time: DateTime<Local>,
+    pub ip: Ipv4Addr,
+    pub kind: AlertKind,
+}
+
+impl Alert {
+    pub fn describe(&self) -> String {
+        let time = self.time.format("%H:%M:%S");
+        match self.kind {
+            AlertKind::Bandwidth { rate, .. } => {
+                format!("{} {} exceeded threshold: {}", time, self.ip, format_rate(rate, DisplayUnit::Bits, BitrateBase::Decimal1000))
+            }
+            AlertKind::IcmpEchoReplies(rate) => format!("{} {} is sending {:.0} ICMP echo replies/s", time, self.ip, rate),
+            AlertKind::IcmpRedirect => format!("{} {} sent an ICMP redirect", time, self.ip),
+            AlertKind::DuplicateIp { mac1, mac2 } => {
+                format!("{} duplicate IP {} seen behind {} and {}", time, self.ip, format_mac(&mac1), format_mac(&mac2))
+            }
+            AlertKind::TtlVariation { old_ttl, new_ttl } => format!("{} {} TTL changed from {} to {}", time, self.ip, old_ttl, new_ttl),
+            AlertKind::InterfaceError { ref interface, errors } => format!("{} {} reported {} new RX/TX errors", time, interface, errors),
+            AlertKind::Connections { count, limit } => format!("{} {} has {} active connections (limit {})", time, self.ip, count, limit),
+        }
+    }
+
+    // `ALERT type=... key=value ...` for `--syslog`; host-level misbehaviour is an error, traffic levels a warning
+    fn syslog_message(&self) -> (Severity, String) {
+        let ip = self.ip;
+        match self.kind {
+            AlertKind::Bandwidth { rate, threshold } => (
+                Severity::Warning,
+                format!("ALERT type=BandwidthThreshold ip={} rate={} threshold={}", ip, bit_rate(rate), bit_rate(threshold / 8.0)),
+            ),
+            AlertKind::IcmpEchoReplies(rate) => (Severity::Warning, format!("ALERT type=IcmpEchoReplies ip={} rate={:.0}/s", ip, rate)),
+            AlertKind::IcmpRedirect => (Severity::Warning, format!("ALERT type=IcmpRedirect ip={}", ip)),
+            AlertKind::DuplicateIp { mac1, mac2 } => (
+                Severity::Err,
+                format!("ALERT type=DuplicateIp ip={} mac1={} mac2={}", ip, format_mac(&mac1), format_mac(&mac2)),
+            ),
+            AlertKind::TtlVariation { old_ttl, new_ttl } => (
+                Severity::Warning,
+                format!("ALERT type=TtlVariation ip={} old_ttl={} new_ttl={}", ip, old_ttl, new_ttl),
+            ),
+            AlertKind::InterfaceError { ref interface, errors } => (
+                Severity::Err,
+                format!("ALERT type=InterfaceError interface={} ip={} errors={}", interface, ip, errors),
+            ),
+            AlertKind::Connections { count, limit } => (
+                Severity::Warning,
+                format!("ALERT type=Connections ip={} count={} limit={}", ip, count, limit),
+            ),
+        }
+    }
+}
+
+// Bytes/s as bits/s with decimal prefixes, whatever the UI is showing
+fn bit_rate(bytes_per_sec: f64) -> String {
+    format_rate(bytes_per_sec, DisplayUnit::Bits, BitrateBase::Decimal1000).replace(' ', "")
+}
+
+// Bandwidth thresholds (bits/s) checked against the top talkers each tick
+pub struct AlertMonitor {
+    pub global_threshold: Option<f64>,
+    pub per_ip: HashMap<Ipv4Addr, f64>,
+    log: Option<LineWriter<File>>,
+    // Also send every alert to the system log, see `--syslog`
+    syslog: Option<Syslog>,
+    // IPs currently above their threshold, so each crossing alerts once
+    over_threshold: HashSet<Ipv4Addr>,
+    // Same for the echo reply rate; redirects alert once per sender
+    echo_flooding: HashSet<Ipv4Addr>,
+    redirect_senders: HashSet<Ipv4Addr>,
+    // Interfaces whose error counters went up on the last tick
+    erroring_interfaces: HashSet<String>,
+    // Live sessions an IP may have, and the IPs currently above that
+    pub max_connections_per_ip: u16,
+    over_connection_limit: HashSet<Ipv4Addr>,
+}
+
+impl AlertMonitor {
+    pub fn new(global_threshold: Option<f64>, per_ip: HashMap<Ipv4Addr, f64>) -> Self {
+        Self {
+            global_threshold,
+            per_ip,
+            log: None,
+            syslog: None,
+            over_threshold: HashSet::new(),
+            echo_flooding: HashSet::new(),
+            redirect_senders: HashSet::new(),
+            erroring_interfaces: HashSet::new(),
+            max_connections_per_ip: DEFAULT_MAX_CONNECTIONS_PER_IP,
+            over_connection_limit: HashSet::new(),
+        }
+    }
+
+    pub fn open_log(&mut self, path: &Path) -> io::Result<()> {
+        let file = OpenOptions::new().create(true).append(true).open(path)?;
+        self.log = Some(LineWriter::new(file));
+        Ok(())
+    }
+
+    pub fn open_syslog(&mut self) -> io::Result<()> {
+        self.syslog = Some(Syslog::connect()?);
+        Ok(())
+    }
+
+    // ARP alerts are kept apart from the others, with the ARP table, but go to the system log alike
+    pub fn report_arp(&mut self, alert: &ArpAlert) -> io::Result<()> {
+        match self.syslog.as_mut() {
+            Some(syslog) => syslog.send(Severity::Err, &alert.syslog_message()),
+            None => Ok(()),
+        }
+    }
+
+    // Raise an alert for every IP whose average rate has just crossed its threshold.
+    // A watchlist threshold overrides `--alert-ip`, which overrides the global one.
+    pub fn check(&mut self, talkers: &[TopTalker], watchlist: &Watchlist, alerts: &mut VecDeque<Alert>) -> io::Result<()> {
+        for talker in talkers {
+            let watched = watchlist.get(&talker.ip).copied().flatten();
+            let Some(threshold) = watched.or(self.per_ip.get(&talker.ip).copied()).or(self.global_threshold) else {
+                continue;
+            };
+            let bits_per_sec = talker.avg_bps * 8.0;
+            if bits_per_sec <= threshold {
+                self.over_threshold.remove(&talker.ip);
+                continue;
+            }
+            if self.over_threshold.insert(talker.ip) {
+                let kind = AlertKind::Bandwidth { rate: talker.avg_bps, threshold };
+                self.raise(Alert { time: Local::now(), ip: talker.ip, kind }, alerts)?;
+            }
+        }
+        Ok(())
+    }
+
+    // Echo replies and redirects sent during the last tick, counted per sender
+    pub fn check_icmp(&mut self, senders: &HashMap<(Ipv4Addr, u8), u64>, config: &Config, alerts: &mut VecDeque<Alert>) -> io::Result<()> {
+        let mut flooding = HashSet::new();
+        for (&(ip, icmp_type), &packets) in senders {
+            let kind = match icmp_type {
+                ECHO_REPLY => {
+                    let rate = config.per_second(packets);
+                    if rate < HIGH_ECHO_REPLY_RATE {
+                        continue;
+                    }
+                    flooding.insert(ip);
+                    if self.echo_flooding.contains(&ip) {
+                        continue;
+                    }
+                    AlertKind::IcmpEchoReplies(rate)
+                }
+                REDIRECT if self.redirect_senders.insert(ip) => AlertKind::IcmpRedirect,
+                _ => continue,
+            };
+            self.raise(Alert { time: Local::now(), ip, kind }, alerts)?;
+        }
+        self.echo_flooding = flooding;
+        Ok(())
+    }
+
+    // Live sessions per IP; an IP alerts once each time it goes over the limit
+    pub fn check_connections(&mut self, connections: &HashMap<Ipv4Addr, u16>, alerts: &mut VecDeque<Alert>) -> io::Result<()> {
+        let limit = self.max_connections_per_ip;
+        self.over_connection_limit.retain(|ip| connections.get(ip).is_some_and(|&count| count > limit));
+        for (&ip, &count) in connections {
+            if count > limit && self.over_connection_limit.insert(ip) {
+                self.raise(Alert { time: Local::now(), ip, kind: AlertKind::Connections { count, limit } }, alerts)?;
+            }
+        }
+        Ok(())
+    }
+
+    // Anomalies found by the capture threads since the last tick
+    pub fn check_anomalies(&mut self, events: &[AnomalyEvent], alerts: &mut VecDeque<Alert>) -> io::Result<()> {
+        for event in events {
+            let alert = match *event {
+                AnomalyEvent::DuplicateIp { ip, mac1, mac2, time } => Alert { time, ip, kind: AlertKind::DuplicateIp { mac1, mac2 } },
+                AnomalyEvent::TtlVariation { ip, old_ttl, new_ttl, time } => Alert { time, ip, kind: AlertKind::TtlVariation { old_ttl, new_ttl } },
+            };
+            self.raise(alert, alerts)?;
+        }
+        Ok(())
+    }
+
+    // Kernel counters of one interface a tick apart; a run of ticks with new errors alerts once
+    pub fn check_interface_errors(
+        &mut self,
+        interface: &str,
+        ip: Ipv4Addr,
+        previous: Option<InterfaceCounters>,
+        current: Option<InterfaceCounters>,
+        alerts: &mut VecDeque<Alert>,
+    ) -> io::Result<()> {
+        let (Some(previous), Some(current)) = (previous, current) else {
+            return Ok(());
+        };
+        // Counters restart from zero when a driver is reloaded
+        let errors = current.errors().saturating_sub(previous.errors());
+        if errors == 0 {
+            self.erroring_interfaces.remove(interface);
+            return Ok(());
+        }
+        if self.erroring_interfaces.insert(interface.to_string()) {
+            self.raise(Alert { time: Local::now(), ip, kind: AlertKind::InterfaceError { interface: interface.to_string(), errors } }, alerts)?;
+        }
+        Ok(())
+    }
+
+    fn raise(&mut self, alert: Alert, alerts: &mut VecDeque<Alert>) -> io::Result<()> {
+        if let Some(log) = self.log.as_mut() {
+            writeln!(log, "{}", alert.describe())?;
+        }
+        if let Some(syslog) = self.syslog.as_mut() {
+            let (severity, message) = alert.syslog_message();
+            syslog.send(severity, &message)?;
+        }
+        if alerts.len() == MAX_ALERTS {
+            alerts.pop_front();
+        }
+        alerts.push_back(alert);
+        Ok(())
+    }
+}
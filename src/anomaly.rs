@@ -0,0 +1,62 @@
+use std::{collections::{HashMap, HashSet}, net::Ipv4Addr};
+use chrono::{DateTime, Local};
+
+// App keeps at most this many anomaly events, dropping the oldest
+pub const MAX_ANOMALIES: usize = 100;
+// Hops a host's TTL may move between packets before it counts as a different path
+const TTL_VARIATION: u8 = 10;
+
+#[derive(Clone)]
+pub enum AnomalyEvent {
+    // One IP seen behind two Ethernet addresses: ARP spoofing, a migrated VM or a clashing static IP
+    DuplicateIp { ip: Ipv4Addr, mac1: [u8; 6], mac2: [u8; 6], time: DateTime<Local> },
+    // Packets from one IP arriving with far apart TTLs: route flapping, asymmetric load balancing or spoofing
+    TtlVariation { ip: Ipv4Addr, old_ttl: u8, new_ttl: u8, time: DateTime<Local> },
+}
+
+impl AnomalyEvent {
+    pub fn ip(&self) -> Ipv4Addr {
+        match self {
+            AnomalyEvent::DuplicateIp { ip, .. } | AnomalyEvent::TtlVariation { ip, .. } => *ip,
+        }
+    }
+}
+
+// Ethernet addresses every IP on one capture was seen behind and the TTLs it sent with.
+// Owned by the capture thread; the events it returns go out with the tick's counters.
+#[derive(Default)]
+pub struct AnomalyTracker {
+    seen_macs_for_ip: HashMap<Ipv4Addr, HashSet<[u8; 6]>>,
+    // Last TTL and every TTL seen from each IP
+    seen_ttls_for_ip: HashMap<Ipv4Addr, (u8, HashSet<u8>)>,
+}
+
+impl AnomalyTracker {
+    // Note that `ip` was carried in a frame to or from `mac`; each new MAC past the first raises an event
+    pub fn record(&mut self, ip: Ipv4Addr, mac: [u8; 6]) -> Option<AnomalyEvent> {
+        // Broadcast and multicast frames say nothing about who owns the address
+        if mac[0] & 0x01 != 0 {
+            return None;
+        }
+        let macs = self.seen_macs_for_ip.entry(ip).or_default();
+        if macs.contains(&mac) {
+            return None;
+        }
+        let event = macs.iter().next().map(|&mac1| AnomalyEvent::DuplicateIp { ip, mac1, mac2: mac, time: Local::now() });
+        macs.insert(mac);
+        event
+    }
+
+    // Note the TTL of a packet sent by `ip`. A jump of more than `TTL_VARIATION` to a value not seen
+    // from it before raises an event, so two paths taking turns only report once.
+    pub fn record_ttl(&mut self, ip: Ipv4Addr, ttl: u8) -> Option<AnomalyEvent> {
+        let Some((last, seen)) = self.seen_ttls_for_ip.get_mut(&ip) else {
+            self.seen_ttls_for_ip.insert(ip, (ttl, HashSet::from([ttl])));
+            return None;
+        };
+        let event = (seen.insert(ttl) && last.abs_diff(ttl) > TTL_VARIATION)
+            .then(|| AnomalyEvent::TtlVariation { ip, old_ttl: *last, new_ttl: ttl, time: Local::now() });
+        *last = ttl;
+        event
+    }
+}
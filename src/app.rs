@@ -1,143 +1,2646 @@
 use std::{
-    collections::{HashMap, VecDeque},
-    net::Ipv4Addr,
-    sync::{Arc, Mutex},
-    time::Instant,
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet, VecDeque},
+    io,
+    net::{IpAddr, Ipv4Addr},
+    path::PathBuf,
+    sync::{atomic::{AtomicBool, Ordering}, mpsc::Receiver, Arc, Mutex},
+    ops::AddAssign,
+    time::{Duration, Instant},
 };
 use chrono::{DateTime, Local};
-use crate::constants::{MAX_SAMPLES, TICK_RATE_MS};
+use pcap::Stat;
+use serde::Serialize;
+use glob::{MatchOptions, Pattern};
+use pnet::ipnetwork::Ipv4Network;
+use crate::alert::{Alert, AlertMonitor, MAX_ALERTS};
+use crate::anomaly::{AnomalyEvent, MAX_ANOMALIES};
+use crate::arp::{ArpAlert, ArpState, MAX_ARP_ALERTS};
+use crate::clipboard::{Clipboard, CopyTarget};
+use crate::columns::{default_columns, Column, ColumnSpec};
+use crate::config::Config;
+use crate::constants::{DEFAULT_DISPLAY_LIMIT, DISPLAY_LIMIT_STEP, MAX_TICK_RATE_MS, MIN_TICK_RATE_MS, RENDER_SAMPLES, RUNTIME_TICK_RATE};
+use crate::csv_log::{CsvLogger, CsvRow};
+use crate::double_buffer::DoubleBuffer;
+use crate::dns::{DnsCacheEntry, DnsResolver, DnsResult, DEFAULT_DNS_TTL_SECS, MAX_DNS_PREFETCH};
+use crate::geoip::GeoLookup;
+use crate::history::RateHistory;
+use crate::history_db::{parse_range, HistoryDb, MinuteRecord};
+use crate::json_log::{JsonLogger, TickSummary, TrafficRecord};
+use crate::ring_buffer::RingBuffer;
+use crate::report::{HtmlReporter, ReportSnapshot, ReportTalker};
+use crate::metrics::{IpMetrics, MetricsSnapshot};
+use crate::proc_stats::{self, InterfaceCounters};
+use crate::influx::{self, InfluxWriter};
+use crate::netflow::{NetFlowExporter, FLOW_IDLE_TIMEOUT};
+use crate::network::{get_local_networks, group_ip_to_subnet, interface_name};
+use crate::session::{
+    client_key, normalize_session_key, ClosedSession, Direction, EndReason, SessionDelta, SessionKey, SessionRow, TcpFlagCounts, TcpState,
+    MAX_CLOSED_SESSIONS, PROTO_TCP, PROTO_UDP, SESSION_IDLE_TIMEOUT,
+};
+use crate::theme::Theme;
+use crate::topology::Topology;
+use crate::util::{format_rate, BitrateBase, DisplayUnit, GraphStyle, TotalUnit};
+use crate::watchlist::{self, Watchlist};
+use crate::ws::{WsFrame, WsServer, WsTalker};
+use crate::snapshot::{self, local_time, AppSnapshot, HostSnapshot, InterfaceSnapshot, SCHEMA_VERSION};
+
+// Bytes and packets counted for one key during a tick
+#[derive(Clone, Copy, Default)]
+pub struct Traffic {
+    pub bytes: u64,
+    pub packets: u64,
+}
+
+impl Traffic {
+    pub fn packet(len: u64) -> Self {
+        Traffic { bytes: len, packets: 1 }
+    }
+}
+
+impl AddAssign for Traffic {
+    fn add_assign(&mut self, other: Self) {
+        self.bytes += other.bytes;
+        self.packets += other.packets;
+    }
+}
+
+// Bytes counted for one IP during a tick, split by transport protocol
+#[derive(Clone, Copy, Default, Serialize)]
+pub struct ProtoDelta {
+    pub tcp: u64,
+    pub udp: u64,
+    pub other: u64,
+}
+
+impl ProtoDelta {
+    // `protocol` is the IPv4 next-level protocol number
+    pub fn packet(protocol: u8, len: u64) -> Self {
+        match protocol {
+            PROTO_TCP => ProtoDelta { tcp: len, ..Default::default() },
+            PROTO_UDP => ProtoDelta { udp: len, ..Default::default() },
+            _ => ProtoDelta { other: len, ..Default::default() },
+        }
+    }
+
+    pub fn total(&self) -> u64 {
+        self.tcp + self.udp + self.other
+    }
+}
+
+impl AddAssign for ProtoDelta {
+    fn add_assign(&mut self, other: Self) {
+        self.tcp += other.tcp;
+        self.udp += other.udp;
+        self.other += other.other;
+    }
+}
 
-// From capture thread to UI thread
+// From capture thread to UI thread. Shared as a plain `Arc`: the tick's counters are
+// double-buffered, so the capture thread fills one copy without taking a lock while a
+// tick swaps out and reads the other.
 pub struct SharedStats {
-    pub traffic_delta: HashMap<Ipv4Addr, u64>,
-    pub rx_delta: u64,
-    pub tx_delta: u64,
+    pub deltas: DoubleBuffer<TickDelta>,
+    // Outcome of the last BPF filter change: the applied expression or the libpcap error
+    pub bpf_result: Mutex<Option<Result<String, String>>>,
+    // Outcome of the last promiscuous mode switch: the new setting or the libpcap error
+    pub promisc_result: Mutex<Option<Result<bool, String>>>,
+    // Last error from the `--write-pcap` writer; the writer stops after reporting it
+    pub write_error: Mutex<Option<String>>,
+    // Why the kernel pre-filter could not be installed; the capture filters in userspace instead
+    pub prefilter_error: Mutex<Option<String>>,
+    // IP -> MAC mappings seen in ARP traffic and any conflicting announcements
+    pub arp: Mutex<ArpState>,
+    // Set once the capture has nothing more to deliver (end of a replayed file)
+    pub capture_done: AtomicBool,
+}
+
+impl SharedStats {
+    pub fn new() -> Self {
+        Self {
+            deltas: DoubleBuffer::new(),
+            bpf_result: Mutex::new(None),
+            promisc_result: Mutex::new(None),
+            write_error: Mutex::new(None),
+            prefilter_error: Mutex::new(None),
+            arp: Mutex::new(ArpState::default()),
+            capture_done: AtomicBool::new(false),
+        }
+    }
+}
+
+impl Default for SharedStats {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 // Single IP history record
+#[derive(Clone)]
 pub struct IpHistory {
-    pub samples: VecDeque<u64>,
+    pub samples: RateHistory<u64>,
+    pub rx_samples: RateHistory<u64>,
+    pub tx_samples: RateHistory<u64>,
     pub total_sum: u64,
+    pub rx_sum: u64,
+    pub tx_sum: u64,
     pub peak_rate: f64,
+    pub peak_rx: f64,
+    pub peak_tx: f64,
     pub peak_time: DateTime<Local>,
+    // Packet counts over the same window, for the packets/s display unit
+    pub packet_samples: RateHistory<u64>,
+    pub rx_packet_samples: RateHistory<u64>,
+    pub tx_packet_samples: RateHistory<u64>,
+    pub packet_sum: u64,
+    pub rx_packet_sum: u64,
+    pub tx_packet_sum: u64,
+    pub peak_pps: f64,
+    // Ethernet address the IP was last seen behind; all zeros until a frame is seen
+    pub mac: [u8; 6],
+    // Last tick with any traffic, for pruning idle hosts
+    pub last_seen: Instant,
+    // Bytes since the IP was first seen; unlike `total_sum` nothing is ever evicted
+    pub lifetime_bytes: u64,
+    pub first_seen: DateTime<Local>,
+    // Wall-clock time of the last tick with traffic, for display and export; `last_seen` drives the timeouts
+    pub last_seen_time: DateTime<Local>,
+    // Bytes since `first_seen` by transport protocol
+    pub tcp_bytes: u64,
+    pub udp_bytes: u64,
+    pub other_bytes: u64,
+    // Measured length of each tick in the window; ticks run late when the machine is busy
+    tick_durations_secs: RateHistory<f64>,
+    duration_sum: f64,
+    // Direction of the rate over the last `TREND_WINDOW` ticks
+    pub trend: Trend,
+    // TTL of the latest packet the IP sent, and every TTL seen from it
+    pub ttl: Option<u8>,
+    pub observed_ttls: BTreeSet<u8>,
+    // Bytes since `first_seen` per DSCP codepoint, and how many of its packets were marked
+    // ECN Congestion Experienced out of all of them
+    pub dscp_distribution: HashMap<u8, u64>,
+    pub ecn_ce_packets: u64,
+    pub lifetime_packets: u64,
+    // Sessions with the IP at either end that have not ended or expired yet
+    pub active_connections: u16,
+}
+
+// Push a sample into a sliding window, keeping the running sum in step
+fn push_sample(samples: &mut RateHistory<u64>, sum: &mut u64, bytes: u64) {
+    *sum += bytes;
+    if let Some(removed) = samples.push(bytes) {
+        *sum -= removed;
+    }
 }
 
 impl IpHistory {
-    pub fn new() -> Self {
+    pub fn new(config: Config) -> Self {
+        let max_samples = config.max_samples();
         Self {
-            samples: VecDeque::with_capacity(MAX_SAMPLES),
+            samples: RateHistory::new(max_samples),
+            rx_samples: RateHistory::new(max_samples),
+            tx_samples: RateHistory::new(max_samples),
             total_sum: 0,
+            rx_sum: 0,
+            tx_sum: 0,
             peak_rate: 0.0,
+            peak_rx: 0.0,
+            peak_tx: 0.0,
             peak_time: Local::now(),
+            packet_samples: RateHistory::new(max_samples),
+            rx_packet_samples: RateHistory::new(max_samples),
+            tx_packet_samples: RateHistory::new(max_samples),
+            packet_sum: 0,
+            rx_packet_sum: 0,
+            tx_packet_sum: 0,
+            peak_pps: 0.0,
+            mac: [0; 6],
+            last_seen: Instant::now(),
+            lifetime_bytes: 0,
+            first_seen: Local::now(),
+            last_seen_time: Local::now(),
+            tcp_bytes: 0,
+            udp_bytes: 0,
+            other_bytes: 0,
+            tick_durations_secs: RateHistory::new(max_samples),
+            duration_sum: 0.0,
+            trend: Trend::Stable,
+            ttl: None,
+            observed_ttls: BTreeSet::new(),
+            dscp_distribution: HashMap::new(),
+            ecn_ce_packets: 0,
+            lifetime_packets: 0,
+            active_connections: 0,
         }
     }
 
-    pub fn update(&mut self, bytes: u64) -> f64 {
-        let instant_rate = (bytes as f64) * (1000.0 / TICK_RATE_MS as f64);
+    // `elapsed_secs` is the wall-clock length of the tick that produced `rx` and `tx`
+    pub fn update(&mut self, rx: Traffic, tx: Traffic, elapsed_secs: f64) -> f64 {
+        let (rx_bytes, tx_bytes) = (rx.bytes, tx.bytes);
+        if rx_bytes + tx_bytes > 0 {
+            self.last_seen = Instant::now();
+            self.last_seen_time = Local::now();
+        }
+        self.lifetime_bytes += rx_bytes + tx_bytes;
+        self.lifetime_packets += rx.packets + tx.packets;
+        let to_rate = |bytes: u64| bytes as f64 / elapsed_secs;
+
+        self.duration_sum += elapsed_secs;
+        if let Some(removed) = self.tick_durations_secs.push(elapsed_secs) {
+            self.duration_sum -= removed;
+        }
+
+        let instant_rate = to_rate(rx_bytes + tx_bytes);
 
         if instant_rate > self.peak_rate {
             self.peak_rate = instant_rate;
             self.peak_time = Local::now();
         }
+        self.peak_rx = self.peak_rx.max(to_rate(rx_bytes));
+        self.peak_tx = self.peak_tx.max(to_rate(tx_bytes));
 
-        self.samples.push_back(bytes);
-        self.total_sum += bytes;
-        if self.samples.len() > MAX_SAMPLES {
-            if let Some(removed) = self.samples.pop_front() {
-                self.total_sum -= removed;
-            }
+        push_sample(&mut self.samples, &mut self.total_sum, rx_bytes + tx_bytes);
+        push_sample(&mut self.rx_samples, &mut self.rx_sum, rx_bytes);
+        push_sample(&mut self.tx_samples, &mut self.tx_sum, tx_bytes);
+
+        self.peak_pps = self.peak_pps.max(to_rate(rx.packets + tx.packets));
+        push_sample(&mut self.packet_samples, &mut self.packet_sum, rx.packets + tx.packets);
+        push_sample(&mut self.rx_packet_samples, &mut self.rx_packet_sum, rx.packets);
+        push_sample(&mut self.tx_packet_samples, &mut self.tx_packet_sum, tx.packets);
+
+        self.trend = self.compute_trend();
+        self.average(self.total_sum)
+    }
+
+    // Least-squares slope of the last `TREND_WINDOW` per-tick rates, as the change it
+    // predicts across the window relative to their mean
+    fn compute_trend(&self) -> Trend {
+        // Both windows get one entry per tick, so they line up
+        if self.samples.len() < TREND_WINDOW {
+            return Trend::Stable;
+        }
+        let rates: Vec<f64> = self.samples.iter()
+            .zip(self.tick_durations_secs.iter())
+            .skip(self.samples.len() - TREND_WINDOW)
+            .map(|(bytes, secs)| *bytes as f64 / secs)
+            .collect();
+
+        let n = rates.len() as f64;
+        let mean_x = (n - 1.0) / 2.0;
+        let mean_y = rates.iter().sum::<f64>() / n;
+        if mean_y <= 0.0 {
+            return Trend::Stable;
+        }
+        let (mut covariance, mut variance) = (0.0, 0.0);
+        for (x, y) in rates.iter().enumerate() {
+            let dx = x as f64 - mean_x;
+            covariance += dx * (y - mean_y);
+            variance += dx * dx;
+        }
+        let change = covariance / variance * (n - 1.0) / mean_y;
+        if change > TREND_THRESHOLD {
+            Trend::Rising
+        } else if change < -TREND_THRESHOLD {
+            Trend::Falling
+        } else {
+            Trend::Stable
+        }
+    }
+
+    fn snapshot(&self, ip: Ipv4Addr) -> HostSnapshot {
+        HostSnapshot {
+            ip,
+            mac: self.mac,
+            peak_rate: self.peak_rate,
+            peak_rx: self.peak_rx,
+            peak_tx: self.peak_tx,
+            peak_pps: self.peak_pps,
+            peak_time: self.peak_time.timestamp(),
+            lifetime_bytes: self.lifetime_bytes,
+            first_seen: self.first_seen.timestamp(),
+            tcp_bytes: self.tcp_bytes,
+            udp_bytes: self.udp_bytes,
+            other_bytes: self.other_bytes,
+        }
+    }
+
+    // A host from a `--resume` snapshot: its records and lifetime totals, with an empty window
+    fn restored(host: &HostSnapshot, config: Config) -> Self {
+        Self {
+            mac: host.mac,
+            peak_rate: host.peak_rate,
+            peak_rx: host.peak_rx,
+            peak_tx: host.peak_tx,
+            peak_pps: host.peak_pps,
+            peak_time: local_time(host.peak_time),
+            lifetime_bytes: host.lifetime_bytes,
+            first_seen: local_time(host.first_seen),
+            tcp_bytes: host.tcp_bytes,
+            udp_bytes: host.udp_bytes,
+            other_bytes: host.other_bytes,
+            ..Self::new(config)
+        }
+    }
+
+    // Share of the packets since `first_seen` marked Congestion Experienced
+    pub fn ecn_ce_ratio(&self) -> f64 {
+        self.ecn_ce_packets as f64 / self.lifetime_packets.max(1) as f64
+    }
+
+    fn record_ttl(&mut self, ttl: u8) {
+        self.ttl = Some(ttl);
+        self.observed_ttls.insert(ttl);
+    }
+
+    fn reset_peaks(&mut self, now: DateTime<Local>) {
+        self.peak_rate = 0.0;
+        self.peak_rx = 0.0;
+        self.peak_tx = 0.0;
+        self.peak_pps = 0.0;
+        self.peak_time = now;
+    }
+
+    pub fn pps(&self) -> f64 {
+        self.average(self.packet_sum)
+    }
+
+    // Mean bytes per packet over the window, 0 when no packets were seen
+    pub fn avg_packet_size(&self) -> f64 {
+        if self.packet_sum == 0 {
+            return 0.0;
         }
+        self.total_sum as f64 / self.packet_sum as f64
+    }
+
+    pub fn rx_pps(&self) -> f64 {
+        self.average(self.rx_packet_sum)
+    }
+
+    pub fn tx_pps(&self) -> f64 {
+        self.average(self.tx_packet_sum)
+    }
+
+    pub fn rx_rate(&self) -> f64 {
+        self.average(self.rx_sum)
+    }
 
-        let duration_secs = self.samples.len() as f64 * (TICK_RATE_MS as f64 / 1000.0);
-        if duration_secs == 0.0 {
+    pub fn tx_rate(&self) -> f64 {
+        self.average(self.tx_sum)
+    }
+
+    // Average per second of `sum` (bytes or packets) over the measured length of the window
+    fn average(&self, sum: u64) -> f64 {
+        if self.duration_sum <= 0.0 {
             0.0
         } else {
-            self.total_sum as f64 / duration_secs
+            sum as f64 / self.duration_sum
         }
     }
 }
 
-// Main application state
-pub struct App {
-    pub rx_history: Vec<f64>,
-    pub tx_history: Vec<f64>,
+// One direction's totals since it was last exported over NetFlow, and when its first and last packets arrived
+#[derive(Clone, Copy)]
+struct FlowTotals {
+    delta: SessionDelta,
+    first: Instant,
+    last: Instant,
+}
+
+// Traffic window for one connection, both directions together, plus the TCP flags seen within the same window
+pub struct SessionState {
+    // The connection as sent by its client; client to server counts as TX and server to client as RX
+    pub client_key: SessionKey,
+    pub history: IpHistory,
+    flag_samples: RateHistory<TcpFlagCounts>,
+    pub window_flags: TcpFlagCounts,
+    // NetFlow v5 records are one-way, so each direction is exported on its own, indexed by `Direction`
+    flows: [FlowTotals; 2],
+    // When the first packet arrived, for the closed session's duration
+    started: Instant,
+    // TCP only, per direction; set from the first tick with packets that way
+    tcp_states: [Option<TcpState>; 2],
+    end_reason: Option<EndReason>,
+    // Since the session was first seen, for the RTx column
+    packets: u64,
+    retransmits: u64,
+}
+
+impl SessionState {
+    fn new(config: Config, client_key: SessionKey) -> Self {
+        let flow = FlowTotals { delta: SessionDelta::default(), first: Instant::now(), last: Instant::now() };
+        Self {
+            client_key,
+            history: IpHistory::new(config),
+            flag_samples: RateHistory::new(config.max_samples()),
+            window_flags: TcpFlagCounts::default(),
+            flows: [flow; 2],
+            started: Instant::now(),
+            tcp_states: [None; 2],
+            end_reason: None,
+            packets: 0,
+            retransmits: 0,
+        }
+    }
+
+    // The furthest state either direction has reached
+    pub fn tcp_state(&self) -> Option<TcpState> {
+        self.tcp_states.iter().flatten().max().copied()
+    }
+
+    // Move one direction's TCP state machine on by the flags it sent this tick
+    fn advance_tcp(&mut self, direction: Direction, flags: TcpFlagCounts) {
+        let current = &mut self.tcp_states[direction as usize];
+        let state = match *current {
+            Some(state) => state.advance(flags),
+            None => TcpState::opened(flags),
+        };
+        if state == TcpState::Closed && *current != Some(TcpState::Closed) {
+            self.end_reason = Some(EndReason::Reset);
+        }
+        *current = Some(state);
+    }
+
+    // Why the session should leave the live table now, if it should
+    fn expired(&self) -> Option<EndReason> {
+        self.end_reason.or((self.history.last_seen.elapsed() >= SESSION_IDLE_TIMEOUT).then_some(EndReason::Idle))
+    }
+
+    // From the first packet to the last one so far
+    fn duration(&self) -> Duration {
+        self.history.last_seen.saturating_duration_since(self.started)
+    }
+
+    fn closed(&self, reason: EndReason) -> ClosedSession {
+        ClosedSession {
+            key: self.client_key,
+            started: self.history.first_seen,
+            duration: self.duration(),
+            total_bytes: self.history.lifetime_bytes,
+            reason,
+        }
+    }
+
+    // Takes this tick's deltas indexed by `Direction`; returns the average bytes/s, like `IpHistory::update`
+    fn update(&mut self, deltas: [SessionDelta; 2], elapsed_secs: f64) -> f64 {
+        let [upload, download] = deltas;
+        let avg_bps = self.history.update(
+            Traffic { bytes: download.bytes, packets: download.packets },
+            Traffic { bytes: upload.bytes, packets: upload.packets },
+            elapsed_secs,
+        );
+        let mut flags = upload.flags;
+        flags += download.flags;
+        self.window_flags += flags;
+        if let Some(removed) = self.flag_samples.push(flags) {
+            self.window_flags -= removed;
+        }
+        self.packets += upload.packets + download.packets;
+        self.retransmits += upload.retransmits + download.retransmits;
+
+        let now = Instant::now();
+        for (flow, delta) in self.flows.iter_mut().zip(deltas) {
+            if delta.packets > 0 {
+                if flow.delta.packets == 0 {
+                    flow.first = now;
+                }
+                flow.last = now;
+                flow.delta += delta;
+            }
+        }
+
+        if self.client_key.proto == PROTO_TCP {
+            for direction in [Direction::ClientToServer, Direction::ServerToClient] {
+                if deltas[direction as usize].packets > 0 {
+                    self.advance_tcp(direction, deltas[direction as usize].flags);
+                }
+            }
+            // A connection is closed once both directions have sent their FIN
+            if self.end_reason.is_none() && self.tcp_states == [Some(TcpState::FinWait); 2] {
+                self.tcp_states = [Some(TcpState::Closed); 2];
+                self.end_reason = Some(EndReason::Fin);
+            }
+        }
+        avg_bps
+    }
+
+    // Segments per second over the window for a count taken from `window_flags`
+    fn flag_rate(&self, count: u64) -> f64 {
+        self.history.average(count)
+    }
+
+    // Share of the session's packets that were retransmissions
+    fn retransmit_rate(&self) -> f64 {
+        if self.packets == 0 { 0.0 } else { self.retransmits as f64 / self.packets as f64 }
+    }
+}
+
+// Samples shown in the Trend sparkline, one bar per sample
+pub const TREND_SAMPLES: usize = 12;
+
+// Ticks fitted for the trend arrow, and the relative change across them that counts as movement
+const TREND_WINDOW: usize = 10;
+const TREND_THRESHOLD: f64 = 0.10;
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Trend {
+    Rising,
+    Falling,
+    Stable,
+}
+
+// How often the device addresses shown in the titles are looked up again
+const ADDRESS_REFRESH_INTERVAL: Duration = Duration::from_secs(30);
+
+// Floor for a measured tick, so a tick right after another cannot divide by zero
+const MIN_TICK_SECS: f64 = 0.001;
+
+// One row of the top talkers table
+pub struct TopTalker {
+    pub ip: Ipv4Addr,
+    pub avg_bps: f64,
+    pub rx_bps: f64,
+    pub tx_bps: f64,
+    pub peak_bps: f64,
+    pub peak_time: DateTime<Local>,
+    // Packets/s counterparts of the rates above
+    pub avg_pps: f64,
+    pub rx_pps: f64,
+    pub tx_pps: f64,
+    pub peak_pps: f64,
+    // Bytes seen within the current history window
+    pub total_bytes: u64,
+    // Bytes since `first_seen`, kept across window rollovers
+    pub lifetime_bytes: u64,
+    pub first_seen: DateTime<Local>,
+    // Last tick the IP had traffic
+    pub last_seen: DateTime<Local>,
+    pub avg_packet_size: f64,
+    // Bytes since `first_seen` by transport protocol, for the detail popup
+    pub proto_bytes: ProtoDelta,
+    // Most recent per-tick byte counts, oldest first, for the Trend sparkline
+    pub trend: Vec<u64>,
+    // Arrow shown in the Trend column
+    pub direction: Trend,
+    pub mac: [u8; 6],
+    // Latest TTL the IP sent with, for the guessed OS next to the hostname
+    pub ttl: Option<u8>,
+    // Live sessions with the IP at either end, for the Conn column
+    pub active_connections: u16,
+}
+
+// One row of the top ports table
+pub struct TopPort {
+    pub port: u16,
+    // Bytes/s during the last tick, averaged over the window and the highest since first seen
+    pub current_bps: f64,
+    pub avg_bps: f64,
+    pub peak_bps: f64,
+    pub current_pps: f64,
+    pub avg_pps: f64,
+    pub peak_pps: f64,
+    // Bytes since the port was first seen
+    pub lifetime_bytes: u64,
+    // Whether most of those bytes were TCP, for the service name
+    pub mostly_tcp: bool,
+}
+
+// Fold one tick of session traffic per port into `histories` and return the rows busiest first.
+// Ports idle for longer than the configured timeout are dropped.
+fn update_ports(
+    histories: &mut HashMap<u16, IpHistory>,
+    per_port: &HashMap<u16, (Traffic, ProtoDelta)>,
+    config: Config,
+    elapsed_secs: f64,
+) -> Vec<TopPort> {
+    for port in per_port.keys() {
+        histories.entry(*port).or_insert_with(|| IpHistory::new(config));
+    }
+    let mut rows = Vec::with_capacity(histories.len());
+    histories.retain(|port, history| {
+        let (traffic, proto) = per_port.get(port).copied().unwrap_or_default();
+        history.tcp_bytes += proto.tcp;
+        history.udp_bytes += proto.udp;
+        let avg_bps = history.update(traffic, Traffic::default(), elapsed_secs);
+        let active = history.last_seen.elapsed() < config.idle_timeout();
+        if active {
+            rows.push(TopPort {
+                port: *port,
+                current_bps: traffic.bytes as f64 / elapsed_secs,
+                avg_bps,
+                peak_bps: history.peak_rate,
+                current_pps: traffic.packets as f64 / elapsed_secs,
+                avg_pps: history.pps(),
+                peak_pps: history.peak_pps,
+                lifetime_bytes: history.lifetime_bytes,
+                mostly_tcp: history.tcp_bytes >= history.udp_bytes,
+            });
+        }
+        active
+    });
+    rows.sort_by(|a, b| b.current_bps.total_cmp(&a.current_bps).then(b.avg_bps.total_cmp(&a.avg_bps)));
+    rows
+}
+
+// How a top talker is shown: the IP itself, or its subnet in CIDR form when aggregating
+fn talker_label(ip: Ipv4Addr, aggregate_prefix: Option<u8>) -> String {
+    match aggregate_prefix {
+        Some(prefix_len) => group_ip_to_subnet(ip, prefix_len).to_string(),
+        None => ip.to_string(),
+    }
+}
+
+// The IP that sent a session's packets, or its subnet's network address when aggregating
+fn session_sender(key: &SessionKey, aggregate_prefix: Option<u8>) -> Option<Ipv4Addr> {
+    let IpAddr::V4(src) = key.src else {
+        return None;
+    };
+    Some(match aggregate_prefix {
+        Some(prefix_len) => group_ip_to_subnet(src, prefix_len).network(),
+        None => src,
+    })
+}
+
+// TCP retransmissions each IP (or subnet) sent, summed over its sessions
+fn retransmits_per_ip(sessions: &HashMap<SessionKey, SessionDelta>, aggregate_prefix: Option<u8>) -> HashMap<Ipv4Addr, u64> {
+    let mut retransmits = HashMap::new();
+    for (key, delta) in sessions.iter().filter(|(_, delta)| delta.retransmits > 0) {
+        if let Some(src) = session_sender(key, aggregate_prefix) {
+            *retransmits.entry(src).or_default() += delta.retransmits;
+        }
+    }
+    retransmits
+}
+
+// Which keyboard handler is active
+#[derive(PartialEq)]
+pub enum InputMode {
+    Normal,
+    BpfPrompt,
+    Search,
+    // `<ip> <range>` typed into the history dialog opened with `h`
+    HistoryQuery,
+}
+
+// Per-minute history of one IP from `--history-db`, drawn full screen until closed
+pub struct HistoryView {
+    pub ip: Ipv4Addr,
+    pub from: i64,
+    pub to: i64,
+    pub records: Vec<MinuteRecord>,
+}
+
+// What changed in the "All" aggregate during one tick, as reported by `App::on_tick`
+#[derive(Debug, Clone, PartialEq)]
+pub enum TickEvent {
+    NewIp(Ipv4Addr),
+    // Average rate in bytes/s after the tick
+    IpUpdated(Ipv4Addr, f64),
+    IpRemoved(Ipv4Addr),
+    // New RX/TX peak records in bytes/s
+    PeakUpdated { rx: f64, tx: f64 },
+}
+
+// Counters filled by a capture thread between two ticks. The capture thread writes
+// into the one `SharedStats::deltas` hands it; a tick swaps it out and merges copies
+// from every interface.
+#[derive(Default)]
+pub struct TickDelta {
+    pub rx: Traffic,
+    pub tx: Traffic,
+    pub rx_per_device: HashMap<u32, Traffic>,
+    pub tx_per_device: HashMap<u32, Traffic>,
+    pub rx_per_ip: HashMap<Ipv4Addr, Traffic>,
+    pub tx_per_ip: HashMap<Ipv4Addr, Traffic>,
+    pub proto_per_ip: HashMap<Ipv4Addr, ProtoDelta>,
+    pub broadcast: Traffic,
+    pub multicast: Traffic,
+    pub multicast_per_group: HashMap<Ipv4Addr, Traffic>,
+    pub vlans: HashMap<u16, u64>,
+    pub macs: HashMap<Ipv4Addr, [u8; 6]>,
+    pub ttls: HashMap<Ipv4Addr, u8>,
+    pub dscp: HashMap<u8, u64>,
+    pub dscp_per_ip: HashMap<(Ipv4Addr, u8), u64>,
+    pub ecn_ce: u64,
+    pub ecn_ce_per_ip: HashMap<Ipv4Addr, u64>,
+    pub sessions: HashMap<SessionKey, SessionDelta>,
+    pub icmp_types: HashMap<(u8, u8), u64>,
+    pub icmp_senders: HashMap<(Ipv4Addr, u8), u64>,
+    // Duplicate IPs and TTL jumps the capture thread spotted, oldest first
+    pub anomalies: Vec<AnomalyEvent>,
+}
+
+impl TickDelta {
+    // Take everything written since the last drain
+    fn drain(stats: &SharedStats) -> Self {
+        stats.deltas.swap(std::mem::take)
+    }
+
+    // Re-key the per-IP counts by the network address of each IP's subnet
+    fn group_by_subnet(&mut self, prefix_len: u8) {
+        for per_ip in [&mut self.rx_per_ip, &mut self.tx_per_ip] {
+            let mut grouped = HashMap::with_capacity(per_ip.len());
+            for (ip, traffic) in per_ip.drain() {
+                *grouped.entry(group_ip_to_subnet(ip, prefix_len).network()).or_default() += traffic;
+            }
+            *per_ip = grouped;
+        }
+        let mut grouped: HashMap<Ipv4Addr, ProtoDelta> = HashMap::with_capacity(self.proto_per_ip.len());
+        for (ip, proto) in self.proto_per_ip.drain() {
+            *grouped.entry(group_ip_to_subnet(ip, prefix_len).network()).or_default() += proto;
+        }
+        self.proto_per_ip = grouped;
+        let mut grouped: HashMap<(Ipv4Addr, u8), u64> = HashMap::with_capacity(self.dscp_per_ip.len());
+        for ((ip, dscp), bytes) in self.dscp_per_ip.drain() {
+            *grouped.entry((group_ip_to_subnet(ip, prefix_len).network(), dscp)).or_default() += bytes;
+        }
+        self.dscp_per_ip = grouped;
+        let mut grouped: HashMap<Ipv4Addr, u64> = HashMap::with_capacity(self.ecn_ce_per_ip.len());
+        for (ip, packets) in self.ecn_ce_per_ip.drain() {
+            *grouped.entry(group_ip_to_subnet(ip, prefix_len).network()).or_default() += packets;
+        }
+        self.ecn_ce_per_ip = grouped;
+        // A subnet has no single MAC address or TTL
+        self.macs.clear();
+        self.ttls.clear();
+    }
+
+    fn merge(&mut self, other: &TickDelta) {
+        self.rx += other.rx;
+        self.tx += other.tx;
+        for (index, traffic) in &other.rx_per_device {
+            *self.rx_per_device.entry(*index).or_default() += *traffic;
+        }
+        for (index, traffic) in &other.tx_per_device {
+            *self.tx_per_device.entry(*index).or_default() += *traffic;
+        }
+        self.broadcast += other.broadcast;
+        self.multicast += other.multicast;
+        for (group, traffic) in &other.multicast_per_group {
+            *self.multicast_per_group.entry(*group).or_default() += *traffic;
+        }
+        for (ip, traffic) in &other.rx_per_ip {
+            *self.rx_per_ip.entry(*ip).or_default() += *traffic;
+        }
+        for (ip, traffic) in &other.tx_per_ip {
+            *self.tx_per_ip.entry(*ip).or_default() += *traffic;
+        }
+        for (ip, proto) in &other.proto_per_ip {
+            *self.proto_per_ip.entry(*ip).or_default() += *proto;
+        }
+        for (id, packets) in &other.vlans {
+            *self.vlans.entry(*id).or_default() += *packets;
+        }
+        self.macs.extend(&other.macs);
+        self.ttls.extend(&other.ttls);
+        for (dscp, bytes) in &other.dscp {
+            *self.dscp.entry(*dscp).or_default() += *bytes;
+        }
+        for (key, bytes) in &other.dscp_per_ip {
+            *self.dscp_per_ip.entry(*key).or_default() += *bytes;
+        }
+        self.ecn_ce += other.ecn_ce;
+        for (ip, packets) in &other.ecn_ce_per_ip {
+            *self.ecn_ce_per_ip.entry(*ip).or_default() += *packets;
+        }
+        for (key, delta) in &other.sessions {
+            *self.sessions.entry(*key).or_default() += *delta;
+        }
+        for (key, packets) in &other.icmp_types {
+            *self.icmp_types.entry(*key).or_default() += *packets;
+        }
+        for (key, packets) in &other.icmp_senders {
+            *self.icmp_senders.entry(*key).or_default() += *packets;
+        }
+        self.anomalies.extend(other.anomalies.iter().cloned());
+    }
+}
+
+// Traffic one interface behind a `-i any` capture carried
+pub struct DeviceTraffic {
+    pub name: String,
+    // During the last tick, and bytes since start
+    pub current_rx: Traffic,
+    pub current_tx: Traffic,
+    pub total_rx_bytes: u64,
+    pub total_tx_bytes: u64,
+}
+
+impl DeviceTraffic {
+    // Named once when first seen; an interface gone by then keeps its index as name
+    fn new(index: u32) -> Self {
+        Self {
+            name: interface_name(index).unwrap_or_else(|| format!("if{}", index)),
+            current_rx: Traffic::default(),
+            current_tx: Traffic::default(),
+            total_rx_bytes: 0,
+            total_tx_bytes: 0,
+        }
+    }
+}
+
+// RX/TX history and top talkers for one interface (or the aggregate of all)
+pub struct InterfaceStats {
+    pub name: String,
+    pub rx_history: RateHistory,
+    pub tx_history: RateHistory,
     pub total_rx_bytes: u64,
     pub total_tx_bytes: u64,
     pub peak_rx_record: (f64, DateTime<Local>),
     pub peak_tx_record: (f64, DateTime<Local>),
-    
+    // Packets per tick and packets/s records, for the packets/s display unit
+    pub rx_packet_history: RateHistory,
+    pub tx_packet_history: RateHistory,
+    pub peak_rx_pps_record: (f64, DateTime<Local>),
+    pub peak_tx_pps_record: (f64, DateTime<Local>),
+    // Broadcast and multicast traffic seen during the last tick, and in total
+    pub current_broadcast: Traffic,
+    pub current_multicast: Traffic,
+    pub total_broadcast_bytes: u64,
+    pub total_multicast_bytes: u64,
+    // Every 802.1Q VLAN ID seen so far
+    pub vlans: BTreeSet<u16>,
+    // Per interface split of a `-i any` capture, by kernel index; empty for other devices
+    pub devices: BTreeMap<u32, DeviceTraffic>,
+    // Bytes per DSCP codepoint and ECN CE-marked packets out of all IPv4 packets, since start
+    pub dscp_bytes: HashMap<u8, u64>,
+    pub ecn_ce_packets: u64,
+    pub total_packets: u64,
+
     ip_histories: HashMap<Ipv4Addr, IpHistory>,
-    
+
     // UI display of top talkers
-    pub top_talkers: Vec<(Ipv4Addr, f64, f64, DateTime<Local>)>,
-    pub last_tick: Instant,
+    pub top_talkers: Vec<TopTalker>,
+    // The capture behind this interface has stopped (replay reached end-of-file)
+    pub finished: bool,
+    // Drop counters from the most recent libpcap report
+    pub drops: Option<Stat>,
+    // Link capacity in bits/s, from `--link-speed` or detected; None when unknown
+    pub link_bps: Option<f64>,
+    // First IPv4 address and prefix of the capture device, for the title; only looked up
+    // when `shows_address` is set, which replays and the "All" tab leave clear
+    pub address: Option<Ipv4Network>,
+    pub shows_address: bool,
+    // Whether the live device is in promiscuous mode; None for replays, dry runs and "All"
+    pub promisc: Option<bool>,
+    // Kernel error and drop counters from /proc/net/dev, and their values a tick earlier. None off
+    // Linux, with `--no-proc-stats` or when replaying; "All" holds the sum over the interfaces.
+    pub counters: Option<InterfaceCounters>,
+    previous_counters: Option<InterfaceCounters>,
+    config: Config,
 }
 
-impl App {
-    pub fn new() -> App {
+impl InterfaceStats {
+    // Packets dropped in the last report and their share of everything received
+    pub fn drop_rate(&self) -> Option<(u32, f64)> {
+        let drops = self.drops?;
+        if drops.dropped == 0 {
+            return None;
+        }
+        let seen = drops.received.max(drops.dropped);
+        Some((drops.dropped, drops.dropped as f64 * 100.0 / seen as f64))
+    }
+
+    // Whether the counter picked out by `field` went up since the previous tick
+    pub fn counter_rose(&self, field: fn(&InterfaceCounters) -> u64) -> bool {
+        match (self.previous_counters, self.counters) {
+            (Some(previous), Some(current)) => field(&current) > field(&previous),
+            _ => false,
+        }
+    }
+
+    // RX and TX rates of the last tick as a percentage of the link speed
+    pub fn utilization(&self) -> Option<(f64, f64)> {
+        let link_bps = self.link_bps?;
+        let percent = |history: &RateHistory| self.config.per_second(history.current() as u64) * 8.0 / link_bps * 100.0;
+        Some((percent(&self.rx_history), percent(&self.tx_history)))
+    }
+
+    // "eth0 [192.168.1.10/24]" for a live device, followed by "[VLAN 100]" when all tagged
+    // traffic shares one VLAN or "[multi-VLAN]" for several
+    pub fn display_name(&self) -> String {
+        let mut name = self.name.clone();
+        if self.shows_address {
+            match self.address {
+                Some(address) => name.push_str(&format!(" [{}]", address)),
+                None => name.push_str(" [no IP]"),
+            }
+        }
+        match self.vlans.len() {
+            0 => name,
+            1 => format!("{} [VLAN {}]", name, self.vlans.first().unwrap()),
+            _ => format!("{} [multi-VLAN]", name),
+        }
+    }
+
+    pub fn new(name: &str, config: Config) -> Self {
         let now = Local::now();
-        App {
-            rx_history: vec![0.0; MAX_SAMPLES],
-            tx_history: vec![0.0; MAX_SAMPLES],
+        Self {
+            name: name.to_string(),
+            rx_history: RateHistory::filled(config.max_samples()),
+            tx_history: RateHistory::filled(config.max_samples()),
             total_rx_bytes: 0,
             total_tx_bytes: 0,
             peak_rx_record: (0.0, now),
             peak_tx_record: (0.0, now),
+            rx_packet_history: RateHistory::filled(config.max_samples()),
+            tx_packet_history: RateHistory::filled(config.max_samples()),
+            peak_rx_pps_record: (0.0, now),
+            peak_tx_pps_record: (0.0, now),
+            current_broadcast: Traffic::default(),
+            current_multicast: Traffic::default(),
+            total_broadcast_bytes: 0,
+            total_multicast_bytes: 0,
+            vlans: BTreeSet::new(),
+            devices: BTreeMap::new(),
+            dscp_bytes: HashMap::new(),
+            ecn_ce_packets: 0,
+            total_packets: 0,
             ip_histories: HashMap::new(),
             top_talkers: vec![],
-            last_tick: Instant::now(),
+            finished: false,
+            drops: None,
+            link_bps: None,
+            address: None,
+            shows_address: false,
+            promisc: None,
+            counters: None,
+            previous_counters: None,
+            config,
         }
     }
 
-    pub fn on_tick(&mut self, shared_stats: &Arc<Mutex<SharedStats>>) {
-        let mut stats = shared_stats.lock().unwrap();
+    pub fn history(&self, ip: &Ipv4Addr) -> Option<&IpHistory> {
+        self.ip_histories.get(ip)
+    }
+
+    fn history_mut(&mut self, ip: Ipv4Addr) -> &mut IpHistory {
+        let config = self.config;
+        self.ip_histories.entry(ip).or_insert_with(|| IpHistory::new(config))
+    }
 
-        // Update overall RX/TX history
-        self.rx_history.remove(0);
-        self.rx_history.push(stats.rx_delta as f64);
-        self.tx_history.remove(0);
-        self.tx_history.push(stats.tx_delta as f64);
+    fn snapshot(&self) -> InterfaceSnapshot {
+        InterfaceSnapshot {
+            name: self.name.clone(),
+            rx_history: self.rx_history.iter().copied().collect(),
+            tx_history: self.tx_history.iter().copied().collect(),
+            total_rx_bytes: self.total_rx_bytes,
+            total_tx_bytes: self.total_tx_bytes,
+            total_broadcast_bytes: self.total_broadcast_bytes,
+            total_multicast_bytes: self.total_multicast_bytes,
+            peak_rx_record: (self.peak_rx_record.0, self.peak_rx_record.1.timestamp()),
+            peak_tx_record: (self.peak_tx_record.0, self.peak_tx_record.1.timestamp()),
+            hosts: self.ip_histories.iter().map(|(ip, history)| history.snapshot(*ip)).collect(),
+        }
+    }
+
+    // Carry on from a saved snapshot; a chart saved with a longer window keeps only its newest samples
+    fn restore(&mut self, snapshot: &InterfaceSnapshot) {
+        for (history, samples) in [(&mut self.rx_history, &snapshot.rx_history), (&mut self.tx_history, &snapshot.tx_history)] {
+            for sample in samples {
+                history.push(*sample);
+            }
+        }
+        self.total_rx_bytes = snapshot.total_rx_bytes;
+        self.total_tx_bytes = snapshot.total_tx_bytes;
+        self.total_broadcast_bytes = snapshot.total_broadcast_bytes;
+        self.total_multicast_bytes = snapshot.total_multicast_bytes;
+        self.peak_rx_record = (snapshot.peak_rx_record.0, local_time(snapshot.peak_rx_record.1));
+        self.peak_tx_record = (snapshot.peak_tx_record.0, local_time(snapshot.peak_tx_record.1));
+        for host in &snapshot.hosts {
+            self.ip_histories.insert(host.ip, IpHistory::restored(host, self.config));
+        }
+    }
+
+    // Forget records, totals and the chart history; the per-IP windows are kept
+    fn reset_peaks(&mut self) {
+        let now = Local::now();
+        for history in [&mut self.rx_history, &mut self.tx_history, &mut self.rx_packet_history, &mut self.tx_packet_history] {
+            history.reset();
+        }
+        self.total_rx_bytes = 0;
+        self.total_tx_bytes = 0;
+        for device in self.devices.values_mut() {
+            device.total_rx_bytes = 0;
+            device.total_tx_bytes = 0;
+        }
+        for record in [&mut self.peak_rx_record, &mut self.peak_tx_record, &mut self.peak_rx_pps_record, &mut self.peak_tx_pps_record] {
+            *record = (0.0, now);
+        }
+        for history in self.ip_histories.values_mut() {
+            history.reset_peaks(now);
+        }
+        // The rows on screen would otherwise show the old peaks until the next tick
+        for talker in &mut self.top_talkers {
+            talker.peak_bps = 0.0;
+            talker.peak_pps = 0.0;
+            talker.peak_time = now;
+        }
+    }
 
-        self.total_rx_bytes += stats.rx_delta;
-        self.total_tx_bytes += stats.tx_delta;
+    fn ingest(&mut self, delta: &TickDelta, elapsed_secs: f64) {
+        // Update overall RX/TX history
+        self.rx_history.push(delta.rx.bytes as f64);
+        self.tx_history.push(delta.tx.bytes as f64);
+        self.rx_packet_history.push(delta.rx.packets as f64);
+        self.tx_packet_history.push(delta.tx.packets as f64);
 
-        let current_rx_rate = (stats.rx_delta as f64) * (1000.0 / TICK_RATE_MS as f64);
-        let current_tx_rate = (stats.tx_delta as f64) * (1000.0 / TICK_RATE_MS as f64);
+        self.total_rx_bytes += delta.rx.bytes;
+        self.total_tx_bytes += delta.tx.bytes;
 
-        if current_rx_rate > self.peak_rx_record.0 {
-            self.peak_rx_record = (current_rx_rate, Local::now());
+        for device in self.devices.values_mut() {
+            device.current_rx = Traffic::default();
+            device.current_tx = Traffic::default();
+        }
+        for (index, traffic) in &delta.rx_per_device {
+            let device = self.devices.entry(*index).or_insert_with(|| DeviceTraffic::new(*index));
+            device.current_rx = *traffic;
+            device.total_rx_bytes += traffic.bytes;
         }
-        if current_tx_rate > self.peak_tx_record.0 {
-            self.peak_tx_record = (current_tx_rate, Local::now());
+        for (index, traffic) in &delta.tx_per_device {
+            let device = self.devices.entry(*index).or_insert_with(|| DeviceTraffic::new(*index));
+            device.current_tx = *traffic;
+            device.total_tx_bytes += traffic.bytes;
         }
 
-        // Update per-IP histories and top talkers
-        let mut all_ips: Vec<Ipv4Addr> = self.ip_histories.keys().cloned().collect();
-        for k in stats.traffic_delta.keys() {
-            if !self.ip_histories.contains_key(k) {
-                all_ips.push(*k);
+        let now = Local::now();
+        for (record, count) in [
+            (&mut self.peak_rx_record, delta.rx.bytes),
+            (&mut self.peak_tx_record, delta.tx.bytes),
+            (&mut self.peak_rx_pps_record, delta.rx.packets),
+            (&mut self.peak_tx_pps_record, delta.tx.packets),
+        ] {
+            let rate = count as f64 / elapsed_secs;
+            if rate > record.0 {
+                *record = (rate, now);
             }
         }
 
-        let mut current_snapshot = Vec::new();
-        for ip in all_ips {
-            let bytes_in = *stats.traffic_delta.get(&ip).unwrap_or(&0);
-            let history = self.ip_histories.entry(ip).or_insert_with(IpHistory::new);
+        self.current_broadcast = delta.broadcast;
+        self.current_multicast = delta.multicast;
+        self.total_broadcast_bytes += delta.broadcast.bytes;
+        self.total_multicast_bytes += delta.multicast.bytes;
+        self.vlans.extend(delta.vlans.keys());
+        for (dscp, bytes) in &delta.dscp {
+            *self.dscp_bytes.entry(*dscp).or_default() += bytes;
+        }
+        self.ecn_ce_packets += delta.ecn_ce;
+        self.total_packets += delta.rx.packets + delta.tx.packets;
+
+        for (ip, ttl) in &delta.ttls {
+            self.history_mut(*ip).record_ttl(*ttl);
+        }
+        for ((ip, dscp), bytes) in &delta.dscp_per_ip {
+            *self.history_mut(*ip).dscp_distribution.entry(*dscp).or_default() += bytes;
+        }
+        for (ip, packets) in &delta.ecn_ce_per_ip {
+            self.history_mut(*ip).ecn_ce_packets += packets;
+        }
+        self.top_talkers = update_talkers(&mut self.ip_histories, &delta.rx_per_ip, &delta.tx_per_ip, &delta.proto_per_ip, &delta.macs, self.config, elapsed_secs);
+    }
+}
+
+// Fold one tick of per-IP traffic into `histories` and return the rows sorted by average rate.
+// IPs idle for longer than the configured timeout are dropped.
+fn update_talkers(
+    histories: &mut HashMap<Ipv4Addr, IpHistory>,
+    rx_per_ip: &HashMap<Ipv4Addr, Traffic>,
+    tx_per_ip: &HashMap<Ipv4Addr, Traffic>,
+    proto_per_ip: &HashMap<Ipv4Addr, ProtoDelta>,
+    macs: &HashMap<Ipv4Addr, [u8; 6]>,
+    config: Config,
+    elapsed_secs: f64,
+) -> Vec<TopTalker> {
+    let all_ips: HashSet<Ipv4Addr> = histories.keys().chain(rx_per_ip.keys()).chain(tx_per_ip.keys()).copied().collect();
+    let mut live_ips: HashSet<Ipv4Addr> = HashSet::with_capacity(all_ips.len());
 
-            let avg_bps = history.update(bytes_in);
+    let mut current_snapshot = Vec::new();
+    for ip in all_ips {
+        let rx = rx_per_ip.get(&ip).copied().unwrap_or_default();
+        let tx = tx_per_ip.get(&ip).copied().unwrap_or_default();
+        let history = histories.entry(ip).or_insert_with(|| IpHistory::new(config));
+        if let Some(mac) = macs.get(&ip) {
+            history.mac = *mac;
+        }
+        if let Some(proto) = proto_per_ip.get(&ip) {
+            history.tcp_bytes += proto.tcp;
+            history.udp_bytes += proto.udp;
+            history.other_bytes += proto.other;
+        }
 
-            if history.total_sum > 0 || history.peak_rate > 0.0 {
-                current_snapshot.push((ip, avg_bps, history.peak_rate, history.peak_time));
-            } else {
-                self.ip_histories.remove(&ip);
-            }
+        let avg_bps = history.update(rx, tx, elapsed_secs);
+
+        if history.last_seen.elapsed() < config.idle_timeout() {
+            live_ips.insert(ip);
+            current_snapshot.push(TopTalker {
+                ip,
+                avg_bps,
+                rx_bps: history.rx_rate(),
+                tx_bps: history.tx_rate(),
+                peak_bps: history.peak_rate,
+                peak_time: history.peak_time,
+                avg_pps: history.pps(),
+                rx_pps: history.rx_pps(),
+                tx_pps: history.tx_pps(),
+                peak_pps: history.peak_pps,
+                total_bytes: history.total_sum,
+                lifetime_bytes: history.lifetime_bytes,
+                first_seen: history.first_seen,
+                last_seen: history.last_seen_time,
+                proto_bytes: ProtoDelta { tcp: history.tcp_bytes, udp: history.udp_bytes, other: history.other_bytes },
+                avg_packet_size: history.avg_packet_size(),
+                trend: history.samples.iter().skip(history.samples.len().saturating_sub(TREND_SAMPLES)).copied().collect(),
+                direction: history.trend,
+                mac: history.mac,
+                ttl: history.ttl,
+                active_connections: history.active_connections,
+            });
         }
+    }
+    histories.retain(|ip, _| live_ips.contains(ip));
+
+    current_snapshot.sort_by(|a, b| b.avg_bps.total_cmp(&a.avg_bps));
+    current_snapshot
+}
+
+// Which table occupies the lower panel
+#[derive(PartialEq)]
+pub enum View {
+    Talkers,
+    Sessions,
+    Multicast,
+    Icmp,
+    ClosedSessions,
+    Ports,
+    Topology,
+}
+
+// Column the top talkers table is ordered by, chosen with keys 1-6
+#[derive(Clone, Copy, PartialEq)]
+pub enum SortColumn {
+    AvgBps,
+    PeakBps,
+    TotalBytes,
+    IpAddr,
+    Hostname,
+    AvgPacketSize,
+}
+
+impl SortColumn {
+    pub const ALL: [SortColumn; 6] = [
+        SortColumn::AvgBps,
+        SortColumn::PeakBps,
+        SortColumn::TotalBytes,
+        SortColumn::IpAddr,
+        SortColumn::Hostname,
+        SortColumn::AvgPacketSize,
+    ];
+
+    // Rates and totals start with the biggest first, addresses and names alphabetically
+    fn default_ascending(self) -> bool {
+        matches!(self, SortColumn::IpAddr | SortColumn::Hostname)
+    }
+}
+
+// Main application state
+pub struct App {
+    pub config: Config,
+    pub theme: Arc<Theme>,
+    // Where the theme came from (preset name or TOML path), re-read on SIGHUP
+    pub theme_source: Option<String>,
+    pub theme_error: Option<String>,
+    // One entry per captured interface, followed by the "All" aggregate
+    pub interfaces: Vec<InterfaceStats>,
+    // Index into `interfaces` of the tab being displayed
+    pub active_tab: usize,
+    pub last_tick: Instant,
+    // Measured length of the last tick, which the exported per-second rates divide by
+    last_tick_secs: f64,
+
+    // Top talkers table scrolling: first visible row and highlighted row
+    pub table_offset: usize,
+    pub selected_row: Option<usize>,
+    // Number of table rows that fit on screen, updated by the UI on each draw
+    pub visible_rows: usize,
+    // Upper bound on the rows listed in each table
+    pub display_limit: usize,
+
+    // Detail popup for the selected talker
+    pub show_detail: bool,
+    // ARP table popup, with the mappings from every capture and the poisoning alerts raised so far
+    pub show_arp: bool,
+    // Keybinding help popup and how many lines it is scrolled down
+    pub show_help: bool,
+    pub help_scroll: u16,
+    pub arp_table: BTreeMap<Ipv4Addr, [u8; 6]>,
+    pub arp_alerts: VecDeque<ArpAlert>,
+    // Duplicate IPs and other anomalies from every capture, oldest first
+    pub anomalies: VecDeque<AnomalyEvent>,
+    // Order, widths and visibility of the talkers table columns, from `--columns`;
+    // `m` toggles the MAC / Vendor column and `T` the lifetime Total column
+    pub columns: Vec<ColumnSpec>,
+    // Bookmarked IPs (`w`), pinned to the top of the talkers table, and the
+    // `--watchlist` file they were loaded from and are saved back to on exit
+    pub watchlist: Watchlist,
+    pub watchlist_path: Option<PathBuf>,
+    // Where totals and peaks are saved on exit for `--resume`; None when replaying
+    pub snapshot_path: Option<PathBuf>,
+
+    pub input_mode: InputMode,
+    pub input_buffer: String,
+    pub active_bpf: Option<String>,
+    pub bpf_error: Option<String>,
+    // Why the last promiscuous mode switch failed, until one succeeds
+    pub promisc_error: Option<String>,
+    // Search bar text; only top talkers whose address or hostname match are listed
+    pub filter_query: String,
+    filter_pattern: Option<Pattern>,
+
+    // Optional per-tick CSV/JSON traffic logs and the last error they reported
+    pub csv_log: Option<CsvLogger>,
+    pub json_log: Option<JsonLogger>,
+    // Periodically rewritten HTML report, from `--html-report`
+    pub html_report: Option<HtmlReporter>,
+    // Per-minute traffic kept in SQLite by `--history-db`, the query on screen and its last error
+    pub history_db: Option<HistoryDb>,
+    pub history_view: Option<HistoryView>,
+    pub history_error: Option<String>,
+    // One per capture, in the same order as `interfaces`; None for replays
+    pub drop_receivers: Vec<Option<Receiver<Stat>>>,
+    // TCP flags each IP sent during the last tick, for the JSON records
+    tick_flags: HashMap<Ipv4Addr, TcpFlagCounts>,
+    tick_retransmits: HashMap<Ipv4Addr, u64>,
+    // Bytes per transport protocol for each IP (or subnet) during the last tick
+    tick_proto: HashMap<Ipv4Addr, ProtoDelta>,
+    // Published on `/metrics` when `--metrics-addr` is given
+    pub metrics: Option<Arc<Mutex<MetricsSnapshot>>>,
+    // Streams each tick to browser dashboards when `--ws-addr` is given
+    pub ws_server: Option<WsServer>,
+    // Exports finished sessions when `--netflow-collector` is given
+    pub netflow: Option<NetFlowExporter>,
+    // Writes interface and per-IP metrics when `--influxdb-url` is given
+    pub influx: Option<InfluxWriter>,
+    pub log_warning: Option<String>,
+    // When `R` last reset the statistics, for the status bar confirmation
+    pub stats_reset_at: Option<(DateTime<Local>, Instant)>,
+    // When `[` / `]` last changed the tick rate, for the same kind of confirmation
+    pub tick_changed_at: Option<Instant>,
+    // What `y` did with the selected IP, shown in the status bar for a moment
+    pub copy_notice: Option<(String, Instant)>,
+    // Held open so X11/Wayland keep serving what `y` copied
+    clipboard: Clipboard,
+    // How many prefetch lookups were queued at startup, and when
+    pub dns_prefetch_notice: Option<(usize, Instant)>,
+    // Why a capture fell back to filtering in userspace, and when that was reported
+    pub prefilter_notice: Option<(String, Instant)>,
+    // IPs queued by the prefetch so far; `None` once the first tick has added the known IPs
+    dns_prefetched: Option<HashSet<Ipv4Addr>>,
+    // When the device addresses in the titles were last looked up
+    addresses_checked_at: Instant,
+    // Wall-clock time of the last `RENDER_SAMPLES` frames, oldest first
+    pub render_times: RingBuffer<Duration, RENDER_SAMPLES>,
+    // Show those render times as a sparkline under the graphs
+    pub debug_perf: bool,
+    // Redraw interval from `--ui-tick`; None redraws after every tick
+    pub ui_tick: Option<Duration>,
+
+    // While paused the capture threads keep accumulating but nothing is consumed
+    pub paused: bool,
+    // Throw away what accumulated during a pause instead of folding it into the next tick
+    pub discard_on_resume: bool,
+    // Resolve port numbers to service names in the session table
+    pub service_names: bool,
+    pub display_unit: DisplayUnit,
+    pub rate_base: BitrateBase,
+    // Prefixes for byte totals, cycled with `U`
+    pub total_unit: TotalUnit,
+    pub graph_style: GraphStyle,
+    // Download/upload graphs on a log2 y axis, toggled with `l`
+    pub log_scale: bool,
+    // Group top talkers into subnets of this prefix length (`--aggregate-subnet`)
+    pub aggregate_prefix: Option<u8>,
+
+    pub view: View,
+    pub sort_column: SortColumn,
+    pub sort_ascending: bool,
+    // Per-session rates across all interfaces, plus the sorted table snapshot
+    pub session_stats: HashMap<SessionKey, SessionState>,
+    pub top_sessions: Vec<SessionRow>,
+    // Order the sessions table by how long each connection has lasted rather than by bandwidth, toggled with `D`
+    pub sessions_by_duration: bool,
+    // Live sessions per IPv4 endpoint, counted up as sessions appear and down as they end
+    pub connections_per_ip: HashMap<Ipv4Addr, u16>,
+    // Sessions that ended or went idle, oldest first, for the `c` table
+    pub closed_sessions: VecDeque<ClosedSession>,
+    // Per-group rates for multicast destinations across all interfaces
+    pub multicast_stats: HashMap<Ipv4Addr, IpHistory>,
+    // ICMP packets per (type, code) since start, across all interfaces
+    pub icmp_type_counts: BTreeMap<(u8, u8), u64>,
+    pub top_multicast: Vec<TopTalker>,
+    // TCP/UDP session bytes by destination port and by source port, across all interfaces. Source
+    // ports matter behind NAT, where the destination ports are all ephemeral; `o` picks the side.
+    pub port_stats: HashMap<u16, IpHistory>,
+    pub source_port_stats: HashMap<u16, IpHistory>,
+    pub top_ports: Vec<TopPort>,
+    pub top_source_ports: Vec<TopPort>,
+    pub by_source_port: bool,
+    // Map of the busiest hosts on the shown tab and the sessions between them, for the `t` view
+    pub topology: Topology,
+
+    // ISO country codes, filled in lazily when a GeoIP database was given
+    pub geoip: Option<GeoLookup>,
+    pub geo_cache: HashMap<Ipv4Addr, String>,
 
-        current_snapshot.sort_by(|a, b| b.1.partial_cmp(&a.1).unwrap());
-        self.top_talkers = current_snapshot;
+    pub alert_monitor: AlertMonitor,
+    pub alerts: VecDeque<Alert>,
+
+    // Reverse DNS for visible talkers. Entries outlive their TTL until the refreshed
+    // answer arrives, so a hostname never flips back to "Resolving...".
+    pub dns_enabled: bool,
+    // Poll /proc/net/dev every tick, unless `--no-proc-stats` or replaying
+    pub proc_stats: bool,
+    pub dns_ttl: Duration,
+    pub dns_cache: HashMap<Ipv4Addr, DnsCacheEntry>,
+    // Lookups currently in flight
+    pub dns_query_sent: HashSet<Ipv4Addr>,
+    // Set up by main unless reverse DNS is disabled
+    pub dns_resolver: Option<DnsResolver>,
+}
+
+// A window with the default `Config`, as a host first seen would get
+impl Default for IpHistory {
+    fn default() -> Self {
+        Self::new(Config::default())
+    }
+}
+
+// Only the "All" tab and no capture behind it, until `main` configures one
+impl Default for App {
+    fn default() -> Self {
+        Self::new(Config::default(), &[])
+    }
+}
+
+impl App {
+    pub fn new(config: Config, interface_names: &[String]) -> App {
+        let mut interfaces: Vec<InterfaceStats> = interface_names
+            .iter()
+            .map(|name| InterfaceStats::new(name, config))
+            .collect();
+        interfaces.push(InterfaceStats::new("All", config));
+        App {
+            config,
+            theme: Arc::new(Theme::default()),
+            theme_source: None,
+            theme_error: None,
+            interfaces,
+            active_tab: 0,
+            last_tick: Instant::now(),
+            last_tick_secs: config.tick_ms as f64 / 1000.0,
+            table_offset: 0,
+            selected_row: None,
+            visible_rows: 0,
+            display_limit: DEFAULT_DISPLAY_LIMIT,
+            show_detail: false,
+            show_arp: false,
+            show_help: false,
+            help_scroll: 0,
+            arp_table: BTreeMap::new(),
+            arp_alerts: VecDeque::new(),
+            anomalies: VecDeque::new(),
+            columns: default_columns(),
+            watchlist: Watchlist::new(),
+            watchlist_path: None,
+            snapshot_path: None,
+            input_mode: InputMode::Normal,
+            input_buffer: String::new(),
+            active_bpf: None,
+            bpf_error: None,
+            promisc_error: None,
+            filter_query: String::new(),
+            filter_pattern: None,
+            csv_log: None,
+            json_log: None,
+            html_report: None,
+            history_db: None,
+            history_view: None,
+            history_error: None,
+            drop_receivers: Vec::new(),
+            tick_flags: HashMap::new(),
+            tick_retransmits: HashMap::new(),
+            tick_proto: HashMap::new(),
+            metrics: None,
+            ws_server: None,
+            netflow: None,
+            influx: None,
+            log_warning: None,
+            stats_reset_at: None,
+            tick_changed_at: None,
+            copy_notice: None,
+            clipboard: Clipboard::default(),
+            dns_prefetch_notice: None,
+            prefilter_notice: None,
+            dns_prefetched: Some(HashSet::new()),
+            addresses_checked_at: Instant::now(),
+            render_times: RingBuffer::new(),
+            debug_perf: false,
+            ui_tick: None,
+            paused: false,
+            discard_on_resume: false,
+            service_names: true,
+            display_unit: DisplayUnit::Bits,
+            rate_base: BitrateBase::Decimal1000,
+            total_unit: TotalUnit::IecBytes,
+            graph_style: GraphStyle::Bar,
+            log_scale: false,
+            aggregate_prefix: None,
+            view: View::Talkers,
+            sort_column: SortColumn::AvgBps,
+            sort_ascending: false,
+            session_stats: HashMap::new(),
+            top_sessions: vec![],
+            sessions_by_duration: false,
+            connections_per_ip: HashMap::new(),
+            closed_sessions: VecDeque::new(),
+            multicast_stats: HashMap::new(),
+            icmp_type_counts: BTreeMap::new(),
+            top_multicast: vec![],
+            port_stats: HashMap::new(),
+            source_port_stats: HashMap::new(),
+            top_ports: vec![],
+            top_source_ports: vec![],
+            by_source_port: false,
+            topology: Topology::default(),
+            geoip: None,
+            geo_cache: HashMap::new(),
+            alert_monitor: AlertMonitor::new(None, HashMap::new()),
+            alerts: VecDeque::with_capacity(MAX_ALERTS),
+            dns_enabled: true,
+            proc_stats: false,
+            dns_ttl: Duration::from_secs(DEFAULT_DNS_TTL_SECS),
+            dns_cache: HashMap::new(),
+            dns_query_sent: HashSet::new(),
+            dns_resolver: None,
+        }
+    }
+
+    pub fn reload_theme(&mut self) {
+        let Some(source) = &self.theme_source else {
+            return;
+        };
+        match Theme::load(source) {
+            Ok(theme) => {
+                self.theme = Arc::new(theme);
+                self.theme_error = None;
+            }
+            Err(err) => self.theme_error = Some(err),
+        }
+    }
+
+    pub fn toggle_view(&mut self) {
+        self.show_view(if self.view == View::Sessions { View::Talkers } else { View::Sessions });
+    }
+
+    pub fn toggle_multicast(&mut self) {
+        self.show_view(if self.view == View::Multicast { View::Talkers } else { View::Multicast });
+    }
+
+    pub fn toggle_icmp(&mut self) {
+        self.show_view(if self.view == View::Icmp { View::Talkers } else { View::Icmp });
+    }
+
+    pub fn toggle_ports(&mut self) {
+        self.show_view(if self.view == View::Ports { View::Talkers } else { View::Ports });
+    }
+
+    // Group the ports table by source instead of destination port, or back
+    pub fn toggle_port_side(&mut self) {
+        self.by_source_port = !self.by_source_port;
+        self.show_view(View::Ports);
+    }
+
+    // What `P` switches the live captures to: the opposite of the first one's mode, so mixed
+    // interfaces end up alike. None without a live capture
+    pub fn toggled_promisc(&self) -> Option<bool> {
+        self.interfaces.iter().find_map(|iface| iface.promisc).map(|promisc| !promisc)
+    }
+
+    // Sort the sessions table by duration instead of bandwidth, or back
+    pub fn toggle_session_sort(&mut self) {
+        self.sessions_by_duration = !self.sessions_by_duration;
+        self.sort_sessions();
+        self.show_view(View::Sessions);
+    }
+
+    // Longest or busiest sessions first, by `sessions_by_duration`
+    fn sort_sessions(&mut self) {
+        if self.sessions_by_duration {
+            self.top_sessions.sort_by_key(|session| std::cmp::Reverse(session.duration));
+        } else {
+            self.top_sessions.sort_by(|a, b| b.avg_bps.total_cmp(&a.avg_bps));
+        }
+    }
+
+    // Rows of the ports table on the side currently picked
+    pub fn shown_ports(&self) -> &[TopPort] {
+        if self.by_source_port { &self.top_source_ports } else { &self.top_ports }
+    }
+
+    pub fn toggle_topology(&mut self) {
+        self.show_view(if self.view == View::Topology { View::Talkers } else { View::Topology });
+        // Draw the map from the last tick straight away rather than on the next one
+        self.update_topology();
+    }
+
+    pub fn toggle_closed_sessions(&mut self) {
+        self.show_view(if self.view == View::ClosedSessions { View::Talkers } else { View::ClosedSessions });
+    }
+
+    fn show_view(&mut self, view: View) {
+        self.view = view;
+        self.selected_row = None;
+        self.table_offset = 0;
+    }
+
+    // Number of rows in the table currently on screen
+    fn row_count(&self) -> usize {
+        let rows = match self.view {
+            View::Talkers => self.filtered_talkers().len(),
+            View::Sessions => self.top_sessions.len(),
+            View::Multicast => self.top_multicast.len(),
+            View::Icmp => self.icmp_type_counts.len(),
+            View::ClosedSessions => self.closed_sessions.len(),
+            View::Ports => self.shown_ports().len(),
+            View::Topology => 0,
+        };
+        rows.min(self.display_limit)
+    }
+
+    pub fn toggle_column(&mut self, column: Column) {
+        if let Some(spec) = self.columns.iter_mut().find(|spec| spec.column == column) {
+            spec.visible = !spec.visible;
+        }
+    }
+
+    pub fn grow_display_limit(&mut self) {
+        self.display_limit += DISPLAY_LIMIT_STEP;
+    }
+
+    pub fn shrink_display_limit(&mut self) {
+        self.display_limit = self.display_limit.saturating_sub(DISPLAY_LIMIT_STEP).max(DISPLAY_LIMIT_STEP);
+        self.clamp_scroll();
+    }
+
+    // `[` halves the tick interval and `]` doubles it, within MIN/MAX_TICK_RATE_MS
+    pub fn faster_tick(&mut self) {
+        self.set_tick_ms(self.config.tick_ms / 2);
+    }
+
+    pub fn slower_tick(&mut self) {
+        self.set_tick_ms(self.config.tick_ms * 2);
+    }
+
+    fn set_tick_ms(&mut self, tick_ms: u64) {
+        let tick_ms = tick_ms.clamp(MIN_TICK_RATE_MS, MAX_TICK_RATE_MS);
+        if tick_ms == self.config.tick_ms {
+            return;
+        }
+        // Histories keep their sample counts, so the window stretches or shrinks with the tick
+        let samples = self.config.max_samples() as u64;
+        self.config.tick_ms = tick_ms;
+        RUNTIME_TICK_RATE.store(tick_ms, Ordering::Relaxed);
+        self.config.history_secs = (samples * tick_ms / 1000).max(1);
+        for iface in &mut self.interfaces {
+            iface.config = self.config;
+        }
+        self.tick_changed_at = Some(Instant::now());
+    }
+
+    pub fn toggle_pause(&mut self, shared_stats: &[Arc<SharedStats>]) {
+        self.paused = !self.paused;
+        if !self.paused && self.discard_on_resume {
+            for shared in shared_stats {
+                // Only the traffic is stale; anomalies still go out on the next tick
+                let stale = TickDelta::drain(shared);
+                shared.deltas.write().anomalies.extend(stale.anomalies);
+            }
+        }
+    }
+
+    // Stats for the tab currently on screen
+    pub fn current(&self) -> &InterfaceStats {
+        &self.interfaces[self.active_tab]
+    }
+
+    pub fn next_tab(&mut self) {
+        self.active_tab = (self.active_tab + 1) % self.interfaces.len();
+        self.selected_row = None;
+        self.table_offset = 0;
+    }
+
+    // Move the selected row by `delta`, scrolling the table to keep it visible
+    pub fn move_selection(&mut self, delta: isize) {
+        let len = self.row_count();
+        if len == 0 {
+            self.selected_row = None;
+            self.table_offset = 0;
+            return;
+        }
+        let current = self.selected_row.unwrap_or(self.table_offset) as isize;
+        let next = (current + delta).clamp(0, len as isize - 1) as usize;
+        self.selected_row = Some(next);
+
+        let visible = self.visible_rows.max(1);
+        if next < self.table_offset {
+            self.table_offset = next;
+        } else if next >= self.table_offset + visible {
+            self.table_offset = next + 1 - visible;
+        }
+        self.clamp_scroll();
+    }
+
+    // Select the row at `index` in the table, ignoring clicks below the last row
+    pub fn select_row(&mut self, index: usize) -> bool {
+        if index >= self.row_count() {
+            return false;
+        }
+        self.selected_row = Some(index);
+        true
+    }
+
+    pub fn page_size(&self) -> isize {
+        self.visible_rows.max(1) as isize
+    }
+
+    // Keep offset and selection within the current top talkers list
+    fn clamp_scroll(&mut self) {
+        let len = self.row_count();
+        if len == 0 {
+            self.selected_row = None;
+            self.table_offset = 0;
+            return;
+        }
+        if let Some(selected) = self.selected_row {
+            self.selected_row = Some(selected.min(len - 1));
+        }
+        self.table_offset = self.table_offset.min(len.saturating_sub(self.visible_rows.max(1)));
+    }
+
+    pub fn selected_talker(&self) -> Option<&TopTalker> {
+        if self.view != View::Talkers {
+            return None;
+        }
+        self.selected_row.and_then(|i| self.filtered_talkers().get(i).copied())
+    }
+
+    // Choosing the active column again reverses the order
+    pub fn set_sort(&mut self, column: SortColumn) {
+        if column == self.sort_column {
+            self.sort_ascending = !self.sort_ascending;
+        } else {
+            self.sort_column = column;
+            self.sort_ascending = column.default_ascending();
+        }
+        self.sort_talkers();
+    }
+
+    pub fn cycle_sort(&mut self) {
+        let index = SortColumn::ALL.iter().position(|c| *c == self.sort_column).unwrap_or(0);
+        self.set_sort(SortColumn::ALL[(index + 1) % SortColumn::ALL.len()]);
+    }
+
+    // Top talkers arrive sorted by average rate; re-order every tab by the chosen column
+    fn sort_talkers(&mut self) {
+        let (column, ascending) = (self.sort_column, self.sort_ascending);
+        let dns_cache = &self.dns_cache;
+        let hostname = |ip: &Ipv4Addr| dns_cache.get(ip).map_or("", |entry| entry.result.hostname());
+        for iface in &mut self.interfaces {
+            iface.top_talkers.sort_by(|a, b| {
+                let order = match column {
+                    SortColumn::AvgBps => a.avg_bps.total_cmp(&b.avg_bps),
+                    SortColumn::PeakBps => a.peak_bps.total_cmp(&b.peak_bps),
+                    SortColumn::TotalBytes => a.lifetime_bytes.cmp(&b.lifetime_bytes),
+                    SortColumn::IpAddr => a.ip.cmp(&b.ip),
+                    SortColumn::Hostname => hostname(&a.ip).cmp(hostname(&b.ip)),
+                    SortColumn::AvgPacketSize => a.avg_packet_size.total_cmp(&b.avg_packet_size),
+                };
+                if ascending { order } else { order.reverse() }
+            });
+            // Stable, so bookmarked IPs keep the chosen order among themselves
+            iface.top_talkers.sort_by_key(|talker| !self.watchlist.contains_key(&talker.ip));
+        }
+    }
+
+    // Bookmark the selected IP, or drop the bookmark along with its threshold
+    pub fn toggle_watch(&mut self) {
+        let Some(ip) = self.selected_talker().map(|talker| talker.ip) else {
+            return;
+        };
+        if self.watchlist.remove(&ip).is_none() {
+            self.watchlist.insert(ip, None);
+        }
+        self.sort_talkers();
+        // Keep the selection on the same IP as it moves to or from the top
+        self.selected_row = self.filtered_talkers().iter().position(|talker| talker.ip == ip);
+    }
+
+    // Copy the selected IP (or subnet) so it can be pasted into another tool
+    pub fn copy_selected_ip(&mut self) {
+        let Some(ip) = self.selected_talker().map(|talker| talker.ip) else {
+            return;
+        };
+        let label = talker_label(ip, self.aggregate_prefix);
+        let notice = match self.clipboard.copy(&label) {
+            Ok(CopyTarget::Clipboard) => format!("[Copied: {}] ", label),
+            Ok(CopyTarget::File(path)) => format!("[Written to {}] ", path),
+            Err(err) => {
+                self.log_warning = Some(format!("Clipboard: {}", err));
+                return;
+            }
+        };
+        self.copy_notice = Some((notice, Instant::now()));
+    }
+
+    pub fn save_watchlist(&self) -> io::Result<()> {
+        match &self.watchlist_path {
+            Some(path) => watchlist::save(path, &self.watchlist),
+            None => Ok(()),
+        }
+    }
+
+    pub fn save_snapshot(&self) -> Result<(), String> {
+        let Some(path) = &self.snapshot_path else {
+            return Ok(());
+        };
+        let snapshot = AppSnapshot {
+            schema_version: SCHEMA_VERSION,
+            saved: Local::now().timestamp(),
+            interfaces: self.interfaces.iter().map(InterfaceStats::snapshot).collect(),
+        };
+        snapshot::save(path, &snapshot)
+    }
+
+    // Interfaces are matched by name; ones not monitored this time are left out
+    pub fn restore_snapshot(&mut self, snapshot: &AppSnapshot) {
+        for saved in &snapshot.interfaces {
+            if let Some(iface) = self.interfaces.iter_mut().find(|iface| iface.name == saved.name) {
+                iface.restore(saved);
+            }
+        }
+    }
+
+    // Queries with `*`, `?` or `[` are glob patterns matched against the whole address
+    // or hostname; anything else is a substring. Both ignore case.
+    pub fn set_filter_query(&mut self, query: String) {
+        let is_glob = query.contains(['*', '?', '[']);
+        self.filter_pattern = if is_glob { Pattern::new(&query).ok() } else { None };
+        self.filter_query = query;
+        self.selected_row = None;
+        self.table_offset = 0;
+    }
+
+    fn matches_filter(&self, talker: &TopTalker) -> bool {
+        if self.filter_query.is_empty() {
+            return true;
+        }
+        let label = self.talker_label(talker.ip);
+        let hostname = self.dns_cache.get(&talker.ip).map_or("", |entry| entry.result.hostname());
+        match &self.filter_pattern {
+            Some(pattern) => {
+                let options = MatchOptions { case_sensitive: false, ..MatchOptions::new() };
+                pattern.matches_with(&label, options) || pattern.matches_with(hostname, options)
+            }
+            None => {
+                let query = self.filter_query.to_lowercase();
+                label.contains(&query) || hostname.to_lowercase().contains(&query)
+            }
+        }
+    }
+
+    // Top talkers of the current tab that pass the search bar, up to the display limit
+    pub fn filtered_talkers(&self) -> Vec<&TopTalker> {
+        self.current().top_talkers.iter().filter(|talker| self.matches_filter(talker)).take(self.display_limit).collect()
+    }
+
+    // Format a rate in the active display unit from its bytes/s and packets/s figures
+    pub fn rate(&self, bytes_per_sec: f64, packets_per_sec: f64) -> String {
+        format_rate(self.display_unit.pick(bytes_per_sec, packets_per_sec), self.display_unit, self.rate_base)
+    }
+
+    pub fn talker_label(&self, ip: Ipv4Addr) -> String {
+        talker_label(ip, self.aggregate_prefix)
+    }
+
+    fn update_geo_cache(&mut self) {
+        let Some(geoip) = self.geoip.as_mut() else {
+            return;
+        };
+        self.geo_cache.extend(geoip.drain_results());
+
+        let aggregate = self.interfaces.last().unwrap();
+        for talker in &aggregate.top_talkers {
+            if !self.geo_cache.contains_key(&talker.ip) {
+                geoip.request(talker.ip);
+            }
+        }
+    }
+
+    // The IP has been seen behind more than one MAC address
+    pub fn is_duplicate_ip(&self, ip: Ipv4Addr) -> bool {
+        self.anomalies.iter().any(|event| matches!(event, AnomalyEvent::DuplicateIp { .. }) && event.ip() == ip)
+    }
+
+    // Hostname column text: the cached name, or a placeholder while the first lookup runs.
+    // Empty for an address without a name, whatever the reason
+    pub fn hostname(&self, ip: Ipv4Addr) -> &str {
+        match self.dns_cache.get(&ip) {
+            Some(entry) => entry.result.hostname(),
+            None if self.dns_query_sent.contains(&ip) => "Resolving...",
+            None => "",
+        }
+    }
+
+    // The last reverse lookup result for `ip`, for the hostname column to style
+    pub fn dns_result(&self, ip: Ipv4Addr) -> Option<&DnsResult> {
+        self.dns_cache.get(&ip).map(|entry| &entry.result)
+    }
+
+    // Look the device addresses up again, so a DHCP renewal shows in the title
+    pub fn refresh_addresses(&mut self) {
+        for iface in self.interfaces.iter_mut().filter(|iface| iface.shows_address) {
+            iface.address = get_local_networks(&iface.name).first().copied();
+        }
+        self.addresses_checked_at = Instant::now();
+    }
+
+    // Read /proc/net/dev again; interfaces with new errors raise an alert
+    fn refresh_counters(&mut self) -> io::Result<()> {
+        let all = proc_stats::read();
+        let (aggregate, per_interface) = self.interfaces.split_last_mut().unwrap();
+        let mut total: Option<InterfaceCounters> = None;
+        for iface in per_interface {
+            let counters = all.as_ref().and_then(|all| all.get(&iface.name)).copied();
+            iface.previous_counters = std::mem::replace(&mut iface.counters, counters);
+            if let Some(counters) = counters {
+                *total.get_or_insert_with(InterfaceCounters::default) += counters;
+            }
+            let ip = iface.address.map_or(Ipv4Addr::UNSPECIFIED, |net| net.ip());
+            self.alert_monitor.check_interface_errors(&iface.name, ip, iface.previous_counters, iface.counters, &mut self.alerts)?;
+        }
+        aggregate.previous_counters = std::mem::replace(&mut aggregate.counters, total);
+        Ok(())
+    }
+
+    pub fn record_render(&mut self, duration: Duration) {
+        self.render_times.push(duration);
+    }
+
+    // 95th percentile of the recent render times
+    pub fn render_p95(&self) -> Option<Duration> {
+        let mut times: Vec<Duration> = self.render_times.iter().copied().collect();
+        times.sort_unstable();
+        let index = (times.len() * 95).div_ceil(100).checked_sub(1)?;
+        times.get(index).copied()
+    }
+
+    // Start the records and totals over; capture threads and the sliding windows are untouched
+    pub fn reset_peaks(&mut self) {
+        for iface in &mut self.interfaces {
+            iface.reset_peaks();
+        }
+        let now = Local::now();
+        for history in self.multicast_stats.values_mut().chain(self.port_stats.values_mut()).chain(self.source_port_stats.values_mut()) {
+            history.reset_peaks(now);
+        }
+        self.stats_reset_at = Some((now, Instant::now()));
+    }
+
+    // Forget every cached hostname so the visible talkers are resolved again
+    pub fn flush_dns_cache(&mut self) {
+        self.dns_cache.clear();
+    }
+
+    // Queue reverse lookups for the hosts of `networks` before any of them shows up in traffic.
+    // The first tick adds the IPs already known by then, at most `MAX_DNS_PREFETCH` in all.
+    // The answers land in the cache like any others
+    pub fn prefetch_dns(&mut self, networks: &[Ipv4Network]) {
+        if networks.is_empty() {
+            return;
+        }
+        // Network and broadcast addresses have no host behind them, except in /31 and /32
+        let hosts = networks.iter().flat_map(|net| net.iter().filter(move |&ip| net.prefix() > 30 || (ip != net.network() && ip != net.broadcast())));
+        self.queue_prefetch(hosts);
+    }
+
+    // Look up each new IP of `ips` until the prefetch cap is reached, keeping the notice current
+    fn queue_prefetch(&mut self, ips: impl IntoIterator<Item = Ipv4Addr>) {
+        let Some(resolver) = self.dns_resolver.as_ref().filter(|_| self.aggregate_prefix.is_none()) else {
+            return;
+        };
+        let Some(queued) = self.dns_prefetched.as_mut() else {
+            return;
+        };
+        let before = queued.len();
+        for ip in ips {
+            if queued.len() == MAX_DNS_PREFETCH {
+                break;
+            }
+            if queued.insert(ip) {
+                resolver.lookup(ip);
+            }
+        }
+        if queued.len() > before {
+            self.dns_prefetch_notice = Some((queued.len(), Instant::now()));
+        }
+    }
+
+    fn update_dns_cache(&mut self) {
+        let Some(resolver) = self.dns_resolver.as_mut() else {
+            return;
+        };
+        for (ip, result) in resolver.results() {
+            // A timeout is only an interim answer while the lookup is retried, and
+            // shouldn't hide a name that is merely being refreshed
+            if result == DnsResult::Timeout {
+                if matches!(self.dns_result(ip), Some(DnsResult::Resolved(_))) {
+                    continue;
+                }
+            } else {
+                self.dns_query_sent.remove(&ip);
+            }
+            self.dns_cache.insert(ip, DnsCacheEntry { result, resolved_at: Instant::now(), ttl: self.dns_ttl });
+        }
+        // Network addresses of aggregated subnets have nothing useful to resolve
+        if !self.dns_enabled || self.aggregate_prefix.is_some() {
+            return;
+        }
+
+        // Only rows that can actually be shown are resolved, however many IPs are tracked
+        let visible: Vec<Ipv4Addr> = self.filtered_talkers().into_iter().skip(self.table_offset).take(self.visible_rows.max(1)).map(|talker| talker.ip).collect();
+        for ip in visible {
+            let stale = self.dns_cache.get(&ip).is_none_or(DnsCacheEntry::is_stale);
+            if stale && self.dns_query_sent.insert(ip) {
+                if let Some(resolver) = &self.dns_resolver {
+                    resolver.lookup(ip);
+                }
+            }
+        }
+    }
+
+    // Session bytes of this tick summed per destination and per source port
+    fn update_ports(&mut self, sessions: &HashMap<SessionKey, SessionDelta>, elapsed_secs: f64) {
+        let mut by_destination: HashMap<u16, (Traffic, ProtoDelta)> = HashMap::new();
+        let mut by_source: HashMap<u16, (Traffic, ProtoDelta)> = HashMap::new();
+        for (key, delta) in sessions {
+            let traffic = Traffic { bytes: delta.bytes, packets: delta.packets };
+            let proto = ProtoDelta::packet(key.proto, delta.bytes);
+            for (per_port, port) in [(&mut by_destination, key.dst_port), (&mut by_source, key.src_port)] {
+                let entry = per_port.entry(port).or_default();
+                entry.0 += traffic;
+                entry.1 += proto;
+            }
+        }
+        self.top_ports = update_ports(&mut self.port_stats, &by_destination, self.config, elapsed_secs);
+        self.top_source_ports = update_ports(&mut self.source_port_stats, &by_source, self.config, elapsed_secs);
+    }
+
+    // Lay the topology map out again from the shown tab's talkers and the sessions between them,
+    // while it is on screen and at most once a second
+    fn update_topology(&mut self) {
+        if self.view != View::Topology || !self.topology.due() {
+            return;
+        }
+        let mut hosts: Vec<(Ipv4Addr, f64)> = self.interfaces[self.active_tab].top_talkers.iter().map(|talker| (talker.ip, talker.avg_bps)).collect();
+        hosts.sort_by(|a, b| b.1.total_cmp(&a.1));
+        let mut links = HashMap::new();
+        for session in &self.top_sessions {
+            if let (IpAddr::V4(src), IpAddr::V4(dst)) = (session.key.src, session.key.dst) {
+                *links.entry((src.min(dst), src.max(dst))).or_insert(0.0) += session.avg_bps;
+            }
+        }
+        self.topology.update(&hosts, &links);
+    }
+
+    fn update_sessions(&mut self, sessions: &HashMap<SessionKey, SessionDelta>, elapsed_secs: f64) {
+        // The capture thread counts each direction on its own; both land on the connection's normalized key
+        let mut connections: HashMap<SessionKey, Vec<(SessionKey, SessionDelta)>> = HashMap::new();
+        for (key, delta) in sessions {
+            connections.entry(normalize_session_key(*key)).or_default().push((*key, *delta));
+        }
+        let mut keys: Vec<SessionKey> = self.session_stats.keys().cloned().collect();
+        keys.extend(connections.keys().filter(|k| !self.session_stats.contains_key(*k)));
+
+        let mut ticks = HashMap::with_capacity(keys.len());
+        for key in &keys {
+            if !self.session_stats.contains_key(key) {
+                count_connection(&mut self.connections_per_ip, key, true);
+            }
+            let deltas = connections.get(key).map(Vec::as_slice).unwrap_or_default();
+            let config = self.config;
+            let state = self.session_stats.entry(*key).or_insert_with(|| SessionState::new(config, client_key(*key, deltas)));
+            let mut split = [SessionDelta::default(); 2];
+            for (sent_on, delta) in deltas {
+                split[Direction::of(sent_on, &state.client_key) as usize] += *delta;
+            }
+            ticks.insert(*key, (state.update(split, elapsed_secs), split));
+        }
+
+        let mut rows = Vec::new();
+        for key in keys {
+            let Some(state) = self.session_stats.get_mut(&key) else {
+                continue;
+            };
+            let (avg_bps, split) = ticks[&key];
+            let expired = state.expired();
+
+            // A flow ends on FIN/RST, after sitting idle, or when the session is about to be dropped
+            if let Some(exporter) = self.netflow.as_mut() {
+                for (index, flow) in state.flows.iter_mut().enumerate() {
+                    let closed = split[index].flags.fin > 0 || split[index].flags.rst > 0;
+                    let idle = flow.last.elapsed() >= FLOW_IDLE_TIMEOUT;
+                    if flow.delta.packets > 0 && (closed || idle || expired.is_some()) {
+                        let sent_on = if index == Direction::ClientToServer as usize { state.client_key } else { state.client_key.reversed() };
+                        exporter.push(&sent_on, &flow.delta, flow.first, flow.last);
+                        flow.delta = SessionDelta::default();
+                    }
+                }
+            }
+
+            // Finished sessions move to the closed list; idle ones stay off the table until they expire
+            if let Some(reason) = expired {
+                if self.closed_sessions.len() == MAX_CLOSED_SESSIONS {
+                    self.closed_sessions.pop_front();
+                }
+                self.closed_sessions.push_back(state.closed(reason));
+                self.session_stats.remove(&key);
+                count_connection(&mut self.connections_per_ip, &key, false);
+            } else if state.history.total_sum > 0 {
+                rows.push(SessionRow {
+                    key: state.client_key,
+                    avg_bps,
+                    upload_bps: state.history.tx_rate(),
+                    download_bps: state.history.rx_rate(),
+                    upload_pps: state.history.tx_pps(),
+                    download_pps: state.history.rx_pps(),
+                    peak_bps: state.history.peak_rate,
+                    avg_pps: state.history.pps(),
+                    peak_pps: state.history.peak_pps,
+                    total_bytes: state.history.total_sum,
+                    avg_packet_size: state.history.avg_packet_size(),
+                    syn_rate: state.flag_rate(state.window_flags.syn),
+                    rst_rate: state.flag_rate(state.window_flags.rst),
+                    duration: state.duration(),
+                    tcp_state: state.tcp_state(),
+                    retransmits: state.retransmits,
+                    retransmit_rate: state.retransmit_rate(),
+                });
+            }
+        }
+
+        self.top_sessions = rows;
+        self.sort_sessions();
+
+        // The talkers were ranked before the sessions moved on, so their counts are brought up to date here
+        for iface in &mut self.interfaces {
+            for (ip, history) in iface.ip_histories.iter_mut() {
+                history.active_connections = self.connections_per_ip.get(ip).copied().unwrap_or(0);
+            }
+            for talker in &mut iface.top_talkers {
+                talker.active_connections = self.connections_per_ip.get(&talker.ip).copied().unwrap_or(0);
+            }
+        }
+        if let Err(err) = self.alert_monitor.check_connections(&self.connections_per_ip, &mut self.alerts) {
+            self.log_warning = Some(format!("Alert log: {}", err));
+        }
+
+        if let Some(exporter) = self.netflow.as_mut() {
+            if let Err(err) = exporter.flush() {
+                self.log_warning = Some(format!("NetFlow: {}", err));
+            }
+        }
+    }
+
+    // One JSON record per top talker across all interfaces, as of the last tick
+    pub fn traffic_records(&self) -> Vec<TrafficRecord> {
+        let ts = Local::now().timestamp();
+        self.interfaces.last().unwrap().top_talkers.iter().map(|talker| TrafficRecord {
+            ts,
+            ip: talker_label(talker.ip, self.aggregate_prefix),
+            avg_bps: talker.avg_bps,
+            peak_bps: talker.peak_bps,
+            avg_pps: talker.avg_pps,
+            peak_pps: talker.peak_pps,
+            avg_packet_bytes: talker.avg_packet_size,
+            total_bytes: talker.total_bytes,
+            first_seen: talker.first_seen.to_rfc3339(),
+            last_seen: talker.last_seen.to_rfc3339(),
+            tcp_flags: self.tick_flags.get(&talker.ip).copied().unwrap_or_default(),
+            tcp_retransmits: self.tick_retransmits.get(&talker.ip).copied().unwrap_or_default(),
+            proto_bytes: self.tick_proto.get(&talker.ip).copied().unwrap_or_default(),
+        }).collect()
+    }
+
+    pub fn ws_frame(&self) -> WsFrame {
+        let all = self.interfaces.last().unwrap();
+        let last = |history: &RateHistory| history.current() / self.last_tick_secs;
+        WsFrame {
+            ts: Local::now().timestamp(),
+            rx_bps: last(&all.rx_history),
+            tx_bps: last(&all.tx_history),
+            talkers: all.top_talkers.iter().map(|talker| WsTalker {
+                ip: talker_label(talker.ip, self.aggregate_prefix),
+                avg_bps: talker.avg_bps,
+                peak_bps: talker.peak_bps,
+            }).collect(),
+        }
+    }
+
+    // This tick in InfluxDB line protocol: each captured interface, then every IP on "All".
+    // Rates are bytes/s like the JSON log; `total_bytes` is the lifetime count, so it only grows
+    fn influx_lines(&self) -> String {
+        let now = Local::now().timestamp_millis();
+        let last = |history: &RateHistory| history.current() / self.last_tick_secs;
+        let mut lines = String::new();
+        let (all, captured) = self.interfaces.split_last().unwrap();
+        for iface in captured {
+            influx::interface_line(&mut lines, &iface.name, last(&iface.rx_history), last(&iface.tx_history), iface.total_rx_bytes, iface.total_tx_bytes, now);
+        }
+        for talker in &all.top_talkers {
+            let hostname = self.dns_cache.get(&talker.ip).map_or("", |entry| entry.result.hostname());
+            let ip = talker_label(talker.ip, self.aggregate_prefix);
+            influx::ip_line(&mut lines, &ip, hostname, talker.avg_bps, talker.peak_bps, talker.lifetime_bytes, now);
+        }
+        lines
+    }
+
+    // Run `<ip> <range>` from the history dialog; the result replaces the main screen
+    pub fn run_history_query(&mut self, text: &str) {
+        match self.history_query(text) {
+            Ok(view) => {
+                self.history_view = Some(view);
+                self.history_error = None;
+            }
+            Err(err) => self.history_error = Some(err),
+        }
+    }
+
+    fn history_query(&self, text: &str) -> Result<HistoryView, String> {
+        let db = self.history_db.as_ref().ok_or("History is only recorded with --history-db")?;
+        let (ip, range) = text.trim().split_once(' ').ok_or("Expected '<ip> <range>', e.g. '192.168.1.5 last 1h'")?;
+        let ip: Ipv4Addr = ip.parse().map_err(|_| format!("Invalid IP address '{}'", ip))?;
+        let (from, to) = parse_range(range, Local::now())?;
+        let records = db.query(ip, from, to)?;
+        Ok(HistoryView { ip, from, to, records })
+    }
+
+    // Top talkers, totals and peak records of the aggregate, for the HTML report
+    pub fn report_snapshot(&self) -> ReportSnapshot {
+        let all = self.interfaces.last().unwrap();
+        let rates = |history: &RateHistory| history.iter().map(|bytes| self.config.per_second(*bytes as u64)).collect();
+        ReportSnapshot {
+            generated: Local::now(),
+            interface: all.name.clone(),
+            rate_base: self.rate_base,
+            total_unit: self.total_unit,
+            history_secs: self.config.history_secs,
+            total_rx_bytes: all.total_rx_bytes,
+            total_tx_bytes: all.total_tx_bytes,
+            rx_history: rates(&all.rx_history),
+            tx_history: rates(&all.tx_history),
+            peak_rx: all.peak_rx_record,
+            peak_tx: all.peak_tx_record,
+            talkers: all.top_talkers.iter().map(|talker| ReportTalker {
+                ip: talker_label(talker.ip, self.aggregate_prefix),
+                hostname: self.hostname(talker.ip).to_string(),
+                avg_bps: talker.avg_bps,
+                peak_bps: talker.peak_bps,
+                peak_time: talker.peak_time,
+                lifetime_bytes: talker.lifetime_bytes,
+                first_seen: talker.first_seen,
+            }).collect(),
+        }
+    }
+
+    // Totals for the last tick plus every top talker, printed by `--headless`
+    pub fn tick_summary(&self) -> TickSummary {
+        let all = self.interfaces.last().unwrap();
+        let last = |history: &RateHistory| history.current() / self.last_tick_secs;
+        TickSummary {
+            ts: Local::now().timestamp(),
+            rx_bps: last(&all.rx_history),
+            tx_bps: last(&all.tx_history),
+            rx_pps: last(&all.rx_packet_history),
+            tx_pps: last(&all.tx_packet_history),
+            talkers: self.traffic_records(),
+        }
+    }
+
+    // `shared_stats` holds one entry per capture thread, in the same order as `interfaces`
+    pub fn on_tick(&mut self, shared_stats: &[Arc<SharedStats>]) -> Vec<TickEvent> {
+        if self.addresses_checked_at.elapsed() >= ADDRESS_REFRESH_INTERVAL {
+            self.refresh_addresses();
+        }
+        if self.paused {
+            return Vec::new();
+        }
+        let mut counters_warning = None;
+        if self.proc_stats {
+            if let Err(err) = self.refresh_counters() {
+                counters_warning = Some(format!("Alert log: {}", err));
+            }
+        }
+        // Rates are averaged over the time that actually passed, not the nominal tick rate
+        let elapsed_secs = self.last_tick.elapsed().as_secs_f64().max(MIN_TICK_SECS);
+        self.last_tick_secs = elapsed_secs;
+        let (aggregate_stats, per_interface) = self.interfaces.split_last_mut().unwrap();
+        let mut csv_rows = Vec::new();
+        let mut total = TickDelta::default();
+
+        for (iface, shared) in per_interface.iter_mut().zip(shared_stats) {
+            let bpf_result = shared.bpf_result.lock().unwrap().take();
+            match bpf_result {
+                Some(Ok(filter)) => {
+                    self.active_bpf = if filter.is_empty() { None } else { Some(filter) };
+                    self.bpf_error = None;
+                }
+                Some(Err(err)) => self.bpf_error = Some(format!("{}: {}", iface.name, err)),
+                None => {}
+            }
+            let promisc_result = shared.promisc_result.lock().unwrap().take();
+            match promisc_result {
+                Some(Ok(promisc)) => {
+                    iface.promisc = Some(promisc);
+                    self.promisc_error = None;
+                }
+                Some(Err(err)) => self.promisc_error = Some(format!("{}: {}", iface.name, err)),
+                None => {}
+            }
+            let mut delta = TickDelta::drain(shared);
+            if let Some(prefix_len) = self.aggregate_prefix {
+                delta.group_by_subnet(prefix_len);
+            }
+            iface.finished = shared.capture_done.load(Ordering::Relaxed);
+
+            iface.ingest(&delta, elapsed_secs);
+            if self.csv_log.is_some() {
+                let retransmits = retransmits_per_ip(&delta.sessions, self.aggregate_prefix);
+                csv_rows.extend(iface.top_talkers.iter().map(|talker| CsvRow {
+                    interface: &iface.name,
+                    ip: talker_label(talker.ip, self.aggregate_prefix),
+                    rx_bytes: delta.rx_per_ip.get(&talker.ip).map_or(0, |t| t.bytes),
+                    tx_bytes: delta.tx_per_ip.get(&talker.ip).map_or(0, |t| t.bytes),
+                    proto_bytes: delta.proto_per_ip.get(&talker.ip).copied().unwrap_or_default(),
+                    tcp_retransmits: retransmits.get(&talker.ip).copied().unwrap_or_default(),
+                    avg_bps: talker.avg_bps,
+                    peak_bps: talker.peak_bps,
+                    avg_pps: talker.avg_pps,
+                    peak_pps: talker.peak_pps,
+                }));
+            }
+            total.merge(&delta);
+        }
+
+        // A failing log must not take the UI down; surface it in the status bar instead
+        let mut log_warning = counters_warning;
+        if let Some(log) = self.csv_log.as_mut() {
+            if let Err(err) = log.write_tick(&csv_rows) {
+                log_warning = Some(format!("CSV log: {}", err));
+            }
+        }
+        let previous_ips: HashSet<Ipv4Addr> = aggregate_stats.top_talkers.iter().map(|talker| talker.ip).collect();
+        let previous_peaks = (aggregate_stats.peak_rx_record.0, aggregate_stats.peak_tx_record.0);
+        aggregate_stats.ingest(&total, elapsed_secs);
+        let events = tick_events(aggregate_stats, &previous_ips, previous_peaks);
+        // Pending lookups for pruned IPs are forgotten so they resolve again if the IP returns
+        self.dns_query_sent.retain(|ip| aggregate_stats.ip_histories.contains_key(ip));
+
+        // Drop reports arrive every few seconds; each one stands until the next
+        let mut all_drops: Option<Stat> = None;
+        for (iface, receiver) in per_interface.iter_mut().zip(&self.drop_receivers) {
+            if let Some(stat) = receiver.as_ref().and_then(|rx| rx.try_iter().last()) {
+                iface.drops = Some(stat);
+            }
+            if let Some(stat) = iface.drops {
+                let sum = all_drops.get_or_insert(Stat { received: 0, dropped: 0, if_dropped: 0 });
+                sum.received += stat.received;
+                sum.dropped += stat.dropped;
+                sum.if_dropped += stat.if_dropped;
+            }
+        }
+        aggregate_stats.drops = all_drops;
+
+        for shared in shared_stats {
+            if let Some(err) = shared.write_error.lock().unwrap().as_ref() {
+                log_warning = Some(err.clone());
+            }
+            if let Some(err) = shared.prefilter_error.lock().unwrap().take() {
+                self.prefilter_notice = Some((err, Instant::now()));
+            }
+            let mut arp = shared.arp.lock().unwrap();
+            self.arp_table.extend(&arp.table);
+            for alert in arp.alerts.drain(..) {
+                if let Err(err) = self.alert_monitor.report_arp(&alert) {
+                    log_warning = Some(format!("Alert log: {}", err));
+                }
+                if self.arp_alerts.len() == MAX_ARP_ALERTS {
+                    self.arp_alerts.pop_front();
+                }
+                self.arp_alerts.push_back(alert);
+            }
+        }
+        if let Err(err) = self.alert_monitor.check_anomalies(&total.anomalies, &mut self.alerts) {
+            log_warning = Some(format!("Alert log: {}", err));
+        }
+        for event in std::mem::take(&mut total.anomalies) {
+            if self.anomalies.len() == MAX_ANOMALIES {
+                self.anomalies.pop_front();
+            }
+            self.anomalies.push_back(event);
+        }
+
+        if let Err(err) = self.alert_monitor.check(&aggregate_stats.top_talkers, &self.watchlist, &mut self.alerts) {
+            log_warning = Some(format!("Alert log: {}", err));
+        }
+        for (key, packets) in &total.icmp_types {
+            *self.icmp_type_counts.entry(*key).or_default() += *packets;
+        }
+        if let Err(err) = self.alert_monitor.check_icmp(&total.icmp_senders, &self.config, &mut self.alerts) {
+            log_warning = Some(format!("Alert log: {}", err));
+        }
+
+        self.tick_proto = std::mem::take(&mut total.proto_per_ip);
+
+        // TCP flags and retransmissions sent by each IP (or subnet) during this tick
+        self.tick_flags.clear();
+        for (key, delta) in &total.sessions {
+            if let Some(src) = session_sender(key, self.aggregate_prefix) {
+                *self.tick_flags.entry(src).or_default() += delta.flags;
+            }
+        }
+        self.tick_retransmits = retransmits_per_ip(&total.sessions, self.aggregate_prefix);
+
+        if let Some(log) = &self.json_log {
+            for record in self.traffic_records() {
+                if let Err(err) = log.send(record) {
+                    log_warning = Some(err);
+                    break;
+                }
+            }
+        }
+        if let Some(db) = self.history_db.as_mut() {
+            let mut bytes_per_ip: HashMap<Ipv4Addr, u64> = HashMap::new();
+            for (ip, traffic) in total.rx_per_ip.iter().chain(&total.tx_per_ip) {
+                *bytes_per_ip.entry(*ip).or_default() += traffic.bytes;
+            }
+            if let Err(err) = db.record(Local::now(), &bytes_per_ip, elapsed_secs) {
+                log_warning = Some(err);
+            }
+        }
+        if self.html_report.as_ref().is_some_and(HtmlReporter::due) {
+            let snapshot = self.report_snapshot();
+            if let Err(err) = self.html_report.as_mut().unwrap().send(snapshot) {
+                log_warning = Some(err);
+            }
+        }
+        // Taken out for the tick so the lines can be built from the rest of `self`
+        if let Some(mut influx) = self.influx.take() {
+            if let Err(err) = influx.record(&self.influx_lines()) {
+                log_warning = Some(err);
+            }
+            self.influx = Some(influx);
+        }
+        self.log_warning = log_warning;
+
+        if let Some(metrics) = &self.metrics {
+            let aggregate_stats = self.interfaces.last().unwrap();
+            let snapshot = MetricsSnapshot {
+                rx_bytes_total: aggregate_stats.total_rx_bytes,
+                tx_bytes_total: aggregate_stats.total_tx_bytes,
+                ips: aggregate_stats.top_talkers.iter().map(|talker| IpMetrics {
+                    ip: talker.ip,
+                    avg_bps: talker.avg_bps * 8.0,
+                    peak_bps: talker.peak_bps * 8.0,
+                }).collect(),
+            };
+            // Built outside the lock so a scrape never waits on the tick
+            *metrics.lock().unwrap() = snapshot;
+        }
+        if let Some(ws) = &self.ws_server {
+            ws.publish(&self.ws_frame());
+        }
+
+        self.sort_talkers();
+        self.update_sessions(&total.sessions, elapsed_secs);
+        self.update_ports(&total.sessions, elapsed_secs);
+        self.update_topology();
+        self.top_multicast = update_talkers(&mut self.multicast_stats, &total.multicast_per_group, &HashMap::new(), &HashMap::new(), &HashMap::new(), self.config, elapsed_secs);
+        self.update_geo_cache();
+
+        self.clamp_scroll();
+        // The talker behind the detail popup may have gone idle and dropped out
+        if self.selected_talker().is_none() {
+            self.show_detail = false;
+        }
+        // Resolve what the first tick already knows about (restored with `--resume` or seen just
+        // now) in one go, rather than only as rows scroll into view
+        if self.dns_prefetched.is_some() {
+            let known: Vec<Ipv4Addr> = self.interfaces.last().map_or_else(Vec::new, |all| all.ip_histories.keys().copied().collect());
+            self.queue_prefetch(known);
+            self.dns_prefetched = None;
+        }
+        self.update_dns_cache();
+        events
+    }
+}
+
+// Count a session starting (`opened`) or ending against each of its IPv4 endpoints
+fn count_connection(counts: &mut HashMap<Ipv4Addr, u16>, key: &SessionKey, opened: bool) {
+    for ip in [key.src, key.dst] {
+        let IpAddr::V4(ip) = ip else {
+            continue;
+        };
+        if opened {
+            let count = counts.entry(ip).or_default();
+            *count = count.saturating_add(1);
+        } else if let Some(count) = counts.get_mut(&ip) {
+            *count = count.saturating_sub(1);
+            if *count == 0 {
+                counts.remove(&ip);
+            }
+        }
+    }
+}
+
+// Compare the aggregate after `ingest` with the talkers and peak records it had before
+fn tick_events(aggregate: &InterfaceStats, previous_ips: &HashSet<Ipv4Addr>, previous_peaks: (f64, f64)) -> Vec<TickEvent> {
+    let mut events: Vec<TickEvent> = aggregate.top_talkers.iter().map(|talker| {
+        if previous_ips.contains(&talker.ip) {
+            TickEvent::IpUpdated(talker.ip, talker.avg_bps)
+        } else {
+            TickEvent::NewIp(talker.ip)
+        }
+    }).collect();
+    let current: HashSet<Ipv4Addr> = aggregate.top_talkers.iter().map(|talker| talker.ip).collect();
+    events.extend(previous_ips.difference(&current).map(|ip| TickEvent::IpRemoved(*ip)));
+
+    let peaks = (aggregate.peak_rx_record.0, aggregate.peak_tx_record.0);
+    if peaks != previous_peaks {
+        events.push(TickEvent::PeakUpdated { rx: peaks.0, tx: peaks.1 });
+    }
+    events
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HOST: Ipv4Addr = Ipv4Addr::new(192, 168, 1, 10);
+
+    fn app() -> (App, Vec<Arc<SharedStats>>) {
+        let config = Config { history_secs: 10, tick_ms: 1000, idle_timeout_secs: 30 };
+        (App::new(config, &["eth0".to_string()]), vec![Arc::new(SharedStats::new())])
+    }
+
+    // One tick's worth of traffic received by `ip`
+    fn receive(stats: &SharedStats, ip: Ipv4Addr, bytes: u64) {
+        let mut delta = stats.deltas.write();
+        *delta.rx_per_ip.entry(ip).or_default() += Traffic::packet(bytes);
+        delta.rx += Traffic::packet(bytes);
+    }
+
+    fn has_peak(events: &[TickEvent]) -> bool {
+        events.iter().any(|event| matches!(event, TickEvent::PeakUpdated { .. }))
+    }
+
+    #[test]
+    fn app_default_has_empty_history() {
+        let app = App::default();
+        // Only the "All" aggregate, with nothing tracked yet
+        assert_eq!(app.interfaces.len(), 1);
+        let all = app.interfaces.last().unwrap();
+        assert!(all.ip_histories.is_empty());
+        assert!(all.top_talkers.is_empty());
+        assert_eq!(all.total_rx_bytes + all.total_tx_bytes, 0);
+        assert!(all.rx_history.iter().chain(all.tx_history.iter()).all(|sample| *sample == 0.0));
+        assert_eq!(all.rx_history.len(), app.config.max_samples());
+    }
+
+    #[test]
+    fn first_traffic_reports_new_ip() {
+        let (mut app, stats) = app();
+        receive(&stats[0], HOST, 1000);
+        let events = app.on_tick(&stats);
+        assert!(events.contains(&TickEvent::NewIp(HOST)));
+        assert!(!events.iter().any(|event| matches!(event, TickEvent::IpUpdated(..))));
+    }
+
+    #[test]
+    fn known_ip_reports_update() {
+        let (mut app, stats) = app();
+        receive(&stats[0], HOST, 1000);
+        app.on_tick(&stats);
+        receive(&stats[0], HOST, 1000);
+        let events = app.on_tick(&stats);
+        assert!(!events.contains(&TickEvent::NewIp(HOST)));
+        match events.iter().find(|event| matches!(event, TickEvent::IpUpdated(ip, _) if *ip == HOST)) {
+            Some(TickEvent::IpUpdated(_, rate)) => assert!(*rate > 0.0),
+            _ => panic!("no IpUpdated for {} in {:?}", HOST, events),
+        }
+    }
+
+    #[test]
+    fn idle_ip_is_removed() {
+        let (mut app, stats) = app();
+        receive(&stats[0], HOST, 1000);
+        app.on_tick(&stats);
+        // Age every copy of the history past the idle timeout instead of sleeping
+        let idle_since = Instant::now() - app.config.idle_timeout() - Duration::from_secs(1);
+        for iface in &mut app.interfaces {
+            for history in iface.ip_histories.values_mut() {
+                history.last_seen = idle_since;
+            }
+        }
+        let events = app.on_tick(&stats);
+        assert_eq!(events, vec![TickEvent::IpRemoved(HOST)]);
+        assert!(app.interfaces.last().unwrap().top_talkers.is_empty());
+    }
+
+    #[test]
+    fn higher_rate_updates_peaks() {
+        let (mut app, stats) = app();
+        receive(&stats[0], HOST, 1000);
+        let events = app.on_tick(&stats);
+        match events.iter().find(|event| matches!(event, TickEvent::PeakUpdated { .. })) {
+            Some(TickEvent::PeakUpdated { rx, tx }) => {
+                assert!(*rx > 0.0);
+                assert_eq!(*tx, 0.0);
+            }
+            _ => panic!("no PeakUpdated in {:?}", events),
+        }
+
+        // A quieter tick leaves the records alone, a busier one raises them again
+        receive(&stats[0], HOST, 10);
+        assert!(!has_peak(&app.on_tick(&stats)));
+        receive(&stats[0], HOST, 1_000_000);
+        assert!(has_peak(&app.on_tick(&stats)));
+    }
+
+    // Trend after one tick per entry of `bytes`, each a second long
+    fn trend_of(bytes: impl IntoIterator<Item = u64>) -> Trend {
+        let config = Config { history_secs: 60, tick_ms: 1000, idle_timeout_secs: 30 };
+        let mut history = IpHistory::new(config);
+        for bytes in bytes {
+            history.update(Traffic { bytes, packets: 1 }, Traffic::default(), 1.0);
+        }
+        history.compute_trend()
+    }
+
+    #[test]
+    fn trend_rising() {
+        assert_eq!(trend_of((0..TREND_WINDOW as u64).map(|i| 1000 + i * 100)), Trend::Rising);
+    }
+
+    #[test]
+    fn trend_falling() {
+        assert_eq!(trend_of((0..TREND_WINDOW as u64).map(|i| 2000 - i * 100)), Trend::Falling);
+    }
+
+    #[test]
+    fn trend_stable_within_threshold() {
+        // Noise around a level, and a slope that moves about 4% across the window
+        assert_eq!(trend_of([1000, 1050, 960, 1020, 990, 1040, 970, 1010, 1000, 990]), Trend::Stable);
+        assert_eq!(trend_of((0..TREND_WINDOW as u64).map(|i| 1000 + i * 5)), Trend::Stable);
+    }
+
+    #[test]
+    fn trend_all_zeros_is_stable() {
+        assert_eq!(trend_of([0; TREND_WINDOW]), Trend::Stable);
+    }
+
+    #[test]
+    fn trend_needs_a_full_window() {
+        assert_eq!(trend_of((0..TREND_WINDOW as u64 - 1).map(|i| 1000 + i * 1000)), Trend::Stable);
+    }
+
+    #[test]
+    fn trend_only_fits_the_last_window() {
+        let ramp = (0..TREND_WINDOW as u64).map(|i| i * 1000);
+        assert_eq!(trend_of(ramp.chain([5000; TREND_WINDOW])), Trend::Stable);
+    }
+
+    #[test]
+    fn quiet_tick_reports_nothing_new() {
+        let (mut app, stats) = app();
+        assert!(app.on_tick(&stats).is_empty());
+    }
 
-        stats.traffic_delta.clear();
-        stats.rx_delta = 0;
-        stats.tx_delta = 0;
+    #[test]
+    fn anomalies_ride_along_with_the_tick() {
+        let (mut app, stats) = app();
+        let event = AnomalyEvent::TtlVariation { ip: HOST, old_ttl: 64, new_ttl: 128, time: Local::now() };
+        stats[0].deltas.write().anomalies.push(event);
+        app.on_tick(&stats);
+        assert_eq!(app.anomalies.iter().map(AnomalyEvent::ip).collect::<Vec<_>>(), [HOST]);
+        app.on_tick(&stats);
+        assert_eq!(app.anomalies.len(), 1);
     }
 }
@@ -0,0 +1,56 @@
+use std::{collections::HashMap, net::Ipv4Addr, sync::Mutex};
+use chrono::{DateTime, Local};
+use pnet::packet::arp::ArpPacket;
+use crate::oui::format_mac;
+
+// App keeps at most this many ARP alerts, dropping the oldest
+pub const MAX_ARP_ALERTS: usize = 100;
+
+// An IP that was announced from a different MAC than before: possible ARP cache poisoning
+#[derive(Clone)]
+pub struct ArpAlert {
+    pub time: DateTime<Local>,
+    pub ip: Ipv4Addr,
+    pub old_mac: [u8; 6],
+    pub new_mac: [u8; 6],
+}
+
+impl ArpAlert {
+    pub fn describe(&self) -> String {
+        format!(
+            "{} {} moved from {} to {}",
+            self.time.format("%H:%M:%S"),
+            self.ip,
+            format_mac(&self.old_mac),
+            format_mac(&self.new_mac)
+        )
+    }
+
+    // A changed mapping is how ARP poisoning shows up, so `--syslog` reports it as such
+    pub fn syslog_message(&self) -> String {
+        format!("ALERT type=ArpPoisoning ip={} old_mac={} new_mac={}", self.ip, format_mac(&self.old_mac), format_mac(&self.new_mac))
+    }
+}
+
+// IP -> MAC mappings learned from ARP traffic on one capture, plus the changes not yet shown
+#[derive(Default)]
+pub struct ArpState {
+    pub table: HashMap<Ipv4Addr, [u8; 6]>,
+    pub alerts: Vec<ArpAlert>,
+}
+
+// Learn the sender's mapping from a request or reply. Probes (sender 0.0.0.0) claim nothing.
+pub fn record(arp: &ArpPacket, state: &Mutex<ArpState>) {
+    let ip = arp.get_sender_proto_addr();
+    let mac = arp.get_sender_hw_addr().octets();
+    if ip.is_unspecified() {
+        return;
+    }
+
+    let mut state = state.lock().unwrap();
+    if let Some(old_mac) = state.table.insert(ip, mac) {
+        if old_mac != mac {
+            state.alerts.push(ArpAlert { time: Local::now(), ip, old_mac, new_mac: mac });
+        }
+    }
+}
@@ -0,0 +1,582 @@
+use std::{collections::HashMap, net::Ipv4Addr, path::PathBuf, sync::atomic::Ordering, time::Duration};
+use pnet::ipnetwork::Ipv4Network;
+use crate::alert::DEFAULT_MAX_CONNECTIONS_PER_IP;
+use crate::columns::{parse_columns, ColumnSpec};
+use crate::config::Config;
+use crate::constants::{
+    ESTIMATED_TRACKED_IPS, HISTORY_BYTES_PER_SAMPLE, HISTORY_MEMORY_LIMIT_MB, HISTORY_MEMORY_WARN_MB, MAX_TICK_RATE_MS, MIN_TICK_RATE_MS,
+    RUNTIME_TICK_RATE,
+};
+use crate::dns::{DEFAULT_DNS_RETRIES, DEFAULT_DNS_TTL_SECS};
+use crate::error::IftopError;
+use crate::influx::{InfluxOptions, DEFAULT_INFLUX_INTERVAL};
+use crate::network::{pcap_lib_version, CaptureConfig, FilterConfig, ReplayOptions, MIN_SNAPLEN};
+use crate::pcap_writer::PcapWriteOptions;
+use crate::report::DEFAULT_REPORT_INTERVAL_SECS;
+use crate::spoof::{DEFAULT_POISON_INTERVAL, MAX_SAFE_POISON_INTERVAL, MIN_POISON_INTERVAL, MIN_SAFE_POISON_INTERVAL};
+use crate::synthetic::DEFAULT_DRY_RUN_SEED;
+use crate::util::{parse_bandwidth, BitrateBase, DisplayUnit, GraphStyle, TotalUnit};
+
+// Command line options
+pub struct Args {
+    // Addresses to account per IP: private ranges or the positional CIDR, plus/minus `--include/exclude-subnet`
+    pub filter: FilterConfig,
+    // BPF expression applied to the capture handle before the first packet
+    pub bpf: Option<String>,
+    // Interfaces given with -i; empty means the default device
+    pub interfaces: Vec<String>,
+    // Append per-IP traffic rows to this CSV file every tick
+    pub log_csv: Option<PathBuf>,
+    // Stream newline-delimited JSON records to this file, or stdout for `-`
+    pub log_json: Option<String>,
+    // Rewrite a self-contained HTML report at this path every `report_interval`
+    pub html_report: Option<PathBuf>,
+    pub report_interval: Duration,
+    // SQLite database of per-minute traffic per IP, queried with `h`
+    pub history_db: Option<PathBuf>,
+    // Sampling window and tick rate
+    pub config: Config,
+    // Drop traffic captured while paused instead of catching up on resume
+    pub discard_on_resume: bool,
+    // Show `443/https` instead of `443` in the session table
+    pub service_names: bool,
+    pub display_unit: DisplayUnit,
+    // K/M/G as powers of 1000 or 1024 for bit and byte rates
+    pub rate_base: BitrateBase,
+    // KiB/MiB, KB/MB or Kb/Mb for byte totals
+    pub total_unit: TotalUnit,
+    pub graph_style: GraphStyle,
+    // Order and widths of the talkers table columns, from `--columns`
+    pub columns: Option<Vec<ColumnSpec>>,
+    // Plot the graphs on a log2 scale so small rates stay visible next to a large one
+    pub log_scale: bool,
+    // Draw the render time of recent frames under the graphs
+    pub debug_perf: bool,
+    // Redraw the UI at this interval instead of after every tick
+    pub ui_tick: Option<Duration>,
+    // MaxMind country database for the Country column
+    pub geoip: Option<PathBuf>,
+    // Bandwidth alert thresholds in bits/s; per-IP entries override the global one
+    pub alert_threshold: Option<f64>,
+    pub alert_ips: HashMap<Ipv4Addr, f64>,
+    pub alert_log: Option<PathBuf>,
+    // Live sessions an IP may have before it alerts
+    pub max_connections_per_ip: u16,
+    // Send alerts to the system log as well
+    pub syslog: bool,
+    // Bookmarked IPs with optional per-IP thresholds, saved back on exit
+    pub watchlist: Option<PathBuf>,
+    // Start from the totals and peaks saved when the last session exited
+    pub resume: bool,
+    // Prefix length to group top talkers by, when `--aggregate-subnet` is given
+    pub aggregate_prefix: Option<u8>,
+    // Reverse DNS for the top talkers (`-n` disables it) and how long answers are cached
+    pub dns_enabled: bool,
+    pub dns_ttl: Duration,
+    // Retries of a timed out reverse lookup, each after twice the wait of the last
+    pub dns_retries: u32,
+    // Subnets whose hosts are reverse resolved at startup, before they show up in traffic
+    pub dns_prefetch: Vec<Ipv4Network>,
+    // "dark", "light" or the path of a TOML theme file
+    pub theme: Option<String>,
+    // Read packets from a pcap file instead of a live device
+    pub replay: Option<ReplayOptions>,
+    // Generate synthetic traffic from this seed instead of capturing, see `--dry-run`
+    pub dry_run: Option<u64>,
+    // Promiscuous mode, snaplen and read timeout for live devices
+    pub capture: CaptureConfig,
+    // Save matched packets to a size-rotated pcap file
+    pub write_pcap: Option<PcapWriteOptions>,
+    // Address to serve Prometheus metrics on, e.g. `0.0.0.0:9090`
+    pub metrics_addr: Option<String>,
+    // Address to stream per-tick JSON over WebSocket on, e.g. `0.0.0.0:8080`
+    pub ws_addr: Option<String>,
+    // UDP `host:port` of a NetFlow v5 collector to export finished sessions to
+    pub netflow_collector: Option<String>,
+    // InfluxDB 2 server to write interface and per-IP metrics to, see `influx.rs`
+    pub influxdb: Option<InfluxOptions>,
+    // Print JSON summaries to stdout instead of drawing the UI, one per `headless_interval` ticks
+    pub headless: bool,
+    pub headless_interval: u64,
+    // Target and gateway to ARP spoof on the first capture interface, see `spoof.rs`
+    pub arp_spoof: Option<(Ipv4Addr, Ipv4Addr)>,
+    // How often the forged ARP replies are repeated
+    pub spoof_interval: Duration,
+    // Read the kernel's error and drop counters from /proc/net/dev; off with `--no-proc-stats`
+    pub proc_stats: bool,
+    // Link capacity in bits/s for the utilization gauges; detected per interface when absent
+    pub link_speed: Option<f64>,
+}
+
+pub fn parse_args() -> Result<Args, IftopError> {
+    let mut args = Args {
+        filter: FilterConfig::default(),
+        bpf: None,
+        interfaces: Vec::new(),
+        log_csv: None,
+        log_json: None,
+        html_report: None,
+        report_interval: Duration::from_secs(DEFAULT_REPORT_INTERVAL_SECS),
+        history_db: None,
+        config: Config::default(),
+        discard_on_resume: false,
+        service_names: true,
+        display_unit: DisplayUnit::Bits,
+        rate_base: BitrateBase::Decimal1000,
+        total_unit: TotalUnit::IecBytes,
+        graph_style: GraphStyle::Bar,
+        columns: None,
+        log_scale: false,
+        debug_perf: false,
+        ui_tick: None,
+        geoip: None,
+        alert_threshold: None,
+        alert_ips: HashMap::new(),
+        max_connections_per_ip: DEFAULT_MAX_CONNECTIONS_PER_IP,
+        alert_log: None,
+        syslog: false,
+        watchlist: None,
+        resume: false,
+        aggregate_prefix: None,
+        dns_enabled: true,
+        dns_ttl: Duration::from_secs(DEFAULT_DNS_TTL_SECS),
+        dns_retries: DEFAULT_DNS_RETRIES,
+        dns_prefetch: Vec::new(),
+        theme: None,
+        replay: None,
+        dry_run: None,
+        capture: CaptureConfig::default(),
+        write_pcap: None,
+        metrics_addr: None,
+        ws_addr: None,
+        netflow_collector: None,
+        influxdb: None,
+        headless: false,
+        headless_interval: 1,
+        arp_spoof: None,
+        spoof_interval: DEFAULT_POISON_INTERVAL,
+        proc_stats: true,
+        link_speed: None,
+    };
+    let mut pcap_rotate_mb = 100;
+    let mut pcap_filter = None;
+    let mut replay_speed = 1.0;
+    let mut replay_loop = false;
+    let mut dry_run = false;
+    let mut dry_run_seed = DEFAULT_DRY_RUN_SEED;
+    let mut aggregate_subnet = false;
+    let mut aggregate_prefix = 24;
+    let mut cidr_arg: Option<String> = None;
+    let mut spoof_target: Option<Ipv4Addr> = None;
+    let mut spoof_gateway: Option<Ipv4Addr> = None;
+    let mut spoof_interval: Option<Duration> = None;
+    let mut influx_url: Option<String> = None;
+    let mut influx_bucket: Option<String> = None;
+    let mut influx_org: Option<String> = None;
+    let mut influx_token: Option<String> = None;
+    let mut influx_interval: Option<u64> = None;
+    let mut version = false;
+    let mut verbose = false;
+
+    let mut iter = std::env::args().skip(1);
+    while let Some(arg) = iter.next() {
+        match arg.as_str() {
+            "-V" | "--version" => version = true,
+            "--verbose" => verbose = true,
+            "--bpf" => args.bpf = Some(next_value(&mut iter, &arg)?),
+            "-i" | "--interface" => args.interfaces.push(next_value(&mut iter, &arg)?),
+            "--log-csv" => args.log_csv = Some(PathBuf::from(next_value(&mut iter, &arg)?)),
+            "--log-json" => args.log_json = Some(next_value(&mut iter, &arg)?),
+            "--html-report" => args.html_report = Some(PathBuf::from(next_value(&mut iter, &arg)?)),
+            "--history-db" => args.history_db = Some(PathBuf::from(next_value(&mut iter, &arg)?)),
+            "--report-interval" => args.report_interval = Duration::from_secs(parse_positive(&next_value(&mut iter, &arg)?, &arg)?),
+            "--discard-on-resume" => args.discard_on_resume = true,
+            "--no-service-names" => args.service_names = false,
+            "--unit" => args.display_unit = DisplayUnit::parse(&next_value(&mut iter, &arg)?).map_err(IftopError::InvalidArgument)?,
+            "--log-scale" => args.log_scale = true,
+            "--debug-perf" => args.debug_perf = true,
+            "--ui-tick" => args.ui_tick = Some(Duration::from_millis(parse_positive(&next_value(&mut iter, &arg)?, &arg)?)),
+            "--columns" => args.columns = Some(parse_columns(&next_value(&mut iter, &arg)?).map_err(IftopError::InvalidArgument)?),
+            "--graph-style" => args.graph_style = GraphStyle::parse(&next_value(&mut iter, &arg)?).map_err(IftopError::InvalidArgument)?,
+            "--rate-base" => args.rate_base = BitrateBase::parse(&next_value(&mut iter, &arg)?).map_err(IftopError::InvalidArgument)?,
+            "--total-unit" => args.total_unit = TotalUnit::parse(&next_value(&mut iter, &arg)?).map_err(IftopError::InvalidArgument)?,
+            "--link-speed" => args.link_speed = Some(parse_bandwidth(&next_value(&mut iter, &arg)?)?),
+            "--alert-threshold" => args.alert_threshold = Some(parse_bandwidth(&next_value(&mut iter, &arg)?)?),
+            "--alert-ip" => {
+                let (ip, threshold) = parse_alert_ip(&next_value(&mut iter, &arg)?)?;
+                args.alert_ips.insert(ip, threshold);
+            }
+            "--max-connections-per-ip" => {
+                let value = next_value(&mut iter, &arg)?;
+                args.max_connections_per_ip = parse_positive(&value, &arg)?
+                    .try_into()
+                    .map_err(|_| IftopError::InvalidArgument(format!("'{}' expects at most {}, got '{}'", arg, u16::MAX, value)))?;
+            }
+            "-n" | "--no-dns" => args.dns_enabled = false,
+            "--no-proc-stats" => args.proc_stats = false,
+            "--dns-ttl" => args.dns_ttl = Duration::from_secs(parse_positive(&next_value(&mut iter, &arg)?, &arg)?),
+            "--dns-prefetch" => args.dns_prefetch.push(parse_subnet(&next_value(&mut iter, &arg)?, &arg)?),
+            "--dns-retries" => {
+                let value = next_value(&mut iter, &arg)?;
+                args.dns_retries = value
+                    .parse()
+                    .map_err(|_| IftopError::InvalidArgument(format!("'{}' expects a number of retries, got '{}'", arg, value)))?;
+            }
+            "--include-subnet" => args.filter.include.push(parse_subnet(&next_value(&mut iter, &arg)?, &arg)?),
+            "--exclude-subnet" => args.filter.exclude.push(parse_subnet(&next_value(&mut iter, &arg)?, &arg)?),
+            "--track-link-local" => args.filter.link_local = true,
+            "--track-loopback" => args.filter.loopback = true,
+            "--track-cgnat" => args.filter.cgnat = true,
+            "--aggregate-subnet" => aggregate_subnet = true,
+            "--aggregate-prefix" => {
+                aggregate_subnet = true;
+                aggregate_prefix = parse_prefix_len(&next_value(&mut iter, &arg)?)?;
+            }
+            "--watchlist" => args.watchlist = Some(PathBuf::from(next_value(&mut iter, &arg)?)),
+            "--alert-log" => args.alert_log = Some(PathBuf::from(next_value(&mut iter, &arg)?)),
+            "--syslog" => args.syslog = true,
+            "--resume" => args.resume = true,
+            "--replay" => args.replay = Some(ReplayOptions {
+                path: PathBuf::from(next_value(&mut iter, &arg)?),
+                speed: 1.0,
+                looped: false,
+            }),
+            "--arp-spoof" => spoof_target = Some(parse_ip(&next_value(&mut iter, &arg)?)?),
+            "--gateway" => spoof_gateway = Some(parse_ip(&next_value(&mut iter, &arg)?)?),
+            "--spoof-interval" => spoof_interval = Some(parse_seconds(&next_value(&mut iter, &arg)?, &arg, MIN_POISON_INTERVAL)?),
+            "--replay-speed" => replay_speed = parse_speed(&next_value(&mut iter, &arg)?)?,
+            "--replay-loop" => replay_loop = true,
+            "--dry-run" => dry_run = true,
+            "--dry-run-seed" => {
+                dry_run = true;
+                let value = next_value(&mut iter, &arg)?;
+                dry_run_seed = value
+                    .parse()
+                    .map_err(|_| IftopError::InvalidArgument(format!("'{}' expects a number, got '{}'", arg, value)))?;
+            }
+            "--promisc" => args.capture.promisc = true,
+            "--no-promisc" => args.capture.promisc = false,
+            "--no-kernel-filter" => args.capture.kernel_filter = false,
+            "--snaplen" => args.capture.snaplen = parse_snaplen(&next_value(&mut iter, &arg)?)?,
+            "--stats-interval" => args.capture.stats_interval = Duration::from_secs(parse_positive(&next_value(&mut iter, &arg)?, &arg)?),
+            "--capture-timeout" => args.capture.timeout_ms = parse_positive_i32(&next_value(&mut iter, &arg)?, &arg)?,
+            "--write-pcap" => args.write_pcap = Some(PcapWriteOptions {
+                path: PathBuf::from(next_value(&mut iter, &arg)?),
+                rotate_bytes: 0,
+                filter: None,
+            }),
+            "--pcap-rotate-mb" => pcap_rotate_mb = parse_positive(&next_value(&mut iter, &arg)?, &arg)?,
+            "--pcap-filter" => pcap_filter = Some(next_value(&mut iter, &arg)?),
+            "--metrics-addr" => args.metrics_addr = Some(next_value(&mut iter, &arg)?),
+            "--ws-addr" => args.ws_addr = Some(next_value(&mut iter, &arg)?),
+            "--netflow-collector" => args.netflow_collector = Some(next_value(&mut iter, &arg)?),
+            "--influxdb-url" => influx_url = Some(next_value(&mut iter, &arg)?),
+            "--influxdb-bucket" => influx_bucket = Some(next_value(&mut iter, &arg)?),
+            "--influxdb-org" => influx_org = Some(next_value(&mut iter, &arg)?),
+            "--influxdb-token" => influx_token = Some(next_value(&mut iter, &arg)?),
+            "--influxdb-interval" => influx_interval = Some(parse_positive(&next_value(&mut iter, &arg)?, &arg)?),
+            "--headless" => args.headless = true,
+            "--headless-interval" => args.headless_interval = parse_positive(&next_value(&mut iter, &arg)?, &arg)?,
+            "--theme" => args.theme = Some(next_value(&mut iter, &arg)?),
+            "--geoip" => args.geoip = Some(PathBuf::from(next_value(&mut iter, &arg)?)),
+            "--history" => args.config.history_secs = parse_positive(&next_value(&mut iter, &arg)?, &arg)?,
+            "--idle-timeout" => args.config.idle_timeout_secs = parse_positive(&next_value(&mut iter, &arg)?, &arg)?,
+            "--tick" | "--capture-tick" => args.config.tick_ms = parse_positive(&next_value(&mut iter, &arg)?, &arg)?,
+            flag if flag.starts_with("--") => return Err(IftopError::InvalidArgument(format!("Unknown option '{}'", flag))),
+            _ => cidr_arg = Some(arg),
+        }
+    }
+    if version {
+        print_version(verbose);
+        std::process::exit(0);
+    }
+
+    if let Some(warning) = validate_config(&args.config)? {
+        eprintln!("Warning: {}", warning);
+    }
+    RUNTIME_TICK_RATE.store(args.config.tick_ms, Ordering::Relaxed);
+    if aggregate_subnet {
+        args.aggregate_prefix = Some(aggregate_prefix);
+    }
+
+    if let Some(replay) = args.replay.as_mut() {
+        replay.speed = replay_speed;
+        replay.looped = replay_loop;
+    }
+
+    if dry_run {
+        args.dry_run = Some(dry_run_seed);
+        let conflict = if args.replay.is_some() {
+            Some("--replay")
+        } else if !args.interfaces.is_empty() {
+            Some("--interface")
+        } else if args.write_pcap.is_some() {
+            Some("--write-pcap")
+        } else {
+            None
+        };
+        if let Some(option) = conflict {
+            return Err(IftopError::InvalidArgument(format!("'--dry-run' generates its own traffic and cannot be combined with '{}'", option)));
+        }
+    }
+
+    args.influxdb = match (influx_url, influx_bucket) {
+        (Some(url), Some(bucket)) => Some(InfluxOptions {
+            url,
+            bucket,
+            org: influx_org,
+            // Like the influx CLI, so the token need not show up in `ps`
+            token: influx_token.or_else(|| std::env::var("INFLUX_TOKEN").ok()),
+            interval: influx_interval.unwrap_or(DEFAULT_INFLUX_INTERVAL),
+        }),
+        (None, None) if influx_org.is_none() && influx_token.is_none() && influx_interval.is_none() => None,
+        (Some(_), None) => return Err(IftopError::InvalidArgument("'--influxdb-url' needs '--influxdb-bucket'".to_string())),
+        _ => return Err(IftopError::InvalidArgument("'--influxdb-bucket', '--influxdb-org', '--influxdb-token' and '--influxdb-interval' need '--influxdb-url'".to_string())),
+    };
+
+    if !args.dns_prefetch.is_empty() && (!args.dns_enabled || args.headless) {
+        return Err(IftopError::InvalidArgument("'--dns-prefetch' needs reverse DNS, which '--no-dns' and '--headless' turn off".to_string()));
+    }
+
+    args.arp_spoof = match (spoof_target, spoof_gateway) {
+        (Some(target), Some(gateway)) => Some((target, gateway)),
+        (None, None) => None,
+        _ => return Err(IftopError::InvalidArgument("'--arp-spoof' and '--gateway' must be given together".to_string())),
+    };
+    if args.arp_spoof.is_some() && (args.replay.is_some() || dry_run) {
+        return Err(IftopError::InvalidArgument("'--arp-spoof' needs a live interface and cannot be combined with '--replay' or '--dry-run'".to_string()));
+    }
+    if let Some(interval) = spoof_interval {
+        if args.arp_spoof.is_none() {
+            return Err(IftopError::InvalidArgument("'--spoof-interval' needs '--arp-spoof'".to_string()));
+        }
+        if interval < MIN_SAFE_POISON_INTERVAL {
+            eprintln!("Warning: ARP replies every {:.2}s may disrupt the network; {}s or more is safer", interval.as_secs_f64(), MIN_SAFE_POISON_INTERVAL.as_secs_f64());
+        } else if interval > MAX_SAFE_POISON_INTERVAL {
+            eprintln!("Warning: ARP replies every {:.0}s may let the victims' ARP caches expire between them", interval.as_secs_f64());
+        }
+        args.spoof_interval = interval;
+    }
+
+    if args.resume && (args.replay.is_some() || dry_run) {
+        return Err(IftopError::InvalidArgument("'--resume' continues a live session and cannot be combined with '--replay' or '--dry-run'".to_string()));
+    }
+
+    // Both would write JSON lines to stdout
+    if args.headless && args.log_json.as_deref() == Some("-") {
+        return Err(IftopError::InvalidArgument("'--log-json -' cannot be combined with '--headless'".to_string()));
+    }
+
+    if let Some(write) = args.write_pcap.as_mut() {
+        write.rotate_bytes = pcap_rotate_mb * 1024 * 1024;
+        write.filter = pcap_filter;
+    }
+
+    // A positional CIDR replaces the private ranges; --include-subnet only adds to them
+    match cidr_arg {
+        Some(s) => {
+            match s.parse::<Ipv4Network>() {
+                Ok(net) => {
+                    eprintln!("Filter mode: Targeting subnet {}", net);
+                    args.filter.private = false;
+                    args.filter.include.insert(0, net);
+                },
+                Err(_) => {
+                    eprintln!("Invalid CIDR provided '{}', falling back to default private ranges.", s);
+                }
+            }
+        },
+        None => {
+            eprintln!("No subnet provided. Targeting all standard private networks (RFC1918).");
+        }
+    };
+    if !args.filter.exclude.is_empty() {
+        let excluded: Vec<String> = args.filter.exclude.iter().map(|net| net.to_string()).collect();
+        eprintln!("Excluding {}", excluded.join(", "));
+    }
+
+    Ok(args)
+}
+
+// Every tracked IP keeps `max_samples` entries per window, so `--history` and `--tick` decide how
+// much memory a busy network costs; refuse settings that would need more than
+// `HISTORY_MEMORY_LIMIT_MB` and return a warning above `HISTORY_MEMORY_WARN_MB`.
+// The tick must also start inside the range `[` / `]` can move it through.
+fn validate_config(config: &Config) -> Result<Option<String>, IftopError> {
+    if !(MIN_TICK_RATE_MS..=MAX_TICK_RATE_MS).contains(&config.tick_ms) {
+        return Err(IftopError::ConfigError(format!(
+            "Tick of {} ms out of range; '--tick' must be between {} and {} ms",
+            config.tick_ms, MIN_TICK_RATE_MS, MAX_TICK_RATE_MS
+        )));
+    }
+    let estimated_memory_mb = ESTIMATED_TRACKED_IPS
+        .saturating_mul(config.max_samples() as u64)
+        .saturating_mul(HISTORY_BYTES_PER_SAMPLE)
+        / 1024
+        / 1024;
+    if estimated_memory_mb > HISTORY_MEMORY_LIMIT_MB {
+        return Err(IftopError::ConfigError(format!(
+            "History too long: a {}s window at {} ms ticks needs about {} MB for {} hosts (limit {} MB); shorten '--history' or raise '--tick'",
+            config.history_secs, config.tick_ms, estimated_memory_mb, ESTIMATED_TRACKED_IPS, HISTORY_MEMORY_LIMIT_MB
+        )));
+    }
+    if estimated_memory_mb > HISTORY_MEMORY_WARN_MB {
+        return Ok(Some(format!(
+            "a {}s window at {} ms ticks needs about {} MB for {} hosts",
+            config.history_secs, config.tick_ms, estimated_memory_mb, ESTIMATED_TRACKED_IPS
+        )));
+    }
+    Ok(None)
+}
+
+// Product name shown by `--version`; the package itself is called `net_monitor`
+const PRODUCT_NAME: &str = "iftop_rs";
+
+// `--version`, plus the toolchain and capture library with `--verbose`
+fn print_version(verbose: bool) {
+    let profile = if cfg!(debug_assertions) { "debug" } else { "release" };
+    println!("{} {} (built {}, {})", PRODUCT_NAME, env!("CARGO_PKG_VERSION"), env!("BUILD_DATE"), profile);
+    if verbose {
+        println!("{}", env!("RUSTC_VERSION"));
+        println!("{}", pcap_lib_version());
+        let backend = if cfg!(windows) { "Npcap" } else { "libpcap" };
+        println!("capture backend: {}", backend);
+    }
+}
+
+fn next_value(iter: &mut impl Iterator<Item = String>, flag: &str) -> Result<String, IftopError> {
+    iter.next().ok_or_else(|| IftopError::InvalidArgument(format!("Missing value for '{}'", flag)))
+}
+
+fn parse_positive(value: &str, flag: &str) -> Result<u64, IftopError> {
+    match value.parse::<u64>() {
+        Ok(n) if n > 0 => Ok(n),
+        _ => Err(IftopError::InvalidArgument(format!("'{}' expects a positive integer, got '{}'", flag, value))),
+    }
+}
+
+fn parse_positive_i32(value: &str, flag: &str) -> Result<i32, IftopError> {
+    match value.parse::<i32>() {
+        Ok(n) if n > 0 => Ok(n),
+        _ => Err(IftopError::InvalidArgument(format!("'{}' expects a positive integer, got '{}'", flag, value))),
+    }
+}
+
+fn parse_snaplen(value: &str) -> Result<i32, IftopError> {
+    match value.parse::<i32>() {
+        Ok(n) if (MIN_SNAPLEN..=65535).contains(&n) => Ok(n),
+        _ => Err(IftopError::InvalidArgument(format!(
+            "'--snaplen' expects {}-65535 bytes (Ethernet + IPv4 header), got '{}'",
+            MIN_SNAPLEN, value
+        ))),
+    }
+}
+
+fn parse_subnet(value: &str, flag: &str) -> Result<Ipv4Network, IftopError> {
+    value
+        .parse()
+        .map_err(|_| IftopError::InvalidCidr(format!("'{}' expects an IPv4 CIDR such as 100.64.0.0/10, got '{}'", flag, value)))
+}
+
+fn parse_prefix_len(value: &str) -> Result<u8, IftopError> {
+    match value.parse::<u8>() {
+        Ok(n) if (1..=32).contains(&n) => Ok(n),
+        _ => Err(IftopError::InvalidArgument(format!("'--aggregate-prefix' expects a prefix length from 1 to 32, got '{}'", value))),
+    }
+}
+
+fn parse_ip(value: &str) -> Result<Ipv4Addr, IftopError> {
+    value.trim().parse().map_err(|_| IftopError::InvalidArgument(format!("Invalid IP address '{}'", value)))
+}
+
+// "192.168.1.5=5M" -> (192.168.1.5, 5_000_000.0)
+fn parse_alert_ip(value: &str) -> Result<(Ipv4Addr, f64), IftopError> {
+    let (ip, threshold) = value
+        .split_once('=')
+        .ok_or_else(|| IftopError::InvalidArgument(format!("'--alert-ip' expects IP=RATE, got '{}'", value)))?;
+    Ok((parse_ip(ip)?, parse_bandwidth(threshold)?))
+}
+
+// "2" or "0.5" -> a Duration in seconds, no shorter than `min`
+fn parse_seconds(value: &str, flag: &str, min: Duration) -> Result<Duration, IftopError> {
+    // NaN, negative and out of range values have no Duration
+    let secs = value.parse::<f64>().ok().and_then(|secs| Duration::try_from_secs_f64(secs).ok());
+    match secs {
+        Some(secs) if secs >= min => Ok(secs),
+        _ => Err(IftopError::InvalidArgument(format!(
+            "'{}' expects a number of seconds no smaller than {}, got '{}'",
+            flag,
+            min.as_secs_f64(),
+            value
+        ))),
+    }
+}
+
+// "10x" or "10" -> 10.0
+fn parse_speed(value: &str) -> Result<f64, IftopError> {
+    match value.trim_end_matches(['x', 'X']).parse::<f64>() {
+        Ok(speed) if speed > 0.0 && speed.is_finite() => Ok(speed),
+        _ => Err(IftopError::InvalidArgument(format!("Invalid replay speed '{}', expected e.g. 10x", value))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn config(history_secs: u64, tick_ms: u64) -> Config {
+        Config { history_secs, tick_ms, ..Config::default() }
+    }
+
+    #[test]
+    fn default_window_is_fine() {
+        assert!(validate_config(&Config::default()).unwrap().is_none());
+        assert!(validate_config(&config(600, 500)).unwrap().is_none());
+    }
+
+    #[test]
+    fn long_window_warns() {
+        // 3600 samples per window, about 192 MB for the assumed hosts
+        let warning = validate_config(&config(1800, 500)).unwrap().expect("warning");
+        assert!(warning.contains("192 MB"), "{}", warning);
+    }
+
+    #[test]
+    fn oversized_window_is_refused() {
+        // 36000 samples per window, well past the limit
+        match validate_config(&config(3600, 100)) {
+            Err(IftopError::ConfigError(message)) => assert!(message.starts_with("History too long"), "{}", message),
+            other => panic!("expected a ConfigError, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn tick_range_edges_are_accepted() {
+        assert!(validate_config(&config(60, MIN_TICK_RATE_MS)).is_ok());
+        assert!(validate_config(&config(60, MAX_TICK_RATE_MS)).is_ok());
+    }
+
+    #[test]
+    fn tick_outside_range_is_refused() {
+        for tick_ms in [MIN_TICK_RATE_MS - 1, 1, MAX_TICK_RATE_MS + 1] {
+            assert!(matches!(validate_config(&config(60, tick_ms)), Err(IftopError::ConfigError(_))), "{} ms", tick_ms);
+        }
+    }
+
+    #[test]
+    fn seconds_accept_fractions() {
+        assert_eq!(parse_seconds("0.5", "--spoof-interval", MIN_POISON_INTERVAL).unwrap(), Duration::from_millis(500));
+        assert_eq!(parse_seconds("0.1", "--spoof-interval", MIN_POISON_INTERVAL).unwrap(), MIN_POISON_INTERVAL);
+    }
+
+    #[test]
+    fn seconds_refuse_what_has_no_usable_duration() {
+        for value in ["1e30", "NaN", "inf", "-1", "0", "0.0000001", "0.09", "two"] {
+            assert!(
+                matches!(parse_seconds(value, "--spoof-interval", MIN_POISON_INTERVAL), Err(IftopError::InvalidArgument(_))),
+                "{}",
+                value
+            );
+        }
+    }
+}
@@ -0,0 +1,36 @@
+use std::{fs, io};
+
+// Written instead when the system clipboard is unreachable (SSH session, bare console)
+pub const FALLBACK_FILE: &str = "/tmp/iftop_rs_clipboard";
+
+// Where `copy` put the text
+pub enum CopyTarget {
+    Clipboard,
+    File(&'static str),
+}
+
+// The system clipboard through arboard: X11 or Wayland on Linux, the native one on macOS and
+// Windows. Opened on the first copy and kept for the life of the app, since under X11 and
+// Wayland this process serves the copied text and it goes away with the handle.
+#[derive(Default)]
+pub struct Clipboard {
+    system: Option<arboard::Clipboard>,
+}
+
+impl Clipboard {
+    // Copy `text` to the system clipboard, or into `FALLBACK_FILE` when there is none
+    pub fn copy(&mut self, text: &str) -> io::Result<CopyTarget> {
+        if self.system.is_none() {
+            self.system = arboard::Clipboard::new().ok();
+        }
+        if let Some(system) = self.system.as_mut() {
+            if system.set_text(text).is_ok() {
+                return Ok(CopyTarget::Clipboard);
+            }
+            // Reopened on the next copy, the display server may have gone away
+            self.system = None;
+        }
+        fs::write(FALLBACK_FILE, text)?;
+        Ok(CopyTarget::File(FALLBACK_FILE))
+    }
+}
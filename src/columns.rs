@@ -0,0 +1,129 @@
+// Columns of the top talkers table, in default order
+#[derive(Clone, Copy, PartialEq)]
+pub enum Column {
+    Ip,
+    Hostname,
+    Mac,
+    Country,
+    Rx,
+    Tx,
+    Speed,
+    Peak,
+    Time,
+    Total,
+    AvgPkt,
+    Conn,
+    Status,
+    Trend,
+}
+
+impl Column {
+    pub const ALL: [Column; 14] = [
+        Column::Ip,
+        Column::Hostname,
+        Column::Mac,
+        Column::Country,
+        Column::Rx,
+        Column::Tx,
+        Column::Speed,
+        Column::Peak,
+        Column::Time,
+        Column::Total,
+        Column::AvgPkt,
+        Column::Conn,
+        Column::Status,
+        Column::Trend,
+    ];
+
+    // Name used by `--columns`
+    pub fn name(self) -> &'static str {
+        match self {
+            Column::Ip => "IP",
+            Column::Hostname => "Hostname",
+            Column::Mac => "MAC",
+            Column::Country => "Country",
+            Column::Rx => "RX",
+            Column::Tx => "TX",
+            Column::Speed => "Speed",
+            Column::Peak => "Peak",
+            Column::Time => "Time",
+            Column::Total => "Total",
+            Column::AvgPkt => "AvgPkt",
+            Column::Conn => "Conn",
+            Column::Status => "Status",
+            Column::Trend => "Trend",
+        }
+    }
+
+    fn parse(name: &str) -> Result<Self, String> {
+        Column::ALL
+            .into_iter()
+            .find(|column| column.name().eq_ignore_ascii_case(name))
+            .ok_or_else(|| {
+                let names: Vec<&str> = Column::ALL.iter().map(|column| column.name()).collect();
+                format!("Unknown column '{}', expected one of {}", name, names.join(", "))
+            })
+    }
+}
+
+// One column of the top talkers table. A `width_pct` of 0 sizes the column to its
+// content (MAC, Country, Conn and Trend by default); `visible` is flipped at runtime by `m`, `T` and `C`.
+#[derive(Clone, Copy)]
+pub struct ColumnSpec {
+    pub column: Column,
+    pub width_pct: u16,
+    pub visible: bool,
+}
+
+// The layout used without `--columns`
+pub fn default_columns() -> Vec<ColumnSpec> {
+    Column::ALL
+        .into_iter()
+        .map(|column| {
+            let width_pct = match column {
+                Column::Ip => 14,
+                Column::Hostname => 16,
+                Column::Mac | Column::Country | Column::Conn | Column::Trend => 0,
+                _ => 8,
+            };
+            ColumnSpec { column, width_pct, visible: !matches!(column, Column::Mac | Column::Conn) }
+        })
+        .collect()
+}
+
+// `IP:20,Hostname:30,Speed:15,Peak:15,Time:20`: the listed columns in that order and width.
+// Columns left out are hidden and follow at their default width, so `m`, `T` and `C` can still show them.
+pub fn parse_columns(spec: &str) -> Result<Vec<ColumnSpec>, String> {
+    let mut columns: Vec<ColumnSpec> = Vec::new();
+    for entry in spec.split(',').map(str::trim).filter(|entry| !entry.is_empty()) {
+        let (name, width) = entry
+            .split_once(':')
+            .ok_or_else(|| format!("Invalid column '{}', expected NAME:PERCENT", entry))?;
+        let column = Column::parse(name.trim())?;
+        let width_pct: u16 = width
+            .trim()
+            .parse()
+            .ok()
+            .filter(|width| (1..=100).contains(width))
+            .ok_or_else(|| format!("Invalid width '{}' for column {}, expected 1-100", width.trim(), column.name()))?;
+        if columns.iter().any(|spec| spec.column == column) {
+            return Err(format!("Column {} is listed twice", column.name()));
+        }
+        columns.push(ColumnSpec { column, width_pct, visible: true });
+    }
+    if columns.is_empty() {
+        return Err("'--columns' needs at least one column".to_string());
+    }
+
+    let total: u16 = columns.iter().map(|spec| spec.width_pct).sum();
+    if total > 100 {
+        return Err(format!("Column widths add up to {}%, more than 100%", total));
+    }
+
+    for default in default_columns() {
+        if !columns.iter().any(|spec| spec.column == default.column) {
+            columns.push(ColumnSpec { visible: false, ..default });
+        }
+    }
+    Ok(columns)
+}
@@ -0,0 +1,48 @@
+use std::{sync::atomic::Ordering, time::Duration};
+use crate::constants::{HISTORY_WINDOW_SECS, IDLE_TIMEOUT_SECS, RUNTIME_TICK_RATE};
+
+// Runtime sampling parameters.
+//
+// `history_secs` is the length of the sliding window behind every average and
+// graph; `tick_ms` is how often the capture deltas are sampled. Together they
+// decide how many samples each history keeps (`max_samples`).
+//
+// Short windows react quickly but the averages become noisy, since a single
+// burst dominates a handful of samples. Long windows smooth the figures out but
+// every tracked IP keeps `max_samples` entries per direction, so memory grows
+// with both the window length and the number of hosts on the network. A very
+// small tick rate multiplies the same cost and also redraws the UI more often.
+//
+// `idle_timeout_secs` is how long a host may go quiet before its history is
+// dropped, so brief pauses do not make rows vanish and reappear.
+#[derive(Clone, Copy)]
+pub struct Config {
+    pub history_secs: u64,
+    pub tick_ms: u64,
+    pub idle_timeout_secs: u64,
+}
+
+impl Config {
+    pub fn max_samples(&self) -> usize {
+        (self.history_secs * 1000 / self.tick_ms).max(1) as usize
+    }
+
+    pub fn idle_timeout(&self) -> Duration {
+        Duration::from_secs(self.idle_timeout_secs)
+    }
+
+    // Convert bytes seen during one tick into bytes/s
+    pub fn per_second(&self, bytes: u64) -> f64 {
+        (bytes as f64) * (1000.0 / self.tick_ms as f64)
+    }
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            history_secs: HISTORY_WINDOW_SECS,
+            tick_ms: RUNTIME_TICK_RATE.load(Ordering::Relaxed),
+            idle_timeout_secs: IDLE_TIMEOUT_SECS,
+        }
+    }
+}
@@ -1,3 +1,33 @@
+use std::{sync::atomic::{AtomicU64, Ordering}, time::Duration};
+
+// Defaults for `Config`; all can be overridden from the command line
 pub const TICK_RATE_MS: u64 = 500; //
 pub const HISTORY_WINDOW_SECS: u64 = 60;  
-pub const MAX_SAMPLES: usize = (HISTORY_WINDOW_SECS * 1000 / TICK_RATE_MS) as usize;
+// Hosts without traffic for this long are dropped from the tables
+pub const IDLE_TIMEOUT_SECS: u64 = 120;
+
+// Limits on the memory the per-IP sliding windows may take, checked at startup. Every IP keeps
+// seven windows of 8-byte samples (bytes and packets each total/RX/TX, plus tick lengths), and
+// a busy network is assumed to have this many hosts in them at once.
+pub const ESTIMATED_TRACKED_IPS: u64 = 1000;
+pub const HISTORY_BYTES_PER_SAMPLE: u64 = 7 * 8;
+pub const HISTORY_MEMORY_WARN_MB: u64 = 128;
+pub const HISTORY_MEMORY_LIMIT_MB: u64 = 512;
+
+// Range the tick rate can be moved through at runtime with `[` / `]`
+pub const MIN_TICK_RATE_MS: u64 = 100;
+pub const MAX_TICK_RATE_MS: u64 = 5000;
+
+// Tick rate in effect, in milliseconds: `--tick` sets it at startup and `[` / `]` move it
+pub static RUNTIME_TICK_RATE: AtomicU64 = AtomicU64::new(TICK_RATE_MS);
+
+pub fn tick_rate() -> Duration {
+    Duration::from_millis(RUNTIME_TICK_RATE.load(Ordering::Relaxed))
+}
+
+// Frame render times kept for the slow-render warning and the `--debug-perf` sparkline
+pub const RENDER_SAMPLES: usize = 120;
+
+// Rows shown in the traffic tables, adjustable at runtime with `+` / `-`
+pub const DEFAULT_DISPLAY_LIMIT: usize = 25;
+pub const DISPLAY_LIMIT_STEP: usize = 5;
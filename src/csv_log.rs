@@ -0,0 +1,151 @@
+use std::{
+    fs::{self, File, OpenOptions},
+    io::{self, BufRead, BufReader, BufWriter, Write},
+    path::{Path, PathBuf},
+};
+use chrono::{Local, NaiveDate};
+use crate::app::ProtoDelta;
+use crate::util::{with_stem_suffix, TotalUnit};
+
+// Flush the buffered rows every N ticks so little is lost on Ctrl+C
+const FLUSH_EVERY_TICKS: u32 = 10;
+
+// One CSV row: a single IP's (or subnet's) traffic during one tick
+pub struct CsvRow<'a> {
+    pub interface: &'a str,
+    pub ip: String,
+    pub rx_bytes: u64,
+    pub tx_bytes: u64,
+    pub proto_bytes: ProtoDelta,
+    pub tcp_retransmits: u64,
+    pub avg_bps: f64,
+    pub peak_bps: f64,
+    pub avg_pps: f64,
+    pub peak_pps: f64,
+}
+
+// Appends per-tick traffic rows to a CSV file, rotating it at midnight
+pub struct CsvLogger {
+    path: PathBuf,
+    writer: BufWriter<File>,
+    opened_on: NaiveDate,
+    ticks_since_flush: u32,
+    // Fixed when the file is opened so every row matches the header; `U` only changes the screen
+    unit: TotalUnit,
+}
+
+impl CsvLogger {
+    pub fn open(path: &Path, unit: TotalUnit) -> io::Result<Self> {
+        Ok(Self {
+            path: path.to_path_buf(),
+            writer: open_with_header(path, unit)?,
+            opened_on: Local::now().date_naive(),
+            ticks_since_flush: 0,
+            unit,
+        })
+    }
+
+    pub fn write_tick(&mut self, rows: &[CsvRow]) -> io::Result<()> {
+        let now = Local::now();
+        if now.date_naive() != self.opened_on {
+            self.rotate(now.date_naive())?;
+        }
+
+        let timestamp = now.format("%Y-%m-%d %H:%M:%S");
+        let (scale, _) = self.unit.kilo();
+        for row in rows {
+            writeln!(
+                self.writer,
+                "{},{},{},{:.3},{:.3},{:.3},{:.3},{:.3},{},{:.3},{:.3},{:.2},{:.2}",
+                timestamp,
+                row.interface,
+                row.ip,
+                row.rx_bytes as f64 * scale,
+                row.tx_bytes as f64 * scale,
+                row.proto_bytes.tcp as f64 * scale,
+                row.proto_bytes.udp as f64 * scale,
+                row.proto_bytes.other as f64 * scale,
+                row.tcp_retransmits,
+                row.avg_bps * scale,
+                row.peak_bps * scale,
+                row.avg_pps,
+                row.peak_pps
+            )?;
+        }
+
+        self.ticks_since_flush += 1;
+        if self.ticks_since_flush >= FLUSH_EVERY_TICKS {
+            self.flush()?;
+        }
+        Ok(())
+    }
+
+    pub fn flush(&mut self) -> io::Result<()> {
+        self.ticks_since_flush = 0;
+        self.writer.flush()
+    }
+
+    // Move the finished day to `<stem>_<date>.<ext>` and start a fresh file
+    fn rotate(&mut self, today: NaiveDate) -> io::Result<()> {
+        self.writer.flush()?;
+        let rotated = with_stem_suffix(&self.path, &self.opened_on.format("%Y-%m-%d").to_string());
+        fs::rename(&self.path, rotated)?;
+
+        self.writer = open_with_header(&self.path, self.unit)?;
+        self.opened_on = today;
+        self.ticks_since_flush = 0;
+        Ok(())
+    }
+}
+
+// Byte and rate columns are named after the unit they are written in, e.g. `rx_kib` and `avg_kib_s`
+fn header(unit: TotalUnit) -> String {
+    let (_, name) = unit.kilo();
+    format!(
+        "timestamp,interface,ip,rx_{0},tx_{0},tcp_{0},udp_{0},other_{0},tcp_retransmits,avg_{0}_s,peak_{0}_s,avg_pps,peak_pps",
+        name
+    )
+}
+
+// Open for appending, writing the header only if the file is new or empty.
+// Appending to a file written in another unit would mix units under one header, so that is refused.
+fn open_with_header(path: &Path, unit: TotalUnit) -> io::Result<BufWriter<File>> {
+    let header = header(unit);
+    let file = OpenOptions::new().create(true).read(true).append(true).open(path)?;
+    if file.metadata()?.len() > 0 {
+        let mut existing = String::new();
+        BufReader::new(&file).read_line(&mut existing)?;
+        if existing.trim_end() != header {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidData,
+                format!("{} has a different header; pick another file or the unit it was written in", path.display()),
+            ));
+        }
+        return Ok(BufWriter::new(file));
+    }
+    let mut writer = BufWriter::new(file);
+    writeln!(writer, "{}", header)?;
+    Ok(writer)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn header_names_the_unit() {
+        assert!(header(TotalUnit::IecBytes).contains(",rx_kib,tx_kib,"));
+        assert!(header(TotalUnit::SiBytes).contains(",avg_kb_s,peak_kb_s,"));
+        assert!(header(TotalUnit::Bits).contains(",other_kbit,"));
+    }
+
+    #[test]
+    fn reopening_in_another_unit_is_refused() {
+        let path = std::env::temp_dir().join(format!("iftop_rs_csv_{}.csv", std::process::id()));
+        let _ = fs::remove_file(&path);
+        drop(CsvLogger::open(&path, TotalUnit::IecBytes).unwrap());
+        assert!(CsvLogger::open(&path, TotalUnit::IecBytes).is_ok());
+        assert_eq!(CsvLogger::open(&path, TotalUnit::Bits).err().map(|err| err.kind()), Some(io::ErrorKind::InvalidData));
+        fs::remove_file(&path).unwrap();
+    }
+}
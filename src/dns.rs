@@ -0,0 +1,160 @@
+use std::{
+    io,
+    net::{IpAddr, Ipv4Addr},
+    sync::Arc,
+    time::{Duration, Instant},
+};
+use hickory_resolver::{
+    config::{ResolverConfig, ResolverOpts},
+    error::{ResolveError, ResolveErrorKind},
+    proto::error::ProtoErrorKind,
+    TokioAsyncResolver,
+};
+use tokio::{
+    runtime::{self, Runtime},
+    sync::{mpsc, Semaphore},
+    time,
+};
+
+// Default lifetime of a cached reverse lookup, overridable with `--dns-ttl`
+pub const DEFAULT_DNS_TTL_SECS: u64 = 300;
+// Timed out lookups are retried this many times by default, overridable with `--dns-retries`
+pub const DEFAULT_DNS_RETRIES: u32 = 3;
+// Wait before the first retry, doubled before each one after it
+const RETRY_BACKOFF: Duration = Duration::from_secs(1);
+// Queries allowed in flight at once; the rest wait for a permit
+const MAX_IN_FLIGHT: usize = 10;
+// Most addresses `--dns-prefetch` queues, however large its subnets
+pub const MAX_DNS_PREFETCH: usize = 256;
+// Answers buffered until `on_tick` collects them
+const RESULT_CAPACITY: usize = 256;
+
+// Outcome of a reverse lookup
+#[derive(Clone, PartialEq)]
+pub enum DnsResult {
+    Resolved(String),
+    // No PTR record, or every retry timed out
+    NxDomain,
+    // Timed out; shown while the retries are still running
+    Timeout,
+    Error(String),
+}
+
+impl DnsResult {
+    fn from_error(err: &ResolveError) -> Self {
+        match err.kind() {
+            ResolveErrorKind::NoRecordsFound { .. } => DnsResult::NxDomain,
+            ResolveErrorKind::Timeout => DnsResult::Timeout,
+            ResolveErrorKind::Proto(proto) if matches!(proto.kind(), ProtoErrorKind::Timeout) => DnsResult::Timeout,
+            _ => DnsResult::Error(err.to_string()),
+        }
+    }
+
+    // The name to sort, filter and report by; empty unless the lookup succeeded
+    pub fn hostname(&self) -> &str {
+        match self {
+            DnsResult::Resolved(hostname) => hostname,
+            _ => "",
+        }
+    }
+}
+
+// A reverse lookup result and when it should be looked up again
+pub struct DnsCacheEntry {
+    pub result: DnsResult,
+    pub resolved_at: Instant,
+    pub ttl: Duration,
+}
+
+impl DnsCacheEntry {
+    pub fn is_stale(&self) -> bool {
+        self.resolved_at.elapsed() >= self.ttl
+    }
+}
+
+// Reverse lookups on a small tokio runtime, with results handed back over a bounded channel
+pub struct DnsResolver {
+    runtime: Runtime,
+    resolver: TokioAsyncResolver,
+    permits: Arc<Semaphore>,
+    results_tx: mpsc::Sender<(Ipv4Addr, DnsResult)>,
+    results_rx: mpsc::Receiver<(Ipv4Addr, DnsResult)>,
+    retries: u32,
+}
+
+impl DnsResolver {
+    pub fn new(retries: u32) -> io::Result<Self> {
+        let runtime = runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .thread_name("dns")
+            .enable_all()
+            .build()?;
+        // Falls back to public resolvers when /etc/resolv.conf cannot be read
+        let resolver = {
+            let _guard = runtime.enter();
+            TokioAsyncResolver::tokio_from_system_conf()
+                .unwrap_or_else(|_| TokioAsyncResolver::tokio(ResolverConfig::default(), ResolverOpts::default()))
+        };
+        let (results_tx, results_rx) = mpsc::channel(RESULT_CAPACITY);
+        Ok(Self {
+            runtime,
+            resolver,
+            permits: Arc::new(Semaphore::new(MAX_IN_FLIGHT)),
+            results_tx,
+            results_rx,
+            retries,
+        })
+    }
+
+    // Queue a reverse lookup of `ip`; the answer shows up in `results`.
+    // A timeout is reported straight away and retried after 1 s, 2 s, 4 s, ...;
+    // when the retries run out the address is taken to have no PTR record.
+    pub fn lookup(&self, ip: Ipv4Addr) {
+        let resolver = self.resolver.clone();
+        let permits = Arc::clone(&self.permits);
+        let results = self.results_tx.clone();
+        let retries = self.retries;
+        self.runtime.spawn(async move {
+            let mut backoff = RETRY_BACKOFF;
+            for attempt in 0..=retries {
+                // The permit is given back while waiting, so a slow server doesn't stall other lookups
+                let result = {
+                    let Ok(_permit) = permits.acquire().await else {
+                        return;
+                    };
+                    Self::resolve(&resolver, ip).await
+                };
+                if result != DnsResult::Timeout {
+                    let _ = results.send((ip, result)).await;
+                    return;
+                }
+                if attempt == retries {
+                    break;
+                }
+                let _ = results.send((ip, DnsResult::Timeout)).await;
+                time::sleep(backoff).await;
+                backoff *= 2;
+            }
+            let _ = results.send((ip, DnsResult::NxDomain)).await;
+        });
+    }
+
+    async fn resolve(resolver: &TokioAsyncResolver, ip: Ipv4Addr) -> DnsResult {
+        match resolver.reverse_lookup(IpAddr::V4(ip)).await {
+            Ok(names) => names
+                .iter()
+                .next()
+                .map_or(DnsResult::NxDomain, |name| DnsResult::Resolved(name.to_utf8().trim_end_matches('.').to_string())),
+            Err(err) => DnsResult::from_error(&err),
+        }
+    }
+
+    // Answers received since the last call
+    pub fn results(&mut self) -> Vec<(Ipv4Addr, DnsResult)> {
+        let mut results = Vec::new();
+        while let Ok(result) = self.results_rx.try_recv() {
+            results.push(result);
+        }
+        results
+    }
+}
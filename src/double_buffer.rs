@@ -0,0 +1,171 @@
+use std::{
+    hint,
+    ops::{Deref, DerefMut},
+    ptr,
+    sync::{
+        atomic::{AtomicPtr, Ordering},
+        Mutex,
+    },
+    thread,
+};
+
+// Tries a writer spins on another writer's claim before yielding
+const WRITER_SPINS: u32 = 64;
+
+// Two copies of `T`: writers fill the current one while `swap` hands the other, finished
+// one to the reader. The current copy is published through an `AtomicPtr`, so a writer
+// never takes a lock nor waits on the reader; after a swap the reader waits at most for
+// the one write that claimed the old copy before it was swapped out.
+//
+// Built for one capture thread writing per packet and one UI tick reading, but any number
+// of either is sound: writers spin on each other, readers queue on a mutex.
+pub struct DoubleBuffer<T> {
+    halves: [*mut T; 2],
+    // Copy that new writes go to
+    current: AtomicPtr<T>,
+    // Copy a writer is using right now, null when none is
+    writing: AtomicPtr<T>,
+    // Only taken by `swap`, so a second reader can't swap back a copy the first still uses
+    reading: Mutex<()>,
+}
+
+// SAFETY: a copy is only reached through a `WriteGuard` or inside `swap`, and the
+// `current`/`writing` handshake keeps those two from ever overlapping on the same copy
+unsafe impl<T: Send> Send for DoubleBuffer<T> {}
+unsafe impl<T: Send> Sync for DoubleBuffer<T> {}
+
+impl<T: Default> DoubleBuffer<T> {
+    pub fn new() -> Self {
+        let halves = [Box::into_raw(Box::default()), Box::into_raw(Box::default())];
+        Self {
+            halves,
+            current: AtomicPtr::new(halves[0]),
+            writing: AtomicPtr::new(ptr::null_mut()),
+            reading: Mutex::new(()),
+        }
+    }
+}
+
+impl<T> DoubleBuffer<T> {
+    // Claim the current copy until the guard drops
+    pub fn write(&self) -> WriteGuard<'_, T> {
+        let mut spins = 0u32;
+        loop {
+            let half = self.current.load(Ordering::SeqCst);
+            // Only fails while another writer holds a copy; past a short spin it has
+            // likely been preempted, so give it the core back
+            if self.writing.compare_exchange(ptr::null_mut(), half, Ordering::SeqCst, Ordering::Relaxed).is_err() {
+                spins += 1;
+                if spins < WRITER_SPINS {
+                    hint::spin_loop();
+                } else {
+                    thread::yield_now();
+                }
+                continue;
+            }
+            if self.current.load(Ordering::SeqCst) == half {
+                return WriteGuard { buffer: self, half };
+            }
+            // A swap slipped in between: that copy belongs to the reader now
+            self.writing.store(ptr::null_mut(), Ordering::SeqCst);
+        }
+    }
+
+    // Point writers at the other copy and run `read` on the one they were filling
+    pub fn swap<R>(&self, read: impl FnOnce(&mut T) -> R) -> R {
+        let _reading = self.reading.lock().unwrap();
+        let old = self.current.load(Ordering::SeqCst);
+        let next = if old == self.halves[0] { self.halves[1] } else { self.halves[0] };
+        self.current.store(next, Ordering::SeqCst);
+        // A write that claimed `old` before the store above finishes first. Yield rather
+        // than spin: on a single core the writer can't finish while this thread runs.
+        while self.writing.load(Ordering::SeqCst) == old {
+            thread::yield_now();
+        }
+        // SAFETY: writers now claim `next`, and any that claims `old` sees `current` moved on
+        // and lets go without touching it; other readers wait on `reading`
+        read(unsafe { &mut *old })
+    }
+}
+
+impl<T: Default> Default for DoubleBuffer<T> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T> Drop for DoubleBuffer<T> {
+    fn drop(&mut self) {
+        for half in self.halves {
+            // SAFETY: both came from `Box::into_raw` and no guard outlives `self`
+            drop(unsafe { Box::from_raw(half) });
+        }
+    }
+}
+
+pub struct WriteGuard<'a, T> {
+    buffer: &'a DoubleBuffer<T>,
+    half: *mut T,
+}
+
+impl<T> Deref for WriteGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        // SAFETY: claimed in `write`; `swap` won't hand this copy out until the guard drops
+        unsafe { &*self.half }
+    }
+}
+
+impl<T> DerefMut for WriteGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        // SAFETY: as above, and only one writer holds a copy at a time
+        unsafe { &mut *self.half }
+    }
+}
+
+impl<T> Drop for WriteGuard<'_, T> {
+    fn drop(&mut self) {
+        self.buffer.writing.store(ptr::null_mut(), Ordering::SeqCst);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Arc;
+
+    #[test]
+    fn swap_hands_over_what_was_written() {
+        let buffer = DoubleBuffer::<Vec<u32>>::new();
+        buffer.write().push(1);
+        buffer.write().push(2);
+        assert_eq!(buffer.swap(std::mem::take), [1, 2]);
+        buffer.write().push(3);
+        assert_eq!(buffer.swap(std::mem::take), [3]);
+        assert!(buffer.swap(std::mem::take).is_empty());
+    }
+
+    #[test]
+    fn concurrent_swaps_lose_nothing() {
+        const WRITES: u64 = 200_000;
+        let buffer = Arc::new(DoubleBuffer::<u64>::new());
+        let writer = {
+            let buffer = buffer.clone();
+            thread::spawn(move || {
+                for _ in 0..WRITES {
+                    *buffer.write() += 1;
+                }
+            })
+        };
+        let mut total = 0;
+        while !writer.is_finished() {
+            total += buffer.swap(std::mem::take);
+        }
+        writer.join().unwrap();
+        // Both copies may still hold writes made after the last swaps above
+        total += buffer.swap(std::mem::take);
+        total += buffer.swap(std::mem::take);
+        assert_eq!(total, WRITES);
+    }
+}
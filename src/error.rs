@@ -0,0 +1,49 @@
+use std::{error::Error, fmt, io};
+use thiserror::Error;
+use crate::util::ParseBandwidthError;
+
+// Everything that can stop net_monitor from starting
+#[derive(Error)]
+pub enum IftopError {
+    #[error("pcap: {0}")]
+    PcapError(#[from] pcap::Error),
+    #[error(transparent)]
+    IoError(#[from] io::Error),
+    #[error("No default capture device found")]
+    NoDevice,
+    #[error("Interface '{0}' not found")]
+    UnknownInterface(String),
+    #[error("{0}")]
+    InvalidCidr(String),
+    #[error("{0}")]
+    InvalidBandwidth(String),
+    // Options that parse but can't be run with, such as a history window too long to keep in memory
+    #[error("{0}")]
+    ConfigError(String),
+    // Any other malformed or conflicting command line option
+    #[error("{0}")]
+    InvalidArgument(String),
+    // An optional feature failed to start: theme, GeoIP database, history DB, metrics server, ...
+    #[error("{0}")]
+    Setup(String),
+}
+
+// `main` prints a returned error with `Debug`; show the message rather than the variant
+impl fmt::Debug for IftopError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        fmt::Display::fmt(self, f)
+    }
+}
+
+impl From<ParseBandwidthError> for IftopError {
+    fn from(err: ParseBandwidthError) -> Self {
+        IftopError::InvalidBandwidth(err.to_string())
+    }
+}
+
+// Modules outside main, cli and network still report startup failures as boxed errors
+impl From<Box<dyn Error>> for IftopError {
+    fn from(err: Box<dyn Error>) -> Self {
+        IftopError::Setup(err.to_string())
+    }
+}
@@ -0,0 +1,60 @@
+use std::{
+    collections::HashSet,
+    net::{IpAddr, Ipv4Addr},
+    path::Path,
+    sync::mpsc::{self, Receiver, Sender},
+    thread,
+};
+use maxminddb::{geoip2, MaxMindDBError, Reader};
+
+// Country lookups served by a background thread that owns the MaxMind reader
+pub struct GeoLookup {
+    requests: Sender<Ipv4Addr>,
+    results: Receiver<(Ipv4Addr, String)>,
+    pending: HashSet<Ipv4Addr>,
+}
+
+impl GeoLookup {
+    pub fn open(path: &Path) -> Result<Self, MaxMindDBError> {
+        let reader = Reader::open_readfile(path)?;
+        let (req_tx, req_rx) = mpsc::channel::<Ipv4Addr>();
+        let (res_tx, res_rx) = mpsc::channel();
+
+        thread::spawn(move || {
+            for ip in req_rx {
+                let code = reader
+                    .lookup::<geoip2::Country>(IpAddr::V4(ip))
+                    .ok()
+                    .and_then(|c| c.country)
+                    .and_then(|c| c.iso_code)
+                    .unwrap_or("--")
+                    .to_string();
+                if res_tx.send((ip, code)).is_err() {
+                    return;
+                }
+            }
+        });
+
+        Ok(Self {
+            requests: req_tx,
+            results: res_rx,
+            pending: HashSet::new(),
+        })
+    }
+
+    // Queue a lookup unless one is already in flight
+    pub fn request(&mut self, ip: Ipv4Addr) {
+        if self.pending.insert(ip) {
+            let _ = self.requests.send(ip);
+        }
+    }
+
+    // Completed lookups since the last call
+    pub fn drain_results(&mut self) -> Vec<(Ipv4Addr, String)> {
+        let results: Vec<_> = self.results.try_iter().collect();
+        for (ip, _) in &results {
+            self.pending.remove(ip);
+        }
+        results
+    }
+}
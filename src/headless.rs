@@ -0,0 +1,59 @@
+use std::{
+    io::{self, BufWriter, Write},
+    sync::{atomic::{AtomicBool, Ordering}, Arc},
+    thread,
+    time::Instant,
+};
+use crate::app::{App, SharedStats};
+use crate::constants::tick_rate;
+use crate::network::CaptureHandle;
+
+// Run without a terminal UI, printing a JSON summary to stdout every `interval` ticks.
+// `terminate` (set by SIGTERM and SIGINT) stops the loop after the current tick so buffered output is flushed.
+pub fn run(mut app: App, captures: Vec<CaptureHandle>, interval: u64, terminate: &AtomicBool) -> io::Result<()> {
+
+    let stats: Vec<Arc<SharedStats>> = captures.iter().map(|c| Arc::clone(&c.stats)).collect();
+    let mut out = BufWriter::new(io::stdout());
+    let mut ticks: u64 = 0;
+
+    let res = loop {
+        if terminate.load(Ordering::Relaxed) {
+            break Ok(());
+        }
+        thread::sleep(tick_rate().saturating_sub(app.last_tick.elapsed()));
+        app.on_tick(&stats);
+        app.last_tick = Instant::now();
+
+        ticks += 1;
+        if ticks.is_multiple_of(interval) {
+            let line = serde_json::to_writer(&mut out, &app.tick_summary())
+                .map_err(io::Error::from)
+                .and_then(|_| writeln!(out))
+                .and_then(|_| out.flush());
+            if let Err(err) = line {
+                break Err(err);
+            }
+        }
+    };
+
+    let _ = out.flush();
+    if let Some(log) = app.csv_log.as_mut() {
+        let _ = log.flush();
+    }
+    if let Some(log) = app.json_log.take() {
+        log.close();
+    }
+    if let Some(report) = app.html_report.take() {
+        report.close(app.report_snapshot());
+    }
+    if let Some(db) = app.history_db.take() {
+        db.close();
+    }
+    if let Some(influx) = app.influx.take() {
+        influx.close();
+    }
+    if let Err(err) = app.save_snapshot() {
+        eprintln!("Failed to save snapshot: {}", err);
+    }
+    res
+}
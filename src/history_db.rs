@@ -0,0 +1,217 @@
+use std::{
+    collections::HashMap,
+    error::Error,
+    net::Ipv4Addr,
+    path::{Path, PathBuf},
+    sync::mpsc::{self, SyncSender, TrySendError},
+    thread::{self, JoinHandle},
+};
+use chrono::{DateTime, Duration, Local, NaiveDateTime, TimeZone};
+use rusqlite::{params, Connection, OpenFlags};
+
+// Finished minutes queued ahead of the writer thread before new ones are dropped
+const CHANNEL_CAPACITY: usize = 16;
+
+const SCHEMA: &str = "CREATE TABLE IF NOT EXISTS traffic (
+    ip TEXT NOT NULL,
+    minute INTEGER NOT NULL,
+    min_bps REAL NOT NULL,
+    max_bps REAL NOT NULL,
+    avg_bps REAL NOT NULL,
+    total_bytes INTEGER NOT NULL,
+    PRIMARY KEY (ip, minute)
+)";
+
+// One IP's traffic during one minute; rates are bytes/s over the ticks of that minute
+pub struct MinuteRecord {
+    pub ip: Ipv4Addr,
+    // Unix timestamp of the start of the minute
+    pub minute: i64,
+    pub min_bps: f64,
+    pub max_bps: f64,
+    pub avg_bps: f64,
+    pub total_bytes: u64,
+}
+
+#[derive(Default)]
+struct MinuteStats {
+    min_bps: f64,
+    max_bps: f64,
+    total_bytes: u64,
+    // Ticks this IP had traffic in; fewer than the minute's ticks means it also sat at zero
+    active_ticks: u32,
+}
+
+// Aggregates per-tick traffic into per-minute rows and writes them to SQLite from a
+// dedicated thread, so the sliding windows can stay short while history is kept
+pub struct HistoryDb {
+    path: PathBuf,
+    tx: SyncSender<Vec<MinuteRecord>>,
+    writer: JoinHandle<()>,
+    minute: i64,
+    // Measured seconds and ticks recorded in the current minute
+    minute_secs: f64,
+    ticks: u32,
+    per_ip: HashMap<Ipv4Addr, MinuteStats>,
+}
+
+impl HistoryDb {
+    pub fn open(path: PathBuf) -> Result<Self, Box<dyn Error>> {
+        // Created here rather than on the writer thread so a bad path fails at startup
+        let conn = Connection::open(&path)?;
+        // WAL lets the query dialog read while the writer inserts
+        conn.pragma_update(None, "journal_mode", "WAL")?;
+        conn.execute(SCHEMA, [])?;
+        let (tx, rx) = mpsc::sync_channel::<Vec<MinuteRecord>>(CHANNEL_CAPACITY);
+
+        let writer = thread::spawn(move || {
+            let mut conn = conn;
+            while let Ok(records) = rx.recv() {
+                if write_minute(&mut conn, &records).is_err() {
+                    return;
+                }
+            }
+        });
+
+        Ok(Self { path, tx, writer, minute: 0, minute_secs: 0.0, ticks: 0, per_ip: HashMap::new() })
+    }
+
+    // Add one tick of per-IP byte counts, handing the previous minute to the writer once it is over
+    pub fn record(&mut self, now: DateTime<Local>, bytes_per_ip: &HashMap<Ipv4Addr, u64>, elapsed_secs: f64) -> Result<(), String> {
+        let minute = now.timestamp().div_euclid(60) * 60;
+        let mut result = Ok(());
+        if minute != self.minute && self.ticks > 0 {
+            let records = self.finish_minute();
+            result = match self.tx.try_send(records) {
+                Ok(()) => Ok(()),
+                Err(TrySendError::Full(_)) => Err("History DB: writer is behind, minute dropped".to_string()),
+                Err(TrySendError::Disconnected(_)) => Err("History DB: writer stopped after an SQLite error".to_string()),
+            };
+        }
+        self.minute = minute;
+        self.minute_secs += elapsed_secs;
+        self.ticks += 1;
+
+        for (ip, bytes) in bytes_per_ip {
+            let rate = *bytes as f64 / elapsed_secs;
+            let stats = self.per_ip.entry(*ip).or_default();
+            stats.min_bps = if stats.active_ticks == 0 { rate } else { stats.min_bps.min(rate) };
+            stats.max_bps = stats.max_bps.max(rate);
+            stats.total_bytes += bytes;
+            stats.active_ticks += 1;
+        }
+        result
+    }
+
+    // Rows for the minute recorded so far; resets the aggregation
+    fn finish_minute(&mut self) -> Vec<MinuteRecord> {
+        let ticks = self.ticks;
+        let secs = self.minute_secs;
+        let records = self.per_ip.drain().map(|(ip, stats)| MinuteRecord {
+            ip,
+            minute: self.minute,
+            min_bps: if stats.active_ticks < ticks { 0.0 } else { stats.min_bps },
+            max_bps: stats.max_bps,
+            avg_bps: stats.total_bytes as f64 / secs,
+            total_bytes: stats.total_bytes,
+        }).collect();
+        self.minute_secs = 0.0;
+        self.ticks = 0;
+        records
+    }
+
+    // Minutes recorded for `ip` overlapping `from`..`to` (Unix timestamps), oldest first
+    pub fn query(&self, ip: Ipv4Addr, from: i64, to: i64) -> Result<Vec<MinuteRecord>, String> {
+        query(&self.path, ip, from, to).map_err(|err| format!("History DB: {}", err))
+    }
+
+    // Write the partial current minute and wait for the writer to finish
+    pub fn close(mut self) {
+        if self.ticks > 0 {
+            let records = self.finish_minute();
+            let _ = self.tx.send(records);
+        }
+        drop(self.tx);
+        let _ = self.writer.join();
+    }
+}
+
+fn write_minute(conn: &mut Connection, records: &[MinuteRecord]) -> rusqlite::Result<()> {
+    let transaction = conn.transaction()?;
+    {
+        let mut insert = transaction.prepare_cached(
+            "INSERT OR REPLACE INTO traffic (ip, minute, min_bps, max_bps, avg_bps, total_bytes) VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+        )?;
+        for record in records {
+            insert.execute(params![
+                record.ip.to_string(),
+                record.minute,
+                record.min_bps,
+                record.max_bps,
+                record.avg_bps,
+                record.total_bytes as i64,
+            ])?;
+        }
+    }
+    transaction.commit()
+}
+
+// Reads through its own connection so a query never waits on the writer thread
+fn query(path: &Path, ip: Ipv4Addr, from: i64, to: i64) -> rusqlite::Result<Vec<MinuteRecord>> {
+    let conn = Connection::open_with_flags(path, OpenFlags::SQLITE_OPEN_READ_ONLY)?;
+    let mut select = conn.prepare(
+        "SELECT minute, min_bps, max_bps, avg_bps, total_bytes FROM traffic WHERE ip = ?1 AND minute > ?2 - 60 AND minute <= ?3 ORDER BY minute",
+    )?;
+    let rows = select.query_map(params![ip.to_string(), from, to], |row| {
+        Ok(MinuteRecord {
+            ip,
+            minute: row.get(0)?,
+            min_bps: row.get(1)?,
+            max_bps: row.get(2)?,
+            avg_bps: row.get(3)?,
+            total_bytes: row.get::<_, i64>(4)? as u64,
+        })
+    })?;
+    rows.collect()
+}
+
+// `last 30m`, `last 1h`, `last 2d`, `2024-01-01 12:00 - 13:00` or
+// `2024-01-01 12:00 - 2024-01-02 08:00`, as Unix timestamps
+pub fn parse_range(text: &str, now: DateTime<Local>) -> Result<(i64, i64), String> {
+    let text = text.trim();
+    if let Some(span) = text.strip_prefix("last ") {
+        let span = span.trim();
+        let unit = span.chars().last().ok_or_else(|| "Missing duration after 'last'".to_string())?;
+        let count: i64 = span[..span.len() - unit.len_utf8()]
+            .parse()
+            .ok()
+            .filter(|n| *n > 0)
+            .ok_or_else(|| format!("Invalid duration '{}'", span))?;
+        let length = match unit {
+            'm' => Duration::minutes(count),
+            'h' => Duration::hours(count),
+            'd' => Duration::days(count),
+            _ => return Err(format!("Invalid duration '{}', expected e.g. 30m, 1h or 2d", span)),
+        };
+        return Ok(((now - length).timestamp(), now.timestamp()));
+    }
+
+    let (start, end) = text.split_once(" - ").ok_or_else(|| format!("Invalid range '{}'", text))?;
+    let start = parse_local(start.trim())?;
+    let end = match parse_local(end.trim()) {
+        Ok(end) => end,
+        // A bare `HH:MM` end is on the start's day
+        Err(_) => parse_local(&format!("{} {}", start.format("%Y-%m-%d"), end.trim()))?,
+    };
+    if end <= start {
+        return Err("The range ends before it starts".to_string());
+    }
+    Ok((start.timestamp(), end.timestamp()))
+}
+
+fn parse_local(text: &str) -> Result<DateTime<Local>, String> {
+    NaiveDateTime::parse_from_str(text, "%Y-%m-%d %H:%M")
+        .ok()
+        .and_then(|naive| Local.from_local_datetime(&naive).earliest())
+        .ok_or_else(|| format!("Invalid time '{}', expected YYYY-MM-DD HH:MM", text))
+}
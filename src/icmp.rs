@@ -0,0 +1,34 @@
+// ICMP message types the alerts care about
+pub const ECHO_REPLY: u8 = 0;
+pub const REDIRECT: u8 = 5;
+
+// Echo replies per second from one host above which a reflection flood is suspected
+pub const HIGH_ECHO_REPLY_RATE: f64 = 50.0;
+
+// Human readable name of an ICMP type/code pair, for the breakdown table
+pub fn describe(icmp_type: u8, code: u8) -> &'static str {
+    match (icmp_type, code) {
+        (0, _) => "Echo Reply",
+        (3, 0) => "Net Unreachable",
+        (3, 1) => "Host Unreachable",
+        (3, 2) => "Protocol Unreachable",
+        (3, 3) => "Port Unreachable",
+        (3, 4) => "Fragmentation Needed",
+        (3, 13) => "Administratively Prohibited",
+        (3, _) => "Destination Unreachable",
+        (4, _) => "Source Quench",
+        (5, 0) => "Redirect (Network)",
+        (5, 1) => "Redirect (Host)",
+        (5, _) => "Redirect",
+        (8, _) => "Echo Request",
+        (9, _) => "Router Advertisement",
+        (10, _) => "Router Solicitation",
+        (11, 0) => "TTL Exceeded",
+        (11, 1) => "Reassembly Time Exceeded",
+        (11, _) => "Time Exceeded",
+        (12, _) => "Parameter Problem",
+        (13, _) => "Timestamp",
+        (14, _) => "Timestamp Reply",
+        _ => "Other",
+    }
+}
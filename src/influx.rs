@@ -0,0 +1,245 @@
+use std::{
+    fmt::Write as _,
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        mpsc::{self, SyncSender, TrySendError},
+        Arc, Mutex,
+    },
+    thread::{self, JoinHandle},
+    time::Duration,
+};
+
+// Ticks collected into one write unless `--influxdb-interval` says otherwise
+pub const DEFAULT_INFLUX_INTERVAL: u64 = 10;
+
+// Batches queued ahead of the writer thread, e.g. while it backs off from an unreachable server
+const CHANNEL_CAPACITY: usize = 8;
+// Tries per batch, waiting 1s, 2s, 4s, ... in between
+const MAX_ATTEMPTS: u32 = 5;
+const INITIAL_BACKOFF: Duration = Duration::from_secs(1);
+// A whole request, TLS handshake included, gives up after this long
+const HTTP_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Settings for `--influxdb-url` and friends
+pub struct InfluxOptions {
+    pub url: String,
+    pub bucket: String,
+    // Needed by InfluxDB 2 unless the token is scoped to a single organization
+    pub org: Option<String>,
+    pub token: Option<String>,
+    // Ticks per batch
+    pub interval: u64,
+}
+
+// Where the batches go: `POST <url>/api/v2/write?bucket=..`
+struct Endpoint {
+    agent: ureq::Agent,
+    // `--influxdb-url` without a trailing slash
+    url: String,
+    bucket: String,
+    org: Option<String>,
+    token: Option<String>,
+}
+
+// Why a batch did not make it
+enum PostError {
+    // Network trouble, 5xx or 429: worth trying again
+    Transient(String),
+    // Bad token, bucket or line: sending the same batch again will not help
+    Rejected(String),
+}
+
+// Collects InfluxDB line protocol every tick and POSTs it to `/api/v2/write` every `interval`
+// ticks from a dedicated thread, retrying failed batches with exponential backoff
+pub struct InfluxWriter {
+    tx: SyncSender<String>,
+    writer: JoinHandle<()>,
+    // Lines collected since the last batch was handed to the writer
+    pending: String,
+    interval: u64,
+    ticks: u64,
+    // Set on close so the writer stops backing off and just makes one last try
+    closing: Arc<AtomicBool>,
+    // Why the writer last gave up on a batch, reported once from `record`
+    failure: Arc<Mutex<Option<String>>>,
+}
+
+impl InfluxWriter {
+    pub fn open(options: &InfluxOptions) -> Result<Self, String> {
+        let endpoint = endpoint(options)?;
+        let (tx, rx) = mpsc::sync_channel::<String>(CHANNEL_CAPACITY);
+        let closing = Arc::new(AtomicBool::new(false));
+        let failure = Arc::new(Mutex::new(None));
+
+        let writer = {
+            let closing = Arc::clone(&closing);
+            let failure = Arc::clone(&failure);
+            thread::spawn(move || {
+                while let Ok(batch) = rx.recv() {
+                    if let Err(err) = post_with_retry(&endpoint, &batch, &closing) {
+                        *failure.lock().unwrap() = Some(format!("InfluxDB: {}, batch dropped", err));
+                    }
+                }
+            })
+        };
+
+        Ok(Self { tx, writer, pending: String::new(), interval: options.interval, ticks: 0, closing, failure })
+    }
+
+    // Add one tick's lines, handing the batch to the writer every `interval` ticks
+    pub fn record(&mut self, lines: &str) -> Result<(), String> {
+        self.pending.push_str(lines);
+        self.ticks += 1;
+        if self.ticks.is_multiple_of(self.interval) && !self.pending.is_empty() {
+            match self.tx.try_send(std::mem::take(&mut self.pending)) {
+                Ok(()) => {}
+                Err(TrySendError::Full(_)) => return Err("InfluxDB: writer is behind, batch dropped".to_string()),
+                Err(TrySendError::Disconnected(_)) => return Err("InfluxDB: writer stopped".to_string()),
+            }
+        }
+        match self.failure.lock().unwrap().take() {
+            Some(err) => Err(err),
+            None => Ok(()),
+        }
+    }
+
+    // Send what was collected since the last batch and wait for the writer to finish
+    pub fn close(mut self) {
+        self.closing.store(true, Ordering::Relaxed);
+        if !self.pending.is_empty() {
+            let _ = self.tx.send(std::mem::take(&mut self.pending));
+        }
+        drop(self.tx);
+        let _ = self.writer.join();
+    }
+}
+
+// `network_interface,interface=<name> rx_bps=..,tx_bps=..,rx_bytes_total=..i,tx_bytes_total=..i <ms>`
+pub fn interface_line(out: &mut String, name: &str, rx_bps: f64, tx_bps: f64, rx_bytes_total: u64, tx_bytes_total: u64, timestamp_ms: i64) {
+    let _ = writeln!(
+        out,
+        "network_interface,interface={} rx_bps={},tx_bps={},rx_bytes_total={}i,tx_bytes_total={}i {}",
+        escape_tag(name),
+        rx_bps,
+        tx_bps,
+        rx_bytes_total,
+        tx_bytes_total,
+        timestamp_ms,
+    );
+}
+
+// `network_ip,ip=<ip>,hostname=<name> avg_bps=..,peak_bps=..,total_bytes=..i <ms>`; the
+// hostname tag is left out while there is none, as line protocol has no empty tag values
+pub fn ip_line(out: &mut String, ip: &str, hostname: &str, avg_bps: f64, peak_bps: f64, total_bytes: u64, timestamp_ms: i64) {
+    let _ = write!(out, "network_ip,ip={}", escape_tag(ip));
+    if !hostname.is_empty() {
+        let _ = write!(out, ",hostname={}", escape_tag(hostname));
+    }
+    let _ = writeln!(out, " avg_bps={},peak_bps={},total_bytes={}i {}", avg_bps, peak_bps, total_bytes, timestamp_ms);
+}
+
+// Commas, equals signs and spaces separate tags, so they are backslash-escaped inside one
+fn escape_tag(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        if matches!(c, ',' | '=' | ' ' | '\\') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+// Check the scheme up front so a typo fails at startup rather than on the first batch;
+// `https://` goes through rustls with the bundled web PKI roots
+fn endpoint(options: &InfluxOptions) -> Result<Endpoint, String> {
+    let rest = ["http://", "https://"]
+        .iter()
+        .find_map(|scheme| options.url.strip_prefix(scheme))
+        .ok_or_else(|| format!("'--influxdb-url' must start with http:// or https://, got '{}'", options.url))?;
+    if rest.split('/').next().unwrap_or_default().is_empty() {
+        return Err(format!("'--influxdb-url' has no host: '{}'", options.url));
+    }
+    Ok(Endpoint {
+        agent: ureq::AgentBuilder::new().timeout(HTTP_TIMEOUT).build(),
+        url: options.url.trim_end_matches('/').to_string(),
+        bucket: options.bucket.clone(),
+        org: options.org.clone(),
+        token: options.token.clone(),
+    })
+}
+
+fn post_with_retry(endpoint: &Endpoint, body: &str, closing: &AtomicBool) -> Result<(), String> {
+    let mut backoff = INITIAL_BACKOFF;
+    let mut attempt = 1;
+    loop {
+        match post(endpoint, body) {
+            Ok(()) => return Ok(()),
+            Err(PostError::Rejected(err)) => return Err(err),
+            Err(PostError::Transient(err)) if attempt >= MAX_ATTEMPTS || closing.load(Ordering::Relaxed) => return Err(err),
+            Err(PostError::Transient(_)) => {
+                thread::sleep(backoff);
+                backoff *= 2;
+                attempt += 1;
+            }
+        }
+    }
+}
+
+fn post(endpoint: &Endpoint, body: &str) -> Result<(), PostError> {
+    let mut request = endpoint
+        .agent
+        .post(&format!("{}/api/v2/write", endpoint.url))
+        .query("bucket", &endpoint.bucket)
+        .query("precision", "ms")
+        .set("Content-Type", "text/plain; charset=utf-8");
+    if let Some(org) = &endpoint.org {
+        request = request.query("org", org);
+    }
+    if let Some(token) = &endpoint.token {
+        request = request.set("Authorization", &format!("Token {}", token));
+    }
+
+    match request.send_string(body) {
+        Ok(_) => Ok(()),
+        // InfluxDB explains a rejection in a JSON body
+        Err(ureq::Error::Status(status, response)) => {
+            let message = response.into_string().unwrap_or_default();
+            let err = format!("HTTP {} {}", status, message.trim());
+            if status == 429 || status >= 500 { Err(PostError::Transient(err)) } else { Err(PostError::Rejected(err)) }
+        }
+        Err(ureq::Error::Transport(err)) => Err(PostError::Transient(format!("{}: {}", endpoint.url, err))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn tag_separators_are_escaped() {
+        assert_eq!(escape_tag("eth0"), "eth0");
+        assert_eq!(escape_tag("Wi-Fi 2"), r"Wi-Fi\ 2");
+        assert_eq!(escape_tag("a,b=c"), r"a\,b\=c");
+        assert_eq!(escape_tag(r"C:\dev"), r"C:\\dev");
+    }
+
+    #[test]
+    fn ip_line_is_exact_line_protocol() {
+        let mut out = String::new();
+        ip_line(&mut out, "192.168.1.5", "nas local", 1500.5, 12000.0, 4096, 1700000000000);
+        ip_line(&mut out, "192.168.1.6", "", 0.0, 0.0, 0, 1700000000000);
+        assert_eq!(
+            out,
+            "network_ip,ip=192.168.1.5,hostname=nas\\ local avg_bps=1500.5,peak_bps=12000,total_bytes=4096i 1700000000000\n\
+             network_ip,ip=192.168.1.6 avg_bps=0,peak_bps=0,total_bytes=0i 1700000000000\n"
+        );
+    }
+
+    #[test]
+    fn interface_line_escapes_the_name() {
+        let mut out = String::new();
+        interface_line(&mut out, "Local Area Connection", 8.0, 16.0, 1, 2, 5);
+        assert_eq!(out, "network_interface,interface=Local\\ Area\\ Connection rx_bps=8,tx_bps=16,rx_bytes_total=1i,tx_bytes_total=2i 5\n");
+    }
+}
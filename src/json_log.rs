@@ -0,0 +1,98 @@
+use std::{
+    fs::OpenOptions,
+    io::{self, BufWriter, Write},
+    sync::mpsc::{self, SyncSender, TrySendError},
+    thread::{self, JoinHandle},
+};
+use serde::Serialize;
+use crate::app::ProtoDelta;
+use crate::session::TcpFlagCounts;
+
+// Records queued ahead of the writer thread before new ones are dropped
+const CHANNEL_CAPACITY: usize = 4096;
+
+// One line of the JSON log: a single IP's traffic at one tick
+#[derive(Serialize)]
+pub struct TrafficRecord {
+    pub ts: i64,
+    pub ip: String,
+    pub avg_bps: f64,
+    pub peak_bps: f64,
+    pub avg_pps: f64,
+    pub peak_pps: f64,
+    pub avg_packet_bytes: f64,
+    pub total_bytes: u64,
+    // When the IP was first seen and last had traffic, ISO 8601 with the local offset
+    pub first_seen: String,
+    pub last_seen: String,
+    // TCP flags this IP sent during the tick
+    pub tcp_flags: TcpFlagCounts,
+    // TCP segments this IP retransmitted during the tick
+    pub tcp_retransmits: u64,
+    // Bytes this IP sent or received during the tick, by transport protocol
+    pub proto_bytes: ProtoDelta,
+}
+
+// One line of `--headless` output: totals across all interfaces plus every top talker
+#[derive(Serialize)]
+pub struct TickSummary {
+    pub ts: i64,
+    pub rx_bps: f64,
+    pub tx_bps: f64,
+    pub rx_pps: f64,
+    pub tx_pps: f64,
+    pub talkers: Vec<TrafficRecord>,
+}
+
+// Streams newline-delimited JSON from a dedicated thread so on_tick never waits on I/O
+pub struct JsonLogger {
+    tx: SyncSender<TrafficRecord>,
+    writer: JoinHandle<()>,
+}
+
+impl JsonLogger {
+    // `target` is a file path, or `-` for stdout
+    pub fn open(target: &str) -> io::Result<Self> {
+        let out: Box<dyn Write + Send> = if target == "-" {
+            Box::new(io::stdout())
+        } else {
+            Box::new(OpenOptions::new().create(true).append(true).open(target)?)
+        };
+        let (tx, rx) = mpsc::sync_channel::<TrafficRecord>(CHANNEL_CAPACITY);
+
+        let writer = thread::spawn(move || {
+            let mut out = BufWriter::new(out);
+            while let Ok(record) = rx.recv() {
+                let mut result = write_record(&mut out, &record);
+                // Drain the rest of the queue, then flush once so consumers like `jq` see whole ticks
+                for next in rx.try_iter() {
+                    result = result.and_then(|_| write_record(&mut out, &next));
+                }
+                if result.and_then(|_| out.flush()).is_err() {
+                    return;
+                }
+            }
+        });
+
+        Ok(Self { tx, writer })
+    }
+
+    pub fn send(&self, record: TrafficRecord) -> Result<(), String> {
+        match self.tx.try_send(record) {
+            Ok(()) => Ok(()),
+            Err(TrySendError::Full(_)) => Err("JSON log: writer is behind, records dropped".to_string()),
+            Err(TrySendError::Disconnected(_)) => Err("JSON log: writer stopped after an I/O error".to_string()),
+        }
+    }
+
+    // Drain the queue and wait for the writer to flush
+    pub fn close(self) {
+        drop(self.tx);
+        let _ = self.writer.join();
+    }
+}
+
+fn write_record(out: &mut impl Write, record: &TrafficRecord) -> io::Result<()> {
+    serde_json::to_writer(&mut *out, record)?;
+    out.write_all(b"\n")
+}
@@ -1,52 +1,209 @@
+mod alert;
+mod anomaly;
 mod app;
+mod arp;
+mod cli;
+mod clipboard;
+mod columns;
+mod config;
 mod constants;
+mod csv_log;
+mod dns;
+mod double_buffer;
+mod error;
+mod geoip;
+mod headless;
+mod history;
+mod history_db;
+mod icmp;
+mod influx;
+mod json_log;
+mod metrics;
+mod netflow;
 mod network;
+mod oui;
+mod pcap_writer;
+mod picker;
+mod proc_stats;
+mod report;
+mod ring_buffer;
+mod service;
+mod session;
+mod spoof;
+mod synthetic;
+mod syslog;
+mod snapshot;
+mod theme;
+mod topology;
 mod ui;
 mod util;
+mod watchlist;
+mod ws;
 
-use std::{
-    collections::HashMap,
-    error::Error,
-    sync::{Arc, Mutex},
-};
-use app::SharedStats;
-use pnet::ipnetwork::Ipv4Network;
-
-fn main() -> Result<(), Box<dyn Error>> {
-    let args: Vec<String> = std::env::args().collect();
-    let cidr_arg = args.get(1);
-
-    let filter_cidr: Option<Ipv4Network> = match cidr_arg {
-        Some(s) => {
-            match s.parse() {
-                Ok(net) => {
-                    println!("Filter mode: Targeting subnet {}", net);
-                    Some(net)
-                },
-                Err(_) => {
-                    eprintln!("Invalid CIDR provided '{}', falling back to default private ranges.", s);
-                    None
+use std::sync::{atomic::AtomicBool, Arc, Mutex};
+use signal_hook::consts::{SIGINT, SIGTERM};
+use alert::AlertMonitor;
+use app::App;
+use csv_log::CsvLogger;
+use dns::DnsResolver;
+use error::IftopError;
+use geoip::GeoLookup;
+use history_db::HistoryDb;
+use influx::InfluxWriter;
+use json_log::JsonLogger;
+use metrics::MetricsSnapshot;
+use netflow::NetFlowExporter;
+use report::HtmlReporter;
+use spoof::ArpSpoofer;
+use theme::Theme;
+use ws::WsServer;
+
+fn main() -> Result<(), IftopError> {
+    let args = cli::parse_args()?;
+    let theme = match &args.theme {
+        Some(source) => Theme::load(source).map_err(IftopError::Setup)?,
+        None => Theme::default(),
+    };
+
+    // A dry run has no device behind it either, so it is handled like a replay from here on
+    let replaying = args.replay.is_some() || args.dry_run.is_some();
+    let mut captures = match (args.replay, args.dry_run) {
+        // offline analysis: a pcap file stands in for the live device
+        (Some(replay), _) => vec![network::start_replay_thread(replay, args.filter.clone(), args.bpf.clone())?],
+        // no root and no interface needed: generated traffic stands in for it
+        (None, Some(seed)) => vec![network::start_dry_run_thread(seed, args.filter.clone(), args.bpf.clone())?],
+        (None, None) => {
+            // network module to resolve the requested devices; without -i the user picks one,
+            // except in headless mode where the default device is used
+            let devices = if args.interfaces.is_empty() && !args.headless {
+                match picker::pick_interface(&theme)? {
+                    Some(device) => vec![device],
+                    None => return Ok(()),
                 }
-            }
-        },
-        None => {
-            println!("No subnet provided. Targeting all standard private networks (RFC1918).");
-            None
+            } else {
+                network::get_devices(&args.interfaces)?
+            };
+
+            // one capture thread per device, each with its own shared stats
+            network::start_capture_thread(devices, args.capture, args.filter.clone(), args.bpf.clone(), args.write_pcap.clone())?
+        }
+    };
+    let names: Vec<String> = captures.iter().map(|c| c.name.clone()).collect();
+
+    // Caught from before the spoofer starts, so a SIGINT or SIGTERM at any point ends the run
+    // through the exit path below, which repairs the victims' ARP caches
+    let terminate = Arc::new(AtomicBool::new(false));
+    signal_hook::flag::register(SIGINT, Arc::clone(&terminate))?;
+    signal_hook::flag::register(SIGTERM, Arc::clone(&terminate))?;
+
+    // Started after the captures so the redirected traffic is seen from the first packet
+    let spoofer = match args.arp_spoof {
+        Some((target, gateway)) => Some(ArpSpoofer::new(&names[0], target, gateway)?.with_interval(args.spoof_interval).start()?),
+        None => None,
+    };
+
+    let mut app = App::new(args.config, &names);
+    for (iface, name) in app.interfaces.iter_mut().zip(&names) {
+        iface.link_bps = args.link_speed.or_else(|| if replaying { None } else { network::detect_link_speed(name) });
+        // `any` has no address of its own
+        iface.shows_address = !replaying && name != network::ANY_DEVICE;
+        iface.promisc = (!replaying).then_some(args.capture.promisc);
+    }
+    app.refresh_addresses();
+    // "All" can only show a percentage when every link is known
+    let total_link: Option<f64> = app.interfaces[..names.len()].iter().map(|iface| iface.link_bps).sum();
+    app.interfaces.last_mut().unwrap().link_bps = total_link;
+    app.drop_receivers = captures.iter_mut().map(|c| c.drop_stats.take()).collect();
+    app.active_bpf = args.bpf;
+    app.discard_on_resume = args.discard_on_resume;
+    app.service_names = args.service_names;
+    app.display_unit = args.display_unit;
+    app.rate_base = args.rate_base;
+    app.total_unit = args.total_unit;
+    app.graph_style = args.graph_style;
+    app.log_scale = args.log_scale;
+    app.debug_perf = args.debug_perf;
+    app.ui_tick = args.ui_tick;
+    if let Some(columns) = args.columns {
+        app.columns = columns;
+    }
+    app.aggregate_prefix = args.aggregate_prefix;
+    app.theme = Arc::new(theme);
+    app.theme_source = args.theme;
+    app.dns_enabled = args.dns_enabled;
+    // A replayed file has no kernel counters behind it
+    app.proc_stats = args.proc_stats && !replaying;
+    // Hostnames are only ever shown in the UI
+    if args.dns_enabled && !args.headless {
+        app.dns_resolver = Some(DnsResolver::new(args.dns_retries)?);
+    }
+    app.prefetch_dns(&args.dns_prefetch);
+    app.dns_ttl = args.dns_ttl;
+    if let Some(path) = &args.log_csv {
+        app.csv_log = Some(CsvLogger::open(path, args.total_unit)?);
+    }
+    app.alert_monitor = AlertMonitor::new(args.alert_threshold, args.alert_ips);
+    app.alert_monitor.max_connections_per_ip = args.max_connections_per_ip;
+    if let Some(path) = &args.alert_log {
+        app.alert_monitor.open_log(path)?;
+    }
+    if args.syslog {
+        app.alert_monitor.open_syslog()?;
+    }
+    if let Some(path) = &args.watchlist {
+        app.watchlist = watchlist::load(path).map_err(IftopError::Setup)?;
+        app.watchlist_path = Some(path.clone());
+    }
+    // A replay or dry run is not a continuation of the live counters, so it neither saves nor resumes
+    if !replaying {
+        app.snapshot_path = snapshot::default_path();
+    }
+    if let Some(path) = app.snapshot_path.as_ref().filter(|_| args.resume) {
+        match snapshot::load(path) {
+            Ok(saved) => app.restore_snapshot(&saved),
+            Err(err) => eprintln!("Not resuming, starting from zero: {}", err),
         }
+    }
+    if let Some(path) = &args.geoip {
+        app.geoip = Some(GeoLookup::open(path).map_err(|err| IftopError::Setup(format!("GeoIP: {}", err)))?);
+    }
+    if let Some(target) = &args.log_json {
+        app.json_log = Some(JsonLogger::open(target)?);
+    }
+    if let Some(path) = &args.history_db {
+        app.history_db = Some(HistoryDb::open(path.clone())?);
+    }
+    if let Some(path) = &args.html_report {
+        app.html_report = Some(HtmlReporter::open(path.clone(), args.report_interval));
+    }
+    if let Some(addr) = &args.metrics_addr {
+        let snapshot = Arc::new(Mutex::new(MetricsSnapshot::default()));
+        metrics::serve(addr, Arc::clone(&snapshot))?;
+        app.metrics = Some(snapshot);
+    }
+    if let Some(addr) = &args.ws_addr {
+        app.ws_server = Some(WsServer::start(addr)?);
+    }
+    if let Some(collector) = &args.netflow_collector {
+        app.netflow = Some(NetFlowExporter::connect(collector)?);
+    }
+    if let Some(options) = &args.influxdb {
+        app.influx = Some(InfluxWriter::open(options).map_err(IftopError::Setup)?);
+    }
+
+    let result = if args.headless {
+        headless::run(app, captures, args.headless_interval, &terminate)
+    } else {
+        let json_on_stdout = args.log_json.as_deref() == Some("-");
+        ui::run(app, captures, json_on_stdout, &terminate)
     };
-    // network module to get default device and local IP
-    let (device, local_ip) = network::get_default_device()?;
-    let device_name = device.name.clone();
-
-    // shared stats between capture thread and UI thread
-    let stats = Arc::new(Mutex::new(SharedStats {
-        traffic_delta: HashMap::new(),
-        rx_delta: 0,
-        tx_delta: 0,
-    }));
-
-    network::start_capture_thread(device, local_ip, Arc::clone(&stats), filter_cidr)?;
-    ui::run(stats, &device_name)?;
+
+    // Repair the victims' ARP caches even when the UI exited with an error; any earlier `?` or a
+    // panic drops the spoofer, which does the same
+    if let Some(spoofer) = spoofer {
+        spoofer.stop();
+    }
+    result?;
 
     Ok(())
 }
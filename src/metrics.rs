@@ -0,0 +1,94 @@
+use std::{
+    error::Error,
+    fmt::Write,
+    net::Ipv4Addr,
+    sync::{Arc, Mutex},
+    thread,
+};
+use tiny_http::{Header, Response, Server};
+
+// Values published on `/metrics`, replaced by `App::on_tick` after every tick
+#[derive(Default)]
+pub struct MetricsSnapshot {
+    pub rx_bytes_total: u64,
+    pub tx_bytes_total: u64,
+    pub ips: Vec<IpMetrics>,
+}
+
+pub struct IpMetrics {
+    pub ip: Ipv4Addr,
+    // bits/s
+    pub avg_bps: f64,
+    pub peak_bps: f64,
+}
+
+impl MetricsSnapshot {
+    // Prometheus text exposition format (version 0.0.4)
+    pub fn render(&self) -> String {
+        let mut out = String::new();
+        let _ = writeln!(out, "# HELP iftop_rx_bytes_total Bytes received on all interfaces since start.");
+        let _ = writeln!(out, "# TYPE iftop_rx_bytes_total counter");
+        let _ = writeln!(out, "iftop_rx_bytes_total {}", self.rx_bytes_total);
+        let _ = writeln!(out, "# HELP iftop_tx_bytes_total Bytes sent on all interfaces since start.");
+        let _ = writeln!(out, "# TYPE iftop_tx_bytes_total counter");
+        let _ = writeln!(out, "iftop_tx_bytes_total {}", self.tx_bytes_total);
+
+        let _ = writeln!(out, "# HELP iftop_ip_avg_bps Average rate of one IP over the history window, in bits per second.");
+        let _ = writeln!(out, "# TYPE iftop_ip_avg_bps gauge");
+        for ip in &self.ips {
+            let _ = writeln!(out, "iftop_ip_avg_bps{{ip=\"{}\"}} {}", ip.ip, ip.avg_bps);
+        }
+        let _ = writeln!(out, "# HELP iftop_ip_peak_bps Highest rate of one IP since it was first seen, in bits per second.");
+        let _ = writeln!(out, "# TYPE iftop_ip_peak_bps gauge");
+        for ip in &self.ips {
+            let _ = writeln!(out, "iftop_ip_peak_bps{{ip=\"{}\"}} {}", ip.ip, ip.peak_bps);
+        }
+        out
+    }
+}
+
+// Serve the snapshot on its own thread; requests only hold the lock long enough to render
+pub fn serve(addr: &str, snapshot: Arc<Mutex<MetricsSnapshot>>) -> Result<(), Box<dyn Error>> {
+    let server = Server::http(addr).map_err(|e| format!("metrics server on {}: {}", addr, e))?;
+    thread::spawn(move || {
+        for request in server.incoming_requests() {
+            let path = request.url().split('?').next().unwrap_or("");
+            let response = if path == "/metrics" {
+                let body = snapshot.lock().unwrap().render();
+                let content_type = Header::from_bytes("Content-Type", "text/plain; version=0.0.4").unwrap();
+                Response::from_string(body).with_header(content_type)
+            } else {
+                Response::from_string("Not Found").with_status_code(404)
+            };
+            let _ = request.respond(response);
+        }
+    });
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn byte_totals_are_counters_and_rates_gauges() {
+        let snapshot = MetricsSnapshot {
+            rx_bytes_total: 1500,
+            tx_bytes_total: 60,
+            ips: vec![IpMetrics { ip: Ipv4Addr::new(192, 168, 1, 5), avg_bps: 800.0, peak_bps: 12000.0 }],
+        };
+        let text = snapshot.render();
+        for line in [
+            "# TYPE iftop_rx_bytes_total counter",
+            "iftop_rx_bytes_total 1500",
+            "# TYPE iftop_tx_bytes_total counter",
+            "iftop_tx_bytes_total 60",
+            "# TYPE iftop_ip_avg_bps gauge",
+            "iftop_ip_avg_bps{ip=\"192.168.1.5\"} 800",
+            "# TYPE iftop_ip_peak_bps gauge",
+            "iftop_ip_peak_bps{ip=\"192.168.1.5\"} 12000",
+        ] {
+            assert!(text.lines().any(|rendered| rendered == line), "{} missing from\n{}", line, text);
+        }
+    }
+}
@@ -0,0 +1,179 @@
+use std::{
+    io::{self, Write},
+    net::{IpAddr, UdpSocket},
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
+use byteorder::{BigEndian, WriteBytesExt};
+use crate::session::{SessionDelta, SessionKey};
+
+// Sessions with no packets for this long are exported as finished flows
+pub const FLOW_IDLE_TIMEOUT: Duration = Duration::from_secs(30);
+// The v5 format allows at most 30 records per datagram
+const MAX_RECORDS_PER_PACKET: usize = 30;
+
+// TCP flag bits as they appear in the record's cumulative `tcp_flags`
+const TCP_FIN: u8 = 0x01;
+const TCP_SYN: u8 = 0x02;
+const TCP_RST: u8 = 0x04;
+const TCP_ACK: u8 = 0x10;
+
+// 24-byte datagram header; all fields are sent big-endian
+#[repr(C)]
+pub struct NetFlowV5Header {
+    pub version: u16,
+    pub count: u16,
+    // Milliseconds since the exporter started
+    pub sys_uptime: u32,
+    pub unix_secs: u32,
+    pub unix_nsecs: u32,
+    // Total flows exported before this datagram
+    pub flow_sequence: u32,
+    pub engine_type: u8,
+    pub engine_id: u8,
+    pub sampling_interval: u16,
+}
+
+impl NetFlowV5Header {
+    fn write_to<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        out.write_u16::<BigEndian>(self.version)?;
+        out.write_u16::<BigEndian>(self.count)?;
+        out.write_u32::<BigEndian>(self.sys_uptime)?;
+        out.write_u32::<BigEndian>(self.unix_secs)?;
+        out.write_u32::<BigEndian>(self.unix_nsecs)?;
+        out.write_u32::<BigEndian>(self.flow_sequence)?;
+        out.write_u8(self.engine_type)?;
+        out.write_u8(self.engine_id)?;
+        out.write_u16::<BigEndian>(self.sampling_interval)
+    }
+}
+
+// 48-byte flow record; routing fields we know nothing about (next hop, ifindex, AS, masks) are zero
+#[repr(C)]
+pub struct NetFlowV5Record {
+    pub src_addr: u32,
+    pub dst_addr: u32,
+    pub next_hop: u32,
+    pub input: u16,
+    pub output: u16,
+    pub packets: u32,
+    pub octets: u32,
+    // sys_uptime at the first and last packet of the flow
+    pub first: u32,
+    pub last: u32,
+    pub src_port: u16,
+    pub dst_port: u16,
+    pub pad1: u8,
+    pub tcp_flags: u8,
+    pub protocol: u8,
+    pub tos: u8,
+    pub src_as: u16,
+    pub dst_as: u16,
+    pub src_mask: u8,
+    pub dst_mask: u8,
+    pub pad2: u16,
+}
+
+impl NetFlowV5Record {
+    fn write_to<W: Write>(&self, out: &mut W) -> io::Result<()> {
+        out.write_u32::<BigEndian>(self.src_addr)?;
+        out.write_u32::<BigEndian>(self.dst_addr)?;
+        out.write_u32::<BigEndian>(self.next_hop)?;
+        out.write_u16::<BigEndian>(self.input)?;
+        out.write_u16::<BigEndian>(self.output)?;
+        out.write_u32::<BigEndian>(self.packets)?;
+        out.write_u32::<BigEndian>(self.octets)?;
+        out.write_u32::<BigEndian>(self.first)?;
+        out.write_u32::<BigEndian>(self.last)?;
+        out.write_u16::<BigEndian>(self.src_port)?;
+        out.write_u16::<BigEndian>(self.dst_port)?;
+        out.write_u8(self.pad1)?;
+        out.write_u8(self.tcp_flags)?;
+        out.write_u8(self.protocol)?;
+        out.write_u8(self.tos)?;
+        out.write_u16::<BigEndian>(self.src_as)?;
+        out.write_u16::<BigEndian>(self.dst_as)?;
+        out.write_u8(self.src_mask)?;
+        out.write_u8(self.dst_mask)?;
+        out.write_u16::<BigEndian>(self.pad2)
+    }
+}
+
+// Queues finished sessions and sends them to a NetFlow v5 collector over UDP
+pub struct NetFlowExporter {
+    socket: UdpSocket,
+    started: Instant,
+    sequence: u32,
+    pending: Vec<NetFlowV5Record>,
+}
+
+impl NetFlowExporter {
+    pub fn connect(collector: &str) -> io::Result<Self> {
+        let socket = UdpSocket::bind("0.0.0.0:0")?;
+        socket.connect(collector)?;
+        Ok(Self { socket, started: Instant::now(), sequence: 0, pending: Vec::new() })
+    }
+
+    fn uptime_ms(&self, at: Instant) -> u32 {
+        at.saturating_duration_since(self.started).as_millis() as u32
+    }
+
+    // Queue one flow; v5 has no room for IPv6 addresses, so those are skipped
+    pub fn push(&mut self, key: &SessionKey, flow: &SessionDelta, first: Instant, last: Instant) {
+        let (IpAddr::V4(src), IpAddr::V4(dst)) = (key.src, key.dst) else {
+            return;
+        };
+        let has = |count: u64, bit: u8| if count > 0 { bit } else { 0 };
+        let tcp_flags = has(flow.flags.fin, TCP_FIN) | has(flow.flags.syn, TCP_SYN) | has(flow.flags.rst, TCP_RST) | has(flow.flags.ack, TCP_ACK);
+
+        self.pending.push(NetFlowV5Record {
+            src_addr: src.into(),
+            dst_addr: dst.into(),
+            next_hop: 0,
+            input: 0,
+            output: 0,
+            packets: flow.packets.min(u32::MAX as u64) as u32,
+            octets: flow.bytes.min(u32::MAX as u64) as u32,
+            first: self.uptime_ms(first),
+            last: self.uptime_ms(last),
+            src_port: key.src_port,
+            dst_port: key.dst_port,
+            pad1: 0,
+            tcp_flags,
+            protocol: key.proto,
+            tos: 0,
+            src_as: 0,
+            dst_as: 0,
+            src_mask: 0,
+            dst_mask: 0,
+            pad2: 0,
+        });
+    }
+
+    // Send everything queued, up to 30 records per datagram
+    pub fn flush(&mut self) -> io::Result<()> {
+        let pending = std::mem::take(&mut self.pending);
+        for chunk in pending.chunks(MAX_RECORDS_PER_PACKET) {
+            let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default();
+            let header = NetFlowV5Header {
+                version: 5,
+                count: chunk.len() as u16,
+                sys_uptime: self.uptime_ms(Instant::now()),
+                unix_secs: now.as_secs() as u32,
+                unix_nsecs: now.subsec_nanos(),
+                flow_sequence: self.sequence,
+                engine_type: 0,
+                engine_id: 0,
+                sampling_interval: 0,
+            };
+
+            let mut datagram = Vec::with_capacity(24 + 48 * chunk.len());
+            header.write_to(&mut datagram)?;
+            for record in chunk {
+                record.write_to(&mut datagram)?;
+            }
+            self.socket.send(&datagram)?;
+            self.sequence = self.sequence.wrapping_add(chunk.len() as u32);
+        }
+        Ok(())
+    }
+}
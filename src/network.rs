@@ -1,19 +1,42 @@
 use std::{
-    error::Error,
+    ffi::{c_char, CStr},
     net::Ipv4Addr,
-    sync::{Arc, Mutex},
+    path::PathBuf,
+    time::{Duration, Instant},
+    sync::{atomic::Ordering, mpsc::{self, Receiver, Sender}, Arc},
     thread,
 };
-use pcap::{Capture, Device};
+use pcap::{Activated, Active, BpfProgram, Capture, Device, Linktype, Offline, Stat};
 use pnet::datalink;
 use pnet::packet::{
-    ethernet::{EtherTypes, EthernetPacket},
+    arp::ArpPacket,
+    ethernet::{EtherType, EtherTypes, EthernetPacket},
+    icmp::IcmpPacket,
+    ip::IpNextHeaderProtocols,
     ipv4::Ipv4Packet,
+    sll::SLLPacket,
+    sll2::SLL2Packet,
+    tcp::{TcpFlags, TcpPacket},
+    udp::UdpPacket,
+    vlan::VlanPacket,
     Packet,
 };
-use crate::app::SharedStats;
+use crate::anomaly::AnomalyTracker;
+use crate::app::{ProtoDelta, SharedStats, TickDelta, Traffic};
+use crate::arp;
+use crate::error::IftopError;
+use crate::icmp::{ECHO_REPLY, REDIRECT};
+use crate::pcap_writer::{PcapWriteOptions, PcapWriter};
+use crate::session::{SeqTracker, SessionDelta, SessionKey, TcpFlagCounts};
+use crate::synthetic::{SyntheticTraffic, SLICE};
 use pnet::ipnetwork::Ipv4Network; 
 
+// Linux pseudo-device capturing on every interface at once, with cooked link headers
+pub const ANY_DEVICE: &str = "any";
+// From cooked headers: the ARPHRD_ETHER link type and the PACKET_OUTGOING packet type
+const ARPHRD_ETHER: u16 = 1;
+const PACKET_OUTGOING: u16 = 4;
+
 pub fn get_local_ip(device_name: &str) -> Option<Ipv4Addr> {
     let interfaces = datalink::interfaces();
     let iface = interfaces.into_iter().find(|i| i.name == device_name)?;
@@ -26,6 +49,65 @@ pub fn get_local_ip(device_name: &str) -> Option<Ipv4Addr> {
     })
 }
 
+// IPv4 networks configured on the interface (every interface for `any`), used to recognise
+// directed broadcasts
+pub fn get_local_networks(device_name: &str) -> Vec<Ipv4Network> {
+    datalink::interfaces()
+        .into_iter()
+        .filter(|i| device_name == ANY_DEVICE || i.name == device_name)
+        .flat_map(|i| i.ips)
+        .filter_map(|ip| match ip {
+            pnet::ipnetwork::IpNetwork::V4(net) => Some(net),
+            _ => None,
+        })
+        .collect()
+}
+
+// Name of the interface with this kernel index, e.g. from a `LINUX_SLL2` header
+pub fn interface_name(index: u32) -> Option<String> {
+    datalink::interfaces().into_iter().find(|i| i.index == index).map(|i| i.name)
+}
+
+// Link speed in bits/s from the kernel, or from `ethtool` when sysfs has no answer.
+// Virtual and wireless devices usually report nothing.
+pub fn detect_link_speed(device_name: &str) -> Option<f64> {
+    let from_sysfs = std::fs::read_to_string(format!("/sys/class/net/{}/speed", device_name))
+        .ok()
+        .and_then(|s| s.trim().parse::<i64>().ok());
+    let mbps = match from_sysfs {
+        Some(mbps) => mbps,
+        None => {
+            // "	Speed: 1000Mb/s"
+            let output = std::process::Command::new("ethtool").arg(device_name).output().ok()?;
+            let stdout = String::from_utf8_lossy(&output.stdout).into_owned();
+            stdout
+                .lines()
+                .find_map(|line| line.trim().strip_prefix("Speed:"))
+                .and_then(|speed| speed.trim().strip_suffix("Mb/s"))
+                .and_then(|speed| speed.parse::<i64>().ok())?
+        }
+    };
+    // Unknown speeds are reported as -1
+    (mbps > 0).then_some(mbps as f64 * 1_000_000.0)
+}
+
+// 255.255.255.255, or the directed broadcast address of one of `local_nets`.
+// Without any known network (e.g. replaying a file) private addresses are assumed to be /24s.
+pub fn is_broadcast(ip: &Ipv4Addr, local_nets: &[Ipv4Network]) -> bool {
+    if ip.is_broadcast() {
+        return true;
+    }
+    if local_nets.is_empty() {
+        return is_rfc1918_private(ip) && ip.octets()[3] == 255;
+    }
+    local_nets.iter().any(|net| net.prefix() < 31 && net.broadcast() == *ip)
+}
+
+// 224.0.0.0/4
+pub fn is_multicast(ip: &Ipv4Addr) -> bool {
+    ip.is_multicast()
+}
+
 pub fn is_rfc1918_private(ip: &Ipv4Addr) -> bool {
     let octets = ip.octets();
     (octets[0] == 192 && octets[1] == 168) ||
@@ -33,60 +115,591 @@ pub fn is_rfc1918_private(ip: &Ipv4Addr) -> bool {
     (octets[0] == 172 && octets[1] >= 16 && octets[1] <= 31)
 }
 
-pub fn get_default_device() -> Result<(Device, Ipv4Addr), Box<dyn Error>> {
-    let device = Device::lookup()?.ok_or("No default device found")?;
+// 169.254.0.0/16
+pub fn is_link_local(ip: &Ipv4Addr) -> bool {
+    ip.is_link_local()
+}
+
+// 127.0.0.0/8
+pub fn is_loopback(ip: &Ipv4Addr) -> bool {
+    ip.is_loopback()
+}
+
+// 100.64.0.0/10, carrier-grade NAT shared address space (RFC 6598)
+pub fn is_cgnat(ip: &Ipv4Addr) -> bool {
+    let octets = ip.octets();
+    octets[0] == 100 && (octets[1] & 0xc0) == 64
+}
+
+pub fn get_default_device() -> Result<(Device, Ipv4Addr), IftopError> {
+    let device = Device::lookup()?.ok_or(IftopError::NoDevice)?;
     let device_name = device.name.clone();
     let local_ip = get_local_ip(&device_name).unwrap_or(Ipv4Addr::new(0, 0, 0, 0));
     Ok((device, local_ip))
 }
 
-fn should_track_ip(ip: &Ipv4Addr, filter_cidr: Option<Ipv4Network>) -> bool {
-    match filter_cidr {
-        // If a CIDR is provided (e.g.,
-        Some(network) => network.contains(*ip),
-        None => is_rfc1918_private(ip),
+extern "C" {
+    // Not wrapped by the pcap crate; libpcap is already linked through it
+    #[link_name = "pcap_lib_version"]
+    fn ffi_pcap_lib_version() -> *const c_char;
+}
+
+// e.g. "libpcap version 1.10.4 (with TPACKET_V3)"
+pub fn pcap_lib_version() -> String {
+    // SAFETY: libpcap returns a pointer to a static NUL-terminated string
+    unsafe { CStr::from_ptr(ffi_pcap_lib_version()) }.to_string_lossy().into_owned()
+}
+
+// Resolve the requested interface names, or the default device when none are given
+pub fn get_devices(names: &[String]) -> Result<Vec<Device>, IftopError> {
+    if names.is_empty() {
+        let (device, _) = get_default_device()?;
+        return Ok(vec![device]);
+    }
+    let available = Device::list()?;
+    names
+        .iter()
+        .map(|name| {
+            available
+                .iter()
+                .find(|d| &d.name == name)
+                .cloned()
+                .ok_or_else(|| IftopError::UnknownInterface(name.clone()))
+        })
+        .collect()
+}
+
+// Which addresses get per-IP and session accounting
+#[derive(Clone)]
+pub struct FilterConfig {
+    // Count the RFC1918 ranges; cleared when a positional CIDR replaces them
+    pub private: bool,
+    // Also count link-local, loopback and carrier-grade NAT addresses, from the `--track-*` flags
+    pub link_local: bool,
+    pub loopback: bool,
+    pub cgnat: bool,
+    // Extra ranges from the positional CIDR and `--include-subnet`
+    pub include: Vec<Ipv4Network>,
+    // Ranges from `--exclude-subnet`, subtracted after inclusion
+    pub exclude: Vec<Ipv4Network>,
+}
+
+impl Default for FilterConfig {
+    fn default() -> Self {
+        Self { private: true, link_local: false, loopback: false, cgnat: false, include: Vec::new(), exclude: Vec::new() }
+    }
+}
+
+impl FilterConfig {
+    pub fn should_track_ip(&self, ip: &Ipv4Addr) -> bool {
+        let included = (self.private && is_rfc1918_private(ip))
+            || (self.link_local && is_link_local(ip))
+            || (self.loopback && is_loopback(ip))
+            || (self.cgnat && is_cgnat(ip))
+            || self.include.iter().any(|net| net.contains(*ip));
+        included && !self.exclude.iter().any(|net| net.contains(*ip))
+    }
+
+    // Kernel BPF expression passing only frames the accounting can use: ARP, link-layer broadcast and
+    // multicast, IPv4 to or from one of `local_ips` or an included range, and 802.1Q-tagged frames.
+    // Exclusions stay in userspace, a packet from an excluded host may still be to a tracked one.
+    // Cooked headers (`any`) carry no destination MAC nor tags, and libpcap refuses `broadcast`,
+    // `multicast` and `vlan` there, so broadcast and multicast are matched on the IPv4 address.
+    pub fn kernel_prefilter(&self, local_ips: &[Ipv4Addr], linktype: Linktype) -> String {
+        let cooked = linktype == Linktype::LINUX_SLL || linktype == Linktype::LINUX_SLL2;
+        let mut terms = if cooked {
+            vec!["arp".to_string(), "ip multicast".to_string(), "dst host 255.255.255.255".to_string()]
+        } else {
+            vec!["arp".to_string(), "broadcast".to_string(), "multicast".to_string()]
+        };
+        terms.extend(local_ips.iter().filter(|ip| !ip.is_unspecified()).map(|ip| format!("host {}", ip)));
+        let mut nets: Vec<Ipv4Network> = Vec::new();
+        if self.private {
+            nets.extend(["10.0.0.0/8", "172.16.0.0/12", "192.168.0.0/16"].map(|net| net.parse::<Ipv4Network>().unwrap()));
+        }
+        for (enabled, net) in [(self.link_local, "169.254.0.0/16"), (self.loopback, "127.0.0.0/8"), (self.cgnat, "100.64.0.0/10")] {
+            if enabled {
+                nets.push(net.parse().unwrap());
+            }
+        }
+        nets.extend(&self.include);
+        // libpcap rejects `net` with host bits set, as in `192.168.1.5/24`
+        terms.extend(nets.iter().map(|net| format!("net {}/{}", net.network(), net.prefix())));
+        // Last, because `vlan` shifts the offsets of every test after it
+        if !cooked {
+            terms.push("vlan".to_string());
+        }
+        terms.join(" or ")
+    }
+}
+
+// A user BPF expression narrowed by the kernel pre-filter. The user's comes first so a
+// `vlan` in the pre-filter cannot shift its offsets; an empty one means no user filter.
+fn combine_filters(prefilter: Option<&str>, user: &str) -> String {
+    match prefilter {
+        Some(prefilter) if user.trim().is_empty() => prefilter.to_string(),
+        Some(prefilter) => format!("({}) and ({})", user, prefilter),
+        None => user.to_string(),
+    }
+}
+
+// The `/prefix_len` network that `ip` belongs to, e.g. 192.168.1.37/24 -> 192.168.1.0/24
+pub fn group_ip_to_subnet(ip: Ipv4Addr, prefix_len: u8) -> Ipv4Network {
+    let mask = u32::MAX.checked_shl(32 - prefix_len as u32).unwrap_or(0);
+    Ipv4Network::new(Ipv4Addr::from(u32::from(ip) & mask), prefix_len)
+        .expect("prefix length validated by the CLI")
+}
+
+// Where a TCP segment sits in its session's sequence space
+struct TcpSegment {
+    seq: u32,
+    // Payload bytes plus one each for SYN and FIN
+    len: u32,
+}
+
+// Build the 5-tuple for TCP and UDP packets, along with the TCP flag bits (0 for UDP) and,
+// for TCP, the segment's sequence range
+fn session_key(ipv4: &Ipv4Packet, vlan_id: Option<u16>) -> Option<(SessionKey, u8, Option<TcpSegment>)> {
+    let proto = ipv4.get_next_level_protocol();
+    let (src_port, dst_port, flags, segment) = match proto {
+        IpNextHeaderProtocols::Tcp => {
+            let tcp = TcpPacket::new(ipv4.payload())?;
+            let flags = tcp.get_flags();
+            // Taken from the headers rather than the captured bytes, which a short snaplen truncates
+            let payload = (ipv4.get_total_length() as u32)
+                .saturating_sub(ipv4.get_header_length() as u32 * 4)
+                .saturating_sub(tcp.get_data_offset() as u32 * 4);
+            let control = (flags & TcpFlags::SYN != 0) as u32 + (flags & TcpFlags::FIN != 0) as u32;
+            let segment = TcpSegment { seq: tcp.get_sequence(), len: payload + control };
+            (tcp.get_source(), tcp.get_destination(), flags, Some(segment))
+        }
+        IpNextHeaderProtocols::Udp => {
+            let udp = UdpPacket::new(ipv4.payload())?;
+            (udp.get_source(), udp.get_destination(), 0, None)
+        }
+        _ => return None,
+    };
+    let key = SessionKey {
+        src: ipv4.get_source().into(),
+        dst: ipv4.get_destination().into(),
+        src_port,
+        dst_port,
+        proto: proto.0,
+        vlan_id,
+    };
+    Some((key, flags, segment))
+}
+
+// What the UI can ask of a running capture thread
+pub enum CaptureCommand {
+    // Replace the user BPF filter; empty clears it
+    SetBpf(String),
+    // Reopen a live device with promiscuous mode on or off; replays and dry runs ignore it
+    SetPromisc(bool),
+}
+
+// A running capture thread and the channels used to talk to it
+pub struct CaptureHandle {
+    pub name: String,
+    pub stats: Arc<SharedStats>,
+    pub commands: Sender<CaptureCommand>,
+    // libpcap drop counters since the previous report; live devices only
+    pub drop_stats: Option<Receiver<Stat>>,
+}
+
+// Smallest snaplen that still covers an Ethernet header plus a minimal IPv4 header
+pub const MIN_SNAPLEN: i32 = 34;
+
+// How live devices are opened; see `--promisc`, `--snaplen` and `--capture-timeout`
+#[derive(Clone, Copy)]
+pub struct CaptureConfig {
+    pub promisc: bool,
+    // Bytes copied per packet. Accounting uses the on-wire length, so only headers are needed
+    pub snaplen: i32,
+    // Read timeout in milliseconds
+    pub timeout_ms: i32,
+    // How often drop counters are reported, see `--stats-interval`
+    pub stats_interval: Duration,
+    // Drop frames no tracked IP is involved in before they reach userspace, see `--no-kernel-filter`
+    pub kernel_filter: bool,
+}
+
+impl Default for CaptureConfig {
+    fn default() -> Self {
+        Self { promisc: true, snaplen: 65535, timeout_ms: 10, stats_interval: Duration::from_secs(5), kernel_filter: true }
     }
 }
 
-// Start a background packet capture thread
+// Start one background packet capture thread per device
 pub fn start_capture_thread(
-    device: Device, 
-    local_ip: Ipv4Addr, 
-    stats: Arc<Mutex<SharedStats>>
-    , filter_cidr: Option<Ipv4Network>
-) -> Result<(), Box<dyn Error>> {
+    devices: Vec<Device>,
+    capture: CaptureConfig,
+    filter: FilterConfig,
+    bpf: Option<String>,
+    pcap_write: Option<PcapWriteOptions>,
+) -> Result<Vec<CaptureHandle>, IftopError> {
+    let mut handles = Vec::with_capacity(devices.len());
+    let multiple = devices.len() > 1;
+    for device in devices {
+        let name = device.name.clone();
+        let stats = Arc::new(SharedStats::new());
+        let (commands, command_rx) = mpsc::channel::<CaptureCommand>();
+        // `any` has no address of its own; cooked headers tell the direction instead
+        let local_ip = if name == ANY_DEVICE { None } else { get_local_ip(&name) };
+        let accounting = PacketAccounting::new(
+            local_ip.unwrap_or(Ipv4Addr::new(0, 0, 0, 0)),
+            get_local_networks(&name),
+            filter.clone(),
+            Arc::clone(&stats),
+        );
+
+        let write = match &pcap_write {
+            Some(options) if multiple => Some(options.for_interface(&name)),
+            other => other.clone(),
+        };
+
+        let (drop_tx, drop_rx) = mpsc::channel::<Stat>();
+        spawn_capture(device, capture, accounting, bpf.clone(), command_rx, drop_tx, write)?;
+        handles.push(CaptureHandle { name, stats, commands, drop_stats: Some(drop_rx) });
+    }
+    Ok(handles)
+}
+
+// Link-layer fields the accounting needs, whichever header carried them
+struct LinkFrame<'a> {
+    ethertype: EtherType,
+    payload: &'a [u8],
+    // Cooked headers only carry the sender's address
+    src_mac: Option<[u8; 6]>,
+    dst_mac: Option<[u8; 6]>,
+    // Whether the packet was sent by this host, when the header says so
+    outgoing: Option<bool>,
+    // Kernel index of the interface the packet crossed, from a `LINUX_SLL2` header
+    if_index: Option<u32>,
+}
+
+// Ethernet address from a cooked header, when the interface is Ethernet-like
+fn cooked_mac(link_type: u16, address_len: usize, address: &[u8]) -> Option<[u8; 6]> {
+    if link_type != ARPHRD_ETHER || address_len != 6 {
+        return None;
+    }
+    address.get(..6)?.try_into().ok()
+}
+
+// Accounts parsed packets into one capture thread's shared stats
+pub struct PacketAccounting {
+    // Link-layer header of the capture: Ethernet, or Linux cooked for `any`
+    linktype: Linktype,
+    local_ip: Ipv4Addr,
+    // Interface networks plus the included subnets, for directed broadcasts
+    local_nets: Vec<Ipv4Network>,
+    filter: FilterConfig,
+    stats: Arc<SharedStats>,
+    seq: SeqTracker,
+    anomalies: AnomalyTracker,
+}
+
+impl PacketAccounting {
+    pub fn new(local_ip: Ipv4Addr, mut local_nets: Vec<Ipv4Network>, filter: FilterConfig, stats: Arc<SharedStats>) -> Self {
+        local_nets.extend(&filter.include);
+        Self { linktype: Linktype::ETHERNET, local_ip, local_nets, filter, stats, seq: SeqTracker::default(), anomalies: AnomalyTracker::default() }
+    }
+
+    fn link_frame<'a>(&self, data: &'a [u8]) -> Option<LinkFrame<'a>> {
+        if self.linktype == Linktype::LINUX_SLL2 {
+            let sll = SLL2Packet::new(data)?;
+            Some(LinkFrame {
+                ethertype: sll.get_protocol_type(),
+                payload: &data[SLL2Packet::minimum_packet_size()..],
+                src_mac: cooked_mac(sll.get_arphrd_type(), sll.get_link_layer_address_length() as usize, sll.get_link_layer_address_raw()),
+                dst_mac: None,
+                outgoing: Some(sll.get_packet_type() as u16 == PACKET_OUTGOING),
+                if_index: Some(sll.get_interface_index()),
+            })
+        } else if self.linktype == Linktype::LINUX_SLL {
+            let sll = SLLPacket::new(data)?;
+            Some(LinkFrame {
+                ethertype: sll.get_protocol(),
+                payload: &data[SLLPacket::minimum_packet_size()..],
+                src_mac: cooked_mac(sll.get_link_layer_address_type(), sll.get_link_layer_address_len() as usize, sll.get_link_layer_address_raw()),
+                dst_mac: None,
+                outgoing: Some(sll.get_packet_type() == PACKET_OUTGOING),
+                if_index: None,
+            })
+        } else {
+            let ethernet = EthernetPacket::new(data)?;
+            Some(LinkFrame {
+                ethertype: ethernet.get_ethertype(),
+                payload: &data[EthernetPacket::minimum_packet_size()..],
+                src_mac: Some(ethernet.get_source().octets()),
+                dst_mac: Some(ethernet.get_destination().octets()),
+                outgoing: None,
+                if_index: None,
+            })
+        }
+    }
+
+    pub fn account(&mut self, data: &[u8], len: u64) {
+        let Some(frame) = self.link_frame(data) else {
+            return;
+        };
+        let stats = &self.stats;
+        let filter = &self.filter;
+
+        // Strip 802.1Q tags (stacked ones too) down to the inner EtherType; the outer tag is reported
+        let mut ethertype = frame.ethertype;
+        let mut payload = frame.payload;
+        let mut vlan_id = None;
+        while ethertype == EtherTypes::Vlan {
+            let Some(vlan) = VlanPacket::new(payload) else {
+                return;
+            };
+            vlan_id = vlan_id.or(Some(vlan.get_vlan_identifier()));
+            ethertype = vlan.get_ethertype();
+            payload = &payload[VlanPacket::minimum_packet_size()..];
+        }
+        if ethertype == EtherTypes::Arp {
+            if let Some(arp) = ArpPacket::new(payload) {
+                arp::record(&arp, &stats.arp);
+            }
+        }
+
+        let mut delta = stats.deltas.write();
+        if let Some(id) = vlan_id {
+            *delta.vlans.entry(id).or_default() += 1;
+        }
+
+        if ethertype == EtherTypes::Ipv4 {
+            if let Some(ipv4) = Ipv4Packet::new(payload) {
+                let src = ipv4.get_source();
+                let dst = ipv4.get_destination();
+                let proto = ProtoDelta::packet(ipv4.get_next_level_protocol().0, len);
+                let dscp = ipv4.get_dscp();
+                // ECN codepoint 11: a router on the way was congested
+                let congested = ipv4.get_ecn() == 3;
+                *delta.dscp.entry(dscp).or_default() += len;
+                if congested {
+                    delta.ecn_ce += 1;
+                }
+                let mark = |delta: &mut TickDelta, ip: Ipv4Addr| {
+                    *delta.dscp_per_ip.entry((ip, dscp)).or_default() += len;
+                    if congested {
+                        *delta.ecn_ce_per_ip.entry(ip).or_default() += 1;
+                    }
+                };
+
+                // Track total transmitted and received bytes, and per interface behind `any`.
+                // Ethernet has no direction, so packets from the local address count as sent.
+                let outgoing = frame.outgoing.unwrap_or(src == self.local_ip);
+                if outgoing {
+                    delta.tx += Traffic::packet(len);
+                } else {
+                    delta.rx += Traffic::packet(len);
+                }
+                if let Some(index) = frame.if_index {
+                    let per_device = if outgoing { &mut delta.tx_per_device } else { &mut delta.rx_per_device };
+                    *per_device.entry(index).or_default() += Traffic::packet(len);
+                }
+
+                // Track per-IP traffic for LAN IPs, split by direction, and the MAC each IP was seen behind
+                if filter.should_track_ip(&src) {
+                    *delta.tx_per_ip.entry(src).or_default() += Traffic::packet(len);
+                    *delta.proto_per_ip.entry(src).or_default() += proto;
+                    mark(&mut delta, src);
+                    if let Some(mac) = frame.src_mac {
+                        delta.macs.insert(src, mac);
+                        delta.anomalies.extend(self.anomalies.record(src, mac));
+                    }
+                    // Multicast and broadcast senders pick their own TTLs (1, 255), unrelated to the path
+                    if !is_broadcast(&dst, &self.local_nets) && !is_multicast(&dst) {
+                        delta.ttls.insert(src, ipv4.get_ttl());
+                        delta.anomalies.extend(self.anomalies.record_ttl(src, ipv4.get_ttl()));
+                    }
+                }
+                // Broadcast and multicast destinations are counted apart from unicast hosts
+                if is_broadcast(&dst, &self.local_nets) {
+                    delta.broadcast += Traffic::packet(len);
+                } else if is_multicast(&dst) {
+                    delta.multicast += Traffic::packet(len);
+                    *delta.multicast_per_group.entry(dst).or_default() += Traffic::packet(len);
+                } else if filter.should_track_ip(&dst) {
+                    *delta.rx_per_ip.entry(dst).or_default() += Traffic::packet(len);
+                    *delta.proto_per_ip.entry(dst).or_default() += proto;
+                    mark(&mut delta, dst);
+                    if let Some(mac) = frame.dst_mac {
+                        delta.macs.insert(dst, mac);
+                        delta.anomalies.extend(self.anomalies.record(dst, mac));
+                    }
+                }
+
+                if ipv4.get_next_level_protocol() == IpNextHeaderProtocols::Icmp {
+                    if let Some(icmp) = IcmpPacket::new(ipv4.payload()) {
+                        let (icmp_type, code) = (icmp.get_icmp_type().0, icmp.get_icmp_code().0);
+                        *delta.icmp_types.entry((icmp_type, code)).or_default() += 1;
+                        if icmp_type == ECHO_REPLY || icmp_type == REDIRECT {
+                            *delta.icmp_senders.entry((src, icmp_type)).or_default() += 1;
+                        }
+                    }
+                }
+
+                // Track TCP/UDP sessions that touch a tracked IP
+                if filter.should_track_ip(&src) || filter.should_track_ip(&dst) {
+                    if let Some((key, flags, segment)) = session_key(&ipv4, vlan_id) {
+                        let retransmit = segment.is_some_and(|segment| self.seq.is_retransmission(key, segment.seq, segment.len));
+                        *delta.sessions.entry(key).or_default() += SessionDelta {
+                            bytes: len,
+                            packets: 1,
+                            flags: TcpFlagCounts::from_flags(flags),
+                            retransmits: retransmit as u64,
+                        };
+                    }
+                }
+            }
+        }
+    }
+}
+
+// Apply a new user filter on top of the pre-filter, returning it if libpcap took it
+fn apply_bpf<T: Activated + ?Sized>(
+    cap: &mut Capture<T>,
+    filter: String,
+    stats: &SharedStats,
+    prefilter: Option<&str>,
+) -> Option<String> {
+    let result = cap.filter(&combine_filters(prefilter, &filter), true)
+        .map(|_| filter)
+        .map_err(|e| e.to_string());
+    let applied = result.as_ref().ok().cloned();
+    *stats.bpf_result.lock().unwrap() = Some(result);
+    applied
+}
+
+// Open `device` live as `capture` says, before any filter is set
+fn open_live(device: Device, capture: &CaptureConfig) -> Result<Capture<Active>, pcap::Error> {
+    let any = device.name == ANY_DEVICE;
     let mut cap = Capture::from_device(device)?
-        .promisc(true)
-        .snaplen(65535)
-        .timeout(10)
+        .promisc(capture.promisc)
+        .snaplen(capture.snaplen)
+        .timeout(capture.timeout_ms)
         .open()?;
+    // `any` defaults to the original cooked header; version 2 also names the interface of each packet
+    if any {
+        let _ = cap.set_datalink(Linktype::LINUX_SLL2);
+    }
+    Ok(cap)
+}
+
+// The same device again with promiscuous mode switched, the filters carried over. Opened while
+// the old handle is still capturing, so there is no gap: the caller swaps it in and only what
+// sat unread in the old handle's buffer at that moment is lost, and nothing is counted twice
+fn reopen_live(device: &Device, capture: &CaptureConfig, prefilter: Option<&str>, bpf: Option<&str>) -> Result<Capture<Active>, pcap::Error> {
+    let mut cap = open_live(device.clone(), capture)?;
+    if prefilter.is_some() || bpf.is_some() {
+        cap.filter(&combine_filters(prefilter, bpf.unwrap_or_default()), true)?;
+    }
+    Ok(cap)
+}
+
+fn open_replay(path: &PathBuf, bpf: Option<&str>) -> Result<Capture<Offline>, pcap::Error> {
+    let mut cap = Capture::from_file(path)?;
+    if let Some(filter) = bpf {
+        cap.filter(filter, true)?;
+    }
+    Ok(cap)
+}
+
+fn spawn_capture(
+    device: Device,
+    capture: CaptureConfig,
+    mut accounting: PacketAccounting,
+    bpf: Option<String>,
+    commands: Receiver<CaptureCommand>,
+    drop_stats: Sender<Stat>,
+    pcap_write: Option<PcapWriteOptions>,
+) -> Result<(), IftopError> {
+    let name = device.name.clone();
+    let mut capture = capture;
+    let mut cap = open_live(device.clone(), &capture)?;
+    accounting.linktype = cap.get_datalink();
+
+    // Without it every frame on a busy link is copied to userspace only to be ignored there
+    let prefilter = if capture.kernel_filter {
+        let local_ips: Vec<Ipv4Addr> = if name == ANY_DEVICE {
+            get_local_networks(&name).iter().map(|net| net.ip()).collect()
+        } else {
+            vec![accounting.local_ip]
+        };
+        let prefilter = accounting.filter.kernel_prefilter(&local_ips, accounting.linktype);
+        match cap.filter(&prefilter, true) {
+            Ok(()) => Some(prefilter),
+            // Reported on the status bar; printing here would land on top of the TUI
+            Err(err) => {
+                *accounting.stats.prefilter_error.lock().unwrap() = Some(format!("{}: kernel pre-filter not applied, filtering in userspace only: {}", name, err));
+                None
+            }
+        }
+    } else {
+        None
+    };
+    if let Some(filter) = &bpf {
+        cap.filter(&combine_filters(prefilter.as_deref(), filter), true)?;
+    }
+    let mut current_bpf = bpf;
+
+    let mut writer = match pcap_write {
+        Some(options) => Some(PcapWriter::open(options, cap.get_datalink())?),
+        None => None,
+    };
+
+    // libpcap counters are cumulative; each report carries the change since the last one
+    let mut baseline = cap.stats().unwrap_or(Stat { received: 0, dropped: 0, if_dropped: 0 });
+    let mut last_poll = Instant::now();
 
     thread::spawn(move || loop {
+        // The read timeout keeps commands responsive on a quiet link
+        while let Ok(command) = commands.try_recv() {
+            match command {
+                CaptureCommand::SetBpf(filter) => {
+                    if let Some(filter) = apply_bpf(&mut cap, filter, &accounting.stats, prefilter.as_deref()) {
+                        current_bpf = Some(filter).filter(|filter| !filter.trim().is_empty());
+                    }
+                }
+                CaptureCommand::SetPromisc(promisc) => {
+                    let settings = CaptureConfig { promisc, ..capture };
+                    let result = reopen_live(&device, &settings, prefilter.as_deref(), current_bpf.as_deref())
+                        .map(|reopened| {
+                            cap = reopened;
+                            capture = settings;
+                            // Counters start over with the new handle
+                            baseline = cap.stats().unwrap_or(Stat { received: 0, dropped: 0, if_dropped: 0 });
+                            promisc
+                        })
+                        .map_err(|e| e.to_string());
+                    *accounting.stats.promisc_result.lock().unwrap() = Some(result);
+                }
+            }
+        }
+
+        if last_poll.elapsed() >= capture.stats_interval {
+            last_poll = Instant::now();
+            if let Ok(current) = cap.stats() {
+                let _ = drop_stats.send(Stat {
+                    received: current.received.wrapping_sub(baseline.received),
+                    dropped: current.dropped.wrapping_sub(baseline.dropped),
+                    if_dropped: current.if_dropped.wrapping_sub(baseline.if_dropped),
+                });
+                baseline = current;
+            }
+        }
+
         if let Ok(packet) = cap.next_packet() {
-            if let Some(ethernet) = EthernetPacket::new(packet.data) {
-                if ethernet.get_ethertype() == EtherTypes::Ipv4 {
-                    if let Some(ipv4) = Ipv4Packet::new(ethernet.payload()) {
-                        let len = packet.header.len as u64;
-                        let src = ipv4.get_source();
-                        let dst = ipv4.get_destination();
-
-                        let mut s = stats.lock().unwrap();
-
-                        // Track total transmitted and received bytes
-                        if src == local_ip {
-                            s.tx_delta += len;
-                        } else {
-                            s.rx_delta += len;
-                        }
+            accounting.account(packet.data, packet.header.len as u64);
 
-                        // Track per-IP traffic for LAN IPs
-                        if should_track_ip(&src, filter_cidr) {
-                            *s.traffic_delta.entry(src).or_insert(0) += len;
-                        }
-                        if should_track_ip(&dst, filter_cidr) {
-                            *s.traffic_delta.entry(dst).or_insert(0) += len;
-                        }
-                    }
+            // Written here rather than in the stats path so the file never delays accounting
+            if let Some(w) = writer.as_mut() {
+                if let Err(err) = w.write(&packet) {
+                    *accounting.stats.write_error.lock().unwrap() = Some(format!("pcap write: {}", err));
+                    writer = None;
                 }
             }
         }
@@ -94,3 +707,189 @@ pub fn start_capture_thread(
     
     Ok(())
 }
+
+// Replay options for offline analysis of a pcap file
+pub struct ReplayOptions {
+    pub path: PathBuf,
+    // 1.0 is real time, 10.0 replays ten times faster
+    pub speed: f64,
+    // Start over at end-of-file instead of stopping
+    pub looped: bool,
+}
+
+// Feed a pcap file through the same pipeline as a live device, pacing packets by their timestamps
+pub fn start_replay_thread(
+    options: ReplayOptions,
+    filter: FilterConfig,
+    bpf: Option<String>,
+) -> Result<CaptureHandle, IftopError> {
+    let mut cap = open_replay(&options.path, bpf.as_deref())?;
+    let mut current_bpf = bpf;
+
+    let file_name = options.path.file_name().map(|n| n.to_string_lossy().into_owned()).unwrap_or_default();
+    let stats = Arc::new(SharedStats::new());
+    let (commands, command_rx) = mpsc::channel::<CaptureCommand>();
+    let mut accounting = PacketAccounting::new(Ipv4Addr::new(0, 0, 0, 0), Vec::new(), filter, Arc::clone(&stats));
+
+    thread::spawn(move || {
+        // Wall-clock start and capture timestamp of the first packet in the current pass
+        let mut origin: Option<(Instant, f64)> = None;
+        loop {
+            while let Ok(command) = command_rx.try_recv() {
+                if let CaptureCommand::SetBpf(filter) = command {
+                    if let Some(filter) = apply_bpf(&mut cap, filter, &accounting.stats, None) {
+                        current_bpf = Some(filter);
+                    }
+                }
+            }
+
+            match cap.next_packet() {
+                Ok(packet) => {
+                    let ts = packet.header.ts.tv_sec as f64 + packet.header.ts.tv_usec as f64 / 1_000_000.0;
+                    let (started, first_ts) = *origin.get_or_insert((Instant::now(), ts));
+                    let due = Duration::from_secs_f64(((ts - first_ts) / options.speed).max(0.0));
+                    if let Some(wait) = due.checked_sub(started.elapsed()) {
+                        thread::sleep(wait);
+                    }
+                    accounting.account(packet.data, packet.header.len as u64);
+                }
+                Err(pcap::Error::NoMorePackets) if options.looped => {
+                    match open_replay(&options.path, current_bpf.as_deref()) {
+                        Ok(reopened) => {
+                            cap = reopened;
+                            origin = None;
+                        }
+                        Err(_) => break,
+                    }
+                }
+                Err(_) => break,
+            }
+        }
+        accounting.stats.capture_done.store(true, Ordering::Relaxed);
+    });
+
+    Ok(CaptureHandle { name: format!("REPLAY: {}", file_name), stats, commands, drop_stats: None })
+}
+
+// Compiled against a dead Ethernet handle, so generated frames are filtered like captured ones
+fn compile_bpf(filter: &str) -> Result<BpfProgram, pcap::Error> {
+    Capture::dead(Linktype::ETHERNET)?.compile(filter, true)
+}
+
+// Feed synthetic traffic through the same pipeline as a live device, without opening one
+pub fn start_dry_run_thread(seed: u64, filter: FilterConfig, bpf: Option<String>) -> Result<CaptureHandle, IftopError> {
+    let mut program = bpf.as_deref().map(compile_bpf).transpose()?;
+    let mut traffic = SyntheticTraffic::new(seed);
+    let stats = Arc::new(SharedStats::new());
+    let (commands, command_rx) = mpsc::channel::<CaptureCommand>();
+    let mut accounting = PacketAccounting::new(traffic.local_ip(), traffic.local_networks(), filter, Arc::clone(&stats));
+
+    thread::spawn(move || {
+        let started = Instant::now();
+        for slice in 1u32.. {
+            while let Ok(command) = command_rx.try_recv() {
+                let CaptureCommand::SetBpf(filter) = command else {
+                    continue;
+                };
+                let result = compile_bpf(&filter)
+                    .map(|compiled| {
+                        program = Some(compiled);
+                        filter
+                    })
+                    .map_err(|e| e.to_string());
+                *accounting.stats.bpf_result.lock().unwrap() = Some(result);
+            }
+
+            for frame in traffic.next_slice() {
+                if program.as_ref().is_none_or(|program| program.filter(&frame)) {
+                    accounting.account(&frame, frame.len() as u64);
+                }
+            }
+            // Paced from the start, so the rates hold however long accounting takes
+            if let Some(wait) = (SLICE * slice).checked_sub(started.elapsed()) {
+                thread::sleep(wait);
+            }
+        }
+    });
+
+    Ok(CaptureHandle { name: "DRY-RUN".to_string(), stats, commands, drop_stats: None })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(s: &str) -> Ipv4Addr {
+        s.parse().unwrap()
+    }
+
+    // Only the given ranges, without the RFC1918 default
+    fn tracking(link_local: bool, loopback: bool, cgnat: bool) -> FilterConfig {
+        FilterConfig { private: false, link_local, loopback, cgnat, ..FilterConfig::default() }
+    }
+
+    #[test]
+    fn link_local_edges_need_the_flag() {
+        for addr in ["169.254.0.0", "169.254.255.255"] {
+            assert!(!tracking(false, false, false).should_track_ip(&ip(addr)), "{}", addr);
+            assert!(!FilterConfig::default().should_track_ip(&ip(addr)), "{}", addr);
+            assert!(tracking(true, false, false).should_track_ip(&ip(addr)), "{}", addr);
+        }
+    }
+
+    #[test]
+    fn neighbours_of_link_local_never_match() {
+        for addr in ["169.255.0.0", "169.253.255.255"] {
+            for filter in [tracking(false, false, false), tracking(true, true, true), FilterConfig::default()] {
+                assert!(!filter.should_track_ip(&ip(addr)), "{}", addr);
+            }
+        }
+    }
+
+    #[test]
+    fn loopback_needs_the_flag() {
+        for addr in ["127.0.0.0", "127.0.0.1", "127.255.255.255"] {
+            assert!(!tracking(true, false, true).should_track_ip(&ip(addr)), "{}", addr);
+            assert!(tracking(false, true, false).should_track_ip(&ip(addr)), "{}", addr);
+        }
+        assert!(!tracking(false, true, false).should_track_ip(&ip("128.0.0.0")));
+        assert!(!tracking(false, true, false).should_track_ip(&ip("126.255.255.255")));
+    }
+
+    #[test]
+    fn cgnat_covers_exactly_100_64_slash_10() {
+        for addr in ["100.64.0.0", "100.100.1.1", "100.127.255.255"] {
+            assert!(!tracking(true, true, false).should_track_ip(&ip(addr)), "{}", addr);
+            assert!(tracking(false, false, true).should_track_ip(&ip(addr)), "{}", addr);
+        }
+        for addr in ["100.63.255.255", "100.128.0.0"] {
+            assert!(!tracking(false, false, true).should_track_ip(&ip(addr)), "{}", addr);
+        }
+    }
+
+    #[test]
+    fn exclusions_win_over_flags() {
+        let filter = FilterConfig { exclude: vec!["169.254.1.0/24".parse().unwrap()], ..tracking(true, false, false) };
+        assert!(!filter.should_track_ip(&ip("169.254.1.7")));
+        assert!(filter.should_track_ip(&ip("169.254.2.7")));
+    }
+
+    #[test]
+    fn prefilter_on_ethernet_keeps_link_keywords() {
+        let prefilter = FilterConfig::default().kernel_prefilter(&[ip("192.168.1.2")], Linktype::ETHERNET);
+        assert!(prefilter.starts_with("arp or broadcast or multicast or host 192.168.1.2 or "), "{}", prefilter);
+        assert!(prefilter.ends_with(" or vlan"), "{}", prefilter);
+    }
+
+    #[test]
+    fn prefilter_on_cooked_links_avoids_link_keywords() {
+        for linktype in [Linktype::LINUX_SLL, Linktype::LINUX_SLL2] {
+            let prefilter = FilterConfig::default().kernel_prefilter(&[ip("192.168.1.2")], linktype);
+            let terms: Vec<&str> = prefilter.split(" or ").collect();
+            for keyword in ["broadcast", "multicast", "vlan"] {
+                assert!(!terms.contains(&keyword), "{}", prefilter);
+            }
+            assert!(terms.contains(&"ip multicast") && terms.contains(&"dst host 255.255.255.255"), "{}", prefilter);
+        }
+    }
+}
@@ -0,0 +1,12 @@
+// MAC prefix -> vendor table for the most common vendors, generated by build.rs from oui_database.txt
+include!(concat!(env!("OUT_DIR"), "/ouis.rs"));
+
+// Vendor owning the MAC's first three octets, or "" when it is not in the embedded table
+pub fn oui_lookup(mac: &[u8; 6]) -> &'static str {
+    let prefix = u32::from_be_bytes([0, mac[0], mac[1], mac[2]]);
+    OUI_VENDORS.get(&prefix).copied().unwrap_or("")
+}
+
+pub fn format_mac(mac: &[u8; 6]) -> String {
+    mac.iter().map(|b| format!("{:02x}", b)).collect::<Vec<_>>().join(":")
+}
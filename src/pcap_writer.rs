@@ -0,0 +1,79 @@
+use std::{fs, path::PathBuf};
+use chrono::Local;
+use pcap::{BpfProgram, Capture, Dead, Linktype, Packet, Savefile};
+use crate::util::with_stem_suffix;
+
+// pcap global header and per-record header sizes, used to track the file size
+const FILE_HEADER_BYTES: u64 = 24;
+const RECORD_HEADER_BYTES: u64 = 16;
+
+// Settings for `--write-pcap`
+#[derive(Clone)]
+pub struct PcapWriteOptions {
+    pub path: PathBuf,
+    // Rotate once the file grows past this many bytes
+    pub rotate_bytes: u64,
+    // BPF expression that only decides what is written to the file
+    pub filter: Option<String>,
+}
+
+impl PcapWriteOptions {
+    // Give each interface its own file when several are captured at once
+    pub fn for_interface(&self, interface: &str) -> PcapWriteOptions {
+        PcapWriteOptions { path: with_stem_suffix(&self.path, interface), ..self.clone() }
+    }
+}
+
+// Writes matched packets to a pcap savefile on the capture thread, rotating by size
+pub struct PcapWriter {
+    options: PcapWriteOptions,
+    // Owns the link type for the savefile and compiles the file-only filter
+    handle: Capture<Dead>,
+    program: Option<BpfProgram>,
+    savefile: Savefile,
+    bytes_written: u64,
+}
+
+impl PcapWriter {
+    pub fn open(options: PcapWriteOptions, linktype: Linktype) -> Result<Self, pcap::Error> {
+        let handle = Capture::dead(linktype)?;
+        let program = match &options.filter {
+            Some(filter) => Some(handle.compile(filter, true)?),
+            None => None,
+        };
+        let savefile = handle.savefile(&options.path)?;
+        Ok(Self {
+            options,
+            handle,
+            program,
+            savefile,
+            bytes_written: FILE_HEADER_BYTES,
+        })
+    }
+
+    pub fn write(&mut self, packet: &Packet) -> Result<(), pcap::Error> {
+        if let Some(program) = &self.program {
+            if !program.filter(packet.data) {
+                return Ok(());
+            }
+        }
+        self.savefile.write(packet);
+        self.bytes_written += RECORD_HEADER_BYTES + packet.header.caplen as u64;
+
+        if self.bytes_written >= self.options.rotate_bytes {
+            self.rotate()?;
+        }
+        Ok(())
+    }
+
+    // Rename the full file to `<stem>_YYYYMMDD_HHMMSS.<ext>` and start a new one
+    fn rotate(&mut self) -> Result<(), pcap::Error> {
+        self.savefile.flush()?;
+        let rotated = with_stem_suffix(&self.options.path, &Local::now().format("%Y%m%d_%H%M%S").to_string());
+        fs::rename(&self.options.path, rotated).map_err(|e| pcap::Error::IoError(e.kind()))?;
+
+        self.savefile = self.handle.savefile(&self.options.path)?;
+        self.bytes_written = FILE_HEADER_BYTES;
+        Ok(())
+    }
+}
@@ -0,0 +1,116 @@
+use std::{error::Error, io, net::IpAddr};
+use crossterm::{
+    event::{self, Event, KeyCode, KeyEventKind},
+    execute,
+    terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
+};
+use pcap::Device;
+use pnet::datalink;
+use ratatui::{
+    backend::CrosstermBackend,
+    style::{Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, List, ListItem, ListState},
+    Terminal,
+};
+use crate::theme::Theme;
+
+// One selectable device with the details shown next to its name
+struct Entry {
+    device: Device,
+    ipv4: Option<IpAddr>,
+    up: bool,
+    loopback: bool,
+}
+
+// Full-screen device menu shown when no `-i` is given. Returns None when the user presses Esc.
+pub fn pick_interface(theme: &Theme) -> Result<Option<Device>, Box<dyn Error>> {
+    let interfaces = datalink::interfaces();
+    let entries: Vec<Entry> = Device::list()?
+        .into_iter()
+        .map(|device| {
+            let iface = interfaces.iter().find(|i| i.name == device.name);
+            Entry {
+                ipv4: device.addresses.iter().map(|a| a.addr).find(IpAddr::is_ipv4),
+                up: iface.is_some_and(|i| i.is_up()),
+                loopback: iface.is_some_and(|i| i.is_loopback()),
+                device,
+            }
+        })
+        .collect();
+    if entries.is_empty() {
+        return Err("No capture devices found".into());
+    }
+
+    // Start on the device libpcap would have picked by default
+    let default_name = Device::lookup().ok().flatten().map(|d| d.name);
+    let mut state = ListState::default()
+        .with_selected(Some(entries.iter().position(|e| Some(&e.device.name) == default_name.as_ref()).unwrap_or(0)));
+
+    enable_raw_mode()?;
+    let mut stdout = io::stdout();
+    execute!(stdout, EnterAlternateScreen)?;
+    let mut terminal = Terminal::new(CrosstermBackend::new(stdout))?;
+
+    let res = run_picker(&mut terminal, theme, &entries, &mut state);
+
+    disable_raw_mode()?;
+    execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
+    terminal.show_cursor()?;
+
+    Ok(res?.map(|index| entries[index].device.clone()))
+}
+
+fn run_picker<B: ratatui::backend::Backend>(
+    terminal: &mut Terminal<B>,
+    theme: &Theme,
+    entries: &[Entry],
+    state: &mut ListState,
+) -> io::Result<Option<usize>> {
+    loop {
+        terminal.draw(|f| {
+            let items: Vec<ListItem> = entries.iter().map(|entry| {
+                let status = if entry.up { "UP  " } else { "DOWN" };
+                let mut spans = vec![
+                    Span::styled(format!("{:<16}", entry.device.name), Style::default().fg(theme.text).add_modifier(Modifier::BOLD)),
+                    Span::styled(format!(" [{}]", status), Style::default().fg(if entry.up { theme.low_traffic } else { theme.muted })),
+                    Span::styled(if entry.loopback { " [LOOPBACK]" } else { "           " }, Style::default().fg(theme.muted)),
+                    Span::styled(
+                        format!(" {:<16}", entry.ipv4.map(|ip| ip.to_string()).unwrap_or_else(|| "-".to_string())),
+                        Style::default().fg(theme.hostname_color),
+                    ),
+                ];
+                if let Some(desc) = &entry.device.desc {
+                    spans.push(Span::styled(format!(" {}", desc), Style::default().fg(theme.muted)));
+                }
+                ListItem::new(Line::from(spans))
+            }).collect();
+
+            let list = List::new(items)
+                .block(
+                    Block::default()
+                        .title(" Select an interface (↑/↓, Enter to start, Esc to quit) ")
+                        .borders(Borders::ALL)
+                        .border_type(ratatui::widgets::BorderType::Rounded)
+                        .border_style(Style::default().fg(theme.accent)),
+                )
+                .highlight_style(Style::default().bg(theme.selection_bg).add_modifier(Modifier::BOLD))
+                .highlight_symbol("> ");
+            f.render_stateful_widget(list, f.size(), state);
+        })?;
+
+        if let Event::Key(key) = event::read()? {
+            if key.kind != KeyEventKind::Press {
+                continue;
+            }
+            let selected = state.selected().unwrap_or(0);
+            match key.code {
+                KeyCode::Up => state.select(Some(selected.saturating_sub(1))),
+                KeyCode::Down => state.select(Some((selected + 1).min(entries.len() - 1))),
+                KeyCode::Enter => return Ok(Some(selected)),
+                KeyCode::Esc | KeyCode::Char('q') => return Ok(None),
+                _ => {}
+            }
+        }
+    }
+}
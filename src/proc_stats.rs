@@ -0,0 +1,71 @@
+use std::{collections::HashMap, ops::AddAssign};
+
+// Kernel error and drop counters of one interface, as listed in /proc/net/dev
+#[derive(Clone, Copy, Default)]
+pub struct InterfaceCounters {
+    pub rx_errs: u64,
+    pub rx_drop: u64,
+    pub rx_fifo: u64,
+    pub rx_frame: u64,
+    pub tx_errs: u64,
+    pub tx_drop: u64,
+    pub tx_fifo: u64,
+}
+
+impl InterfaceCounters {
+    // Every error counter, for telling whether any of them moved
+    pub fn errors(&self) -> u64 {
+        self.rx_errs + self.rx_fifo + self.rx_frame + self.tx_errs + self.tx_fifo
+    }
+}
+
+impl AddAssign for InterfaceCounters {
+    fn add_assign(&mut self, other: Self) {
+        self.rx_errs += other.rx_errs;
+        self.rx_drop += other.rx_drop;
+        self.rx_fifo += other.rx_fifo;
+        self.rx_frame += other.rx_frame;
+        self.tx_errs += other.tx_errs;
+        self.tx_drop += other.tx_drop;
+        self.tx_fifo += other.tx_fifo;
+    }
+}
+
+// Counters of every interface in /proc/net/dev; None where the file is missing or unreadable
+#[cfg(target_os = "linux")]
+pub fn read() -> Option<HashMap<String, InterfaceCounters>> {
+    std::fs::read_to_string("/proc/net/dev").ok().map(|contents| parse(&contents))
+}
+
+// Only Linux has /proc/net/dev; the stats panel shows N/A elsewhere
+#[cfg(not(target_os = "linux"))]
+pub fn read() -> Option<HashMap<String, InterfaceCounters>> {
+    None
+}
+
+// After two header lines, `  eth0: <8 receive columns> <8 transmit columns>`:
+// bytes packets errs drop fifo frame compressed multicast | bytes packets errs drop fifo colls carrier compressed
+#[cfg_attr(not(target_os = "linux"), allow(dead_code))]
+fn parse(contents: &str) -> HashMap<String, InterfaceCounters> {
+    contents
+        .lines()
+        .skip(2)
+        .filter_map(|line| {
+            let (name, columns) = line.split_once(':')?;
+            let columns: Vec<u64> = columns.split_whitespace().map(|column| column.parse().ok()).collect::<Option<_>>()?;
+            if columns.len() < 16 {
+                return None;
+            }
+            let counters = InterfaceCounters {
+                rx_errs: columns[2],
+                rx_drop: columns[3],
+                rx_fifo: columns[4],
+                rx_frame: columns[5],
+                tx_errs: columns[10],
+                tx_drop: columns[11],
+                tx_fifo: columns[12],
+            };
+            Some((name.trim().to_string(), counters))
+        })
+        .collect()
+}
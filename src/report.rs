@@ -0,0 +1,173 @@
+use std::{
+    fmt::Write as _,
+    fs,
+    path::PathBuf,
+    sync::mpsc::{self, SyncSender, TrySendError},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+use chrono::{DateTime, Local};
+use crate::util::{format_bytes_total, format_rate, BitrateBase, DisplayUnit, TotalUnit};
+
+pub const DEFAULT_REPORT_INTERVAL_SECS: u64 = 300;
+
+// Placeholders are `{{name}}`; everything else in the template is copied as is
+const TEMPLATE: &str = include_str!("report_template.html");
+
+// Size of each inline SVG sparkline, in CSS pixels
+const SPARK_WIDTH: f64 = 720.0;
+const SPARK_HEIGHT: f64 = 80.0;
+
+// One row of the top talkers table
+pub struct ReportTalker {
+    pub ip: String,
+    pub hostname: String,
+    pub avg_bps: f64,
+    pub peak_bps: f64,
+    pub peak_time: DateTime<Local>,
+    pub lifetime_bytes: u64,
+    pub first_seen: DateTime<Local>,
+}
+
+// Everything one report shows, copied out of the App so rendering happens off the UI thread
+pub struct ReportSnapshot {
+    pub generated: DateTime<Local>,
+    pub interface: String,
+    pub rate_base: BitrateBase,
+    pub total_unit: TotalUnit,
+    pub history_secs: u64,
+    pub total_rx_bytes: u64,
+    pub total_tx_bytes: u64,
+    // Bytes/s, oldest first
+    pub rx_history: Vec<f64>,
+    pub tx_history: Vec<f64>,
+    pub peak_rx: (f64, DateTime<Local>),
+    pub peak_tx: (f64, DateTime<Local>),
+    pub talkers: Vec<ReportTalker>,
+}
+
+// Rewrites a self-contained HTML report every `interval` from a dedicated thread
+pub struct HtmlReporter {
+    tx: SyncSender<ReportSnapshot>,
+    writer: JoinHandle<()>,
+    interval: Duration,
+    last_sent: Instant,
+}
+
+impl HtmlReporter {
+    pub fn open(path: PathBuf, interval: Duration) -> Self {
+        // Only the newest snapshot matters; a writer still busy with the last one skips this
+        let (tx, rx) = mpsc::sync_channel::<ReportSnapshot>(1);
+
+        let writer = thread::spawn(move || {
+            // Written next to the target and renamed, so a browser never loads half a report
+            let tmp_path = path.with_extension("html.tmp");
+            while let Ok(snapshot) = rx.recv() {
+                let html = render(&snapshot);
+                if fs::write(&tmp_path, html).and_then(|_| fs::rename(&tmp_path, &path)).is_err() {
+                    return;
+                }
+            }
+        });
+
+        Self { tx, writer, interval, last_sent: Instant::now() }
+    }
+
+    // A new report is due once `interval` has passed since the last one
+    pub fn due(&self) -> bool {
+        self.last_sent.elapsed() >= self.interval
+    }
+
+    pub fn send(&mut self, snapshot: ReportSnapshot) -> Result<(), String> {
+        self.last_sent = Instant::now();
+        match self.tx.try_send(snapshot) {
+            Ok(()) => Ok(()),
+            Err(TrySendError::Full(_)) => Err("HTML report: writer is behind, report skipped".to_string()),
+            Err(TrySendError::Disconnected(_)) => Err("HTML report: writer stopped after an I/O error".to_string()),
+        }
+    }
+
+    // Write one last report with the final numbers and wait for it to land
+    pub fn close(self, snapshot: ReportSnapshot) {
+        let _ = self.tx.send(snapshot);
+        drop(self.tx);
+        let _ = self.writer.join();
+    }
+}
+
+fn render(snapshot: &ReportSnapshot) -> String {
+    let rate = |bytes_per_sec: f64| format_rate(bytes_per_sec, DisplayUnit::Bits, snapshot.rate_base);
+    let time = |t: &DateTime<Local>| t.format("%Y-%m-%d %H:%M:%S").to_string();
+
+    let mut rows = String::new();
+    for talker in &snapshot.talkers {
+        let _ = writeln!(
+            rows,
+            "    <tr><td>{}</td><td>{}</td><td class=\"num\">{}</td><td class=\"num\">{}</td><td>{}</td><td class=\"num\">{}</td><td>{}</td></tr>",
+            escape(&talker.ip),
+            escape(&talker.hostname),
+            rate(talker.avg_bps),
+            rate(talker.peak_bps),
+            time(&talker.peak_time),
+            format_bytes_total(talker.lifetime_bytes, snapshot.total_unit),
+            time(&talker.first_seen),
+        );
+    }
+    if snapshot.talkers.is_empty() {
+        rows.push_str("    <tr><td colspan=\"7\">No traffic in the current window</td></tr>\n");
+    }
+
+    let replacements = [
+        ("{{interface}}", escape(&snapshot.interface)),
+        ("{{generated}}", time(&snapshot.generated)),
+        ("{{total_rx}}", format_bytes_total(snapshot.total_rx_bytes, snapshot.total_unit)),
+        ("{{total_tx}}", format_bytes_total(snapshot.total_tx_bytes, snapshot.total_unit)),
+        ("{{current_rx}}", rate(snapshot.rx_history.last().copied().unwrap_or_default())),
+        ("{{current_tx}}", rate(snapshot.tx_history.last().copied().unwrap_or_default())),
+        ("{{peak_rx}}", rate(snapshot.peak_rx.0)),
+        ("{{peak_rx_time}}", time(&snapshot.peak_rx.1)),
+        ("{{peak_tx}}", rate(snapshot.peak_tx.0)),
+        ("{{peak_tx_time}}", time(&snapshot.peak_tx.1)),
+        ("{{window}}", format!("{}s", snapshot.history_secs)),
+        ("{{rx_sparkline}}", sparkline(&snapshot.rx_history, "#2e8b57")),
+        ("{{tx_sparkline}}", sparkline(&snapshot.tx_history, "#c0392b")),
+        ("{{talker_rows}}", rows),
+    ];
+    replacements.iter().fold(TEMPLATE.to_string(), |html, (placeholder, value)| html.replace(placeholder, value))
+}
+
+// Filled line chart of `samples` scaled to the largest one
+fn sparkline(samples: &[f64], color: &str) -> String {
+    let max = samples.iter().copied().fold(0.0, f64::max);
+    let step = SPARK_WIDTH / samples.len().saturating_sub(1).max(1) as f64;
+    let mut points = String::new();
+    for (i, sample) in samples.iter().enumerate() {
+        let y = if max > 0.0 { SPARK_HEIGHT - sample / max * SPARK_HEIGHT } else { SPARK_HEIGHT };
+        let _ = write!(points, "{:.1},{:.1} ", i as f64 * step, y);
+    }
+    format!(
+        "<svg xmlns=\"http://www.w3.org/2000/svg\" width=\"{w}\" height=\"{h}\" viewBox=\"0 0 {w} {h}\">\
+         <polygon points=\"0,{h} {points}{w},{h}\" fill=\"{color}\" fill-opacity=\"0.2\" stroke=\"none\"/>\
+         <polyline points=\"{points}\" fill=\"none\" stroke=\"{color}\" stroke-width=\"1.5\"/></svg>",
+        w = SPARK_WIDTH,
+        h = SPARK_HEIGHT,
+        points = points,
+        color = color,
+    )
+}
+
+// Hostnames come off the network, so they must not be able to inject markup
+fn escape(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\'' => out.push_str("&#39;"),
+            _ => out.push(c),
+        }
+    }
+    out
+}
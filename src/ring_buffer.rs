@@ -0,0 +1,221 @@
+use std::{iter::FusedIterator, mem::MaybeUninit, ops::Index};
+
+// Fixed-capacity FIFO over an inline `[MaybeUninit<T>; N]`: pushing is O(1) and, once
+// full, overwrites the oldest element in place; index 0 is always the oldest element.
+pub struct RingBuffer<T, const N: usize> {
+    slots: [MaybeUninit<T>; N],
+    // Slot of the oldest element
+    head: usize,
+    // Initialised slots, counted from `head` and wrapping around
+    len: usize,
+}
+
+impl<T, const N: usize> RingBuffer<T, N> {
+    pub fn new() -> Self {
+        Self { slots: [const { MaybeUninit::uninit() }; N], head: 0, len: 0 }
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_full(&self) -> bool {
+        self.len == N
+    }
+
+    // Append an element, returning the oldest one when it had to make room
+    pub fn push(&mut self, value: T) -> Option<T> {
+        if self.len < N {
+            self.slots[self.slot(self.len)].write(value);
+            self.len += 1;
+            return None;
+        }
+        // SAFETY: the buffer is full, so the head slot is initialised; it is refilled straight away
+        let removed = unsafe { std::mem::replace(&mut self.slots[self.head], MaybeUninit::new(value)).assume_init() };
+        self.head = self.slot(1);
+        Some(removed)
+    }
+
+    pub fn get(&self, index: usize) -> Option<&T> {
+        // SAFETY: the first `len` slots from `head` are initialised
+        (index < self.len).then(|| unsafe { self.slots[self.slot(index)].assume_init_ref() })
+    }
+
+    // Oldest to newest
+    pub fn iter(&self) -> Iter<'_, T, N> {
+        Iter { buffer: self, front: 0, back: self.len }
+    }
+
+    // Drop every element, keeping the storage
+    pub fn clear(&mut self) {
+        for index in 0..self.len {
+            let slot = self.slot(index);
+            // SAFETY: initialised, and `len` is zeroed below so it is never read again
+            unsafe { self.slots[slot].assume_init_drop() };
+        }
+        self.head = 0;
+        self.len = 0;
+    }
+
+    fn slot(&self, index: usize) -> usize {
+        (self.head + index) % N
+    }
+}
+
+impl<T: Copy + PartialOrd, const N: usize> RingBuffer<T, N> {
+    // Largest element, `None` when empty
+    pub fn max(&self) -> Option<T> {
+        self.iter().copied().reduce(|max, value| if value > max { value } else { max })
+    }
+}
+
+impl<T: Copy + std::iter::Sum<T>, const N: usize> RingBuffer<T, N> {
+    pub fn sum(&self) -> T {
+        self.iter().copied().sum()
+    }
+}
+
+impl<T, const N: usize> Default for RingBuffer<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl<T: Clone, const N: usize> Clone for RingBuffer<T, N> {
+    fn clone(&self) -> Self {
+        let mut copy = Self::new();
+        for value in self {
+            copy.push(value.clone());
+        }
+        copy
+    }
+}
+
+impl<T, const N: usize> Drop for RingBuffer<T, N> {
+    fn drop(&mut self) {
+        self.clear();
+    }
+}
+
+impl<T, const N: usize> Index<usize> for RingBuffer<T, N> {
+    type Output = T;
+
+    fn index(&self, index: usize) -> &T {
+        match self.get(index) {
+            Some(value) => value,
+            None => panic!("index {} out of range for a ring buffer holding {}", index, self.len),
+        }
+    }
+}
+
+impl<'a, T, const N: usize> IntoIterator for &'a RingBuffer<T, N> {
+    type Item = &'a T;
+    type IntoIter = Iter<'a, T, N>;
+
+    fn into_iter(self) -> Iter<'a, T, N> {
+        self.iter()
+    }
+}
+
+#[derive(Clone)]
+pub struct Iter<'a, T, const N: usize> {
+    buffer: &'a RingBuffer<T, N>,
+    // Logical indexes still to yield: `front..back`
+    front: usize,
+    back: usize,
+}
+
+impl<'a, T, const N: usize> Iterator for Iter<'a, T, N> {
+    type Item = &'a T;
+
+    fn next(&mut self) -> Option<&'a T> {
+        if self.front == self.back {
+            return None;
+        }
+        self.front += 1;
+        self.buffer.get(self.front - 1)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let remaining = self.back - self.front;
+        (remaining, Some(remaining))
+    }
+}
+
+impl<T, const N: usize> DoubleEndedIterator for Iter<'_, T, N> {
+    fn next_back(&mut self) -> Option<Self::Item> {
+        if self.front == self.back {
+            return None;
+        }
+        self.back -= 1;
+        self.buffer.get(self.back)
+    }
+}
+
+impl<T, const N: usize> ExactSizeIterator for Iter<'_, T, N> {}
+
+impl<T, const N: usize> FusedIterator for Iter<'_, T, N> {}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::rc::Rc;
+
+    #[test]
+    fn overwrites_oldest_once_full() {
+        let mut ring = RingBuffer::<u32, 3>::new();
+        assert_eq!(ring.push(1), None);
+        assert_eq!(ring.push(2), None);
+        assert_eq!(ring.push(3), None);
+        assert!(ring.is_full());
+        assert_eq!(ring.push(4), Some(1));
+        assert_eq!(ring.push(5), Some(2));
+        assert_eq!(ring.iter().copied().collect::<Vec<_>>(), [3, 4, 5]);
+        assert_eq!(ring.iter().rev().copied().collect::<Vec<_>>(), [5, 4, 3]);
+    }
+
+    #[test]
+    fn index_zero_is_oldest() {
+        let mut ring = RingBuffer::<u32, 2>::new();
+        for value in 1..=5 {
+            ring.push(value);
+        }
+        assert_eq!((ring[0], ring[1]), (4, 5));
+        assert_eq!(ring.get(2), None);
+    }
+
+    #[test]
+    #[should_panic(expected = "out of range")]
+    fn index_past_len_panics() {
+        let mut ring = RingBuffer::<u32, 4>::new();
+        ring.push(1);
+        let _ = ring[1];
+    }
+
+    #[test]
+    fn max_and_sum_cover_the_window() {
+        let mut ring = RingBuffer::<u64, 3>::new();
+        assert_eq!(ring.max(), None);
+        assert_eq!(ring.sum(), 0);
+        for value in [9, 1, 2, 3] {
+            ring.push(value);
+        }
+        assert_eq!(ring.max(), Some(3));
+        assert_eq!(ring.sum(), 6);
+    }
+
+    #[test]
+    fn drops_what_it_holds() {
+        let counted = Rc::new(());
+        let mut ring = RingBuffer::<Rc<()>, 2>::new();
+        for _ in 0..3 {
+            ring.push(counted.clone());
+        }
+        assert_eq!(Rc::strong_count(&counted), 3);
+        let copy = ring.clone();
+        assert_eq!(Rc::strong_count(&counted), 5);
+        drop(ring);
+        drop(copy);
+        assert_eq!(Rc::strong_count(&counted), 1);
+    }
+}
@@ -0,0 +1,18 @@
+// Port -> service name table, generated by build.rs from /etc/services (or a built-in list)
+include!(concat!(env!("OUT_DIR"), "/services.rs"));
+
+// Well-known service name for a port, or "" when the port is not listed
+pub fn resolve_service(port: u16, proto: &str) -> &'static str {
+    let key = format!("{}/{}", port, proto.to_ascii_lowercase());
+    SERVICES.get(key.as_str()).copied().unwrap_or("")
+}
+
+// `443/https` when the service is known, otherwise just the number
+pub fn format_port(port: u16, proto: &str, resolve: bool) -> String {
+    let name = if resolve { resolve_service(port, proto) } else { "" };
+    if name.is_empty() {
+        port.to_string()
+    } else {
+        format!("{}/{}", port, name)
+    }
+}
@@ -0,0 +1,482 @@
+use std::{
+    cmp::Ordering,
+    collections::HashMap,
+    net::IpAddr,
+    ops::{AddAssign, SubAssign},
+    time::{Duration, Instant},
+};
+use chrono::{DateTime, Local};
+use pnet::packet::tcp::TcpFlags;
+use serde::Serialize;
+
+pub const PROTO_TCP: u8 = 6;
+pub const PROTO_UDP: u8 = 17;
+
+// Sessions with no packets for this long are expired into the closed sessions list
+pub const SESSION_IDLE_TIMEOUT: Duration = Duration::from_secs(60);
+// Closed sessions kept for the `c` table; the oldest are dropped first
+pub const MAX_CLOSED_SESSIONS: usize = 1000;
+
+// A unidirectional transport-layer flow (5-tuple)
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SessionKey {
+    pub src: IpAddr,
+    pub dst: IpAddr,
+    pub src_port: u16,
+    pub dst_port: u16,
+    pub proto: u8,
+    // 802.1Q tag the session was seen under, if any
+    pub vlan_id: Option<u16>,
+}
+
+impl SessionKey {
+    pub fn proto_name(&self) -> &'static str {
+        match self.proto {
+            PROTO_TCP => "TCP",
+            PROTO_UDP => "UDP",
+            _ => "?",
+        }
+    }
+
+    // The other direction of the same connection
+    pub fn reversed(&self) -> Self {
+        SessionKey { src: self.dst, dst: self.src, src_port: self.dst_port, dst_port: self.src_port, ..*self }
+    }
+}
+
+// The same key for both directions of a connection: the lower address first (octet by octet,
+// then port), so `a:1 -> b:2` and `b:2 -> a:1` are counted together
+pub fn normalize_session_key(key: SessionKey) -> SessionKey {
+    if (key.src, key.src_port) <= (key.dst, key.dst_port) {
+        key
+    } else {
+        key.reversed()
+    }
+}
+
+// The connection behind `normalized` as seen from its client, from the first tick's deltas of one or
+// both directions: the side that sent a bare SYN, or else the one on the higher, usually ephemeral, port
+pub fn client_key(normalized: SessionKey, deltas: &[(SessionKey, SessionDelta)]) -> SessionKey {
+    if let Some((key, _)) = deltas.iter().find(|(_, delta)| delta.flags.syn > 0 && delta.flags.ack == 0) {
+        return *key;
+    }
+    match normalized.src_port.cmp(&normalized.dst_port) {
+        Ordering::Less => normalized.reversed(),
+        _ => normalized,
+    }
+}
+
+// Which way a packet of a connection went, relative to the side that opened it
+#[derive(Clone, Copy, PartialEq)]
+pub enum Direction {
+    ClientToServer,
+    ServerToClient,
+}
+
+impl Direction {
+    // For a packet sent on `key`, in the connection whose client side sends on `client_key`
+    pub fn of(key: &SessionKey, client_key: &SessionKey) -> Self {
+        if key == client_key { Direction::ClientToServer } else { Direction::ServerToClient }
+    }
+}
+
+// Simplified TCP state of one direction of a connection, in the order a connection goes through them
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub enum TcpState {
+    SynSent,
+    Established,
+    FinWait,
+    Closed,
+}
+
+impl TcpState {
+    // State of a direction first seen with `flags`; one picked up mid-stream is already established
+    pub fn opened(flags: TcpFlagCounts) -> Self {
+        let state = if flags.syn > 0 && flags.ack == 0 { TcpState::SynSent } else { TcpState::Established };
+        state.advance(flags)
+    }
+
+    // State after the segments sent during one tick, which may hold a whole handshake.
+    // A reset closes at once; a FIN only waits here until the other direction sends its own.
+    pub fn advance(self, flags: TcpFlagCounts) -> Self {
+        if flags.rst > 0 {
+            return TcpState::Closed;
+        }
+        let mut state = self;
+        if state == TcpState::SynSent && flags.ack > 0 {
+            state = TcpState::Established;
+        }
+        if state == TcpState::Established && flags.fin > 0 {
+            state = TcpState::FinWait;
+        }
+        state
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            TcpState::SynSent => "SYN_SENT",
+            TcpState::Established => "ESTABLISHED",
+            TcpState::FinWait => "FIN_WAIT",
+            TcpState::Closed => "CLOSED",
+        }
+    }
+}
+
+// Why a session left the live table
+#[derive(Clone, Copy, PartialEq)]
+pub enum EndReason {
+    // Both directions sent a FIN
+    Fin,
+    Reset,
+    // No packets for `SESSION_IDLE_TIMEOUT`
+    Idle,
+}
+
+impl EndReason {
+    pub fn name(self) -> &'static str {
+        match self {
+            EndReason::Fin => "FIN",
+            EndReason::Reset => "RST",
+            EndReason::Idle => "Idle",
+        }
+    }
+}
+
+// One row of the closed sessions table
+pub struct ClosedSession {
+    // Client to server
+    pub key: SessionKey,
+    pub started: DateTime<Local>,
+    // From the first packet to the last
+    pub duration: Duration,
+    pub total_bytes: u64,
+    pub reason: EndReason,
+}
+
+// One row of the session table: both directions of a connection
+pub struct SessionRow {
+    // Client to server
+    pub key: SessionKey,
+    pub avg_bps: f64,
+    // Client to server and server to client parts of `avg_bps` and `avg_pps`
+    pub upload_bps: f64,
+    pub download_bps: f64,
+    pub upload_pps: f64,
+    pub download_pps: f64,
+    pub peak_bps: f64,
+    pub avg_pps: f64,
+    pub peak_pps: f64,
+    pub total_bytes: u64,
+    pub avg_packet_size: f64,
+    // From the first packet to the latest one
+    pub duration: Duration,
+    // SYN and RST segments per second over the history window
+    pub syn_rate: f64,
+    pub rst_rate: f64,
+    // The furthest state either direction reached; None for UDP
+    pub tcp_state: Option<TcpState>,
+    // Retransmitted segments since the session was first seen, and their share of its packets
+    pub retransmits: u64,
+    pub retransmit_rate: f64,
+}
+
+// TCP control flags seen on a session (always zero for UDP)
+#[derive(Clone, Copy, Default, Serialize)]
+pub struct TcpFlagCounts {
+    pub syn: u64,
+    pub fin: u64,
+    pub rst: u64,
+    pub ack: u64,
+}
+
+impl TcpFlagCounts {
+    // Counts for a single segment with the given flag bits
+    pub fn from_flags(flags: u8) -> Self {
+        let has = |flag: u8| (flags & flag != 0) as u64;
+        Self {
+            syn: has(TcpFlags::SYN),
+            fin: has(TcpFlags::FIN),
+            rst: has(TcpFlags::RST),
+            ack: has(TcpFlags::ACK),
+        }
+    }
+}
+
+impl AddAssign for TcpFlagCounts {
+    fn add_assign(&mut self, other: Self) {
+        self.syn += other.syn;
+        self.fin += other.fin;
+        self.rst += other.rst;
+        self.ack += other.ack;
+    }
+}
+
+impl SubAssign for TcpFlagCounts {
+    fn sub_assign(&mut self, other: Self) {
+        self.syn -= other.syn;
+        self.fin -= other.fin;
+        self.rst -= other.rst;
+        self.ack -= other.ack;
+    }
+}
+
+// What the capture thread accumulates for one session between ticks
+#[derive(Clone, Copy, Default)]
+pub struct SessionDelta {
+    pub bytes: u64,
+    pub packets: u64,
+    pub flags: TcpFlagCounts,
+    // TCP segments that resent sequence space already seen, see `SeqTracker`
+    pub retransmits: u64,
+}
+
+impl AddAssign for SessionDelta {
+    fn add_assign(&mut self, other: Self) {
+        self.bytes += other.bytes;
+        self.packets += other.packets;
+        self.flags += other.flags;
+        self.retransmits += other.retransmits;
+    }
+}
+
+// Whether sequence number `a` comes before `b`, with the 32-bit space wrapping around (RFC 1982)
+fn seq_before(a: u32, b: u32) -> bool {
+    (a.wrapping_sub(b) as i32) < 0
+}
+
+// Holes in a session's sequence space remembered at once; older ones are forgotten
+const MAX_SEQ_GAPS: usize = 4;
+// A hole filled this soon after it opened was reordering on the way, not a resend
+const REORDER_WINDOW: Duration = Duration::from_millis(3);
+
+// Sequence space sent so far on one TCP session
+struct SeqState {
+    // End of the highest sequence space sent
+    highest: u32,
+    seen: Instant,
+    // Skipped ranges `[start, end)` below `highest`, and when each was skipped
+    gaps: Vec<(u32, u32, Instant)>,
+}
+
+// Sequence space sent on each TCP session, kept per capture thread since retransmissions
+// can only be told apart packet by packet, before deltas are summed
+#[derive(Default)]
+pub struct SeqTracker {
+    sessions: HashMap<SessionKey, SeqState>,
+    pruned_at: Option<Instant>,
+}
+
+impl SeqTracker {
+    // Record a segment starting at `seq` and covering `len` sequence numbers (payload plus SYN
+    // and FIN); true if it starts before the highest one already sent, i.e. resends data.
+    // Pure ACKs take up no sequence space and are never counted. A segment that fills a hole
+    // left moments earlier arrived out of order and is not counted either.
+    pub fn is_retransmission(&mut self, key: SessionKey, seq: u32, len: u32) -> bool {
+        self.record(key, seq, len, Instant::now())
+    }
+
+    fn record(&mut self, key: SessionKey, seq: u32, len: u32, now: Instant) -> bool {
+        if len == 0 {
+            return false;
+        }
+        self.prune(now);
+
+        let end = seq.wrapping_add(len);
+        let Some(state) = self.sessions.get_mut(&key) else {
+            self.sessions.insert(key, SeqState { highest: end, seen: now, gaps: Vec::new() });
+            return false;
+        };
+        state.seen = now;
+        if !seq_before(seq, state.highest) {
+            // Anything skipped on the way may still turn up late
+            if seq_before(state.highest, seq) {
+                if state.gaps.len() == MAX_SEQ_GAPS {
+                    state.gaps.remove(0);
+                }
+                state.gaps.push((state.highest, seq, now));
+            }
+            state.highest = end;
+            return false;
+        }
+
+        let filled = state.gaps.iter().position(|&(start, stop, _)| !seq_before(seq, start) && !seq_before(stop, end));
+        if let Some(index) = filled {
+            let (start, stop, opened) = state.gaps.remove(index);
+            for (start, stop) in [(start, seq), (end, stop)] {
+                if start != stop {
+                    state.gaps.push((start, stop, opened));
+                }
+            }
+            return now.duration_since(opened) > REORDER_WINDOW;
+        }
+        if seq_before(state.highest, end) {
+            state.highest = end;
+        }
+        true
+    }
+
+    // Forget sessions idle long enough that the App has expired them too
+    fn prune(&mut self, now: Instant) {
+        if self.pruned_at.is_some_and(|at| now.duration_since(at) < SESSION_IDLE_TIMEOUT) {
+            return;
+        }
+        self.sessions.retain(|_, state| now.duration_since(state.seen) < SESSION_IDLE_TIMEOUT);
+        self.pruned_at = Some(now);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn flags(syn: u64, ack: u64, fin: u64, rst: u64) -> TcpFlagCounts {
+        TcpFlagCounts { syn, fin, rst, ack }
+    }
+
+    #[test]
+    fn handshake_establishes_both_directions() {
+        let client = TcpState::opened(flags(1, 0, 0, 0));
+        assert_eq!(client, TcpState::SynSent);
+        // SYN-ACK: the server side is established as soon as it answers
+        assert_eq!(TcpState::opened(flags(1, 1, 0, 0)), TcpState::Established);
+        assert_eq!(client.advance(flags(0, 1, 0, 0)), TcpState::Established);
+        // A retransmitted SYN changes nothing
+        assert_eq!(client.advance(flags(1, 0, 0, 0)), TcpState::SynSent);
+    }
+
+    #[test]
+    fn fin_from_each_side_only_closes_that_direction() {
+        let client = TcpState::Established.advance(flags(0, 1, 1, 0));
+        assert_eq!(client, TcpState::FinWait);
+        assert_eq!(TcpState::Established.advance(flags(0, 1, 0, 0)), TcpState::Established);
+        let server = TcpState::Established.advance(flags(0, 1, 1, 0));
+        assert_eq!(server, TcpState::FinWait);
+        // Further ACKs after the FIN leave it waiting
+        assert_eq!(client.advance(flags(0, 1, 0, 0)), TcpState::FinWait);
+    }
+
+    #[test]
+    fn whole_connection_within_one_tick() {
+        assert_eq!(TcpState::SynSent.advance(flags(0, 3, 1, 0)), TcpState::FinWait);
+    }
+
+    #[test]
+    fn rst_closes_from_any_state() {
+        for state in [TcpState::SynSent, TcpState::Established, TcpState::FinWait, TcpState::Closed] {
+            assert_eq!(state.advance(flags(0, 0, 0, 1)), TcpState::Closed);
+            assert_eq!(state.advance(flags(0, 1, 1, 1)), TcpState::Closed);
+        }
+        assert_eq!(TcpState::opened(flags(1, 0, 0, 1)), TcpState::Closed);
+    }
+
+    fn tcp_key() -> SessionKey {
+        key("192.168.1.10", 50000, "203.0.113.5", 443)
+    }
+
+    #[test]
+    fn resent_segment_is_a_retransmission() {
+        let (mut seq, now) = (SeqTracker::default(), Instant::now());
+        assert!(!seq.record(tcp_key(), 1000, 100, now));
+        assert!(!seq.record(tcp_key(), 1100, 100, now));
+        assert!(seq.record(tcp_key(), 1000, 100, now));
+        // Other sessions keep their own sequence space
+        assert!(!seq.record(tcp_key().reversed(), 1000, 100, now));
+    }
+
+    #[test]
+    fn sequence_numbers_wrap_around() {
+        let (mut seq, now) = (SeqTracker::default(), Instant::now());
+        assert!(!seq.record(tcp_key(), u32::MAX - 99, 100, now));
+        // Continues at 0, which comes after u32::MAX
+        assert!(!seq.record(tcp_key(), 0, 100, now));
+        assert!(!seq.record(tcp_key(), 100, 100, now));
+        assert!(seq.record(tcp_key(), u32::MAX - 99, 100, now));
+        // Straddling the wrap
+        assert!(!seq.record(tcp_key(), 200, u32::MAX - 250, now));
+        assert!(!seq.record(tcp_key(), u32::MAX - 50, 100, now));
+        assert!(seq.record(tcp_key(), u32::MAX - 50, 100, now));
+    }
+
+    #[test]
+    fn reordered_segment_is_not_a_retransmission() {
+        let (mut seq, now) = (SeqTracker::default(), Instant::now());
+        seq.record(tcp_key(), 1000, 100, now);
+        // 1100..1300 is skipped, then arrives in two pieces right behind
+        assert!(!seq.record(tcp_key(), 1300, 100, now));
+        assert!(!seq.record(tcp_key(), 1200, 100, now + Duration::from_millis(1)));
+        assert!(!seq.record(tcp_key(), 1100, 100, now + Duration::from_millis(1)));
+        // The hole is gone, so the same range again is a resend
+        assert!(seq.record(tcp_key(), 1100, 100, now + Duration::from_millis(2)));
+    }
+
+    #[test]
+    fn hole_filled_late_is_a_retransmission() {
+        let (mut seq, now) = (SeqTracker::default(), Instant::now());
+        seq.record(tcp_key(), 1000, 100, now);
+        seq.record(tcp_key(), 1200, 100, now);
+        // Lost before the capture point and sent again after a timeout
+        assert!(seq.record(tcp_key(), 1100, 100, now + Duration::from_millis(200)));
+    }
+
+    #[test]
+    fn pure_acks_are_never_retransmissions() {
+        let (mut seq, now) = (SeqTracker::default(), Instant::now());
+        assert!(!seq.record(tcp_key(), 1000, 0, now));
+        seq.record(tcp_key(), 1000, 100, now);
+        // Every ACK after data repeats the next sequence number, often many times
+        for _ in 0..3 {
+            assert!(!seq.record(tcp_key(), 1100, 0, now));
+            assert!(!seq.record(tcp_key(), 1000, 0, now));
+        }
+        assert!(!seq.record(tcp_key(), 1100, 100, now));
+    }
+
+    fn key(src: &str, src_port: u16, dst: &str, dst_port: u16) -> SessionKey {
+        SessionKey { src: src.parse().unwrap(), dst: dst.parse().unwrap(), src_port, dst_port, proto: PROTO_TCP, vlan_id: None }
+    }
+
+    fn syn() -> SessionDelta {
+        SessionDelta { packets: 1, flags: TcpFlagCounts { syn: 1, ..Default::default() }, ..Default::default() }
+    }
+
+    #[test]
+    fn both_directions_normalize_to_one_key() {
+        for forward in [key("192.168.1.10", 50000, "10.0.0.1", 443), key("10.0.0.1", 80, "10.0.0.1", 8080), key("192.168.1.2", 123, "192.168.1.3", 123)] {
+            let normalized = normalize_session_key(forward);
+            assert!(normalized == normalize_session_key(forward.reversed()));
+            assert!(normalized == forward || normalized == forward.reversed());
+            assert!((normalized.src, normalized.src_port) <= (normalized.dst, normalized.dst_port));
+        }
+        // The VLAN tag is part of the key and survives normalizing
+        let tagged = SessionKey { vlan_id: Some(20), ..key("192.168.1.10", 50000, "10.0.0.1", 443) };
+        assert_eq!(normalize_session_key(tagged.reversed()).vlan_id, Some(20));
+    }
+
+    #[test]
+    fn client_is_the_ephemeral_port_without_a_syn() {
+        let client = key("10.0.0.1", 50000, "192.168.1.10", 443);
+        for seen in [client, client.reversed()] {
+            assert!(client_key(normalize_session_key(seen), &[]) == client);
+        }
+    }
+
+    #[test]
+    fn bare_syn_picks_the_client_over_ports() {
+        // An active FTP data connection: the server connects from port 20 up to the client's high port
+        let server_to_client = key("192.168.1.10", 20, "10.0.0.1", 50000);
+        let normalized = normalize_session_key(server_to_client);
+        assert!(client_key(normalized, &[(server_to_client, syn())]) == server_to_client);
+        // A SYN-ACK is the server's answer and says nothing
+        let syn_ack = SessionDelta { flags: TcpFlagCounts { syn: 1, ack: 1, ..Default::default() }, ..syn() };
+        assert!(client_key(normalized, &[(server_to_client.reversed(), syn_ack)]) == server_to_client.reversed());
+    }
+
+    #[test]
+    fn equal_ports_make_the_lower_address_the_client() {
+        let high_first = key("192.168.1.3", 123, "192.168.1.2", 123);
+        let client = client_key(normalize_session_key(high_first), &[]);
+        assert!(client == high_first.reversed());
+        // Unless the other side is seen opening the connection
+        assert!(client_key(normalize_session_key(high_first), &[(high_first, syn())]) == high_first);
+    }
+}
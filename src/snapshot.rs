@@ -0,0 +1,96 @@
+use std::{
+    env, fs,
+    net::Ipv4Addr,
+    path::{Path, PathBuf},
+};
+use chrono::{DateTime, Local, TimeZone};
+use serde::{Deserialize, Serialize};
+
+// Bumped whenever a field changes meaning; snapshots of another version are ignored
+pub const SCHEMA_VERSION: u32 = 1;
+
+// Totals, peaks and chart history saved on exit and loaded back by `--resume`.
+// Times are Unix timestamps; the per-IP sliding windows are not kept, they start empty.
+#[derive(Serialize, Deserialize)]
+pub struct AppSnapshot {
+    pub schema_version: u32,
+    pub saved: i64,
+    pub interfaces: Vec<InterfaceSnapshot>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct InterfaceSnapshot {
+    pub name: String,
+    // Bytes per tick, oldest first
+    pub rx_history: Vec<f64>,
+    pub tx_history: Vec<f64>,
+    pub total_rx_bytes: u64,
+    pub total_tx_bytes: u64,
+    pub total_broadcast_bytes: u64,
+    pub total_multicast_bytes: u64,
+    // Bytes/s and when it was reached
+    pub peak_rx_record: (f64, i64),
+    pub peak_tx_record: (f64, i64),
+    pub hosts: Vec<HostSnapshot>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct HostSnapshot {
+    pub ip: Ipv4Addr,
+    pub mac: [u8; 6],
+    pub peak_rate: f64,
+    pub peak_rx: f64,
+    pub peak_tx: f64,
+    pub peak_pps: f64,
+    pub peak_time: i64,
+    pub lifetime_bytes: u64,
+    pub first_seen: i64,
+    pub tcp_bytes: u64,
+    pub udp_bytes: u64,
+    pub other_bytes: u64,
+}
+
+// Only the version is read first, so a snapshot with other fields still gets a clear message
+#[derive(Deserialize)]
+struct SchemaProbe {
+    schema_version: u32,
+}
+
+// `$XDG_CACHE_HOME/iftop_rs/snapshot.json`, or `~/.cache/iftop_rs/snapshot.json`
+pub fn default_path() -> Option<PathBuf> {
+    let cache = match env::var_os("XDG_CACHE_HOME").filter(|dir| !dir.is_empty()) {
+        Some(dir) => PathBuf::from(dir),
+        None => PathBuf::from(env::var_os("HOME")?).join(".cache"),
+    };
+    Some(cache.join("iftop_rs").join("snapshot.json"))
+}
+
+pub fn load(path: &Path) -> Result<AppSnapshot, String> {
+    let text = fs::read_to_string(path).map_err(|err| format!("{}: {}", path.display(), err))?;
+    let probe: SchemaProbe = serde_json::from_str(&text).map_err(|err| format!("{}: {}", path.display(), err))?;
+    if probe.schema_version != SCHEMA_VERSION {
+        return Err(format!(
+            "{}: snapshot format {} is not supported (expected {})",
+            path.display(),
+            probe.schema_version,
+            SCHEMA_VERSION
+        ));
+    }
+    serde_json::from_str(&text).map_err(|err| format!("{}: {}", path.display(), err))
+}
+
+// Written next to the target and renamed, so an interrupted save keeps the previous snapshot
+pub fn save(path: &Path, snapshot: &AppSnapshot) -> Result<(), String> {
+    let json = serde_json::to_string(snapshot).map_err(|err| err.to_string())?;
+    let tmp_path = path.with_extension("json.tmp");
+    path.parent()
+        .map_or(Ok(()), fs::create_dir_all)
+        .and_then(|_| fs::write(&tmp_path, json))
+        .and_then(|_| fs::rename(&tmp_path, path))
+        .map_err(|err| format!("{}: {}", path.display(), err))
+}
+
+// Back from a saved timestamp; one that no longer maps to a local time becomes now
+pub fn local_time(timestamp: i64) -> DateTime<Local> {
+    Local.timestamp_opt(timestamp, 0).single().unwrap_or_else(Local::now)
+}
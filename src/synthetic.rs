@@ -0,0 +1,251 @@
+use std::{f64::consts::TAU, net::Ipv4Addr, time::Duration};
+use pnet::ipnetwork::Ipv4Network;
+use pnet::packet::{
+    ethernet::{EtherTypes, MutableEthernetPacket},
+    ip::{IpNextHeaderProtocol, IpNextHeaderProtocols},
+    ipv4::{self, MutableIpv4Packet},
+    tcp::{MutableTcpPacket, TcpFlags},
+    udp::MutableUdpPacket,
+};
+use pnet::util::MacAddr;
+
+// Seed used by `--dry-run` without `--dry-run-seed`
+pub const DEFAULT_DRY_RUN_SEED: u64 = 42;
+
+// Traffic is generated in steps of this much simulated time
+pub const SLICE: Duration = Duration::from_millis(10);
+
+// The host `--dry-run` pretends to capture on, and the LAN around it
+const LOCAL_IP: Ipv4Addr = Ipv4Addr::new(192, 168, 1, 2);
+const LOCAL_NET: &str = "192.168.1.0/24";
+// Remote peers are reached through the router, so they all share its MAC
+const ROUTER_MAC: [u8; 6] = [0x02, 0x00, 0x00, 0x00, 0x00, 0x01];
+// TTL remote peers' packets arrive with, 12 hops from an initial 64
+const PEER_TTL: u8 = 52;
+
+const ETHERNET_HEADER: usize = 14;
+const IPV4_HEADER: usize = 20;
+const TCP_HEADER: usize = 20;
+const UDP_HEADER: usize = 8;
+
+// SplitMix64: the same sequence for a given seed on every platform
+struct Rng(u64);
+
+impl Rng {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e37_79b9_7f4a_7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+
+    // Uniform in [low, high)
+    fn range(&mut self, low: f64, high: f64) -> f64 {
+        let unit = (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64;
+        low + (high - low) * unit
+    }
+}
+
+// What one LAN host does: who it talks to, how, and how its rate swings
+struct Profile {
+    host: u8,
+    peer: Ipv4Addr,
+    protocol: IpNextHeaderProtocol,
+    host_port: u16,
+    peer_port: u16,
+    dscp: u8,
+    // Initial TTL of the host's own packets: 64 for Linux, 128 for Windows
+    ttl: u8,
+    // Bytes/s the rate swings around, and by how much at most
+    base: f64,
+    amplitude: f64,
+    // Share of the bytes the host receives; it sends the rest
+    download: f64,
+    // Frame sizes in bytes, picked uniformly
+    frame: (usize, usize),
+    // Share of packets marked ECN Congestion Experienced
+    ecn_ce: f64,
+}
+
+const PROFILES: [Profile; 7] = [
+    // The monitoring host itself, so RX and TX both move
+    Profile { host: 2, peer: Ipv4Addr::new(198, 51, 100, 10), protocol: IpNextHeaderProtocols::Tcp, host_port: 51512, peer_port: 443, dscp: 0, ttl: 64, base: 150_000.0, amplitude: 120_000.0, download: 0.85, frame: (1514, 1514), ecn_ce: 0.0 },
+    // Video streaming
+    Profile { host: 10, peer: Ipv4Addr::new(198, 51, 100, 20), protocol: IpNextHeaderProtocols::Tcp, host_port: 50344, peer_port: 443, dscp: 0, ttl: 128, base: 600_000.0, amplitude: 450_000.0, download: 0.97, frame: (1514, 1514), ecn_ce: 0.0 },
+    // Off-site backup over a congested uplink
+    Profile { host: 11, peer: Ipv4Addr::new(203, 0, 113, 5), protocol: IpNextHeaderProtocols::Tcp, host_port: 40022, peer_port: 22, dscp: 8, ttl: 64, base: 300_000.0, amplitude: 250_000.0, download: 0.05, frame: (1514, 1514), ecn_ce: 0.02 },
+    // VoIP call, steady and marked EF
+    Profile { host: 12, peer: Ipv4Addr::new(203, 0, 113, 40), protocol: IpNextHeaderProtocols::Udp, host_port: 16384, peer_port: 16384, dscp: 46, ttl: 64, base: 21_000.0, amplitude: 2_000.0, download: 0.5, frame: (214, 214), ecn_ce: 0.0 },
+    // Online game
+    Profile { host: 13, peer: Ipv4Addr::new(198, 51, 100, 77), protocol: IpNextHeaderProtocols::Udp, host_port: 3074, peer_port: 3074, dscp: 34, ttl: 128, base: 30_000.0, amplitude: 20_000.0, download: 0.6, frame: (90, 320), ecn_ce: 0.0 },
+    // DNS lookups
+    Profile { host: 14, peer: Ipv4Addr::new(198, 51, 100, 53), protocol: IpNextHeaderProtocols::Udp, host_port: 53001, peer_port: 53, dscp: 0, ttl: 64, base: 2_000.0, amplitude: 1_500.0, download: 0.6, frame: (80, 512), ecn_ce: 0.0 },
+    // mDNS announcements, so the multicast counters move too
+    Profile { host: 14, peer: Ipv4Addr::new(224, 0, 0, 251), protocol: IpNextHeaderProtocols::Udp, host_port: 5353, peer_port: 5353, dscp: 0, ttl: 255, base: 400.0, amplitude: 300.0, download: 0.0, frame: (120, 400), ecn_ce: 0.0 },
+];
+
+// A profile in motion: its phase and period come from the seed
+struct Flow {
+    profile: &'static Profile,
+    // Seconds per swing and where in it the flow starts
+    period: f64,
+    phase: f64,
+    // Bytes owed from earlier slices, less than a frame
+    backlog: f64,
+    // Next TCP sequence number sent by the host and by the peer
+    host_seq: u32,
+    peer_seq: u32,
+}
+
+// Believable traffic for `--dry-run`: a few LAN hosts whose rates follow sine waves, from a
+// seeded generator so the same seed yields the same packets
+pub struct SyntheticTraffic {
+    rng: Rng,
+    flows: Vec<Flow>,
+    slice: u64,
+}
+
+impl SyntheticTraffic {
+    pub fn new(seed: u64) -> Self {
+        let mut rng = Rng(seed);
+        let flows = PROFILES
+            .iter()
+            .map(|profile| Flow {
+                profile,
+                period: rng.range(20.0, 120.0),
+                phase: rng.range(0.0, TAU),
+                backlog: 0.0,
+                host_seq: rng.next_u64() as u32,
+                peer_seq: rng.next_u64() as u32,
+            })
+            .collect();
+        Self { rng, flows, slice: 0 }
+    }
+
+    pub fn local_ip(&self) -> Ipv4Addr {
+        LOCAL_IP
+    }
+
+    pub fn local_networks(&self) -> Vec<Ipv4Network> {
+        vec![LOCAL_NET.parse().expect("valid network")]
+    }
+
+    // Whole Ethernet frames for the next `SLICE` of simulated time
+    pub fn next_slice(&mut self) -> Vec<Vec<u8>> {
+        let t = self.slice as f64 * SLICE.as_secs_f64();
+        self.slice += 1;
+
+        let mut frames = Vec::new();
+        for flow in &mut self.flows {
+            let profile = flow.profile;
+            let wave = (TAU * t / flow.period + flow.phase).sin();
+            // Up to 20% of noise on top of the wave
+            let rate = (profile.base + profile.amplitude * wave).max(0.0) * self.rng.range(0.8, 1.2);
+            flow.backlog += rate * SLICE.as_secs_f64();
+            loop {
+                let size = self.rng.range(profile.frame.0 as f64, profile.frame.1 as f64 + 1.0) as usize;
+                if flow.backlog < size as f64 {
+                    break;
+                }
+                flow.backlog -= size as f64;
+                let from_host = self.rng.range(0.0, 1.0) >= profile.download;
+                let congested = self.rng.range(0.0, 1.0) < profile.ecn_ce;
+                frames.push(flow.frame(size, from_host, congested));
+            }
+        }
+        frames
+    }
+}
+
+impl Flow {
+    fn frame(&mut self, size: usize, from_host: bool, congested: bool) -> Vec<u8> {
+        let profile = self.profile;
+        let transport = if profile.protocol == IpNextHeaderProtocols::Tcp { TCP_HEADER } else { UDP_HEADER };
+        let size = size.max(ETHERNET_HEADER + IPV4_HEADER + transport);
+        let payload = size - ETHERNET_HEADER - IPV4_HEADER - transport;
+
+        let host_ip = Ipv4Addr::new(192, 168, 1, profile.host);
+        let host_mac = MacAddr::new(0x02, 0x00, 0x00, 0x00, 0x01, profile.host);
+        let peer_mac = if profile.peer.is_multicast() {
+            let octets = profile.peer.octets();
+            MacAddr::new(0x01, 0x00, 0x5e, octets[1] & 0x7f, octets[2], octets[3])
+        } else {
+            MacAddr::from(ROUTER_MAC)
+        };
+        let (src, dst, src_mac, dst_mac, src_port, dst_port, ttl) = if from_host {
+            (host_ip, profile.peer, host_mac, peer_mac, profile.host_port, profile.peer_port, profile.ttl)
+        } else {
+            (profile.peer, host_ip, peer_mac, host_mac, profile.peer_port, profile.host_port, PEER_TTL)
+        };
+
+        let mut buffer = vec![0u8; size];
+        let mut ethernet = MutableEthernetPacket::new(&mut buffer).expect("frame holds an Ethernet header");
+        ethernet.set_source(src_mac);
+        ethernet.set_destination(dst_mac);
+        ethernet.set_ethertype(EtherTypes::Ipv4);
+
+        let mut ip = MutableIpv4Packet::new(&mut buffer[ETHERNET_HEADER..]).expect("frame holds an IPv4 header");
+        ip.set_version(4);
+        ip.set_header_length((IPV4_HEADER / 4) as u8);
+        ip.set_total_length((size - ETHERNET_HEADER) as u16);
+        ip.set_dscp(profile.dscp);
+        ip.set_ecn(if congested { 3 } else if profile.ecn_ce > 0.0 { 2 } else { 0 });
+        ip.set_ttl(ttl);
+        ip.set_next_level_protocol(profile.protocol);
+        ip.set_source(src);
+        ip.set_destination(dst);
+        let checksum = ipv4::checksum(&ip.to_immutable());
+        ip.set_checksum(checksum);
+
+        let segment = &mut buffer[ETHERNET_HEADER + IPV4_HEADER..];
+        if profile.protocol == IpNextHeaderProtocols::Tcp {
+            let seq = if from_host { &mut self.host_seq } else { &mut self.peer_seq };
+            let mut tcp = MutableTcpPacket::new(segment).expect("frame holds a TCP header");
+            tcp.set_source(src_port);
+            tcp.set_destination(dst_port);
+            tcp.set_sequence(*seq);
+            tcp.set_data_offset((TCP_HEADER / 4) as u8);
+            tcp.set_flags(TcpFlags::ACK | TcpFlags::PSH);
+            tcp.set_window(65535);
+            *seq = seq.wrapping_add(payload as u32);
+        } else {
+            let mut udp = MutableUdpPacket::new(segment).expect("frame holds a UDP header");
+            udp.set_source(src_port);
+            udp.set_destination(dst_port);
+            udp.set_length((UDP_HEADER + payload) as u16);
+        }
+        buffer
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn slices(seed: u64, count: usize) -> Vec<Vec<Vec<u8>>> {
+        let mut traffic = SyntheticTraffic::new(seed);
+        (0..count).map(|_| traffic.next_slice()).collect()
+    }
+
+    #[test]
+    fn same_seed_same_frames() {
+        let first = slices(DEFAULT_DRY_RUN_SEED, 200);
+        assert!(first.iter().any(|frames| !frames.is_empty()));
+        assert_eq!(first, slices(DEFAULT_DRY_RUN_SEED, 200));
+    }
+
+    #[test]
+    fn other_seed_other_frames() {
+        assert_ne!(slices(1, 200), slices(2, 200));
+    }
+
+    #[test]
+    fn frames_come_from_the_lan() {
+        let lan: Ipv4Network = LOCAL_NET.parse().unwrap();
+        for frame in slices(DEFAULT_DRY_RUN_SEED, 100).concat() {
+            let ip = pnet::packet::ipv4::Ipv4Packet::new(&frame[ETHERNET_HEADER..]).unwrap();
+            assert!(lan.contains(ip.get_source()) || lan.contains(ip.get_destination()));
+        }
+    }
+}
@@ -0,0 +1,51 @@
+use std::io;
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+use std::process;
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+use syslog::{Facility, Formatter3164, Logger, LoggerBackend};
+
+// Tag in front of every message, as in `iftop_rs[1234]: ALERT ...`
+#[cfg(any(target_os = "linux", target_os = "macos"))]
+const TAG: &str = "iftop_rs";
+
+#[derive(Clone, Copy)]
+pub enum Severity {
+    Err,
+    Warning,
+}
+
+// RFC 3164 messages with the LOG_DAEMON facility for `--syslog`, through the `syslog` crate.
+// It finds the local daemon's socket: `/dev/log` on Linux, `/var/run/syslog` on macOS.
+// Other platforms compile the calls out, so `connect` fails there and nothing is sent.
+pub struct Syslog {
+    #[cfg(any(target_os = "linux", target_os = "macos"))]
+    logger: Logger<LoggerBackend, Formatter3164>,
+}
+
+impl Syslog {
+    #[cfg(any(target_os = "linux", target_os = "macos"))]
+    pub fn connect() -> io::Result<Self> {
+        let formatter = Formatter3164 { facility: Facility::LOG_DAEMON, hostname: None, process: TAG.to_string(), pid: process::id() };
+        let logger = syslog::unix(formatter).map_err(|err| io::Error::other(format!("syslog: {}", err)))?;
+        Ok(Self { logger })
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "macos")))]
+    pub fn connect() -> io::Result<Self> {
+        Err(io::Error::new(io::ErrorKind::Unsupported, "syslog is only supported on Linux and macOS"))
+    }
+
+    #[cfg(any(target_os = "linux", target_os = "macos"))]
+    pub fn send(&mut self, severity: Severity, message: &str) -> io::Result<()> {
+        let sent = match severity {
+            Severity::Err => self.logger.err(message),
+            Severity::Warning => self.logger.warning(message),
+        };
+        sent.map_err(|err| io::Error::other(format!("syslog: {}", err)))
+    }
+
+    #[cfg(not(any(target_os = "linux", target_os = "macos")))]
+    pub fn send(&mut self, _severity: Severity, _message: &str) -> io::Result<()> {
+        Ok(())
+    }
+}
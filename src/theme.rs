@@ -0,0 +1,167 @@
+use std::{fs, path::Path, str::FromStr};
+use ratatui::style::Color;
+
+// Every color the UI draws with. `--theme` picks a preset or a TOML file that
+// starts from a preset (`preset = "light"`, dark by default) and overrides fields:
+//
+//     preset = "dark"
+//     rx_color = "lightred"
+//     header_bg = "#303030"
+#[derive(Clone)]
+pub struct Theme {
+    pub rx_color: Color,
+    pub tx_color: Color,
+    pub header_bg: Color,
+    pub header_fg: Color,
+    // Rate thresholds: above 1 Mb/s, above 100 kb/s, below
+    pub high_traffic: Color,
+    pub medium_traffic: Color,
+    pub low_traffic: Color,
+    pub hostname_color: Color,
+    pub peak_color: Color,
+    // Peaks above 1 Mb/s
+    pub high_peak: Color,
+    // Session rows with a high RST rate
+    pub rst_warning: Color,
+    // Session rows retransmitting more than 1% of their packets
+    pub retransmit_warning: Color,
+    pub status_bg: Color,
+    // Borders, active tab and other highlights
+    pub accent: Color,
+    // Labels, timestamps and other secondary text
+    pub muted: Color,
+    // Headline numbers in the stats panel
+    pub text: Color,
+    pub warning: Color,
+    pub selection_bg: Color,
+    // Backgrounds of alternating rows in the traffic table, first row even
+    pub row_even_bg: Color,
+    pub row_odd_bg: Color,
+}
+
+impl Theme {
+    pub fn dark() -> Self {
+        Self {
+            rx_color: Color::Red,
+            tx_color: Color::Blue,
+            header_bg: Color::Rgb(40, 40, 40),
+            header_fg: Color::Yellow,
+            high_traffic: Color::Red,
+            medium_traffic: Color::LightYellow,
+            low_traffic: Color::Green,
+            hostname_color: Color::LightCyan,
+            peak_color: Color::Cyan,
+            high_peak: Color::Magenta,
+            rst_warning: Color::Magenta,
+            retransmit_warning: Color::Rgb(255, 165, 0),
+            status_bg: Color::Rgb(20, 20, 20),
+            accent: Color::Cyan,
+            muted: Color::DarkGray,
+            text: Color::White,
+            warning: Color::Yellow,
+            selection_bg: Color::DarkGray,
+            row_even_bg: Color::Rgb(30, 30, 30),
+            row_odd_bg: Color::Rgb(20, 20, 20),
+        }
+    }
+
+    pub fn light() -> Self {
+        Self {
+            rx_color: Color::Red,
+            tx_color: Color::Blue,
+            header_bg: Color::Rgb(215, 215, 215),
+            header_fg: Color::Black,
+            high_traffic: Color::Red,
+            medium_traffic: Color::Rgb(175, 115, 0),
+            low_traffic: Color::Rgb(0, 135, 0),
+            hostname_color: Color::Rgb(0, 95, 135),
+            peak_color: Color::Rgb(0, 125, 125),
+            high_peak: Color::Magenta,
+            rst_warning: Color::Magenta,
+            retransmit_warning: Color::Rgb(205, 102, 0),
+            status_bg: Color::Rgb(230, 230, 230),
+            accent: Color::Blue,
+            muted: Color::Gray,
+            text: Color::Black,
+            warning: Color::Rgb(175, 115, 0),
+            selection_bg: Color::Rgb(200, 200, 200),
+            row_even_bg: Color::Rgb(245, 245, 245),
+            row_odd_bg: Color::Rgb(232, 232, 232),
+        }
+    }
+
+    pub fn preset(name: &str) -> Option<Self> {
+        match name {
+            "dark" => Some(Self::dark()),
+            "light" => Some(Self::light()),
+            _ => None,
+        }
+    }
+
+    // A preset name, or the path of a TOML theme file
+    pub fn load(source: &str) -> Result<Self, String> {
+        if let Some(theme) = Self::preset(source) {
+            return Ok(theme);
+        }
+        Self::from_file(Path::new(source))
+    }
+
+    pub fn from_file(path: &Path) -> Result<Self, String> {
+        let contents = fs::read_to_string(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+        let table: toml::Table = contents.parse().map_err(|e| format!("{}: {}", path.display(), e))?;
+
+        let mut theme = match table.get("preset") {
+            Some(toml::Value::String(name)) => Self::preset(name).ok_or_else(|| format!("Unknown theme preset '{}'", name))?,
+            Some(_) => return Err("'preset' must be a string".to_string()),
+            None => Self::dark(),
+        };
+        for (key, value) in table.iter().filter(|(key, _)| *key != "preset") {
+            let value = value.as_str().ok_or_else(|| format!("'{}' must be a color string", key))?;
+            let color = Color::from_str(value).map_err(|_| format!("Invalid color '{}' for '{}'", value, key))?;
+            theme.set(key, color)?;
+        }
+        Ok(theme)
+    }
+
+    fn set(&mut self, key: &str, color: Color) -> Result<(), String> {
+        let field = match key {
+            "rx_color" => &mut self.rx_color,
+            "tx_color" => &mut self.tx_color,
+            "header_bg" => &mut self.header_bg,
+            "header_fg" => &mut self.header_fg,
+            "high_traffic" => &mut self.high_traffic,
+            "medium_traffic" => &mut self.medium_traffic,
+            "low_traffic" => &mut self.low_traffic,
+            "hostname_color" => &mut self.hostname_color,
+            "peak_color" => &mut self.peak_color,
+            "high_peak" => &mut self.high_peak,
+            "rst_warning" => &mut self.rst_warning,
+            "retransmit_warning" => &mut self.retransmit_warning,
+            "status_bg" => &mut self.status_bg,
+            "accent" => &mut self.accent,
+            "muted" => &mut self.muted,
+            "text" => &mut self.text,
+            "warning" => &mut self.warning,
+            "selection_bg" => &mut self.selection_bg,
+            "row_even_bg" => &mut self.row_even_bg,
+            "row_odd_bg" => &mut self.row_odd_bg,
+            _ => return Err(format!("Unknown theme color '{}'", key)),
+        };
+        *field = color;
+        Ok(())
+    }
+
+    pub fn rate_color(&self, bps: f64) -> Color {
+        if bps > 1_000_000.0 { self.high_traffic } else if bps > 100_000.0 { self.medium_traffic } else { self.low_traffic }
+    }
+
+    pub fn peak(&self, bps: f64) -> Color {
+        if bps > 1_000_000.0 { self.high_peak } else { self.peak_color }
+    }
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Self::dark()
+    }
+}
@@ -0,0 +1,132 @@
+use std::{
+    collections::HashMap,
+    f64::consts::TAU,
+    net::Ipv4Addr,
+    time::{Duration, Instant},
+};
+
+// The map shows at most this many hosts, the busiest ones
+pub const MAX_TOPOLOGY_NODES: usize = 20;
+// How often the layout is recomputed; frames in between draw the last one
+const LAYOUT_INTERVAL: Duration = Duration::from_secs(1);
+// Force-directed steps per layout, starting from the previous positions so the map does not jump
+const LAYOUT_ITERATIONS: usize = 50;
+// Furthest a node may move in the first step; each later step allows a little less
+const INITIAL_TEMPERATURE: f64 = 0.1;
+const COOLING: f64 = 0.95;
+// Pull towards the centre, so hosts without links stay on the map instead of drifting to the edge
+const GRAVITY: f64 = 1.0;
+// Nodes are kept this far inside the unit square, leaving room for their labels
+const MARGIN: f64 = 0.05;
+
+pub struct TopologyNode {
+    pub ip: Ipv4Addr,
+    // Position in the unit square
+    pub x: f64,
+    pub y: f64,
+    pub avg_bps: f64,
+}
+
+// Traffic between two nodes, by index into `Topology::nodes`
+pub struct TopologyEdge {
+    pub a: usize,
+    pub b: usize,
+    pub avg_bps: f64,
+}
+
+// Local hosts and the links between them, laid out by a simple force-directed placement
+#[derive(Default)]
+pub struct Topology {
+    pub nodes: Vec<TopologyNode>,
+    pub edges: Vec<TopologyEdge>,
+    laid_out_at: Option<Instant>,
+}
+
+impl Topology {
+    // Whether a second has passed since the last layout
+    pub fn due(&self) -> bool {
+        self.laid_out_at.is_none_or(|at| at.elapsed() >= LAYOUT_INTERVAL)
+    }
+
+    // Lay out `hosts` (IP and bytes/s, busiest first) and the bytes/s exchanged between pairs of them;
+    // links to hosts that did not make the cut are left out
+    pub fn update(&mut self, hosts: &[(Ipv4Addr, f64)], links: &HashMap<(Ipv4Addr, Ipv4Addr), f64>) {
+        let previous: HashMap<Ipv4Addr, (f64, f64)> = self.nodes.iter().map(|node| (node.ip, (node.x, node.y))).collect();
+        self.nodes = hosts
+            .iter()
+            .take(MAX_TOPOLOGY_NODES)
+            .map(|&(ip, avg_bps)| {
+                let (x, y) = previous.get(&ip).copied().unwrap_or_else(|| initial_position(ip));
+                TopologyNode { ip, x, y, avg_bps }
+            })
+            .collect();
+
+        let index: HashMap<Ipv4Addr, usize> = self.nodes.iter().enumerate().map(|(i, node)| (node.ip, i)).collect();
+        self.edges = links
+            .iter()
+            .filter_map(|(&(a, b), &avg_bps)| Some(TopologyEdge { a: *index.get(&a)?, b: *index.get(&b)?, avg_bps }))
+            .collect();
+        self.edges.sort_by(|a, b| a.avg_bps.total_cmp(&b.avg_bps));
+
+        self.layout();
+        self.laid_out_at = Some(Instant::now());
+    }
+
+    // Fruchterman-Reingold: every pair of nodes pushes apart, every edge pulls its ends together
+    fn layout(&mut self) {
+        let count = self.nodes.len();
+        if count == 0 {
+            return;
+        }
+        // Ideal distance between nodes, short enough that the centre pull keeps them off the border
+        let k = 0.5 * (1.0 / count as f64).sqrt();
+        let mut temperature = INITIAL_TEMPERATURE;
+        for _ in 0..LAYOUT_ITERATIONS {
+            let mut shifts = vec![(0.0, 0.0); count];
+            for a in 0..count {
+                for b in a + 1..count {
+                    let (dx, dy, distance) = self.offset(a, b);
+                    let force = k * k / distance;
+                    shifts[a].0 += dx / distance * force;
+                    shifts[a].1 += dy / distance * force;
+                    shifts[b].0 -= dx / distance * force;
+                    shifts[b].1 -= dy / distance * force;
+                }
+            }
+            for edge in &self.edges {
+                let (dx, dy, distance) = self.offset(edge.a, edge.b);
+                let force = distance * distance / k;
+                shifts[edge.a].0 -= dx / distance * force;
+                shifts[edge.a].1 -= dy / distance * force;
+                shifts[edge.b].0 += dx / distance * force;
+                shifts[edge.b].1 += dy / distance * force;
+            }
+            for (node, (sx, sy)) in self.nodes.iter_mut().zip(shifts) {
+                let sx = sx + (0.5 - node.x) * GRAVITY;
+                let sy = sy + (0.5 - node.y) * GRAVITY;
+                let length = (sx * sx + sy * sy).sqrt();
+                if length > 0.0 {
+                    let step = length.min(temperature);
+                    node.x = (node.x + sx / length * step).clamp(MARGIN, 1.0 - MARGIN);
+                    node.y = (node.y + sy / length * step).clamp(MARGIN, 1.0 - MARGIN);
+                }
+            }
+            temperature *= COOLING;
+        }
+    }
+
+    // From node `b` to node `a`, and the distance, kept off zero so coinciding nodes still separate
+    fn offset(&self, a: usize, b: usize) -> (f64, f64, f64) {
+        let (a, b) = (&self.nodes[a], &self.nodes[b]);
+        let (dx, dy) = (a.x - b.x, a.y - b.y);
+        (dx, dy, (dx * dx + dy * dy).sqrt().max(0.001))
+    }
+}
+
+// Where a host first appears: on a circle round the centre, at an angle picked from its address
+// so the same hosts start from the same picture on every run
+fn initial_position(ip: Ipv4Addr) -> (f64, f64) {
+    const GOLDEN_RATIO: f64 = 0.618_033_988_749_895;
+    let angle = (u32::from(ip) as f64 * GOLDEN_RATIO).fract() * TAU;
+    (0.5 + 0.35 * angle.cos(), 0.5 + 0.35 * angle.sin())
+}
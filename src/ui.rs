@@ -1,42 +1,160 @@
-use std::{io, sync::{Arc, Mutex}, time::{Duration, Instant}};
+use std::{collections::{HashMap, VecDeque}, io::{self, Write}, sync::{atomic::{AtomicBool, Ordering}, mpsc::Sender, Arc}, time::{Duration, Instant}};
 use crossterm::{
-    event::{self, Event, KeyCode},
+    event::{self, DisableMouseCapture, EnableMouseCapture, Event, KeyCode, KeyModifiers, MouseButton, MouseEvent, MouseEventKind},
     execute,
     terminal::{disable_raw_mode, enable_raw_mode, EnterAlternateScreen, LeaveAlternateScreen},
 };
+use chrono::{Local, TimeZone};
+use signal_hook::consts::SIGHUP;
 use ratatui::{
     backend::CrosstermBackend,
-    layout::{Constraint, Direction, Layout},
+    buffer::Buffer,
+    layout::{Constraint, Direction, Layout, Rect},
     style::{Color, Modifier, Style},
     symbols::Marker,
     text::{Line, Span},
     widgets::{
-        canvas::{Canvas, Line as CanvasLine},
-        Block, Borders, Cell, Paragraph, Row, Table,
+        canvas::{Canvas, Context, Line as CanvasLine, Points},
+        Bar, BarChart, BarGroup, Block, Borders, Cell, Clear, Gauge, Paragraph, Row, Sparkline, Table, TableState, Tabs, Widget,
     },
     Terminal,
 };
 
-use crate::app::{App, SharedStats};
-use crate::constants::TICK_RATE_MS;
-use crate::util::{format_bps, format_bytes_total};
+use crate::app::{App, HistoryView, InputMode, InterfaceStats, SharedStats, SortColumn, TopTalker, Traffic, Trend, View, TREND_SAMPLES};
+use crate::columns::Column;
+use crate::constants;
+use crate::dns::DnsResult;
+use crate::history::RateHistory;
+use crate::icmp;
+use crate::network::{CaptureCommand, CaptureHandle};
+use crate::oui::{format_mac, oui_lookup};
+use crate::proc_stats::InterfaceCounters;
+use crate::service::{format_port, resolve_service};
+use crate::session::{EndReason, PROTO_TCP};
+use crate::theme::Theme;
+use crate::util::{dscp_name, format_bytes_total, format_duration, format_hms, format_rate, os_from_ttl, DisplayUnit, GraphStyle};
 
-pub fn run(stats: Arc<Mutex<SharedStats>>, device_name: &str) -> io::Result<()> {
+// RST segments per second at which a session row is highlighted
+const HIGH_RST_RATE: f64 = 1.0;
+// Many tiny packets per second look like a port scan or SYN flood
+const SMALL_PACKET_BYTES: f64 = 100.0;
+const HIGH_PPS: f64 = 100.0;
+// Share of a TCP session's packets that were retransmitted above which its row is highlighted
+const HIGH_RETRANSMIT_RATE: f64 = 0.01;
+// Share of packets marked ECN Congestion Experienced above which the path counts as congested
+const HIGH_ECN_CE_RATE: f64 = 0.01;
+// DSCP codepoints listed in the stats panel and in the detail popup
+const DSCP_SUMMARY_CLASSES: usize = 2;
+const DSCP_DETAIL_CLASSES: usize = 4;
+
+// Top border plus the header row of the table
+const TABLE_HEADER_HEIGHT: u16 = 2;
+
+// The Trend sparkline only fits on terminals wider than this
+const WIDE_TERMINAL_COLS: u16 = 180;
+
+// How long the "[Stats reset at ...]" and "[Tick ...]" notices stay in the status bar
+const NOTICE_TIME: Duration = Duration::from_secs(3);
+// The `y` confirmation is shorter, as it is usually pasted right away
+const COPY_NOTICE_TIME: Duration = Duration::from_secs(2);
+// A kernel pre-filter that failed to install only costs CPU, so it is reported once, for a while
+const PREFILTER_NOTICE_TIME: Duration = Duration::from_secs(10);
+
+// Every keybinding, as listed by the `?` help popup
+const HELP_KEYS: &[(&str, &str)] = &[
+    ("q / Ctrl+C", "Quit"),
+    ("?", "Show this help"),
+    ("Space", "Pause/resume statistics"),
+    ("Tab", "Next interface tab"),
+    ("S", "Toggle hosts/sessions table"),
+    ("D", "Sort sessions by duration or bandwidth"),
+    ("1-6", "Sort by average, peak, total, IP, hostname or avg packet size; again to reverse"),
+    ("s", "Cycle the sort column"),
+    ("Up / Down", "Move the selection"),
+    ("PageUp / PageDown", "Scroll the table by a page"),
+    ("[ / ]", "Halve/double the sampling interval"),
+    ("+ / -", "Show more/fewer table rows"),
+    ("a", "ARP table"),
+    ("d", "Details of the selected IP"),
+    ("u", "Cycle bits/s, bytes/s and packets/s"),
+    ("U", "Cycle KiB, KB and Kb for byte totals"),
+    ("M", "Toggle hosts/multicast groups table"),
+    ("i", "Toggle hosts/ICMP table"),
+    ("c", "Toggle hosts/closed sessions table"),
+    ("p", "Toggle hosts/top ports table"),
+    ("o", "Group the ports table by destination or source port"),
+    ("P", "Switch promiscuous mode on the live captures"),
+    ("t", "Toggle hosts/topology map"),
+    ("m", "Show/hide MAC and vendor columns"),
+    ("T", "Show/hide the Total column"),
+    ("C", "Show/hide the Conn (active connections) column"),
+    ("w", "Add/remove the selected IP on the watchlist"),
+    ("y", "Copy the selected IP to the clipboard"),
+    ("l", "Toggle linear/logarithmic graphs"),
+    ("r", "Flush the DNS cache"),
+    ("R", "Reset peaks, totals and graphs"),
+    ("/", "Search by IP or hostname; Enter keeps, Esc clears"),
+    (":", "Set a BPF filter"),
+    ("h", "Query the --history-db history"),
+    ("Left click", "Select a row"),
+    ("Right click", "Open the details of a row"),
+];
+
+// The status bar warns once the p95 frame takes more than this share of a tick
+const SLOW_RENDER_FRACTION: f64 = 0.8;
+// Rows of the `--debug-perf` render time sparkline, border included
+const PERF_HEIGHT: u16 = 3;
+// Rows of the top box: two halves of seven stats lines plus a utilization gauge each, and the border
+const NET_BOX_HEIGHT: u16 = 18;
+
+// `draw_to_stderr` keeps stdout free for piped output such as `--log-json -`
+// `terminate` is set by SIGINT and SIGTERM and ends the loop like `q`, so the terminal is restored and the logs flushed
+pub fn run(mut app: App, captures: Vec<CaptureHandle>, draw_to_stderr: bool, terminate: &AtomicBool) -> io::Result<()> {
     // Initialize terminal
     enable_raw_mode()?;
-    let mut stdout = io::stdout();
-    execute!(stdout, EnterAlternateScreen)?;
-    let backend = CrosstermBackend::new(stdout);
+    let mut out: Box<dyn Write> = if draw_to_stderr { Box::new(io::stderr()) } else { Box::new(io::stdout()) };
+    execute!(out, EnterAlternateScreen, EnableMouseCapture)?;
+    let backend = CrosstermBackend::new(out);
     let mut terminal = Terminal::new(backend)?;
 
-    let app = App::new();
-    let res = run_app_loop(&mut terminal, app, stats, device_name);
+    // SIGHUP re-reads the `--theme` file without restarting
+    let reload_theme = Arc::new(AtomicBool::new(false));
+    signal_hook::flag::register(SIGHUP, Arc::clone(&reload_theme))?;
+
+    let stats: Vec<Arc<SharedStats>> = captures.iter().map(|c| Arc::clone(&c.stats)).collect();
+    let command_senders: Vec<Sender<CaptureCommand>> = captures.into_iter().map(|c| c.commands).collect();
+
+    let res = run_app_loop(&mut terminal, &mut app, stats, command_senders, &reload_theme, terminate);
+
+    if let Some(log) = app.csv_log.as_mut() {
+        let _ = log.flush();
+    }
+    if let Some(log) = app.json_log.take() {
+        log.close();
+    }
+    if let Some(report) = app.html_report.take() {
+        report.close(app.report_snapshot());
+    }
+    if let Some(db) = app.history_db.take() {
+        db.close();
+    }
+    if let Some(influx) = app.influx.take() {
+        influx.close();
+    }
 
     // Cleanup
     disable_raw_mode()?;
-    execute!(terminal.backend_mut(), LeaveAlternateScreen)?;
+    execute!(terminal.backend_mut(), LeaveAlternateScreen, DisableMouseCapture)?;
     terminal.show_cursor()?;
 
+    // Bookmarks added with `w` are written back to the `--watchlist` file
+    if let Err(err) = app.save_watchlist() {
+        eprintln!("Failed to save watchlist: {}", err);
+    }
+    if let Err(err) = app.save_snapshot() {
+        eprintln!("Failed to save snapshot: {}", err);
+    }
+
     if let Err(err) = res {
         println!("Error: {:?}", err)
     }
@@ -45,183 +163,1401 @@ pub fn run(stats: Arc<Mutex<SharedStats>>, device_name: &str) -> io::Result<()>
 
 fn run_app_loop<B: ratatui::backend::Backend>(
     terminal: &mut Terminal<B>,
-    mut app: App,
-    stats: Arc<Mutex<SharedStats>>,
-    device_name: &str,
+    app: &mut App,
+    stats: Vec<Arc<SharedStats>>,
+    command_senders: Vec<Sender<CaptureCommand>>,
+    reload_theme: &AtomicBool,
+    terminate: &AtomicBool,
 ) -> io::Result<()> {
-    let tick_rate = Duration::from_millis(TICK_RATE_MS);
+    // Where the table was last drawn, for mapping mouse clicks to rows
+    let mut table_area = Rect::default();
+    let mut redraw = true;
+    let mut last_draw = Instant::now();
 
     loop {
-        terminal.draw(|f| {
-            // ============= whole screen layout ============
-            let main_chunks = Layout::default()
-                .direction(Direction::Vertical)
-                .margin(0)
-                .constraints([
-                    Constraint::Length(16), // Upside Net Box
-                    Constraint::Min(10),    // Middle Table
-                    Constraint::Length(1),  // Bottom Status Bar
-                ].as_ref())
-                .split(f.size());
-
-            // ============= Top Net Monitor Box ============
-            let net_block = Block::default()
-                .borders(Borders::ALL)
-                .title(format!(" Net Monitor [{}] ", device_name))
-                .border_type(ratatui::widgets::BorderType::Rounded)
-                .border_style(Style::default().fg(Color::Cyan));
-            f.render_widget(net_block.clone(), main_chunks[0]);
-
-            let inner_area = net_block.inner(main_chunks[0]);
-            let graph_chunks = Layout::default()
-                .direction(Direction::Horizontal)
-                .constraints([Constraint::Percentage(75), Constraint::Percentage(25)].as_ref())
-                .split(inner_area);
-
-            // ======== Left Graphs (Download/Upload) ========
-            let chart_chunks = Layout::default()
-                .direction(Direction::Vertical)
-                .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
-                .split(graph_chunks[0]);
-
-            let max_rx = app.rx_history.iter().cloned().fold(100.0, f64::max);
-            let max_tx = app.tx_history.iter().cloned().fold(100.0, f64::max);
-            let x_limit = app.rx_history.len() as f64;
-
-            // Download Canvas
-            let download_canvas = Canvas::default()
-                .block(Block::default().title(" Download ").title_style(Style::default().fg(Color::Red)))
-                .marker(Marker::Braille)
-                .x_bounds([0.0, x_limit])
-                .y_bounds([0.0, max_rx])
-                .paint(|ctx| {
-                    for (i, &val) in app.rx_history.iter().enumerate() {
-                        ctx.draw(&CanvasLine {
-                            x1: i as f64,
-                            y1: 0.0,
-                            x2: i as f64,
-                            y2: val,
-                            color: Color::Red,
-                        });
-                    }
-                });
-            f.render_widget(download_canvas, chart_chunks[0]);
-
-            // Upload Canvas
-            let upload_canvas = Canvas::default()
-                .block(Block::default().title(" Upload ").title_style(Style::default().fg(Color::Blue)))
-                .marker(Marker::Braille)
-                .x_bounds([0.0, x_limit])
-                .y_bounds([0.0, max_tx])
-                .paint(|ctx| {
-                    for (i, &val) in app.tx_history.iter().enumerate() {
-                        ctx.draw(&CanvasLine {
-                            x1: i as f64,
-                            y1: 0.0,
-                            x2: i as f64,
-                            y2: val,
-                            color: Color::Blue,
-                        });
-                    }
-                });
-            f.render_widget(upload_canvas, chart_chunks[1]);
-
-            // textual stats on the right
-            let text_chunks = Layout::default()
-                .direction(Direction::Vertical)
-                .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
-                .split(graph_chunks[1]);
-
-            let current_rx_bps = (*app.rx_history.last().unwrap_or(&0.0)) * (1000.0 / TICK_RATE_MS as f64);
-            let current_tx_bps = (*app.tx_history.last().unwrap_or(&0.0)) * (1000.0 / TICK_RATE_MS as f64);
-            
-            let peak_rx_bps = app.peak_rx_record.0;
-            let peak_tx_bps = app.peak_tx_record.0;
-
-            let rx_text = vec![
-                Line::from(vec![Span::raw("▼ "), Span::styled(format_bps(current_rx_bps), Style::default().fg(Color::White).add_modifier(Modifier::BOLD))]),
-                Line::from(vec![Span::styled("  Peak: ", Style::default().fg(Color::DarkGray)), Span::raw(format_bps(peak_rx_bps))]),
-                Line::from(vec![Span::styled("  Tot:  ", Style::default().fg(Color::DarkGray)), Span::raw(format_bytes_total(app.total_rx_bytes))]),
-            ];
-            f.render_widget(Paragraph::new(rx_text).block(Block::default().style(Style::default().fg(Color::Red))), text_chunks[0]);
-
-            let tx_text = vec![
-                Line::from(vec![Span::raw("▲ "), Span::styled(format_bps(current_tx_bps), Style::default().fg(Color::White).add_modifier(Modifier::BOLD))]),
-                Line::from(vec![Span::styled("  Peak: ", Style::default().fg(Color::DarkGray)), Span::raw(format_bps(peak_tx_bps))]),
-                Line::from(vec![Span::styled("  Tot:  ", Style::default().fg(Color::DarkGray)), Span::raw(format_bytes_total(app.total_tx_bytes))]),
-            ];
-            f.render_widget(Paragraph::new(tx_text).block(Block::default().style(Style::default().fg(Color::Blue))), text_chunks[1]);
-
-            // ============= Middle Top Talkers Table ============
-            let header_cells = ["IP Address", "Avg Bandwidth", "Peak Rate", "Peak Time", "Status"]
-                .iter()
-                .map(|h| Cell::from(*h).style(Style::default().fg(Color::Yellow).add_modifier(Modifier::BOLD)));
-            let header = Row::new(header_cells)
-                .style(Style::default().bg(Color::Rgb(40, 40, 40)))
-                .height(1)
-                .bottom_margin(0);
-
-            let rows = app.top_talkers.iter().take(25).map(|(ip, avg_bps, peak_bps, peak_time)| {
-                let avg_color = if *avg_bps > 1_000_000.0 { Color::Red } else if *avg_bps > 100_000.0 { Color::LightYellow } else { Color::Green };
-                let peak_color = if *peak_bps > 1_000_000.0 { Color::Magenta } else { Color::Cyan };
-
-                Row::new(vec![
-                    Cell::from(ip.to_string()),
-                    Cell::from(format_bps(*avg_bps)).style(Style::default().fg(avg_color)),
-                    Cell::from(format_bps(*peak_bps)).style(Style::default().fg(peak_color)),
-                    Cell::from(peak_time.format("%H:%M:%S").to_string()).style(Style::default().fg(Color::DarkGray)),
-                    Cell::from("Active"),
-                ]).height(1)
-            });
-
-            let table = Table::new(
-                rows,
-                [
-                    Constraint::Percentage(20),
-                    Constraint::Percentage(20),
-                    Constraint::Percentage(20),
-                    Constraint::Percentage(20),
-                    Constraint::Percentage(20),
-                ]
-            )
-            .header(header)
-            .block(Block::default().title(" Local Network Traffic ").borders(Borders::ALL).border_type(ratatui::widgets::BorderType::Rounded));
-            f.render_widget(table, main_chunks[1]);
-
-            // ============ Bottom Status Bar ============
-            let global_rx_time = app.peak_rx_record.1.format("%H:%M:%S").to_string();
-            let global_tx_time = app.peak_tx_record.1.format("%H:%M:%S").to_string();
-
-            let status_content = Line::from(vec![
-                Span::styled(" GLOBAL RECORDS ", Style::default().bg(Color::White).fg(Color::Black).add_modifier(Modifier::BOLD)),
-                Span::raw(" | "),
-                Span::styled("MAX RX: ", Style::default().fg(Color::Red).add_modifier(Modifier::BOLD)),
-                Span::raw(format!("{} ", format_bps(app.peak_rx_record.0))),
-                Span::styled(format!("(@{})", global_rx_time), Style::default().fg(Color::DarkGray)),
-                Span::raw(" | "),
-                Span::styled("MAX TX: ", Style::default().fg(Color::Blue).add_modifier(Modifier::BOLD)),
-                Span::raw(format!("{} ", format_bps(app.peak_tx_record.0))),
-                Span::styled(format!("(@{})", global_tx_time), Style::default().fg(Color::DarkGray)),
-                Span::raw(" | Press 'q' to quit"),
-            ]);
-
-            let status_bar = Paragraph::new(status_content)
-                .style(Style::default().bg(Color::Rgb(20, 20, 20)));
-            f.render_widget(status_bar, main_chunks[2]);
-        })?;
+        if terminate.load(Ordering::Relaxed) {
+            return Ok(());
+        }
+        // Re-read every pass, `[` and `]` change it
+        let tick_rate = constants::tick_rate();
+        let ui_rate = app.ui_tick;
+        if reload_theme.swap(false, Ordering::Relaxed) {
+            app.reload_theme();
+            redraw = true;
+        }
+        // Redrawn at the UI rate, after every tick without `--ui-tick`, and straight after input
+        if redraw || ui_rate.is_some_and(|rate| last_draw.elapsed() >= rate) {
+            let mut visible_rows = app.visible_rows;
+            let mut help_max_scroll = app.help_scroll;
+            let render_started = Instant::now();
+            terminal.draw(|f| draw(f, app, &mut table_area, &mut visible_rows, &mut help_max_scroll))?;
+            app.visible_rows = visible_rows;
+            // Scrolling stops once the last binding is on screen
+            app.help_scroll = app.help_scroll.min(help_max_scroll);
+            app.record_render(render_started.elapsed());
+            last_draw = Instant::now();
+            redraw = false;
+        }
 
-        // Handle input
-        let timeout = tick_rate.checked_sub(app.last_tick.elapsed()).unwrap_or_else(|| Duration::from_secs(0));
+        // Handle input, waking up for whichever of the next tick and the next draw comes first
+        let mut timeout = tick_rate.saturating_sub(app.last_tick.elapsed());
+        if let Some(rate) = ui_rate {
+            timeout = timeout.min(rate.saturating_sub(last_draw.elapsed()));
+        }
         if crossterm::event::poll(timeout)? {
-            if let Event::Key(key) = event::read()? {
-                if key.code == KeyCode::Char('q') || key.code == KeyCode::Char('c') {
+            let event = event::read()?;
+            redraw = true;
+            if let Event::Mouse(MouseEvent { kind: MouseEventKind::Down(button), row, column, .. }) = event {
+                // Rows start below the top border and the header row
+                let inside = column > table_area.x && column < table_area.right().saturating_sub(1)
+                    && row >= table_area.y + TABLE_HEADER_HEIGHT && row < table_area.bottom().saturating_sub(1);
+                if inside && app.input_mode == InputMode::Normal {
+                    let index = app.table_offset + (row - table_area.y - TABLE_HEADER_HEIGHT) as usize;
+                    if app.select_row(index) && button == MouseButton::Right {
+                        app.show_detail = app.selected_talker().is_some();
+                    }
+                }
+            }
+            if let Event::Key(key) = event {
+                if key.code == KeyCode::Char('c') && key.modifiers.contains(KeyModifiers::CONTROL) {
                     return Ok(());
                 }
+                if app.input_mode == InputMode::BpfPrompt {
+                    match key.code {
+                        KeyCode::Enter => {
+                            let filter = std::mem::take(&mut app.input_buffer);
+                            for commands in &command_senders {
+                                let _ = commands.send(CaptureCommand::SetBpf(filter.trim().to_string()));
+                            }
+                            app.input_mode = InputMode::Normal;
+                        }
+                        KeyCode::Esc => {
+                            app.input_buffer.clear();
+                            app.input_mode = InputMode::Normal;
+                        }
+                        KeyCode::Backspace => {
+                            app.input_buffer.pop();
+                        }
+                        KeyCode::Char(c) => app.input_buffer.push(c),
+                        _ => {}
+                    }
+                    continue;
+                }
+                if app.input_mode == InputMode::HistoryQuery {
+                    match key.code {
+                        KeyCode::Enter => {
+                            let query = std::mem::take(&mut app.input_buffer);
+                            app.run_history_query(&query);
+                            app.input_mode = InputMode::Normal;
+                        }
+                        KeyCode::Esc => {
+                            app.input_buffer.clear();
+                            app.input_mode = InputMode::Normal;
+                        }
+                        KeyCode::Backspace => {
+                            app.input_buffer.pop();
+                        }
+                        KeyCode::Char(c) => app.input_buffer.push(c),
+                        _ => {}
+                    }
+                    continue;
+                }
+                if app.history_view.is_some() {
+                    match key.code {
+                        KeyCode::Esc | KeyCode::Char('h') => app.history_view = None,
+                        KeyCode::Char('q') => return Ok(()),
+                        _ => {}
+                    }
+                    continue;
+                }
+                if app.input_mode == InputMode::Search {
+                    // The table follows every keystroke; Enter keeps the filter, Esc clears it
+                    match key.code {
+                        KeyCode::Enter => app.input_mode = InputMode::Normal,
+                        KeyCode::Esc => {
+                            app.set_filter_query(String::new());
+                            app.input_mode = InputMode::Normal;
+                        }
+                        KeyCode::Backspace => {
+                            let mut query = app.filter_query.clone();
+                            query.pop();
+                            app.set_filter_query(query);
+                        }
+                        KeyCode::Char(c) => {
+                            let query = format!("{}{}", app.filter_query, c);
+                            app.set_filter_query(query);
+                        }
+                        _ => {}
+                    }
+                    continue;
+                }
+                if app.show_help {
+                    match key.code {
+                        KeyCode::Up => app.help_scroll = app.help_scroll.saturating_sub(1),
+                        KeyCode::Down => app.help_scroll = app.help_scroll.saturating_add(1),
+                        KeyCode::PageUp => app.help_scroll = app.help_scroll.saturating_sub(10),
+                        KeyCode::PageDown => app.help_scroll = app.help_scroll.saturating_add(10),
+                        _ => app.show_help = false,
+                    }
+                    continue;
+                }
+                if app.show_arp {
+                    match key.code {
+                        KeyCode::Esc | KeyCode::Char('a') => app.show_arp = false,
+                        KeyCode::Char('q') => return Ok(()),
+                        _ => {}
+                    }
+                    continue;
+                }
+                if app.show_detail {
+                    match key.code {
+                        KeyCode::Esc | KeyCode::Char('d') => app.show_detail = false,
+                        KeyCode::Char('q') => return Ok(()),
+                        _ => {}
+                    }
+                    continue;
+                }
+                match key.code {
+                    KeyCode::Char('q') => return Ok(()),
+                    KeyCode::Char('d') => app.show_detail = app.selected_talker().is_some(),
+                    KeyCode::Char('a') => app.show_arp = true,
+                    KeyCode::Char('?') => {
+                        app.show_help = true;
+                        app.help_scroll = 0;
+                    }
+                    KeyCode::Char('/') => app.input_mode = InputMode::Search,
+                    KeyCode::Esc => app.set_filter_query(String::new()),
+                    KeyCode::Char(':') => {
+                        app.input_buffer = app.active_bpf.clone().unwrap_or_default();
+                        app.input_mode = InputMode::BpfPrompt;
+                    }
+                    KeyCode::Char('h') => {
+                        // Prefill the selected talker, the usual thing to look up
+                        app.input_buffer = app.selected_talker().map(|talker| format!("{} last 1h", talker.ip)).unwrap_or_default();
+                        app.history_error = None;
+                        app.input_mode = InputMode::HistoryQuery;
+                    }
+                    KeyCode::Char(' ') => app.toggle_pause(&stats),
+                    KeyCode::Tab => app.next_tab(),
+                    KeyCode::Char('S') => app.toggle_view(),
+                    KeyCode::Char('D') => app.toggle_session_sort(),
+                    KeyCode::Char('s') => app.cycle_sort(),
+                    KeyCode::Char(c @ '1'..='6') => app.set_sort(SortColumn::ALL[c as usize - '1' as usize]),
+                    KeyCode::Char('r') => app.flush_dns_cache(),
+                    KeyCode::Char('R') => app.reset_peaks(),
+                    KeyCode::Char('M') => app.toggle_multicast(),
+                    KeyCode::Char('i') => app.toggle_icmp(),
+                    KeyCode::Char('c') => app.toggle_closed_sessions(),
+                    KeyCode::Char('p') => app.toggle_ports(),
+                    KeyCode::Char('o') => app.toggle_port_side(),
+                    KeyCode::Char('t') => app.toggle_topology(),
+                    // The title follows once the captures report back
+                    KeyCode::Char('P') => {
+                        if let Some(promisc) = app.toggled_promisc() {
+                            for commands in &command_senders {
+                                let _ = commands.send(CaptureCommand::SetPromisc(promisc));
+                            }
+                        }
+                    }
+                    KeyCode::Char('m') => app.toggle_column(Column::Mac),
+                    KeyCode::Char('l') => app.log_scale = !app.log_scale,
+                    KeyCode::Char('T') => app.toggle_column(Column::Total),
+                    KeyCode::Char('C') => app.toggle_column(Column::Conn),
+                    KeyCode::Char('w') => app.toggle_watch(),
+                    KeyCode::Char('y') => app.copy_selected_ip(),
+                    KeyCode::Char('u') => app.display_unit = app.display_unit.next(),
+                    KeyCode::Char('U') => app.total_unit = app.total_unit.next(),
+                    KeyCode::Char('+') | KeyCode::Char('=') => app.grow_display_limit(),
+                    KeyCode::Char('-') => app.shrink_display_limit(),
+                    KeyCode::Char('[') => app.faster_tick(),
+                    KeyCode::Char(']') => app.slower_tick(),
+                    KeyCode::Up => app.move_selection(-1),
+                    KeyCode::Down => app.move_selection(1),
+                    KeyCode::PageUp => app.move_selection(-app.page_size()),
+                    KeyCode::PageDown => app.move_selection(app.page_size()),
+                    _ => {}
+                }
             }
         }
         if app.last_tick.elapsed() >= tick_rate {
             app.on_tick(&stats);
             app.last_tick = Instant::now();
+            redraw |= ui_rate.is_none();
+        }
+    }
+}
+
+// One frame of the whole screen. Reports back where the table went and how many rows it has,
+// for mouse clicks and scrolling, and how far the help popup can scroll
+fn draw(f: &mut ratatui::Frame, app: &App, table_area: &mut Rect, visible_rows: &mut usize, help_max_scroll: &mut u16) {
+    let theme = Arc::clone(&app.theme);
+
+    // ============= whole screen layout ============
+    let main_chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .margin(0)
+        .constraints([
+            Constraint::Length(1),  // Interface Tabs
+            Constraint::Length(if app.debug_perf { NET_BOX_HEIGHT + PERF_HEIGHT } else { NET_BOX_HEIGHT }), // Upside Net Box
+            Constraint::Min(10),    // Middle Table
+            Constraint::Length(1),  // Bottom Status Bar
+            Constraint::Length(app.alerts.len().min(3) as u16), // Recent Alerts
+        ].as_ref())
+        .split(f.size());
+
+    let iface = app.current();
+
+    // ============= Interface Tabs ============
+    let tab_titles: Vec<Line> = app.interfaces.iter().map(|i| Line::from(format!(" {} ", i.name))).collect();
+    let tabs = Tabs::new(tab_titles)
+        .select(app.active_tab)
+        .style(Style::default().fg(theme.muted))
+        .highlight_style(Style::default().fg(theme.accent).add_modifier(Modifier::BOLD))
+        .divider("|");
+    f.render_widget(tabs, main_chunks[0]);
+
+    // ============= Top Net Monitor Box ============
+    let net_block = Block::default()
+        .borders(Borders::ALL)
+        .title(if iface.finished {
+            format!(" Net Monitor [{}] [REPLAY COMPLETE] ", iface.display_name())
+        } else if !iface.devices.is_empty() {
+            format!(" Net Monitor [{}]{} {} ", iface.display_name(), promisc_tag(iface), device_summary(app, iface))
+        } else {
+            format!(" Net Monitor [{}]{} ", iface.display_name(), promisc_tag(iface))
+        })
+        .border_type(ratatui::widgets::BorderType::Rounded)
+        .border_style(Style::default().fg(theme.accent));
+    let (net_area, perf_area) = if app.debug_perf {
+        let chunks = Layout::default()
+            .direction(Direction::Vertical)
+            .constraints([Constraint::Min(0), Constraint::Length(PERF_HEIGHT)])
+            .split(main_chunks[1]);
+        (chunks[0], Some(chunks[1]))
+    } else {
+        (main_chunks[1], None)
+    };
+    f.render_widget(net_block.clone(), net_area);
+    if let Some(area) = perf_area {
+        render_perf(f, app, area);
+    }
+
+    let inner_area = net_block.inner(net_area);
+    let graph_chunks = Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([Constraint::Percentage(75), Constraint::Percentage(25)].as_ref())
+        .split(inner_area);
+
+    // ======== Left Graphs (Download/Upload) ========
+    let chart_chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
+        .split(graph_chunks[0]);
+
+    // Graphs and records follow the display unit: packet counts for packets/s, bytes otherwise
+    let unit = app.display_unit;
+    let packets = unit == DisplayUnit::Packets;
+    let (rx_history, tx_history) = if packets {
+        (&iface.rx_packet_history, &iface.tx_packet_history)
+    } else {
+        (&iface.rx_history, &iface.tx_history)
+    };
+    let (peak_rx_record, peak_tx_record) = if packets {
+        (iface.peak_rx_pps_record, iface.peak_tx_pps_record)
+    } else {
+        (iface.peak_rx_record, iface.peak_tx_record)
+    };
+
+    let floor = if packets { 10.0 } else { 100.0 };
+    let x_limit = rx_history.len() as f64;
+    // On a log scale samples are plotted as log2(1 + count), so an idle tick stays at zero
+    let scale = |value: f64| if app.log_scale { value.ln_1p() / std::f64::consts::LN_2 } else { value };
+    let rx_plot: VecDeque<f64> = rx_history.iter().map(|&value| scale(value)).collect();
+    let tx_plot: VecDeque<f64> = tx_history.iter().map(|&value| scale(value)).collect();
+    // The log transform keeps the order of samples, so the largest one stays on top
+    let max_rx = scale(rx_history.max().max(floor));
+    let max_tx = scale(tx_history.max().max(floor));
+    // Samples are counts per tick; tick labels are rates in the display unit
+    let per_sample = app.config.per_second(1) * if unit == DisplayUnit::Bits { 8.0 } else { 1.0 };
+
+    // Download Canvas
+    let download_canvas = Canvas::default()
+        .block(Block::default().title(" Download ").title_style(Style::default().fg(theme.rx_color)))
+        .marker(Marker::Braille)
+        .x_bounds([0.0, x_limit])
+        .y_bounds([0.0, max_rx])
+        .paint(|ctx| {
+            paint_history(ctx, &rx_plot, app.graph_style, theme.rx_color, max_rx / graph_rows(chart_chunks[0]));
+            if app.log_scale {
+                paint_log_ticks(ctx, max_rx, per_sample, theme.muted);
+            }
+        });
+    f.render_widget(download_canvas, chart_chunks[0]);
+
+    // Upload Canvas
+    let upload_canvas = Canvas::default()
+        .block(Block::default().title(" Upload ").title_style(Style::default().fg(theme.tx_color)))
+        .marker(Marker::Braille)
+        .x_bounds([0.0, x_limit])
+        .y_bounds([0.0, max_tx])
+        .paint(|ctx| {
+            paint_history(ctx, &tx_plot, app.graph_style, theme.tx_color, max_tx / graph_rows(chart_chunks[1]));
+            if app.log_scale {
+                paint_log_ticks(ctx, max_tx, per_sample, theme.muted);
+            }
+        });
+    f.render_widget(upload_canvas, chart_chunks[1]);
+
+    // textual stats on the right
+    let text_chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Percentage(50), Constraint::Percentage(50)].as_ref())
+        .split(graph_chunks[1]);
+
+    let current_rx = app.config.per_second(rx_history.current() as u64);
+    let current_tx = app.config.per_second(tx_history.current() as u64);
+    // Packet rate under the headline figure, or bandwidth when packets/s is already the headline
+    let secondary = |bytes: &RateHistory, packets: &RateHistory| {
+        let (history, unit) = if unit == DisplayUnit::Packets { (bytes, DisplayUnit::Bits) } else { (packets, DisplayUnit::Packets) };
+        format_rate(app.config.per_second(history.current() as u64), unit, app.rate_base)
+    };
+
+    // Kernel error and drop counts, red on a tick where they went up
+    let counter_line = |errs: fn(&InterfaceCounters) -> u64, drops: fn(&InterfaceCounters) -> u64| {
+        let Some(counters) = iface.counters else {
+            return Line::from(vec![Span::styled("  ERR: ", Style::default().fg(theme.muted)), Span::raw("N/A")]);
+        };
+        let style = |field| {
+            if iface.counter_rose(field) { Style::default().fg(theme.high_traffic).add_modifier(Modifier::BOLD) } else { Style::default() }
+        };
+        Line::from(vec![
+            Span::styled("  ERR: ", Style::default().fg(theme.muted)),
+            Span::styled(errs(&counters).to_string(), style(errs)),
+            Span::styled("  DROP: ", Style::default().fg(theme.muted)),
+            Span::styled(drops(&counters).to_string(), style(drops)),
+        ])
+    };
+
+    // Busiest DSCP classes by share of the bytes, and the ECN CE share of the packets
+    let dscp_total = iface.dscp_bytes.values().sum::<u64>().max(1);
+    let dscp_summary: Vec<String> = top_dscp(&iface.dscp_bytes, DSCP_SUMMARY_CLASSES)
+        .into_iter()
+        .map(|(dscp, bytes)| format!("{} {}%", dscp_name(dscp), bytes * 100 / dscp_total))
+        .collect();
+    let ecn_ce_rate = iface.ecn_ce_packets as f64 / iface.total_packets.max(1) as f64;
+    let ecn_style = if ecn_ce_rate > HIGH_ECN_CE_RATE { Style::default().fg(theme.warning) } else { Style::default() };
+
+    let mut rx_text = vec![
+        Line::from(vec![Span::raw("▼ "), Span::styled(format_rate(current_rx, unit, app.rate_base), Style::default().fg(theme.text).add_modifier(Modifier::BOLD))]),
+        Line::from(vec![Span::raw("  "), Span::styled(secondary(&iface.rx_history, &iface.rx_packet_history), Style::default().fg(theme.muted))]),
+        Line::from(vec![Span::styled("  Peak: ", Style::default().fg(theme.muted)), Span::raw(format_rate(peak_rx_record.0, unit, app.rate_base))]),
+        Line::from(vec![Span::styled("  Tot:  ", Style::default().fg(theme.muted)), Span::raw(format_bytes_total(iface.total_rx_bytes, app.total_unit))]),
+        Line::from(vec![Span::styled("  Bcast: ", Style::default().fg(theme.muted)), Span::raw(app.rate(app.config.per_second(iface.current_broadcast.bytes), app.config.per_second(iface.current_broadcast.packets)))]),
+        counter_line(|counters| counters.rx_errs, |counters| counters.rx_drop),
+        Line::from(vec![Span::styled("  DSCP: ", Style::default().fg(theme.muted)), Span::raw(if dscp_summary.is_empty() { "-".to_string() } else { dscp_summary.join(" ") })]),
+    ];
+    let utilization = iface.utilization();
+    let rx_chunks = gauge_split(text_chunks[0], utilization.is_some());
+    match utilization {
+        Some((rx_percent, _)) => f.render_widget(utilization_gauge(&theme, "RX", rx_percent), rx_chunks[1]),
+        None => rx_text.push(Line::from(vec![Span::styled("  Util: ", Style::default().fg(theme.muted)), Span::raw("N/A")])),
+    }
+    f.render_widget(Paragraph::new(rx_text).block(Block::default().style(Style::default().fg(theme.rx_color))), rx_chunks[0]);
+
+    let mut tx_text = vec![
+        Line::from(vec![Span::raw("▲ "), Span::styled(format_rate(current_tx, unit, app.rate_base), Style::default().fg(theme.text).add_modifier(Modifier::BOLD))]),
+        Line::from(vec![Span::raw("  "), Span::styled(secondary(&iface.tx_history, &iface.tx_packet_history), Style::default().fg(theme.muted))]),
+        Line::from(vec![Span::styled("  Peak: ", Style::default().fg(theme.muted)), Span::raw(format_rate(peak_tx_record.0, unit, app.rate_base))]),
+        Line::from(vec![Span::styled("  Tot:  ", Style::default().fg(theme.muted)), Span::raw(format_bytes_total(iface.total_tx_bytes, app.total_unit))]),
+        Line::from(vec![Span::styled("  Mcast: ", Style::default().fg(theme.muted)), Span::raw(app.rate(app.config.per_second(iface.current_multicast.bytes), app.config.per_second(iface.current_multicast.packets)))]),
+        counter_line(|counters| counters.tx_errs, |counters| counters.tx_drop),
+        Line::from(vec![Span::styled("  ECN CE: ", Style::default().fg(theme.muted)), Span::styled(format!("{:.2}%", ecn_ce_rate * 100.0), ecn_style)]),
+    ];
+    let tx_chunks = gauge_split(text_chunks[1], utilization.is_some());
+    match utilization {
+        Some((_, tx_percent)) => f.render_widget(utilization_gauge(&theme, "TX", tx_percent), tx_chunks[1]),
+        None => tx_text.push(Line::from(vec![Span::styled("  Util: ", Style::default().fg(theme.muted)), Span::raw("N/A")])),
+    }
+    f.render_widget(Paragraph::new(tx_text).block(Block::default().style(Style::default().fg(theme.tx_color))), tx_chunks[0]);
+
+    // ============= Middle Table (Top Talkers / Sessions) ============
+    // Table area minus borders and header row, capped by the display limit
+    *visible_rows = (main_chunks[2].height.saturating_sub(3) as usize).min(app.display_limit);
+    *table_area = main_chunks[2];
+
+    if app.view == View::Topology {
+        f.render_widget(topology_map(app), main_chunks[2]);
+    } else {
+        let table = match app.view {
+            View::Talkers => talkers_table(app, iface, main_chunks[2].width > WIDE_TERMINAL_COLS),
+            View::Sessions => sessions_table(app),
+            View::Multicast => multicast_table(app),
+            View::Icmp => icmp_table(app),
+            View::ClosedSessions => closed_sessions_table(app),
+            View::Ports => ports_table(app),
+            View::Topology => unreachable!("the topology view is a map, not a table"),
+        }
+        .highlight_style(Style::default().bg(theme.selection_bg).add_modifier(Modifier::BOLD));
+
+        let mut table_state = TableState::default()
+            .with_offset(app.table_offset)
+            .with_selected(app.selected_row);
+        f.render_stateful_widget(table, main_chunks[2], &mut table_state);
+    }
+
+    // ============ Bottom Status Bar ============
+    let global_rx_time = peak_rx_record.1.format("%H:%M:%S").to_string();
+    let global_tx_time = peak_tx_record.1.format("%H:%M:%S").to_string();
+
+    let capture_badge = if app.paused {
+        Span::styled(" [PAUSED] ", Style::default().bg(theme.warning).fg(theme.status_bg).add_modifier(Modifier::BOLD | Modifier::SLOW_BLINK))
+    } else {
+        Span::styled(" [LIVE] ", Style::default().fg(theme.low_traffic).add_modifier(Modifier::BOLD))
+    };
+
+    let mut status_spans = vec![capture_badge];
+    if let Some((time, at)) = app.stats_reset_at {
+        if at.elapsed() < NOTICE_TIME {
+            status_spans.push(Span::styled(
+                format!("[Stats reset at {}] ", time.format("%H:%M:%S")),
+                Style::default().fg(theme.accent).add_modifier(Modifier::BOLD),
+            ));
+        }
+    }
+    // Frames taking most of a tick leave little time for input and the capture threads
+    if let Some(p95) = app.render_p95().filter(|p95| p95.as_secs_f64() > constants::tick_rate().as_secs_f64() * SLOW_RENDER_FRACTION) {
+        status_spans.push(Span::styled(
+            format!("[Render slow: {}ms p95] ", p95.as_millis()),
+            Style::default().fg(theme.warning).add_modifier(Modifier::BOLD),
+        ));
+    }
+    if let Some((notice, _)) = app.copy_notice.as_ref().filter(|(_, at)| at.elapsed() < COPY_NOTICE_TIME) {
+        status_spans.push(Span::styled(notice.clone(), Style::default().fg(theme.accent).add_modifier(Modifier::BOLD)));
+    }
+    if let Some((queued, _)) = app.dns_prefetch_notice.filter(|(_, at)| at.elapsed() < NOTICE_TIME) {
+        status_spans.push(Span::styled(
+            format!("[DNS prefetch: {} queries queued] ", queued),
+            Style::default().fg(theme.accent).add_modifier(Modifier::BOLD),
+        ));
+    }
+    if let Some((notice, _)) = app.prefilter_notice.as_ref().filter(|(_, at)| at.elapsed() < PREFILTER_NOTICE_TIME) {
+        status_spans.push(Span::styled(format!("[{}] ", notice), Style::default().fg(theme.warning)));
+    }
+    if app.tick_changed_at.is_some_and(|at| at.elapsed() < NOTICE_TIME) {
+        status_spans.push(Span::styled(
+            format!("[Tick {} ms, window {}s] ", app.config.tick_ms, app.config.history_secs),
+            Style::default().fg(theme.accent).add_modifier(Modifier::BOLD),
+        ));
+    }
+    // More than 5% of packets lost in the kernel buffer makes the numbers unreliable
+    if let Some((dropped, percent)) = iface.drop_rate() {
+        let color = if percent > 5.0 { theme.high_traffic } else { theme.warning };
+        status_spans.push(Span::styled(
+            format!("[⚠ {:.1}% dropped ({})] ", percent, dropped),
+            Style::default().fg(color).add_modifier(Modifier::BOLD),
+        ));
+    }
+    status_spans.extend([
+        Span::styled(" GLOBAL RECORDS ", Style::default().bg(theme.text).fg(theme.status_bg).add_modifier(Modifier::BOLD)),
+        Span::raw(" | "),
+        Span::styled("MAX RX: ", Style::default().fg(theme.rx_color).add_modifier(Modifier::BOLD)),
+        Span::raw(format!("{} ", format_rate(peak_rx_record.0, unit, app.rate_base))),
+        Span::styled(format!("(@{})", global_rx_time), Style::default().fg(theme.muted)),
+        Span::raw(" | "),
+        Span::styled("MAX TX: ", Style::default().fg(theme.tx_color).add_modifier(Modifier::BOLD)),
+        Span::raw(format!("{} ", format_rate(peak_tx_record.0, unit, app.rate_base))),
+        Span::styled(format!("(@{})", global_tx_time), Style::default().fg(theme.muted)),
+    ]);
+    if let Some(talker) = app.selected_talker() {
+        status_spans.push(Span::raw(" | "));
+        status_spans.push(Span::styled("SELECTED: ", Style::default().fg(theme.warning).add_modifier(Modifier::BOLD)));
+        status_spans.push(Span::raw(app.talker_label(talker.ip)));
+    }
+    if !app.filter_query.is_empty() {
+        status_spans.push(Span::raw(" | "));
+        status_spans.push(Span::styled("SEARCH: ", Style::default().fg(theme.accent).add_modifier(Modifier::BOLD)));
+        status_spans.push(Span::raw(format!("{} {}", app.filter_query, match_count(app, iface))));
+    }
+    if let Some(filter) = &app.active_bpf {
+        status_spans.push(Span::raw(" | "));
+        status_spans.push(Span::styled("BPF: ", Style::default().fg(theme.low_traffic).add_modifier(Modifier::BOLD)));
+        status_spans.push(Span::raw(filter.clone()));
+    }
+    if let Some(warning) = &app.log_warning {
+        status_spans.push(Span::raw(" | "));
+        status_spans.push(Span::styled(format!("⚠ {}", warning), Style::default().fg(theme.warning)));
+    }
+    if let Some(err) = &app.theme_error {
+        status_spans.push(Span::raw(" | "));
+        status_spans.push(Span::styled(format!("Theme: {}", err), Style::default().fg(theme.warning)));
+    }
+    if let Some(err) = &app.bpf_error {
+        status_spans.push(Span::raw(" | "));
+        status_spans.push(Span::styled(format!("BPF error: {}", err), Style::default().fg(theme.high_traffic)));
+    }
+    if let Some(err) = &app.promisc_error {
+        status_spans.push(Span::raw(" | "));
+        status_spans.push(Span::styled(format!("Promisc: {}", err), Style::default().fg(theme.high_traffic)));
+    }
+    if let Some(err) = &app.history_error {
+        status_spans.push(Span::raw(" | "));
+        status_spans.push(Span::styled(format!("History: {}", err), Style::default().fg(theme.warning)));
+    }
+    status_spans.push(Span::raw(" | Press 'q' to quit, '/' to search, ':' for BPF"));
+    let mut status_content = Line::from(status_spans);
+
+    // The BPF prompt and search bar replace the status line while open
+    match app.input_mode {
+        InputMode::BpfPrompt => {
+            status_content = Line::from(vec![
+                Span::styled(":", Style::default().fg(theme.warning).add_modifier(Modifier::BOLD)),
+                Span::raw(app.input_buffer.clone()),
+            ]);
+            f.set_cursor(main_chunks[3].x + 1 + app.input_buffer.chars().count() as u16, main_chunks[3].y);
+        }
+        InputMode::Search => {
+            status_content = Line::from(vec![
+                Span::styled("/", Style::default().fg(theme.warning).add_modifier(Modifier::BOLD)),
+                Span::raw(app.filter_query.clone()),
+                Span::styled(format!("  {}", match_count(app, iface)), Style::default().fg(theme.muted)),
+            ]);
+            f.set_cursor(main_chunks[3].x + 1 + app.filter_query.chars().count() as u16, main_chunks[3].y);
+        }
+        InputMode::HistoryQuery => {
+            const PROMPT: &str = "history <ip> <last 1h | YYYY-MM-DD HH:MM - HH:MM>: ";
+            status_content = Line::from(vec![
+                Span::styled(PROMPT, Style::default().fg(theme.warning).add_modifier(Modifier::BOLD)),
+                Span::raw(app.input_buffer.clone()),
+            ]);
+            f.set_cursor(main_chunks[3].x + (PROMPT.chars().count() + app.input_buffer.chars().count()) as u16, main_chunks[3].y);
+        }
+        InputMode::Normal => {}
+    }
+
+    let status_bar = Paragraph::new(status_content)
+        .style(Style::default().bg(theme.status_bg));
+    f.render_widget(status_bar, main_chunks[3]);
+
+    // ============ Recent Alerts ============
+    let alert_lines: Vec<Line> = app.alerts.iter().rev().take(3)
+        .map(|alert| Line::from(Span::styled(format!(" ⚠ {}", alert.describe()), Style::default().fg(theme.high_traffic))))
+        .collect();
+    f.render_widget(Paragraph::new(alert_lines), main_chunks[4]);
+
+    // ============ ARP Table Popup ============
+    if app.show_arp {
+        let area = centered_rect(60, 60, f.size());
+        f.render_widget(Clear, area);
+        render_arp(f, app, area);
+    }
+
+    // ============ Detail Popup ============
+    if app.show_detail {
+        if let Some(talker) = app.selected_talker() {
+            let area = centered_rect(60, 60, f.size());
+            f.render_widget(Clear, area);
+            render_detail(f, app, talker, area);
+        }
+    }
+
+    // ============ History Query Result ============
+    if let Some(view) = &app.history_view {
+        let area = f.size();
+        f.render_widget(Clear, area);
+        render_history(f, app, view, area);
+    }
+
+    // ============ Help Popup ============
+    if app.show_help {
+        let area = centered_rect(60, 60, f.size());
+        f.render_widget(Clear, area);
+        *help_max_scroll = render_help(f, app, area);
+    }
+}
+
+// Text on top and, when the link speed is known, a one-line gauge at the bottom
+fn gauge_split(area: Rect, with_gauge: bool) -> std::rc::Rc<[Rect]> {
+    Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(1), Constraint::Length(if with_gauge { 1 } else { 0 })])
+        .split(area)
+}
+
+// Link utilization: green below 50%, yellow up to 80%, red above
+fn utilization_gauge(theme: &Theme, label: &str, percent: f64) -> Gauge<'static> {
+    let color = if percent > 80.0 {
+        theme.high_traffic
+    } else if percent >= 50.0 {
+        theme.warning
+    } else {
+        theme.low_traffic
+    };
+    Gauge::default()
+        .gauge_style(Style::default().fg(color).bg(theme.status_bg))
+        .ratio((percent / 100.0).clamp(0.0, 1.0))
+        .label(format!("{} {:.1}%", label, percent))
+}
+
+// Terminal rows available to a graph below its title
+fn graph_rows(area: Rect) -> f64 {
+    area.height.saturating_sub(1).max(1) as f64
+}
+
+// Draw one history onto a canvas; `row_height` is how much of the y axis one terminal row covers
+fn paint_history(ctx: &mut Context, history: &VecDeque<f64>, style: GraphStyle, color: Color, row_height: f64) {
+    if style == GraphStyle::Bar {
+        for (i, &val) in history.iter().enumerate() {
+            ctx.draw(&CanvasLine { x1: i as f64, y1: 0.0, x2: i as f64, y2: val, color });
+        }
+        return;
+    }
+
+    // Shade only the rows entirely below the sample so the polyline stays visible on top
+    if style == GraphStyle::Filled {
+        for (i, &val) in history.iter().enumerate() {
+            let full_rows = (val / row_height).floor() as usize;
+            for row in 0..full_rows {
+                ctx.print(i as f64, row as f64 * row_height, Span::styled("⣿", Style::default().fg(color).add_modifier(Modifier::DIM)));
+            }
+        }
+    }
+    for (i, (&prev, &val)) in history.iter().zip(history.iter().skip(1)).enumerate() {
+        ctx.draw(&CanvasLine { x1: i as f64, y1: prev, x2: (i + 1) as f64, y2: val, color });
+    }
+}
+
+// Label a log2 y axis at decades of the true rate; `per_sample` converts a per-tick count into that rate
+fn paint_log_ticks(ctx: &mut Context, max_y: f64, per_sample: f64, color: Color) {
+    const TICKS: [(f64, &str); 7] = [
+        (1e3, "1K"),
+        (1e4, "10K"),
+        (1e5, "100K"),
+        (1e6, "1M"),
+        (1e7, "10M"),
+        (1e8, "100M"),
+        (1e9, "1G"),
+    ];
+    for (rate, label) in TICKS {
+        let y = (1.0 + rate / per_sample).log2();
+        if y <= max_y {
+            ctx.print(0.0, y, Span::styled(label, Style::default().fg(color)));
+        }
+    }
+}
+
+// A rectangle of `percent_x` by `percent_y` of `area`, centered in it
+fn centered_rect(percent_x: u16, percent_y: u16, area: Rect) -> Rect {
+    let vertical = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Percentage((100 - percent_y) / 2),
+            Constraint::Percentage(percent_y),
+            Constraint::Percentage((100 - percent_y) / 2),
+        ])
+        .split(area);
+    Layout::default()
+        .direction(Direction::Horizontal)
+        .constraints([
+            Constraint::Percentage((100 - percent_x) / 2),
+            Constraint::Percentage(percent_x),
+            Constraint::Percentage((100 - percent_x) / 2),
+        ])
+        .split(vertical[1])[1]
+}
+
+// "aa:bb:cc:dd:ee:ff Vendor", or "-" before any frame was seen
+fn mac_label(mac: &[u8; 6]) -> String {
+    if *mac == [0; 6] {
+        return "-".to_string();
+    }
+    format!("{} {}", format_mac(mac), oui_lookup(mac))
+}
+
+// Render time of each recent frame, in microseconds, under the graphs
+fn render_perf(f: &mut ratatui::Frame, app: &App, area: Rect) {
+    let theme = &app.theme;
+    let samples: Vec<u64> = app.render_times.iter().map(|time| time.as_micros() as u64).collect();
+    let max = app.render_times.max().unwrap_or_default();
+    let p95 = app.render_p95().unwrap_or_default();
+    let sparkline = Sparkline::default()
+        .block(
+            Block::default()
+                .title(format!(
+                    " Render time: last {:.1} ms, p95 {:.1} ms, max {:.1} ms ",
+                    app.render_times.iter().next_back().copied().unwrap_or_default().as_secs_f64() * 1000.0,
+                    p95.as_secs_f64() * 1000.0,
+                    max.as_secs_f64() * 1000.0,
+                ))
+                .borders(Borders::ALL)
+                .border_type(ratatui::widgets::BorderType::Rounded)
+                .border_style(Style::default().fg(theme.muted)),
+        )
+        .data(&samples)
+        .style(Style::default().fg(theme.accent));
+    f.render_widget(sparkline, area);
+}
+
+// Full history and peak details for one talker
+fn render_detail(f: &mut ratatui::Frame, app: &App, talker: &TopTalker, area: Rect) {
+    let theme = &app.theme;
+    let block = Block::default()
+        .title(format!(" {} (Esc to close) ", app.talker_label(talker.ip)))
+        .borders(Borders::ALL)
+        .border_type(ratatui::widgets::BorderType::Rounded)
+        .border_style(Style::default().fg(theme.warning));
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+
+    let history = app.current().history(&talker.ip);
+    let dscp_classes = history.map(|history| top_dscp(&history.dscp_distribution, DSCP_DETAIL_CLASSES)).unwrap_or_default();
+    let dscp_height = if dscp_classes.is_empty() { 0 } else { dscp_classes.len() as u16 + 1 };
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Length(11), Constraint::Length(4), Constraint::Length(dscp_height), Constraint::Min(1)])
+        .split(inner);
+
+    let label = |text: &str| Span::styled(format!("{:<10}", text), Style::default().fg(theme.muted));
+    let hostname = app.hostname(talker.ip);
+    let mut hostname_line = vec![label("Hostname"), Span::raw(if hostname.is_empty() { "-" } else { hostname }.to_string())];
+    match app.dns_result(talker.ip) {
+        Some(DnsResult::NxDomain) => hostname_line.push(Span::styled(" (no PTR record)", Style::default().fg(theme.muted))),
+        Some(DnsResult::Timeout) => hostname_line.push(Span::styled(" (timed out, retrying)", Style::default().fg(theme.warning))),
+        Some(DnsResult::Error(err)) => hostname_line.push(Span::styled(format!(" ({})", err), Style::default().fg(theme.muted))),
+        _ => {}
+    }
+    let mut ttl_line = vec![label("TTL")];
+    match talker.ttl {
+        Some(ttl) => {
+            ttl_line.push(Span::raw(format!("{} ({})", ttl, os_from_ttl(ttl))));
+            let observed: Vec<String> = history
+                .map(|history| history.observed_ttls.iter().map(u8::to_string).collect())
+                .unwrap_or_default();
+            if observed.len() > 1 {
+                ttl_line.push(Span::styled(format!(", seen {}", observed.join("/")), Style::default().fg(theme.warning)));
+            }
+        }
+        None => ttl_line.push(Span::raw("-")),
+    }
+    let lines = vec![
+        Line::from(hostname_line),
+        Line::from(vec![label("MAC"), Span::raw(mac_label(&talker.mac))]),
+        Line::from(ttl_line),
+        Line::from(vec![
+            label("Current"),
+            Span::styled(format!("▼ {}  ", app.rate(talker.rx_bps, talker.rx_pps)), Style::default().fg(theme.rx_color)),
+            Span::styled(format!("▲ {}", app.rate(talker.tx_bps, talker.tx_pps)), Style::default().fg(theme.tx_color)),
+        ]),
+        Line::from(vec![label("Average"), Span::styled(app.rate(talker.avg_bps, talker.avg_pps), Style::default().fg(theme.rate_color(talker.avg_bps)))]),
+        Line::from(vec![
+            label("Peak"),
+            Span::styled(app.rate(talker.peak_bps, talker.peak_pps), Style::default().fg(theme.peak(talker.peak_bps))),
+            Span::styled(format!(" (@{})", talker.peak_time.format("%H:%M:%S")), Style::default().fg(theme.muted)),
+        ]),
+        Line::from(vec![label("Bytes"), Span::raw(format!("{} in the last {}s", format_bytes_total(talker.total_bytes, app.total_unit), app.config.history_secs))]),
+        Line::from(vec![
+            label("Total"),
+            Span::raw(format_bytes_total(talker.lifetime_bytes, app.total_unit)),
+            Span::styled(format!(" since {}", talker.first_seen.format("%Y-%m-%d %H:%M:%S")), Style::default().fg(theme.muted)),
+        ]),
+        Line::from(vec![label("Avg size"), Span::raw(format!("{:.0} B/packet", talker.avg_packet_size))]),
+        Line::from(vec![
+            label("Active"),
+            Span::raw(format!("since {}", talker.first_seen.format("%H:%M:%S"))),
+            Span::styled(format!(", last seen {}", talker.last_seen.format("%H:%M:%S")), Style::default().fg(theme.muted)),
+        ]),
+    ];
+    f.render_widget(Paragraph::new(lines), chunks[0]);
+
+    // Share of the bytes since first seen carried by each transport, one bar per protocol
+    let proto = talker.proto_bytes;
+    let total = proto.total().max(1);
+    let bars: Vec<Bar> = [("TCP", proto.tcp, theme.rx_color), ("UDP", proto.udp, theme.tx_color), ("Other", proto.other, theme.muted)]
+        .into_iter()
+        .map(|(name, bytes, color)| {
+            let percent = bytes * 100 / total;
+            Bar::default()
+                .label(Line::from(format!("{:<6}", name)))
+                .value(percent)
+                .text_value(format!("{}% ({})", percent, format_bytes_total(bytes, app.total_unit)))
+                .style(Style::default().fg(color))
+                .value_style(Style::default().fg(theme.text).bg(color))
+        })
+        .collect();
+    let chart = BarChart::default()
+        .block(Block::default().title(" Protocols ").borders(Borders::TOP))
+        .direction(Direction::Horizontal)
+        .bar_width(1)
+        .bar_gap(0)
+        .max(100)
+        .data(BarGroup::default().bars(&bars));
+    f.render_widget(chart, chunks[1]);
+
+    if let Some(history) = history {
+        // Busiest DSCP classes by share of the bytes since first seen; the title carries the ECN CE share
+        let total = history.dscp_distribution.values().sum::<u64>().max(1);
+        let bars: Vec<Bar> = dscp_classes
+            .iter()
+            .map(|&(dscp, bytes)| {
+                let percent = bytes * 100 / total;
+                Bar::default()
+                    .label(Line::from(format!("{:<6}", dscp_name(dscp))))
+                    .value(percent)
+                    .text_value(format!("{}% ({})", percent, format_bytes_total(bytes, app.total_unit)))
+                    .style(Style::default().fg(theme.accent))
+                    .value_style(Style::default().fg(theme.text).bg(theme.accent))
+            })
+            .collect();
+        let ce_rate = history.ecn_ce_ratio();
+        let ce_style = if ce_rate > HIGH_ECN_CE_RATE { Style::default().fg(theme.warning) } else { Style::default().fg(theme.muted) };
+        let title = Line::from(vec![Span::raw(" DSCP "), Span::styled(format!("(ECN CE {:.2}%) ", ce_rate * 100.0), ce_style)]);
+        let chart = BarChart::default()
+            .block(Block::default().title(title).borders(Borders::TOP))
+            .direction(Direction::Horizontal)
+            .bar_width(1)
+            .bar_gap(0)
+            .max(100)
+            .data(BarGroup::default().bars(&bars));
+        f.render_widget(chart, chunks[2]);
+
+        let samples: Vec<u64> = history.samples.iter().copied().collect();
+        let sparkline = Sparkline::default()
+            .block(Block::default().title(" History ").borders(Borders::TOP))
+            .data(&samples)
+            .style(Style::default().fg(theme.rate_color(talker.avg_bps)));
+        f.render_widget(sparkline, chunks[3]);
+    }
+}
+
+// ` [PROMISC]` or ` [NON-PROMISC]` after a live interface's name, nothing otherwise
+fn promisc_tag(iface: &InterfaceStats) -> &'static str {
+    match iface.promisc {
+        Some(true) => " [PROMISC]",
+        Some(false) => " [NON-PROMISC]",
+        None => "",
+    }
+}
+
+// "eth0 ▼1.20 Mb/s ▲310 Kb/s | wlan0 ▼0 b/s ▲0 b/s" for the interfaces behind a `-i any` capture
+fn device_summary(app: &App, iface: &InterfaceStats) -> String {
+    let rate = |traffic: Traffic| app.rate(app.config.per_second(traffic.bytes), app.config.per_second(traffic.packets));
+    iface
+        .devices
+        .values()
+        .map(|device| format!("{} ▼{} ▲{}", device.name, rate(device.current_rx), rate(device.current_tx)))
+        .collect::<Vec<_>>()
+        .join(" | ")
+}
+
+// The `count` DSCP codepoints carrying the most bytes, busiest first
+fn top_dscp(bytes_per_dscp: &HashMap<u8, u64>, count: usize) -> Vec<(u8, u64)> {
+    let mut classes: Vec<(u8, u64)> = bytes_per_dscp.iter().map(|(dscp, bytes)| (*dscp, *bytes)).collect();
+    classes.sort_by(|a, b| b.1.cmp(&a.1).then(a.0.cmp(&b.0)));
+    classes.truncate(count);
+    classes
+}
+
+// Average rate per minute over the queried range; minutes without a record are idle
+fn render_history(f: &mut ratatui::Frame, app: &App, view: &HistoryView, area: Rect) {
+    let theme = &app.theme;
+    let unit = if app.display_unit == DisplayUnit::Bytes { DisplayUnit::Bytes } else { DisplayUnit::Bits };
+    let time = |ts: i64| Local.timestamp_opt(ts, 0).single().map_or_else(String::new, |t| t.format("%Y-%m-%d %H:%M").to_string());
+    let peak = view.records.iter().map(|record| record.max_bps).fold(0.0, f64::max);
+    let total: u64 = view.records.iter().map(|record| record.total_bytes).sum();
+    let block = Block::default()
+        .title(format!(
+            " History {} | {} - {} | Peak {} | Total {} (h/Esc to close) ",
+            view.ip,
+            time(view.from),
+            time(view.to),
+            format_rate(peak, unit, app.rate_base),
+            format_bytes_total(total, app.total_unit),
+        ))
+        .borders(Borders::ALL)
+        .border_type(ratatui::widgets::BorderType::Rounded)
+        .border_style(Style::default().fg(theme.accent));
+
+    if view.records.is_empty() {
+        let message = Paragraph::new(Line::from(Span::styled(" No traffic recorded for this address in that range", Style::default().fg(theme.muted))));
+        f.render_widget(message.block(block), area);
+        return;
+    }
+
+    let first_minute = view.from.div_euclid(60) * 60;
+    let minutes = ((view.to - first_minute) / 60 + 1).max(1) as usize;
+    let mut samples: VecDeque<f64> = VecDeque::from(vec![0.0; minutes]);
+    for record in &view.records {
+        if let Some(sample) = samples.get_mut(((record.minute - first_minute) / 60) as usize) {
+            *sample = record.avg_bps;
+        }
+    }
+    let max_y = samples.iter().copied().fold(1.0, f64::max);
+    let rows = graph_rows(block.inner(area));
+    let canvas = Canvas::default()
+        .block(block)
+        .marker(Marker::Braille)
+        .x_bounds([0.0, minutes as f64])
+        .y_bounds([0.0, max_y])
+        .paint(|ctx| paint_history(ctx, &samples, app.graph_style, theme.rx_color, max_y / rows));
+    f.render_widget(canvas, area);
+}
+
+// Every IP -> MAC mapping learned from ARP, with poisoned IPs in red and their alerts below
+fn render_arp(f: &mut ratatui::Frame, app: &App, area: Rect) {
+    let theme = &app.theme;
+    let block = Block::default()
+        .title(format!(" ARP Table: {} entries (a/Esc to close) ", app.arp_table.len()))
+        .borders(Borders::ALL)
+        .border_type(ratatui::widgets::BorderType::Rounded)
+        .border_style(Style::default().fg(theme.accent));
+    let inner = block.inner(area);
+    f.render_widget(block, area);
+
+    let alert_height = if app.arp_alerts.is_empty() { 0 } else { app.arp_alerts.len().min(6) as u16 + 1 };
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([Constraint::Min(1), Constraint::Length(alert_height)])
+        .split(inner);
+
+    let rows: Vec<Row> = app.arp_table.iter().map(|(ip, mac)| {
+        let poisoned = app.arp_alerts.iter().any(|alert| alert.ip == *ip);
+        let style = if poisoned { Style::default().fg(theme.high_traffic).add_modifier(Modifier::BOLD) } else { Style::default() };
+        Row::new(vec![
+            Cell::from(ip.to_string()),
+            Cell::from(format_mac(mac)),
+            Cell::from(oui_lookup(mac)),
+        ]).style(style)
+    }).collect();
+    let table = Table::new(rows, [Constraint::Length(16), Constraint::Length(18), Constraint::Min(10)])
+        .header(header_row(theme, &["IP Address", "MAC", "Vendor"]));
+    f.render_widget(table, chunks[0]);
+
+    let alert_lines: Vec<Line> = std::iter::once(Line::from(Span::styled("Possible ARP poisoning:", Style::default().fg(theme.high_traffic).add_modifier(Modifier::BOLD))))
+        .chain(app.arp_alerts.iter().rev().take(6).map(|alert| Line::from(Span::styled(format!(" ⚠ {}", alert.describe()), Style::default().fg(theme.high_traffic)))))
+        .collect();
+    f.render_widget(Paragraph::new(alert_lines), chunks[1]);
+}
+
+// The keybindings, scrolled by `app.help_scroll`; returns the furthest the list can scroll
+fn render_help(f: &mut ratatui::Frame, app: &App, area: Rect) -> u16 {
+    let theme = &app.theme;
+    let block = Block::default()
+        .title(" Keybindings (↑/↓ to scroll, any other key to close) ")
+        .borders(Borders::ALL)
+        .border_type(ratatui::widgets::BorderType::Rounded)
+        .border_style(Style::default().fg(theme.accent));
+    let inner = block.inner(area);
+
+    let key_width = HELP_KEYS.iter().map(|(key, _)| key.len()).max().unwrap_or(0);
+    let lines: Vec<Line> = HELP_KEYS
+        .iter()
+        .map(|(key, action)| Line::from(vec![
+            Span::styled(format!(" {:<width$}  ", key, width = key_width), Style::default().fg(theme.accent).add_modifier(Modifier::BOLD)),
+            Span::raw(*action),
+        ]))
+        .collect();
+    let max_scroll = (lines.len() as u16).saturating_sub(inner.height);
+    let help = Paragraph::new(lines).block(block).scroll((app.help_scroll.min(max_scroll), 0));
+    f.render_widget(help, area);
+    max_scroll
+}
+
+fn header_row<S: AsRef<str>>(theme: &Theme, titles: &[S]) -> Row<'static> {
+    let header_cells = titles
+        .iter()
+        .map(|h| Cell::from(h.as_ref().to_string()).style(Style::default().fg(theme.header_fg).add_modifier(Modifier::BOLD)));
+    Row::new(header_cells)
+        .style(Style::default().bg(theme.header_bg))
+        .height(1)
+        .bottom_margin(0)
+}
+
+// Render a one-line sparkline off-screen and return its symbols, so it can sit in a table cell
+fn sparkline_text(data: &[u64]) -> String {
+    let area = Rect::new(0, 0, TREND_SAMPLES as u16, 1);
+    let mut buf = Buffer::empty(area);
+    Sparkline::default().data(data).render(area, &mut buf);
+    (0..area.width).map(|x| buf.get(x, 0).symbol()).collect()
+}
+
+// "[matching/total]" for the search bar
+fn match_count(app: &App, iface: &InterfaceStats) -> String {
+    format!("[{}/{}]", app.filtered_talkers().len(), iface.top_talkers.len())
+}
+
+fn talkers_table(app: &App, iface: &InterfaceStats, show_sparkline: bool) -> Table<'static> {
+    let theme = &app.theme;
+    // Hostnames need reverse DNS on single hosts; the Country column needs a GeoIP database
+    let available = |column: Column| match column {
+        Column::Hostname => app.dns_enabled && app.aggregate_prefix.is_none(),
+        Column::Country => app.geoip.is_some(),
+        _ => true,
+    };
+    let columns: Vec<_> = app.columns.iter().filter(|spec| spec.visible && available(spec.column)).collect();
+
+    // The active sort column carries ▲ or ▼
+    let title = |name: &str, column: SortColumn| {
+        if app.sort_column != column {
+            return name.to_string();
+        }
+        format!("{} {}", name, if app.sort_ascending { "▲" } else { "▼" })
+    };
+
+    let titles: Vec<String> = columns.iter().map(|spec| match spec.column {
+        Column::Ip => title(if app.aggregate_prefix.is_some() { "Subnet" } else { "IP Address" }, SortColumn::IpAddr),
+        Column::Hostname => title("Hostname", SortColumn::Hostname),
+        Column::Mac => "MAC / Vendor".to_string(),
+        Column::Country => "Country".to_string(),
+        Column::Rx => "RX Rate".to_string(),
+        Column::Tx => "TX Rate".to_string(),
+        Column::Speed => title("Avg Bandwidth", SortColumn::AvgBps),
+        Column::Peak => title("Peak Rate", SortColumn::PeakBps),
+        Column::Time => "Peak Time".to_string(),
+        Column::Total => title("Total", SortColumn::TotalBytes),
+        Column::AvgPkt => title("Avg Pkt", SortColumn::AvgPacketSize),
+        Column::Conn => "Conn".to_string(),
+        Column::Status => "Status".to_string(),
+        Column::Trend => "Trend".to_string(),
+    }).collect();
+    let widths: Vec<Constraint> = columns.iter().map(|spec| match (spec.width_pct, spec.column) {
+        (0, Column::Mac) => Constraint::Length(36),
+        (0, Column::Country) => Constraint::Length(8),
+        (0, Column::Conn) => Constraint::Length(6),
+        // The arrow always fits; the sparkline follows it on wide terminals
+        (0, Column::Trend) => Constraint::Length(if show_sparkline { TREND_SAMPLES as u16 + 2 } else { 5 }),
+        (width, _) => Constraint::Percentage(width),
+    }).collect();
+    let header = header_row(theme, &titles);
+
+    let talkers = app.filtered_talkers();
+    // Striped so a row can be followed across the columns; the selection highlight is drawn over it
+    let rows: Vec<Row> = talkers.iter().enumerate().map(|(i, talker)| {
+        let cells: Vec<Cell> = columns.iter().map(|spec| talker_cell(app, talker, spec.column, show_sparkline)).collect();
+        let bg = if i % 2 == 0 { theme.row_even_bg } else { theme.row_odd_bg };
+        Row::new(cells).height(1).style(Style::default().bg(bg))
+    }).collect();
+
+    let title = if app.filter_query.is_empty() {
+        " Local Network Traffic ".to_string()
+    } else {
+        format!(" Local Network Traffic {} ", match_count(app, iface))
+    };
+    Table::new(rows, widths)
+    .header(header)
+    .block(Block::default().title(title).borders(Borders::ALL).border_type(ratatui::widgets::BorderType::Rounded))
+}
+
+fn talker_cell(app: &App, talker: &TopTalker, column: Column, show_sparkline: bool) -> Cell<'static> {
+    let theme = &app.theme;
+    match column {
+        Column::Ip => {
+            let mut spans = Vec::new();
+            if app.watchlist.contains_key(&talker.ip) {
+                spans.push(Span::styled("★ ", Style::default().fg(theme.warning).add_modifier(Modifier::BOLD)));
+            }
+            if app.is_duplicate_ip(talker.ip) {
+                spans.push(Span::styled("⚠ ", Style::default().fg(theme.high_traffic).add_modifier(Modifier::BOLD)));
+            }
+            spans.push(Span::raw(app.talker_label(talker.ip)));
+            Cell::from(Line::from(spans))
+        }
+        // Failed lookups fall back to the address, styled by why there is no name; the OS guessed
+        // from the TTL follows in parentheses
+        Column::Hostname => {
+            let with_os = |name: String| match talker.ttl {
+                Some(ttl) if name.is_empty() => format!("({})", os_from_ttl(ttl)),
+                Some(ttl) => format!("{} ({})", name, os_from_ttl(ttl)),
+                None => name,
+            };
+            match app.dns_result(talker.ip) {
+                Some(DnsResult::NxDomain) => Cell::from(with_os(talker.ip.to_string())).style(Style::default().fg(theme.muted).add_modifier(Modifier::ITALIC)),
+                Some(DnsResult::Timeout) => Cell::from(with_os(talker.ip.to_string())).style(Style::default().fg(theme.warning)),
+                Some(DnsResult::Error(_)) => Cell::from(with_os(talker.ip.to_string())),
+                _ => Cell::from(with_os(app.hostname(talker.ip).to_string())).style(Style::default().fg(theme.hostname_color)),
+            }
+        }
+        Column::Mac => Cell::from(mac_label(&talker.mac)).style(Style::default().fg(theme.muted)),
+        Column::Country => {
+            let code = app.geo_cache.get(&talker.ip).map(String::as_str).unwrap_or("..");
+            Cell::from(code.to_string()).style(Style::default().fg(theme.accent))
+        }
+        Column::Rx => Cell::from(app.rate(talker.rx_bps, talker.rx_pps)).style(Style::default().fg(theme.rx_color)),
+        Column::Tx => Cell::from(app.rate(talker.tx_bps, talker.tx_pps)).style(Style::default().fg(theme.tx_color)),
+        Column::Speed => Cell::from(app.rate(talker.avg_bps, talker.avg_pps)).style(Style::default().fg(theme.rate_color(talker.avg_bps))),
+        Column::Peak => Cell::from(app.rate(talker.peak_bps, talker.peak_pps)).style(Style::default().fg(theme.peak(talker.peak_bps))),
+        Column::Time => Cell::from(talker.peak_time.format("%H:%M:%S").to_string()).style(Style::default().fg(theme.muted)),
+        Column::Total => Cell::from(format_bytes_total(talker.lifetime_bytes, app.total_unit)).style(Style::default().fg(theme.muted)),
+        Column::AvgPkt => Cell::from(format!("{:.0} B", talker.avg_packet_size)).style(Style::default().fg(theme.muted)),
+        Column::Conn => {
+            let color = if talker.active_connections > app.alert_monitor.max_connections_per_ip { theme.warning } else { theme.muted };
+            Cell::from(talker.active_connections.to_string()).style(Style::default().fg(color))
+        }
+        Column::Status => Cell::from("Active"),
+        Column::Trend => {
+            let (arrow, color) = match talker.direction {
+                Trend::Rising => ("↑", theme.high_traffic),
+                Trend::Falling => ("↓", theme.low_traffic),
+                Trend::Stable => ("→", theme.muted),
+            };
+            let mut trend = vec![Span::styled(arrow, Style::default().fg(color).add_modifier(Modifier::BOLD))];
+            if show_sparkline {
+                trend.push(Span::raw(" "));
+                trend.push(Span::styled(sparkline_text(&talker.trend), Style::default().fg(theme.rate_color(talker.avg_bps))));
+            }
+            Cell::from(Line::from(trend))
+        }
+    }
+}
+
+fn multicast_table(app: &App) -> Table<'static> {
+    let theme = &app.theme;
+    let header = header_row(theme, &["Group", "Avg Bandwidth", "Peak Rate", "Peak Time", "Bytes (window)"]);
+
+    let rows: Vec<Row> = app.top_multicast.iter().take(app.display_limit).map(|group| {
+        Row::new(vec![
+            Cell::from(group.ip.to_string()),
+            Cell::from(app.rate(group.avg_bps, group.avg_pps)).style(Style::default().fg(theme.rate_color(group.avg_bps))),
+            Cell::from(app.rate(group.peak_bps, group.peak_pps)).style(Style::default().fg(theme.peak(group.peak_bps))),
+            Cell::from(group.peak_time.format("%H:%M:%S").to_string()).style(Style::default().fg(theme.muted)),
+            Cell::from(format_bytes_total(group.total_bytes, app.total_unit)).style(Style::default().fg(theme.muted)),
+        ]).height(1)
+    }).collect();
+
+    Table::new(rows, [Constraint::Percentage(20); 5])
+    .header(header)
+    .block(Block::default().title(" Multicast Groups (press 'M' for hosts) ").borders(Borders::ALL).border_type(ratatui::widgets::BorderType::Rounded))
+}
+
+// Hosts as dots placed by the force-directed layout, joined by lines that are brighter for busier links
+fn topology_map(app: &App) -> impl Widget + '_ {
+    let theme = &app.theme;
+    let topology = &app.topology;
+    let busiest = topology.edges.iter().map(|edge| edge.avg_bps).fold(0.0, f64::max);
+    Canvas::default()
+        .block(
+            Block::default()
+                .title(format!(" Topology: {} hosts (press 't' for hosts) ", topology.nodes.len()))
+                .borders(Borders::ALL)
+                .border_type(ratatui::widgets::BorderType::Rounded),
+        )
+        .marker(Marker::Dot)
+        .x_bounds([0.0, 1.0])
+        .y_bounds([0.0, 1.0])
+        .paint(move |ctx| {
+            // Edges come quietest first, so busy links are drawn over quiet ones
+            for edge in &topology.edges {
+                let (a, b) = (&topology.nodes[edge.a], &topology.nodes[edge.b]);
+                let color = link_color(theme, edge.avg_bps / busiest);
+                ctx.draw(&CanvasLine { x1: a.x, y1: a.y, x2: b.x, y2: b.y, color });
+            }
+            ctx.layer();
+            for node in &topology.nodes {
+                ctx.draw(&Points { coords: &[(node.x, node.y)], color: theme.rate_color(node.avg_bps) });
+                ctx.print(node.x, node.y, Span::styled(format!(" {}", node.ip), Style::default().fg(theme.text)));
+            }
+        })
+}
+
+// Colour for a link carrying `share` of the busiest link's bandwidth, standing in for line width
+fn link_color(theme: &Theme, share: f64) -> Color {
+    if share >= 0.75 {
+        theme.high_traffic
+    } else if share >= 0.5 {
+        theme.medium_traffic
+    } else if share >= 0.25 {
+        theme.low_traffic
+    } else {
+        theme.muted
+    }
+}
+
+fn ports_table(app: &App) -> Table<'static> {
+    let theme = &app.theme;
+    let side = if app.by_source_port { "Source Port" } else { "Dest Port" };
+    let header = header_row(theme, &[side, "Service", "Current Rate", "Avg Bandwidth", "Peak Rate", "Total"]);
+
+    let rows: Vec<Row> = app.shown_ports().iter().take(app.display_limit).map(|port| {
+        let service = if app.service_names { resolve_service(port.port, if port.mostly_tcp { "tcp" } else { "udp" }) } else { "" };
+        Row::new(vec![
+            Cell::from(port.port.to_string()),
+            Cell::from(service).style(Style::default().fg(theme.muted)),
+            Cell::from(app.rate(port.current_bps, port.current_pps)).style(Style::default().fg(theme.rate_color(port.current_bps))),
+            Cell::from(app.rate(port.avg_bps, port.avg_pps)).style(Style::default().fg(theme.rate_color(port.avg_bps))),
+            Cell::from(app.rate(port.peak_bps, port.peak_pps)).style(Style::default().fg(theme.peak(port.peak_bps))),
+            Cell::from(format_bytes_total(port.lifetime_bytes, app.total_unit)).style(Style::default().fg(theme.muted)),
+        ]).height(1)
+    }).collect();
+
+    let other = if app.by_source_port { "destination" } else { "source" };
+    Table::new(rows, [Constraint::Percentage(12), Constraint::Percentage(18), Constraint::Percentage(18), Constraint::Percentage(18), Constraint::Percentage(18), Constraint::Percentage(16)])
+    .header(header)
+    .block(
+        Block::default()
+            .title(format!(" Top Ports (press 'p' for hosts, 'o' for {} ports) ", other))
+            .borders(Borders::ALL)
+            .border_type(ratatui::widgets::BorderType::Rounded),
+    )
+}
+
+fn icmp_table(app: &App) -> Table<'static> {
+    let theme = &app.theme;
+    let header = header_row(theme, &["Type", "Code", "Message", "Packets"]);
+
+    let rows: Vec<Row> = app.icmp_type_counts.iter().take(app.display_limit).map(|(&(icmp_type, code), packets)| {
+        // Redirects should only ever come from a router
+        let style = if icmp_type == icmp::REDIRECT { Style::default().fg(theme.warning) } else { Style::default() };
+        Row::new(vec![
+            Cell::from(icmp_type.to_string()),
+            Cell::from(code.to_string()),
+            Cell::from(icmp::describe(icmp_type, code)),
+            Cell::from(packets.to_string()).style(Style::default().fg(theme.muted)),
+        ]).style(style).height(1)
+    }).collect();
+
+    Table::new(rows, [Constraint::Length(6), Constraint::Length(6), Constraint::Percentage(50), Constraint::Percentage(30)])
+    .header(header)
+    .block(Block::default().title(" ICMP Messages (press 'i' for hosts) ").borders(Borders::ALL).border_type(ratatui::widgets::BorderType::Rounded))
+}
+
+fn sessions_table(app: &App) -> Table<'static> {
+    let theme = &app.theme;
+    let (bandwidth, duration) = if app.sessions_by_duration { ("Avg Bandwidth", "Duration ▼") } else { ("Avg Bandwidth ▼", "Duration") };
+    let header = header_row(theme, &["Proto", "Client", "Server", "State", bandwidth, "C→S", "S→C", "Peak Rate", "Bytes (window)", duration, "SYN/s", "RST/s", "RTx"]);
+
+    let rows: Vec<Row> = app.top_sessions.iter().take(app.display_limit).map(|session| {
+        let key = &session.key;
+        // Many resets usually mean a refused or scanned port, as do floods of tiny packets; flag the whole row
+        let flood = session.avg_packet_size < SMALL_PACKET_BYTES && session.avg_pps >= HIGH_PPS;
+        let warning = if session.rst_rate >= HIGH_RST_RATE || flood {
+            Some(theme.rst_warning)
+        } else if session.retransmit_rate > HIGH_RETRANSMIT_RATE {
+            Some(theme.retransmit_warning)
+        } else {
+            None
+        };
+        let color = |normal: Color| warning.unwrap_or(normal);
+        let plain = warning.map_or(Style::default(), |warning| Style::default().fg(warning));
+        let retransmits = if key.proto == PROTO_TCP {
+            format!("{} ({:.1}%)", session.retransmits, session.retransmit_rate * 100.0)
+        } else {
+            "-".to_string()
+        };
+        Row::new(vec![
+            Cell::from(key.proto_name()).style(plain),
+            Cell::from(format!("{}:{}", key.src, format_port(key.src_port, key.proto_name(), app.service_names))).style(plain),
+            Cell::from(format!("{}:{}", key.dst, format_port(key.dst_port, key.proto_name(), app.service_names))).style(plain),
+            Cell::from(session.tcp_state.map_or("-", |state| state.name())).style(Style::default().fg(color(theme.muted))),
+            Cell::from(app.rate(session.avg_bps, session.avg_pps)).style(Style::default().fg(color(theme.rate_color(session.avg_bps)))),
+            Cell::from(app.rate(session.upload_bps, session.upload_pps)).style(Style::default().fg(color(theme.rate_color(session.upload_bps)))),
+            Cell::from(app.rate(session.download_bps, session.download_pps)).style(Style::default().fg(color(theme.rate_color(session.download_bps)))),
+            Cell::from(app.rate(session.peak_bps, session.peak_pps)).style(Style::default().fg(color(theme.peak(session.peak_bps)))),
+            Cell::from(format_bytes_total(session.total_bytes, app.total_unit)).style(Style::default().fg(color(theme.muted))),
+            Cell::from(format_hms(session.duration)).style(Style::default().fg(color(theme.muted))),
+            Cell::from(format!("{:.1}", session.syn_rate)).style(Style::default().fg(color(theme.muted))),
+            Cell::from(format!("{:.1}", session.rst_rate)).style(Style::default().fg(color(theme.muted))),
+            Cell::from(retransmits).style(Style::default().fg(color(theme.muted))),
+        ]).height(1)
+    }).collect();
+
+    Table::new(
+        rows,
+        [
+            Constraint::Percentage(4),
+            Constraint::Percentage(12),
+            Constraint::Percentage(12),
+            Constraint::Percentage(8),
+            Constraint::Percentage(9),
+            Constraint::Percentage(8),
+            Constraint::Percentage(8),
+            Constraint::Percentage(8),
+            Constraint::Percentage(7),
+            Constraint::Percentage(8),
+            Constraint::Percentage(4),
+            Constraint::Percentage(4),
+            Constraint::Percentage(8),
+        ]
+    )
+    .header(header)
+    .block(Block::default().title(" Sessions (press 'S' for hosts) ").borders(Borders::ALL).border_type(ratatui::widgets::BorderType::Rounded))
+}
+
+// Sessions that ended or expired, newest first
+fn closed_sessions_table(app: &App) -> Table<'static> {
+    let theme = &app.theme;
+    let header = header_row(theme, &["Proto", "Client", "Server", "Started", "Duration", "Bytes", "Reason"]);
+
+    let rows: Vec<Row> = app.closed_sessions.iter().rev().take(app.display_limit).map(|session| {
+        let key = &session.key;
+        let reason_color = match session.reason {
+            EndReason::Reset => theme.rst_warning,
+            EndReason::Fin | EndReason::Idle => theme.muted,
+        };
+        Row::new(vec![
+            Cell::from(key.proto_name()),
+            Cell::from(format!("{}:{}", key.src, format_port(key.src_port, key.proto_name(), app.service_names))),
+            Cell::from(format!("{}:{}", key.dst, format_port(key.dst_port, key.proto_name(), app.service_names))),
+            Cell::from(session.started.format("%H:%M:%S").to_string()).style(Style::default().fg(theme.muted)),
+            Cell::from(format_duration(session.duration)),
+            Cell::from(format_bytes_total(session.total_bytes, app.total_unit)),
+            Cell::from(session.reason.name()).style(Style::default().fg(reason_color)),
+        ]).height(1)
+    }).collect();
+
+    Table::new(
+        rows,
+        [
+            Constraint::Percentage(6),
+            Constraint::Percentage(22),
+            Constraint::Percentage(22),
+            Constraint::Percentage(12),
+            Constraint::Percentage(12),
+            Constraint::Percentage(14),
+            Constraint::Percentage(12),
+        ]
+    )
+    .header(header)
+    .block(
+        Block::default()
+            .title(format!(" Closed Sessions: {} (press 'c' for hosts) ", app.closed_sessions.len()))
+            .borders(Borders::ALL)
+            .border_type(ratatui::widgets::BorderType::Rounded),
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use ratatui::backend::TestBackend;
+    use super::*;
+    use crate::config::Config;
+    use crate::network::{FilterConfig, PacketAccounting};
+    use crate::synthetic::{SyntheticTraffic, DEFAULT_DRY_RUN_SEED};
+
+    // A second of `--dry-run` traffic, accounted the way the dry-run capture thread does
+    fn synthetic_stats() -> Arc<SharedStats> {
+        let mut traffic = SyntheticTraffic::new(DEFAULT_DRY_RUN_SEED);
+        let stats = Arc::new(SharedStats::new());
+        let mut accounting = PacketAccounting::new(traffic.local_ip(), traffic.local_networks(), FilterConfig::default(), Arc::clone(&stats));
+        for _ in 0..100 {
+            for frame in traffic.next_slice() {
+                accounting.account(&frame, frame.len() as u64);
+            }
         }
+        stats
+    }
+
+    #[test]
+    fn renders_a_synthetic_frame() {
+        let mut app = App::new(Config::default(), &["DRY-RUN".to_string()]);
+        app.on_tick(&[synthetic_stats()]);
+        assert!(!app.current().top_talkers.is_empty());
+
+        let mut terminal = Terminal::new(TestBackend::new(160, 50)).unwrap();
+        let (mut table_area, mut visible_rows, mut help_max_scroll) = (Rect::default(), 0, 0);
+        terminal.draw(|f| draw(f, &app, &mut table_area, &mut visible_rows, &mut help_max_scroll)).unwrap();
+
+        let buffer = terminal.backend().buffer();
+        let screen: String = buffer.content().iter().map(|cell| cell.symbol()).collect();
+        assert!(screen.contains("DRY-RUN"));
+        assert!(screen.contains("192.168.1.10"));
+        assert!(table_area.height > 0 && visible_rows > 0);
     }
 }
@@ -1,32 +1,528 @@
-// Format function: Convert Bytes/s to bits/s for display
-pub fn format_bps(bytes_per_sec: f64) -> String {
-    let bps = bytes_per_sec * 8.0; //convert to bits per second
-    const KB: f64 = 1000.0;
-    const MB: f64 = 1000.0 * KB;
-    const GB: f64 = 1000.0 * MB;
-
-    if bps >= GB {
-        format!("{:.2} Gb/s", bps / GB)
-    } else if bps >= MB {
-        format!("{:.2} Mb/s", bps / MB)
-    } else if bps >= KB {
-        format!("{:.2} Kb/s", bps / KB)
+use std::{error::Error, fmt, path::{Path, PathBuf}, time::Duration};
+
+// How rates are shown; `u` cycles through them at runtime
+#[derive(Clone, Copy, PartialEq)]
+pub enum DisplayUnit {
+    Bits,
+    Bytes,
+    Packets,
+}
+
+impl DisplayUnit {
+    pub fn next(self) -> Self {
+        match self {
+            DisplayUnit::Bits => DisplayUnit::Bytes,
+            DisplayUnit::Bytes => DisplayUnit::Packets,
+            DisplayUnit::Packets => DisplayUnit::Bits,
+        }
+    }
+
+    pub fn parse(s: &str) -> Result<Self, String> {
+        match s {
+            "bits" => Ok(DisplayUnit::Bits),
+            "bytes" => Ok(DisplayUnit::Bytes),
+            "packets" => Ok(DisplayUnit::Packets),
+            _ => Err(format!("Invalid unit '{}', expected bits, bytes or packets", s)),
+        }
+    }
+
+    // Pick the figure this unit is based on: bytes/s for bits and bytes, packets/s otherwise
+    pub fn pick(self, bytes_per_sec: f64, packets_per_sec: f64) -> f64 {
+        match self {
+            DisplayUnit::Packets => packets_per_sec,
+            _ => bytes_per_sec,
+        }
+    }
+}
+
+// Prefixes for byte totals, selected with `--total-unit` and cycled with `U`
+#[derive(Clone, Copy, PartialEq)]
+pub enum TotalUnit {
+    // 1 KiB = 1024 B
+    IecBytes,
+    // 1 KB = 1000 B
+    SiBytes,
+    // 1 Kb = 1000 bits
+    Bits,
+}
+
+impl TotalUnit {
+    pub fn next(self) -> Self {
+        match self {
+            TotalUnit::IecBytes => TotalUnit::SiBytes,
+            TotalUnit::SiBytes => TotalUnit::Bits,
+            TotalUnit::Bits => TotalUnit::IecBytes,
+        }
+    }
+
+    pub fn parse(s: &str) -> Result<Self, String> {
+        match s {
+            "iec" => Ok(TotalUnit::IecBytes),
+            "si" => Ok(TotalUnit::SiBytes),
+            "bits" => Ok(TotalUnit::Bits),
+            _ => Err(format!("Invalid total unit '{}', expected iec, si or bits", s)),
+        }
+    }
+
+    // Factor from bytes to this unit's kilo step, and the name the CSV header gives it
+    pub fn kilo(self) -> (f64, &'static str) {
+        match self {
+            TotalUnit::IecBytes => (1.0 / 1024.0, "kib"),
+            TotalUnit::SiBytes => (1.0 / 1000.0, "kb"),
+            TotalUnit::Bits => (8.0 / 1000.0, "kbit"),
+        }
+    }
+}
+
+// How the download/upload history is drawn, selected with `--graph-style`
+#[derive(Clone, Copy, PartialEq)]
+pub enum GraphStyle {
+    // One vertical line per sample
+    Bar,
+    // Samples joined into a polyline
+    Line,
+    // Polyline with the area below it shaded, like iftop's bars
+    Filled,
+}
+
+impl GraphStyle {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        match s {
+            "bar" => Ok(GraphStyle::Bar),
+            "line" => Ok(GraphStyle::Line),
+            "filled" => Ok(GraphStyle::Filled),
+            _ => Err(format!("Invalid graph style '{}', expected filled, line or bar", s)),
+        }
+    }
+}
+
+// Prefix scale for bit and byte rates, selected with `--rate-base`
+#[derive(Clone, Copy, PartialEq)]
+pub enum BitrateBase {
+    // SI prefixes: 1 Kb/s = 1000 b/s
+    Decimal1000,
+    // IEC prefixes: 1 Kib/s = 1024 b/s
+    Binary1024,
+}
+
+impl BitrateBase {
+    pub fn parse(s: &str) -> Result<Self, String> {
+        match s {
+            "decimal" => Ok(BitrateBase::Decimal1000),
+            "binary" => Ok(BitrateBase::Binary1024),
+            _ => Err(format!("Invalid rate base '{}', expected decimal or binary", s)),
+        }
+    }
+
+    fn step(self) -> f64 {
+        match self {
+            BitrateBase::Decimal1000 => 1000.0,
+            BitrateBase::Binary1024 => 1024.0,
+        }
+    }
+
+    // Infix between the K/M/G letter and the unit, e.g. the "i" in "Kib/s"
+    fn infix(self) -> &'static str {
+        match self {
+            BitrateBase::Decimal1000 => "",
+            BitrateBase::Binary1024 => "i",
+        }
+    }
+}
+
+// Operating system guessed from a received TTL: the next common initial value at or above it,
+// which the sender started from before each router took one off
+pub fn os_from_ttl(ttl: u8) -> &'static str {
+    match ttl {
+        0..=64 => "Linux",
+        65..=128 => "Windows",
+        _ => "Cisco",
+    }
+}
+
+// Per-hop behaviour name of a DSCP codepoint (RFC 2474, 2597, 3246, 5865), or the number itself
+pub fn dscp_name(dscp: u8) -> String {
+    match dscp {
+        46 => "EF".to_string(),
+        44 => "VA".to_string(),
+        // Assured forwarding: class 1-4, drop precedence 1-3
+        10 | 12 | 14 | 18 | 20 | 22 | 26 | 28 | 30 | 34 | 36 | 38 => format!("AF{}{}", dscp / 8, dscp % 8 / 2),
+        _ if dscp.is_multiple_of(8) => format!("CS{}", dscp / 8),
+        _ => dscp.to_string(),
+    }
+}
+
+// Format a rate for display. `rate` is bytes/s for the bit and byte units
+// and packets/s for `Packets`; packet rates always use powers of 1000.
+pub fn format_rate(rate: f64, unit: DisplayUnit, base: BitrateBase) -> String {
+    let (value, suffix) = match unit {
+        DisplayUnit::Bits => (rate * 8.0, "b/s"),
+        DisplayUnit::Bytes => (rate, "B/s"),
+        DisplayUnit::Packets => (rate, "p/s"),
+    };
+    let base = if unit == DisplayUnit::Packets { BitrateBase::Decimal1000 } else { base };
+    let k = base.step();
+    let m = k * k;
+    let g = m * k;
+    let t = g * k;
+    let i = base.infix();
+
+    if value >= t {
+        format!("{:.2} T{}{}", value / t, i, suffix)
+    } else if value >= g {
+        format!("{:.2} G{}{}", value / g, i, suffix)
+    } else if value >= m {
+        format!("{:.2} M{}{}", value / m, i, suffix)
+    } else if value >= k {
+        format!("{:.2} K{}{}", value / k, i, suffix)
+    } else {
+        format!("{:.0} {}", value, suffix)
+    }
+}
+
+pub fn format_bytes_total(bytes: u64, unit: TotalUnit) -> String {
+    let (value, step, suffixes) = match unit {
+        TotalUnit::IecBytes => (bytes as f64, 1024.0, ["B", "KiB", "MiB", "GiB", "TiB", "PiB", "EiB"]),
+        TotalUnit::SiBytes => (bytes as f64, 1000.0, ["B", "KB", "MB", "GB", "TB", "PB", "EB"]),
+        TotalUnit::Bits => (bytes as f64 * 8.0, 1000.0, ["b", "Kb", "Mb", "Gb", "Tb", "Pb", "Eb"]),
+    };
+    // u64::MAX bytes is just under 16 EiB (148 Eb), so E is the last prefix needed
+    let mut scaled = value;
+    let mut prefix = 0;
+    while scaled >= step && prefix < suffixes.len() - 1 {
+        scaled /= step;
+        prefix += 1;
+    }
+    // 1024³ - 1 bytes is 1023.999 MiB, which two decimals would round up to "1024.00 MiB"
+    if prefix > 0 && prefix < suffixes.len() - 1 && (scaled * 100.0).round() >= step * 100.0 {
+        scaled /= step;
+        prefix += 1;
+    }
+    if prefix == 0 {
+        format!("{} {}", value, suffixes[0])
     } else {
-        format!("{:.0} b/s", bps)
+        format!("{:.2} {}", scaled, suffixes[prefix])
     }
 }
 
-pub fn format_bytes_total(bytes: u64) -> String {
-    const KB: u64 = 1024;
-    const MB: u64 = 1024 * KB;
-    const GB: u64 = 1024 * MB;
-    if bytes >= GB {
-        format!("{:.2} GiB", bytes as f64 / GB as f64)
-    } else if bytes >= MB {
-        format!("{:.2} MiB", bytes as f64 / MB as f64)
-    } else if bytes >= KB {
-        format!("{:.2} KiB", bytes as f64 / KB as f64)
+// `45.2s`, `12m05s` or `3h20m`
+pub fn format_duration(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    if secs < 60 {
+        format!("{:.1}s", duration.as_secs_f64())
+    } else if secs < 3600 {
+        format!("{}m{:02}s", secs / 60, secs % 60)
     } else {
-        format!("{} B", bytes)
+        format!("{}h{:02}m", secs / 3600, secs % 3600 / 60)
+    }
+}
+
+// `HH:MM:SS`, hours not wrapping at a day
+pub fn format_hms(duration: Duration) -> String {
+    let secs = duration.as_secs();
+    format!("{:02}:{:02}:{:02}", secs / 3600, secs % 3600 / 60, secs % 60)
+}
+
+// A bandwidth string `parse_bandwidth` could not make sense of
+#[derive(Debug)]
+pub struct ParseBandwidthError {
+    input: String,
+    reason: &'static str,
+}
+
+impl fmt::Display for ParseBandwidthError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "Invalid bandwidth '{}': {}, expected e.g. 500K, 10M, 1.5Gb/s or 100MB/s", self.input, self.reason)
+    }
+}
+
+impl Error for ParseBandwidthError {}
+
+// Parse a bandwidth such as "10M", "500K", "1.5Mb/s" or "1024B" into bits/s.
+// K/M/G are powers of 1000 in either case; a trailing `b` means bits (the default)
+// and `B` bytes, optionally followed by `/s`.
+pub fn parse_bandwidth(s: &str) -> Result<f64, ParseBandwidthError> {
+    let input = s.trim();
+    let error = |reason| ParseBandwidthError { input: input.to_string(), reason };
+
+    let mut rest = input.strip_suffix("/s").unwrap_or(input);
+    let mut bits_per_unit = 1.0;
+    if let Some(stripped) = rest.strip_suffix('B') {
+        rest = stripped;
+        bits_per_unit = 8.0;
+    } else if let Some(stripped) = rest.strip_suffix('b') {
+        rest = stripped;
+    }
+    let (number, multiplier) = match rest.chars().last() {
+        Some('K') | Some('k') => (&rest[..rest.len() - 1], 1_000.0),
+        Some('M') | Some('m') => (&rest[..rest.len() - 1], 1_000_000.0),
+        Some('G') | Some('g') => (&rest[..rest.len() - 1], 1_000_000_000.0),
+        _ => (rest, 1.0),
+    };
+
+    if number.is_empty() {
+        return Err(error("missing number"));
+    }
+    let value: f64 = number.parse().map_err(|_| error("not a number"))?;
+    if value.is_nan() {
+        return Err(error("not a number"));
+    }
+    if value < 0.0 {
+        return Err(error("must not be negative"));
+    }
+    let bits = value * multiplier * bits_per_unit;
+    if !bits.is_finite() {
+        return Err(error("too large"));
+    }
+    Ok(bits)
+}
+
+// "dir/traffic.csv" + "2024-01-01" -> "dir/traffic_2024-01-01.csv"
+pub fn with_stem_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let stem = path.file_stem().and_then(|s| s.to_str()).unwrap_or("output");
+    let mut name = format!("{}_{}", stem, suffix);
+    if let Some(ext) = path.extension().and_then(|e| e.to_str()) {
+        name = format!("{}.{}", name, ext);
+    }
+    path.with_file_name(name)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // `format_rate` takes bytes/s, so bit rates are passed in eighths
+    fn bits(bps: f64, base: BitrateBase) -> String {
+        format_rate(bps / 8.0, DisplayUnit::Bits, base)
+    }
+
+    #[test]
+    fn decimal_rate_boundaries() {
+        assert_eq!(bits(999.0, BitrateBase::Decimal1000), "999 b/s");
+        assert_eq!(bits(1000.0, BitrateBase::Decimal1000), "1.00 Kb/s");
+        assert_eq!(bits(1024.0, BitrateBase::Decimal1000), "1.02 Kb/s");
+    }
+
+    #[test]
+    fn binary_rate_boundaries() {
+        assert_eq!(bits(999.0, BitrateBase::Binary1024), "999 b/s");
+        assert_eq!(bits(1000.0, BitrateBase::Binary1024), "1000 b/s");
+        assert_eq!(bits(1024.0, BitrateBase::Binary1024), "1.00 Kib/s");
+    }
+
+    #[test]
+    fn byte_rates_follow_the_base() {
+        assert_eq!(format_rate(1000.0, DisplayUnit::Bytes, BitrateBase::Decimal1000), "1.00 KB/s");
+        assert_eq!(format_rate(1000.0, DisplayUnit::Bytes, BitrateBase::Binary1024), "1000 B/s");
+        assert_eq!(format_rate(1024.0, DisplayUnit::Bytes, BitrateBase::Binary1024), "1.00 KiB/s");
+    }
+
+    #[test]
+    fn packet_rates_always_use_base_1000() {
+        for base in [BitrateBase::Decimal1000, BitrateBase::Binary1024] {
+            assert_eq!(format_rate(999.0, DisplayUnit::Packets, base), "999 p/s");
+            assert_eq!(format_rate(1000.0, DisplayUnit::Packets, base), "1.00 Kp/s");
+            assert_eq!(format_rate(1024.0, DisplayUnit::Packets, base), "1.02 Kp/s");
+            assert_eq!(format_rate(2_500_000.0, DisplayUnit::Packets, base), "2.50 Mp/s");
+        }
+    }
+
+    #[test]
+    fn rates_reach_the_tera_prefix() {
+        assert_eq!(bits(400e9, BitrateBase::Decimal1000), "400.00 Gb/s");
+        assert_eq!(bits(1e12, BitrateBase::Decimal1000), "1.00 Tb/s");
+        assert_eq!(bits(1.5e12, BitrateBase::Decimal1000), "1.50 Tb/s");
+        assert_eq!(bits(1024f64.powi(4), BitrateBase::Binary1024), "1.00 Tib/s");
+        // Nothing beyond T, so larger rates stay in it
+        assert_eq!(bits(2e15, BitrateBase::Decimal1000), "2000.00 Tb/s");
+    }
+
+    const KIB: u64 = 1024;
+    const MIB: u64 = 1024 * KIB;
+    const GIB: u64 = 1024 * MIB;
+    const TIB: u64 = 1024 * GIB;
+    const PIB: u64 = 1024 * TIB;
+
+    fn totals(bytes: u64) -> [String; 3] {
+        [TotalUnit::IecBytes, TotalUnit::SiBytes, TotalUnit::Bits].map(|unit| format_bytes_total(bytes, unit))
+    }
+
+    #[test]
+    fn total_zero() {
+        assert_eq!(totals(0), ["0 B", "0 B", "0 b"]);
+    }
+
+    #[test]
+    fn total_below_one_kib() {
+        assert_eq!(totals(1023), ["1023 B", "1.02 KB", "8.18 Kb"]);
+    }
+
+    #[test]
+    fn total_one_kib() {
+        assert_eq!(totals(KIB), ["1.00 KiB", "1.02 KB", "8.19 Kb"]);
+    }
+
+    #[test]
+    fn total_just_below_one_mib() {
+        assert_eq!(totals(MIB - 1), ["1.00 MiB", "1.05 MB", "8.39 Mb"]);
+    }
+
+    #[test]
+    fn total_just_below_one_gib() {
+        assert_eq!(totals(GIB - 1), ["1.00 GiB", "1.07 GB", "8.59 Gb"]);
+    }
+
+    #[test]
+    fn total_one_gib() {
+        assert_eq!(totals(GIB), ["1.00 GiB", "1.07 GB", "8.59 Gb"]);
+    }
+
+    #[test]
+    fn total_just_below_one_tib() {
+        assert_eq!(totals(TIB - 1), ["1.00 TiB", "1.10 TB", "8.80 Tb"]);
+    }
+
+    #[test]
+    fn total_one_tib() {
+        assert_eq!(totals(TIB), ["1.00 TiB", "1.10 TB", "8.80 Tb"]);
+    }
+
+    #[test]
+    fn total_one_pib() {
+        assert_eq!(totals(PIB), ["1.00 PiB", "1.13 PB", "9.01 Pb"]);
+    }
+
+    #[test]
+    fn total_u64_max() {
+        assert_eq!(totals(u64::MAX), ["16.00 EiB", "18.45 EB", "147.57 Eb"]);
+    }
+
+    fn bandwidth(s: &str) -> f64 {
+        parse_bandwidth(s).unwrap_or_else(|err| panic!("{}", err))
+    }
+
+    fn bandwidth_error(s: &str) -> &'static str {
+        match parse_bandwidth(s) {
+            Ok(bits) => panic!("'{}' parsed as {} bits/s", s, bits),
+            Err(err) => err.reason,
+        }
+    }
+
+    #[test]
+    fn bandwidth_zero() {
+        assert_eq!(bandwidth("0"), 0.0);
+    }
+
+    #[test]
+    fn bandwidth_plain_number_is_bits() {
+        assert_eq!(bandwidth("1500"), 1500.0);
+    }
+
+    #[test]
+    fn bandwidth_kilo() {
+        assert_eq!(bandwidth("500K"), 500_000.0);
+        assert_eq!(bandwidth("500k"), 500_000.0);
+    }
+
+    #[test]
+    fn bandwidth_lowercase_mega() {
+        assert_eq!(bandwidth("10m"), 10_000_000.0);
+    }
+
+    #[test]
+    fn bandwidth_giga() {
+        assert_eq!(bandwidth("2G"), 2_000_000_000.0);
+        assert_eq!(bandwidth("2g"), 2_000_000_000.0);
+    }
+
+    #[test]
+    fn bandwidth_fractional_bits_per_second() {
+        assert_eq!(bandwidth("1.5Mb/s"), 1_500_000.0);
+    }
+
+    #[test]
+    fn bandwidth_bytes_per_second() {
+        assert_eq!(bandwidth("100MB/s"), 800_000_000.0);
+    }
+
+    #[test]
+    fn bandwidth_plain_bytes() {
+        assert_eq!(bandwidth("1024B"), 8192.0);
+    }
+
+    #[test]
+    fn bandwidth_explicit_bits() {
+        assert_eq!(bandwidth("8b"), 8.0);
+        assert_eq!(bandwidth("1Gb"), 1_000_000_000.0);
+    }
+
+    #[test]
+    fn bandwidth_per_second_without_unit() {
+        assert_eq!(bandwidth("10M/s"), 10_000_000.0);
+    }
+
+    #[test]
+    fn bandwidth_surrounding_whitespace() {
+        assert_eq!(bandwidth("  10M \t"), 10_000_000.0);
+    }
+
+    #[test]
+    fn bandwidth_empty() {
+        assert_eq!(bandwidth_error(""), "missing number");
+        assert_eq!(bandwidth_error("   "), "missing number");
+    }
+
+    #[test]
+    fn bandwidth_only_per_second() {
+        assert_eq!(bandwidth_error("/s"), "missing number");
+    }
+
+    #[test]
+    fn bandwidth_only_prefix() {
+        assert_eq!(bandwidth_error("M"), "missing number");
+        assert_eq!(bandwidth_error("KB/s"), "missing number");
+    }
+
+    #[test]
+    fn bandwidth_negative() {
+        assert_eq!(bandwidth_error("-5"), "must not be negative");
+        assert_eq!(bandwidth_error("-1.5M"), "must not be negative");
+    }
+
+    #[test]
+    fn bandwidth_nan() {
+        assert_eq!(bandwidth_error("NaN"), "not a number");
+    }
+
+    #[test]
+    fn bandwidth_overflow() {
+        assert_eq!(bandwidth_error("1e400"), "too large");
+    }
+
+    #[test]
+    fn bandwidth_overflow_after_multiplier() {
+        assert_eq!(bandwidth_error("1e308GB"), "too large");
+    }
+
+    #[test]
+    fn bandwidth_infinity() {
+        assert_eq!(bandwidth_error("inf"), "too large");
+    }
+
+    #[test]
+    fn bandwidth_letters() {
+        assert_eq!(bandwidth_error("abc"), "not a number");
+    }
+
+    #[test]
+    fn bandwidth_unknown_prefix() {
+        assert_eq!(bandwidth_error("5T"), "not a number");
+        assert_eq!(bandwidth_error("5 Mbps"), "not a number");
+    }
+
+    #[test]
+    fn bandwidth_error_message_names_the_input() {
+        let err = parse_bandwidth("abc").unwrap_err();
+        assert_eq!(err.to_string(), "Invalid bandwidth 'abc': not a number, expected e.g. 500K, 10M, 1.5Gb/s or 100MB/s");
     }
 }
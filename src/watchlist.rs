@@ -0,0 +1,65 @@
+use std::{
+    collections::HashMap,
+    fs,
+    io::{self, ErrorKind},
+    net::Ipv4Addr,
+    path::Path,
+};
+use crate::util::parse_bandwidth;
+
+// Bookmarked IPs and their optional alert threshold in bits/s
+pub type Watchlist = HashMap<Ipv4Addr, Option<f64>>;
+
+// One IP per line, optionally `=RATE` (e.g. `192.168.1.10=5M`); blank lines and `#` comments
+// are skipped. A missing file is an empty watchlist, created when it is saved on exit.
+pub fn load(path: &Path) -> Result<Watchlist, String> {
+    let text = match fs::read_to_string(path) {
+        Ok(text) => text,
+        Err(err) if err.kind() == ErrorKind::NotFound => return Ok(Watchlist::new()),
+        Err(err) => return Err(format!("{}: {}", path.display(), err)),
+    };
+
+    let mut watchlist = Watchlist::new();
+    for (number, line) in text.lines().enumerate() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') {
+            continue;
+        }
+        let (ip, threshold) = match line.split_once('=') {
+            Some((ip, threshold)) => {
+                let threshold = parse_bandwidth(threshold).map_err(|err| format!("{}:{}: {}", path.display(), number + 1, err))?;
+                (ip, Some(threshold))
+            }
+            None => (line, None),
+        };
+        let ip = ip
+            .trim()
+            .parse()
+            .map_err(|_| format!("{}:{}: invalid IP address '{}'", path.display(), number + 1, ip.trim()))?;
+        watchlist.insert(ip, threshold);
+    }
+    Ok(watchlist)
+}
+
+pub fn save(path: &Path, watchlist: &Watchlist) -> io::Result<()> {
+    let mut entries: Vec<_> = watchlist.iter().collect();
+    entries.sort_by_key(|(ip, _)| **ip);
+    let text: String = entries
+        .into_iter()
+        .map(|(ip, threshold)| match threshold {
+            Some(bits) => format!("{}={}\n", ip, format_threshold(*bits)),
+            None => format!("{}\n", ip),
+        })
+        .collect();
+    fs::write(path, text)
+}
+
+// Back to the `--alert-threshold` notation, so a saved `5M` reads as `5M`
+fn format_threshold(bits: f64) -> String {
+    for (multiplier, suffix) in [(1e9, "G"), (1e6, "M"), (1e3, "K")] {
+        if bits >= multiplier && (bits / multiplier).fract() == 0.0 {
+            return format!("{}{}", bits / multiplier, suffix);
+        }
+    }
+    bits.to_string()
+}
@@ -0,0 +1,154 @@
+use std::{error::Error, io, net, time::Duration};
+use futures_util::{SinkExt, StreamExt};
+use serde::Serialize;
+use tokio::{
+    io::AsyncWriteExt,
+    net::{TcpListener, TcpStream},
+    runtime::{self, Runtime},
+    sync::broadcast::{self, error::RecvError},
+    time::timeout,
+};
+use tokio_tungstenite::tungstenite::Message;
+
+// Frames buffered per client; one that falls further behind skips ahead to the newest
+const FRAME_CAPACITY: usize = 16;
+// A connection that has not sent its request head by then is dropped
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Dashboard served on `GET /`; it opens a WebSocket back to the same address
+const DASHBOARD: &str = include_str!("ws_dashboard.html");
+
+// One text frame per tick, sent to every connected client:
+//
+//   {
+//     "ts": 1700000000,            Unix timestamp of the tick
+//     "rx_bps": 125000.0,          bytes/s received on all interfaces during the tick
+//     "tx_bps": 40000.0,           bytes/s sent on all interfaces during the tick
+//     "talkers": [                 top talkers of the "All" tab, in table order
+//       { "ip": "192.168.1.5",     address, or `192.168.1.0/24` with `--aggregate-subnet`
+//         "avg_bps": 52000.0,      bytes/s averaged over the history window
+//         "peak_bps": 98000.0 }    highest bytes/s since the IP was first seen
+//     ]
+//   }
+//
+// Rates are in bytes/s like the `--headless` and `--log-json` records.
+#[derive(Serialize)]
+pub struct WsFrame {
+    pub ts: i64,
+    pub rx_bps: f64,
+    pub tx_bps: f64,
+    pub talkers: Vec<WsTalker>,
+}
+
+#[derive(Serialize)]
+pub struct WsTalker {
+    pub ip: String,
+    pub avg_bps: f64,
+    pub peak_bps: f64,
+}
+
+// Streams a `WsFrame` per tick to browser dashboards from a small tokio runtime
+pub struct WsServer {
+    // Kept so the accept loop and the client tasks keep running
+    _runtime: Runtime,
+    frames: broadcast::Sender<String>,
+}
+
+impl WsServer {
+    pub fn start(addr: &str) -> Result<Self, Box<dyn Error>> {
+        // Bound here rather than on the runtime so a bad address fails at startup
+        let listener = net::TcpListener::bind(addr).map_err(|e| format!("WebSocket server on {}: {}", addr, e))?;
+        listener.set_nonblocking(true)?;
+        let runtime = runtime::Builder::new_multi_thread()
+            .worker_threads(1)
+            .thread_name("ws")
+            .enable_all()
+            .build()?;
+        let (frames, _) = broadcast::channel(FRAME_CAPACITY);
+
+        let clients = frames.clone();
+        runtime.spawn(async move {
+            let Ok(listener) = TcpListener::from_std(listener) else {
+                return;
+            };
+            loop {
+                // A failed accept (e.g. out of file descriptors) only loses that one connection
+                if let Ok((stream, _)) = listener.accept().await {
+                    tokio::spawn(handle_connection(stream, clients.subscribe()));
+                }
+            }
+        });
+        Ok(Self { _runtime: runtime, frames })
+    }
+
+    // Nothing is serialized while no dashboard is connected
+    pub fn publish(&self, frame: &WsFrame) {
+        if self.frames.receiver_count() == 0 {
+            return;
+        }
+        if let Ok(json) = serde_json::to_string(frame) {
+            let _ = self.frames.send(json);
+        }
+    }
+}
+
+// WebSocket upgrades get the frame stream; any other request is answered as plain HTTP
+async fn handle_connection(stream: TcpStream, frames: broadcast::Receiver<String>) {
+    let mut head = [0u8; 2048];
+    let len = match timeout(HANDSHAKE_TIMEOUT, peek_head(&stream, &mut head)).await {
+        Ok(Ok(len)) => len,
+        _ => return,
+    };
+    let request = String::from_utf8_lossy(&head[..len]).to_ascii_lowercase();
+    if request.contains("upgrade: websocket") {
+        stream_frames(stream, frames).await;
+    } else {
+        let _ = serve_page(stream, &request).await;
+    }
+}
+
+// The request head, left in the socket so the WebSocket handshake can still read it
+async fn peek_head(stream: &TcpStream, buf: &mut [u8]) -> io::Result<usize> {
+    loop {
+        let len = stream.peek(buf).await?;
+        if len == 0 || len == buf.len() || buf[..len].windows(4).any(|w| w == b"\r\n\r\n") {
+            return Ok(len);
+        }
+        // Only part of the head has arrived; peek returns the same bytes until more do
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    }
+}
+
+async fn serve_page(mut stream: TcpStream, request: &str) -> io::Result<()> {
+    let path = request.split_whitespace().nth(1).unwrap_or("");
+    let response = match path.split('?').next() {
+        Some("/") | Some("/index.html") => format!(
+            "HTTP/1.1 200 OK\r\nContent-Type: text/html; charset=utf-8\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+            DASHBOARD.len(),
+            DASHBOARD
+        ),
+        _ => "HTTP/1.1 404 Not Found\r\nContent-Length: 9\r\nConnection: close\r\n\r\nNot Found".to_string(),
+    };
+    stream.write_all(response.as_bytes()).await?;
+    stream.shutdown().await
+}
+
+async fn stream_frames(stream: TcpStream, mut frames: broadcast::Receiver<String>) {
+    let Ok(socket) = tokio_tungstenite::accept_async(stream).await else {
+        return;
+    };
+    let (mut sink, mut incoming) = socket.split();
+    // Clients only ever send pings and the close frame; reading keeps those answered
+    let reader = tokio::spawn(async move { while let Some(Ok(_)) = incoming.next().await {} });
+    loop {
+        let frame = match frames.recv().await {
+            Ok(frame) => frame,
+            Err(RecvError::Lagged(_)) => continue,
+            Err(RecvError::Closed) => break,
+        };
+        if reader.is_finished() || sink.send(Message::Text(frame)).await.is_err() {
+            break;
+        }
+    }
+    reader.abort();
+}